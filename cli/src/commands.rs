@@ -2,6 +2,7 @@
 
 use std::path::PathBuf;
 use std::fs;
+use std::io::{self, Write};
 use miette::{IntoDiagnostic, Result, WrapErr, miette};
 
 pub struct ParseCommand {
@@ -20,14 +21,18 @@ impl ParseCommand {
             .into_diagnostic()
             .wrap_err("Failed to read input file")?;
 
-        let module = synton_parser::parse_module(&source)
-            .map_err(|e| miette!("Parse error: {}", e))?;
+        let module = parse_source(&source)?;
 
         let result = match self.format.as_str() {
             "json" => serde_json::to_string_pretty(&module)
+                .into_diagnostic()
+                .wrap_err("Failed to serialize AST")?
+                .into_bytes(),
+            "text" => format!("{:#?}", module).into_bytes(),
+            "bin" => module
+                .to_binary()
                 .into_diagnostic()
                 .wrap_err("Failed to serialize AST")?,
-            "text" => format!("{:#?}", module),
             _ => return Err(miette!("Unknown format: {}", self.format)),
         };
 
@@ -38,8 +43,14 @@ impl ParseCommand {
                     .wrap_err("Failed to write output")?;
                 eprintln!("AST written to {}", path.display());
             }
+            None if self.format == "bin" => {
+                io::stdout()
+                    .write_all(&result)
+                    .into_diagnostic()
+                    .wrap_err("Failed to write output")?;
+            }
             None => {
-                println!("{}", result);
+                println!("{}", String::from_utf8_lossy(&result));
             }
         }
 
@@ -50,11 +61,12 @@ impl ParseCommand {
 pub struct CheckCommand {
     input: PathBuf,
     emit_dso: bool,
+    dump_types: bool,
 }
 
 impl CheckCommand {
-    pub fn new(input: PathBuf, emit_dso: bool) -> Self {
-        Self { input, emit_dso }
+    pub fn new(input: PathBuf, emit_dso: bool, dump_types: bool) -> Self {
+        Self { input, emit_dso, dump_types }
     }
 
     pub fn run(self) -> Result<()> {
@@ -62,11 +74,13 @@ impl CheckCommand {
             .into_diagnostic()
             .wrap_err("Failed to read input file")?;
 
-        let module = synton_parser::parse_module(&source)
-            .map_err(|e| miette!("Parse error: {}", e))?;
+        let mut module = parse_source(&source)?;
+        resolve_names(&source, &mut module)?;
 
-        synton_typeck::check(&module)
-            .map_err(|e| miette!("Type check error: {}", e))?;
+        let typed = type_check(&source, &module)?;
+        if self.dump_types {
+            print!("{}", typed);
+        }
 
         eprintln!("Type check passed!");
         Ok(())
@@ -90,12 +104,13 @@ impl RunCommand {
             .wrap_err("Failed to read input file")?;
 
         // Parse
-        let module = synton_parser::parse_module(&source)
-            .map_err(|e| miette!("Parse error: {}", e))?;
+        let mut module = parse_source(&source)?;
+
+        // Resolve names
+        resolve_names(&source, &mut module)?;
 
         // Type check
-        synton_typeck::check(&module)
-            .map_err(|e| miette!("Type check error: {}", e))?;
+        type_check(&source, &module)?;
 
         // TODO: Compile and run
         eprintln!("Run not yet fully implemented");
@@ -119,8 +134,7 @@ impl DecompileCommand {
             .into_diagnostic()
             .wrap_err("Failed to read input file")?;
 
-        let module = synton_parser::parse_module(&source)
-            .map_err(|e| miette!("Parse error: {}", e))?;
+        let module = parse_source(&source)?;
 
         let lang = synton_decompiler::TargetLang::from_name(&self.lang)
             .ok_or_else(|| miette!("Unknown target language: {}", self.lang))?;
@@ -143,6 +157,173 @@ impl DecompileCommand {
     }
 }
 
+pub struct FmtCommand {
+    inputs: Vec<PathBuf>,
+    check: bool,
+    width: usize,
+}
+
+impl FmtCommand {
+    pub fn new(inputs: Vec<PathBuf>, check: bool, width: usize) -> Self {
+        Self { inputs, check, width }
+    }
+
+    pub fn run(self) -> Result<()> {
+        let config = synton_parser::format::FormatConfig {
+            line_width: self.width,
+            ..Default::default()
+        };
+
+        let mut unformatted = 0;
+        for input in &self.inputs {
+            let source = fs::read_to_string(input)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to read {}", input.display()))?;
+
+            parse_source(&source).wrap_err_with(|| format!("Cannot format {}", input.display()))?;
+            let formatted = synton_parser::format::format_source(&source, &config)
+                .map_err(|e| miette!("Parse error: {}", e))?;
+            if formatted == source {
+                continue;
+            }
+
+            unformatted += 1;
+            if self.check {
+                println!("Would reformat {}", input.display());
+            } else {
+                fs::write(input, formatted)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("Failed to write {}", input.display()))?;
+                eprintln!("Formatted {}", input.display());
+            }
+        }
+
+        if self.check && unformatted > 0 {
+            return Err(miette!("{} file(s) need formatting", unformatted));
+        }
+        Ok(())
+    }
+}
+
+pub struct CompactCommand {
+    input: PathBuf,
+    rename: bool,
+    names: Option<PathBuf>,
+    restore: Option<PathBuf>,
+    stats: bool,
+    counter: String,
+    output: Option<PathBuf>,
+}
+
+impl CompactCommand {
+    pub fn new(
+        input: PathBuf,
+        rename: bool,
+        names: Option<PathBuf>,
+        restore: Option<PathBuf>,
+        stats: bool,
+        counter: String,
+        output: Option<PathBuf>,
+    ) -> Self {
+        Self { input, rename, names, restore, stats, counter, output }
+    }
+
+    pub fn run(self) -> Result<()> {
+        use synton_parser::compact::{self, CharTokenCounter, CompactConfig, LexTokenCounter, NameMap, TokenCounter};
+
+        let counter: Box<dyn TokenCounter> = match self.counter.as_str() {
+            "lex" => Box::new(LexTokenCounter),
+            "chars" => Box::new(CharTokenCounter::default()),
+            other => return Err(miette!("Unknown token counter: {}", other)),
+        };
+
+        let source = fs::read_to_string(&self.input)
+            .into_diagnostic()
+            .wrap_err("Failed to read input file")?;
+        parse_source(&source)?;
+
+        let result = match &self.restore {
+            Some(path) => {
+                let map = fs::read_to_string(path)
+                    .into_diagnostic()
+                    .wrap_err("Failed to read name map")?;
+                let map = NameMap::parse(&map).map_err(|e| miette!("Invalid name map: {}", e))?;
+                compact::restore_names(&source, &map).map_err(|e| miette!("Parse error: {}", e))?
+            }
+            None => {
+                let config = CompactConfig { rename_locals: self.rename };
+                let out = compact::compact_source(&source, &config).map_err(|e| miette!("Parse error: {}", e))?;
+                if let Some(path) = &self.names {
+                    fs::write(path, out.names.to_string())
+                        .into_diagnostic()
+                        .wrap_err("Failed to write name map")?;
+                    eprintln!("Name map written to {}", path.display());
+                }
+                out.text
+            }
+        };
+
+        if self.stats {
+            let before = counter.count(&source);
+            let after = counter.count(&result);
+            let saved = before.saturating_sub(after) as f64 * 100.0 / before.max(1) as f64;
+            eprintln!("tokens ({}): {} -> {} ({:.1}% fewer)", self.counter, before, after, saved);
+        }
+
+        match self.output {
+            Some(path) => {
+                fs::write(&path, result)
+                    .into_diagnostic()
+                    .wrap_err("Failed to write output")?;
+                eprintln!("Compact source written to {}", path.display());
+            }
+            None => {
+                println!("{}", result);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DiffCommand {
+    old: PathBuf,
+    new: PathBuf,
+    format: String,
+}
+
+impl DiffCommand {
+    pub fn new(old: PathBuf, new: PathBuf, format: String) -> Self {
+        Self { old, new, format }
+    }
+
+    pub fn run(self) -> Result<()> {
+        let read = |path: &PathBuf| {
+            let source = fs::read_to_string(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+            parse_source(&source).wrap_err_with(|| format!("Cannot parse {}", path.display()))
+        };
+        let old = read(&self.old)?;
+        let new = read(&self.new)?;
+
+        let diff = synton_ast::diff::diff(&old, &new);
+        match self.format.as_str() {
+            "json" => {
+                let json = serde_json::to_string_pretty(&diff)
+                    .into_diagnostic()
+                    .wrap_err("Failed to serialize diff")?;
+                println!("{}", json);
+            }
+            "text" if diff.is_empty() => eprintln!("No structural changes"),
+            "text" => print!("{}", diff),
+            _ => return Err(miette!("Unknown format: {}", self.format)),
+        }
+
+        Ok(())
+    }
+}
+
 pub struct LspCommand {
     stdio: bool,
 }
@@ -179,3 +360,77 @@ impl LspCommand {
         }
     }
 }
+
+/// Parse a module, reporting every syntax error in the file at once
+fn parse_source(source: &str) -> Result<synton_ast::Module> {
+    let parsed = synton_parser::parse_module_recovering(source);
+    if !parsed.has_errors() {
+        return parsed.into_result().map_err(|e| miette!("Parse error: {}", e));
+    }
+
+    let report = parsed.errors
+        .iter()
+        .map(|e| {
+            let (line, column) = line_column(source, e.span().start);
+            format!("  {}:{}: {}", line, column, e)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(miette!("{} parse error(s):\n{}", parsed.errors.len(), report))
+}
+
+/// Resolve names against the standard library, printing warnings and
+/// failing on errors
+fn resolve_names(source: &str, module: &mut synton_ast::Module) -> Result<()> {
+    let stdlib = synton_runtime::StdLib::new();
+    let resolution = synton_typeck::Resolver::new()
+        .with_globals(stdlib.functions())
+        .resolve(module);
+
+    let located = |e: &synton_typeck::ResolveError| {
+        let (line, column) = line_column(source, e.span().start.offset as usize);
+        format!("{}:{}: {}", line, column, e)
+    };
+    for warning in resolution.diagnostics.iter().filter(|d| d.is_warning()) {
+        eprintln!("warning: {}", located(warning));
+    }
+
+    let errors: Vec<_> = resolution.errors().map(|e| format!("  {}", located(e))).collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(miette!("{} name resolution error(s):\n{}", errors.len(), errors.join("\n")))
+}
+
+/// Type check a module with the standard library in scope, printing every
+/// warning and error
+fn type_check(source: &str, module: &synton_ast::Module) -> Result<synton_typeck::TypedModule> {
+    let stdlib = synton_runtime::StdLib::new();
+    let print = |diagnostics: &[synton_typeck::TypeDiagnostic]| {
+        for diagnostic in diagnostics {
+            let report = miette::Report::new(diagnostic.clone()).with_source_code(source.to_string());
+            eprintln!("{:?}", report);
+        }
+    };
+    let mut checker =
+        synton_typeck::TypeChecker::new().with_globals(stdlib.functions()).with_effects(stdlib.effects());
+    let diagnostics = match checker.check_module(module) {
+        Ok(typed) => {
+            print(&typed.warnings);
+            return Ok(typed);
+        }
+        Err(diagnostics) => diagnostics,
+    };
+
+    print(&diagnostics);
+    let errors = diagnostics.iter().filter(|d| !d.is_warning()).count();
+    Err(miette!("{} type error(s)", errors))
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (line, column)
+}
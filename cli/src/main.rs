@@ -14,7 +14,7 @@ mod commands;
 mod repl;
 mod output;
 
-use commands::{ParseCommand, CheckCommand, RunCommand, DecompileCommand, LspCommand};
+use commands::{ParseCommand, CheckCommand, RunCommand, DecompileCommand, FmtCommand, CompactCommand, DiffCommand, LspCommand};
 
 /// Synton - AI-native programming language
 #[derive(Parser, Debug)]
@@ -42,7 +42,7 @@ enum Commands {
         /// Input file
         input: PathBuf,
 
-        /// Output format (json, text)
+        /// Output format (json, text, bin)
         #[arg(short, long, default_value = "text")]
         format: String,
 
@@ -59,6 +59,10 @@ enum Commands {
         /// Emit DSO (Debug State Object) on error
         #[arg(long)]
         emit_dso: bool,
+
+        /// Print the type of every binding and expression
+        #[arg(long)]
+        dump_types: bool,
     },
 
     /// Run a Synton program
@@ -89,6 +93,64 @@ enum Commands {
         output: Option<PathBuf>,
     },
 
+    /// Format source files in place
+    Fmt {
+        /// Input files
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Report files that need formatting instead of rewriting them
+        #[arg(long)]
+        check: bool,
+
+        /// Maximum line width
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+    },
+
+    /// Print the most compact equivalent source
+    Compact {
+        /// Input file
+        input: PathBuf,
+
+        /// Rename locals to short IDs such as `%1`
+        #[arg(long)]
+        rename: bool,
+
+        /// File to write the short-to-original name map to
+        #[arg(long, requires = "rename")]
+        names: Option<PathBuf>,
+
+        /// Restore the original names from a name map instead
+        #[arg(long, conflicts_with_all = ["rename", "names"])]
+        restore: Option<PathBuf>,
+
+        /// Report token counts before and after
+        #[arg(long)]
+        stats: bool,
+
+        /// Token counter for --stats (lex, chars)
+        #[arg(long, default_value = "lex")]
+        counter: String,
+
+        /// Output file (stdout if not specified)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show structural changes between two versions of a module
+    Diff {
+        /// Old version
+        old: PathBuf,
+
+        /// New version
+        new: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
     /// Start REPL
     Repl {
         /// Enable verbose output
@@ -143,8 +205,8 @@ fn main() -> Result<()> {
         Commands::Parse { input, format, output } => {
             ParseCommand::new(input, format, output).run()?;
         }
-        Commands::Check { input, emit_dso } => {
-            CheckCommand::new(input, emit_dso).run()?;
+        Commands::Check { input, emit_dso, dump_types } => {
+            CheckCommand::new(input, emit_dso, dump_types).run()?;
         }
         Commands::Run { input, values, trace } => {
             RunCommand::new(input, values, trace).run()?;
@@ -152,6 +214,15 @@ fn main() -> Result<()> {
         Commands::Decompile { input, lang, output } => {
             DecompileCommand::new(input, lang, output).run()?;
         }
+        Commands::Fmt { inputs, check, width } => {
+            FmtCommand::new(inputs, check, width).run()?;
+        }
+        Commands::Compact { input, rename, names, restore, stats, counter, output } => {
+            CompactCommand::new(input, rename, names, restore, stats, counter, output).run()?;
+        }
+        Commands::Diff { old, new, format } => {
+            DiffCommand::new(old, new, format).run()?;
+        }
         Commands::Repl { verbose } => {
             repl::Repl::new(verbose).run()?;
         }
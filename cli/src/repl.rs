@@ -66,7 +66,7 @@ impl Repl {
             input.push('\n');
 
             // Try to parse
-            match synton_parser::Parser::new().parse_stmt(&input) {
+            match synton_parser::SyntonParser::new().parse_stmt(&input) {
                 Ok(_stmt) => {
                     // Successfully parsed
                     self.history.push(input.clone());
@@ -0,0 +1,824 @@
+//! Compact binary encoding of the AST
+//!
+//! Layout, with every integer a LEB128 varint unless noted:
+//!
+//! ```text
+//! file   = magic "SYNB" | version: u8 | node count | node* | string count | string* | root length | root
+//! node   = type id: u8 | payload length | payload
+//! string = byte length | UTF-8 bytes
+//! ```
+//!
+//! Values follow the serde data model without type tags, so decoding is
+//! driven by the type being read. An enum variant carrying data becomes a
+//! node whose type id is the variant index; its parent refers to it by
+//! node index. Unit variants, structs, tuples, sequences and options are
+//! written inline, and every string is an index into the deduplicated
+//! string table. Signed integers are zigzag-encoded and floats are stored
+//! as little-endian IEEE 754 bits.
+//!
+//! Every node is referenced once, by the root or by a node before it, so
+//! the nodes form a tree. Decoding fails past [`MAX_DEPTH`] nested nodes.
+
+use std::cell::Cell;
+use std::mem;
+
+use rustc_hash::FxHashMap;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use thiserror::Error;
+
+/// File magic
+pub const MAGIC: &[u8; 4] = b"SYNB";
+
+/// Current format version
+pub const VERSION: u8 = 1;
+
+/// Maximum nesting depth of nodes accepted by the decoder. A parenthesized
+/// group can take more than one node, so this is twice the parser's default
+/// nesting limit.
+pub const MAX_DEPTH: usize = 512;
+
+/// Binary encoding or decoding error
+#[derive(Debug, Error)]
+pub enum BinaryError {
+    /// Input does not start with [`MAGIC`]
+    #[error("not a binary Synton AST")]
+    BadMagic,
+
+    /// Input was written by an unknown format version
+    #[error("unsupported binary AST version {0}")]
+    UnsupportedVersion(u8),
+
+    /// Input ends in the middle of a value
+    #[error("unexpected end of binary AST")]
+    UnexpectedEof,
+
+    /// Reference to a node that does not exist, does not follow its parent
+    /// or was already referenced
+    #[error("invalid node reference {0}")]
+    InvalidNode(u64),
+
+    /// Nodes nested deeper than [`MAX_DEPTH`]
+    #[error("binary AST nesting exceeds the maximum depth of {0}")]
+    RecursionLimit(usize),
+
+    /// Reference past the end of the string table
+    #[error("invalid string reference {0}")]
+    InvalidString(u64),
+
+    /// Bytes left over after a value was read
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+
+    /// Value that cannot be encoded, or does not match the type being read
+    #[error("{0}")]
+    Message(String),
+}
+
+impl ser::Error for BinaryError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl de::Error for BinaryError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, BinaryError>;
+
+/// Encode a value in the binary AST format
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut encoder = Encoder::default();
+    value.serialize(&mut encoder)?;
+
+    let mut out = Vec::with_capacity(encoder.buf.len() * 2);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    write_varint(&mut out, encoder.nodes.len() as u128);
+    for (type_id, payload) in &encoder.nodes {
+        out.push(*type_id);
+        write_varint(&mut out, payload.len() as u128);
+        out.extend_from_slice(payload);
+    }
+    write_varint(&mut out, encoder.strings.len() as u128);
+    for string in &encoder.strings {
+        write_varint(&mut out, string.len() as u128);
+        out.extend_from_slice(string.as_bytes());
+    }
+    write_varint(&mut out, encoder.buf.len() as u128);
+    out.extend_from_slice(&encoder.buf);
+    Ok(out)
+}
+
+/// Decode a value from the binary AST format
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut input = bytes;
+    if take(&mut input, MAGIC.len())? != MAGIC {
+        return Err(BinaryError::BadMagic);
+    }
+    let version = take(&mut input, 1)?[0];
+    if version != VERSION {
+        return Err(BinaryError::UnsupportedVersion(version));
+    }
+
+    let node_count = read_len(&mut input)?;
+    let mut nodes = Vec::with_capacity(node_count.min(input.len()));
+    for _ in 0..node_count {
+        let type_id = take(&mut input, 1)?[0];
+        let len = read_len(&mut input)?;
+        nodes.push((type_id, take(&mut input, len)?));
+    }
+
+    let string_count = read_len(&mut input)?;
+    let mut strings = Vec::with_capacity(string_count.min(input.len()));
+    for _ in 0..string_count {
+        let len = read_len(&mut input)?;
+        let bytes = take(&mut input, len)?;
+        let string = std::str::from_utf8(bytes).map_err(|e| BinaryError::Message(e.to_string()))?;
+        strings.push(string.to_string());
+    }
+
+    let len = read_len(&mut input)?;
+    let root = take(&mut input, len)?;
+    if !input.is_empty() {
+        return Err(BinaryError::TrailingBytes(input.len()));
+    }
+
+    let visited = vec![Cell::new(false); nodes.len()];
+    let decoder = Decoder { nodes, strings, visited };
+    let mut reader = Reader { decoder: &decoder, input: root, node: None, depth: 0 };
+    let value = T::deserialize(&mut reader)?;
+    reader.finish()?;
+    Ok(value)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+fn unzigzag(value: u128) -> i128 {
+    (value >> 1) as i128 ^ -((value & 1) as i128)
+}
+
+fn take<'b>(input: &mut &'b [u8], len: usize) -> Result<&'b [u8]> {
+    if input.len() < len {
+        return Err(BinaryError::UnexpectedEof);
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u128> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let byte = take(input, 1)?[0];
+        value |= u128::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(BinaryError::Message("varint too long".to_string()))
+}
+
+fn read_len(input: &mut &[u8]) -> Result<usize> {
+    let value = read_varint(input)?;
+    usize::try_from(value).map_err(|_| BinaryError::Message(format!("length {} too large", value)))
+}
+
+#[derive(Default)]
+struct Encoder {
+    nodes: Vec<(u8, Vec<u8>)>,
+    strings: Vec<String>,
+    string_ids: FxHashMap<String, u128>,
+    /// Payload of the value being written
+    buf: Vec<u8>,
+    /// Nodes being written, with the payload of their parent
+    open: Vec<(usize, Vec<u8>)>,
+}
+
+impl Encoder {
+    fn string(&mut self, s: &str) {
+        let id = match self.string_ids.get(s) {
+            Some(&id) => id,
+            None => {
+                let id = self.strings.len() as u128;
+                self.strings.push(s.to_string());
+                self.string_ids.insert(s.to_string(), id);
+                id
+            }
+        };
+        write_varint(&mut self.buf, id);
+    }
+
+    fn begin_node(&mut self, variant_index: u32) -> Result<()> {
+        let type_id = u8::try_from(variant_index)
+            .map_err(|_| BinaryError::Message(format!("variant index {} does not fit a type id", variant_index)))?;
+        let index = self.nodes.len();
+        self.nodes.push((type_id, Vec::new()));
+        write_varint(&mut self.buf, (index as u128) << 1 | 1);
+        let parent = mem::take(&mut self.buf);
+        self.open.push((index, parent));
+        Ok(())
+    }
+
+    fn end_node(&mut self) {
+        let (index, parent) = self.open.pop().expect("node was opened");
+        self.nodes[index].1 = mem::replace(&mut self.buf, parent);
+    }
+}
+
+impl ser::Serializer for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.buf.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        write_varint(&mut self.buf, zigzag(v));
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        write_varint(&mut self.buf, v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_u128(u32::from(v).into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.string(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        write_varint(&mut self.buf, v.len() as u128);
+        self.buf.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.buf.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.buf.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<()> {
+        write_varint(&mut self.buf, u128::from(variant_index) << 1);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.begin_node(variant_index)?;
+        value.serialize(&mut *self)?;
+        self.end_node();
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        let len = len.ok_or_else(|| BinaryError::Message("sequence length must be known".to_string()))?;
+        write_varint(&mut self.buf, len as u128);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.begin_node(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        self.serialize_seq(len)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.begin_node(variant_index)?;
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_node();
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Encoder {
+    type Ok = ();
+    type Error = BinaryError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_node();
+        Ok(())
+    }
+}
+
+struct Decoder<'b> {
+    nodes: Vec<(u8, &'b [u8])>,
+    strings: Vec<String>,
+    /// Whether each node has been referenced
+    visited: Vec<Cell<bool>>,
+}
+
+/// Reads values from the payload of one node, or from the root
+struct Reader<'d, 'b> {
+    decoder: &'d Decoder<'b>,
+    input: &'b [u8],
+    /// Index of the node being read. Children must come after their parent,
+    /// which rules out reference cycles.
+    node: Option<usize>,
+    /// Number of nodes enclosing this one
+    depth: usize,
+}
+
+impl<'d, 'b> Reader<'d, 'b> {
+    fn byte(&mut self) -> Result<u8> {
+        Ok(take(&mut self.input, 1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u128> {
+        read_varint(&mut self.input)
+    }
+
+    fn unsigned<T: TryFrom<u128>>(&mut self) -> Result<T> {
+        let value = self.varint()?;
+        T::try_from(value).map_err(|_| BinaryError::Message(format!("integer {} out of range", value)))
+    }
+
+    fn signed<T: TryFrom<i128>>(&mut self) -> Result<T> {
+        let value = unzigzag(self.varint()?);
+        T::try_from(value).map_err(|_| BinaryError::Message(format!("integer {} out of range", value)))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        read_len(&mut self.input)
+    }
+
+    fn string(&mut self) -> Result<&'d str> {
+        let id = self.varint()?;
+        usize::try_from(id)
+            .ok()
+            .and_then(|id| self.decoder.strings.get(id))
+            .map(String::as_str)
+            .ok_or(BinaryError::InvalidString(id as u64))
+    }
+
+    fn child(&self, index: u128) -> Result<(u8, Reader<'d, 'b>)> {
+        let invalid = || BinaryError::InvalidNode(index as u64);
+        let index = usize::try_from(index).map_err(|_| invalid())?;
+        if self.node.is_some_and(|parent| index <= parent) {
+            return Err(invalid());
+        }
+        let (type_id, payload) = *self.decoder.nodes.get(index).ok_or_else(invalid)?;
+        // A node shared by several parents would be decoded once per parent
+        if self.decoder.visited[index].replace(true) {
+            return Err(invalid());
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(BinaryError::RecursionLimit(MAX_DEPTH));
+        }
+        let reader = Reader { decoder: self.decoder, input: payload, node: Some(index), depth: self.depth + 1 };
+        Ok((type_id, reader))
+    }
+
+    fn finish(&self) -> Result<()> {
+        match self.input.len() {
+            0 => Ok(()),
+            n => Err(BinaryError::TrailingBytes(n)),
+        }
+    }
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident => $visit:ident via $read:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.$read()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Reader<'_, '_> {
+    type Error = BinaryError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(BinaryError::Message("the binary AST format is not self-describing".to_string()))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.byte()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            b => Err(BinaryError::Message(format!("invalid bool {}", b))),
+        }
+    }
+
+    deserialize_int! {
+        deserialize_i8 => visit_i8 via signed,
+        deserialize_i16 => visit_i16 via signed,
+        deserialize_i32 => visit_i32 via signed,
+        deserialize_i64 => visit_i64 via signed,
+        deserialize_i128 => visit_i128 via signed,
+        deserialize_u8 => visit_u8 via unsigned,
+        deserialize_u16 => visit_u16 via unsigned,
+        deserialize_u32 => visit_u32 via unsigned,
+        deserialize_u64 => visit_u64 via unsigned,
+        deserialize_u128 => visit_u128 via unsigned,
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = take(&mut self.input, 4)?;
+        visitor.visit_f32(f32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = take(&mut self.input, 8)?;
+        visitor.visit_f64(f64::from_le_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let code: u32 = self.unsigned()?;
+        let c = char::from_u32(code).ok_or_else(|| BinaryError::Message(format!("invalid char {}", code)))?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_str(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.len()?;
+        visitor.visit_bytes(take(&mut self.input, len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.byte()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            b => Err(BinaryError::Message(format!("invalid option tag {}", b))),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let left = self.len()?;
+        visitor.visit_seq(Counted { reader: self, left })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Counted { reader: self, left: len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Counted { reader: self, left: len })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let left = self.len()?;
+        visitor.visit_map(Counted { reader: self, left })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Counted { reader: self, left: fields.len() })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let tag = self.varint()?;
+        if tag & 1 == 0 {
+            let index = u32::try_from(tag >> 1).map_err(|_| BinaryError::Message(format!("invalid variant {}", tag)))?;
+            return visitor.visit_enum(Variant { index, content: None });
+        }
+        let (type_id, content) = self.child(tag >> 1)?;
+        visitor.visit_enum(Variant { index: type_id.into(), content: Some(content) })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(BinaryError::Message("identifiers are not stored in the binary AST format".to_string()))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// A fixed number of values read in a row
+struct Counted<'r, 'd, 'b> {
+    reader: &'r mut Reader<'d, 'b>,
+    left: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Counted<'_, '_, '_> {
+    type Error = BinaryError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        seed.deserialize(&mut *self.reader).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for Counted<'_, '_, '_> {
+    type Error = BinaryError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        seed.deserialize(&mut *self.reader).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.reader)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+/// An enum variant, with the node holding its data if it has any
+struct Variant<'d, 'b> {
+    index: u32,
+    content: Option<Reader<'d, 'b>>,
+}
+
+impl Variant<'_, '_> {
+    fn read<T>(self, f: impl FnOnce(&mut Reader<'_, '_>) -> Result<T>) -> Result<T> {
+        let mut reader = self
+            .content
+            .ok_or_else(|| BinaryError::Message(format!("variant {} has no data", self.index)))?;
+        let value = f(&mut reader)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'_, '_> {
+    type Error = BinaryError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let value = seed.deserialize(self.index.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, '_> {
+    type Error = BinaryError;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.content {
+            None => Ok(()),
+            Some(content) => content.finish(),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        self.read(|reader| seed.deserialize(reader))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.read(|reader| visitor.visit_seq(Counted { reader, left: len }))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        self.read(|reader| visitor.visit_seq(Counted { reader, left: fields.len() }))
+    }
+}
@@ -0,0 +1,785 @@
+//! Structural diff between two versions of a module
+//!
+//! [`diff`] compares modules by structure rather than by text, so layout,
+//! spans and comments never show up as changes. Declarations are matched by
+//! kind and name, top-level statements by [`NodeId`] when both versions have
+//! one and by content otherwise. Matched functions are compared part by
+//! part: signature, each contract, and the body down to the smallest
+//! expression that differs.
+//!
+//! ```
+//! use synton_ast::diff::diff;
+//! use synton_ast::{Module, ModuleId};
+//!
+//! let module = Module::new(ModuleId::new("example".into()));
+//! assert!(diff(&module, &module.clone()).is_empty());
+//! ```
+
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AssignTarget, Contract, ContractKind, Effects, ExportDecl, Expr, ExprKind, FnDecl, ImportDecl, ImportKind, Literal,
+    MatchArm, Module, NodeId, Param, Pattern, Stmt, StmtKind, Type,
+};
+
+/// What happened to a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Only in the new module
+    Added,
+    /// Only in the old module
+    Removed,
+    /// In both, with different content
+    Changed,
+}
+
+/// Kind of node a change is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    /// Import declaration
+    Import,
+    /// Export declaration
+    Export,
+    /// Function declaration
+    Function,
+    /// Parameters and return type of a function
+    Signature,
+    /// Contract of a function
+    Contract,
+    /// Struct declaration
+    Struct,
+    /// Enum declaration
+    Enum,
+    /// Type alias
+    TypeAlias,
+    /// Const declaration
+    Const,
+    /// Top-level `let` binding
+    Variable,
+    /// Statement
+    Statement,
+    /// Expression
+    Expression,
+}
+
+/// A single difference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    /// What happened
+    pub kind: ChangeKind,
+    /// What it happened to
+    pub item: ItemKind,
+    /// Location of the node, such as `fn fact/body/else/args[1]`
+    pub path: String,
+    /// Old version of the node, unless it was added
+    pub old: Option<String>,
+    /// New version of the node, unless it was removed
+    pub new: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.old, &self.new) {
+            (ChangeKind::Added, _, Some(new)) => write!(f, "+ {}: {}", self.path, new),
+            (ChangeKind::Removed, Some(old), _) => write!(f, "- {}: {}", self.path, old),
+            (_, Some(old), Some(new)) => write!(f, "~ {}: {} => {}", self.path, old, new),
+            _ => write!(f, "~ {}", self.path),
+        }
+    }
+}
+
+/// Differences between two modules
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleDiff {
+    /// Changes, declarations before statements
+    pub changes: Vec<Change>,
+}
+
+impl ModuleDiff {
+    /// Check if the modules are structurally equal
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ModuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two versions of a module
+pub fn diff(old: &Module, new: &Module) -> ModuleDiff {
+    let mut d = Differ::default();
+
+    d.matched(
+        ItemKind::Import,
+        &keyed(&old.imports, |i| format!("import {}", import_name(i))),
+        &keyed(&new.imports, |i| format!("import {}", import_name(i))),
+        |d, path, a, b| d.whole(ItemKind::Import, path, import(a), import(b)),
+        import,
+    );
+    d.matched(
+        ItemKind::Export,
+        &keyed(&old.exports, |e| format!("export {}", e.name)),
+        &keyed(&new.exports, |e| format!("export {}", e.name)),
+        |d, path, a, b| d.whole(ItemKind::Export, path, export(a), export(b)),
+        export,
+    );
+
+    let is_decl = |(_, s): &(usize, &Stmt)| decl_key(s).is_some();
+    let (old_decls, old_rest): (Vec<_>, Vec<_>) = old.stmts.iter().enumerate().partition(is_decl);
+    let (new_decls, new_rest): (Vec<_>, Vec<_>) = new.stmts.iter().enumerate().partition(is_decl);
+    let decl_path = |(_, s): &(usize, &Stmt)| decl_key(s).map(|(_, path)| path).unwrap_or_default();
+    for (path, old_decl, new_decl) in pair_keyed(&keyed(&old_decls, decl_path), &keyed(&new_decls, decl_path)) {
+        match (old_decl, new_decl) {
+            (Some((_, a)), Some((_, b))) => d.decl(&path, a, b),
+            (Some((_, a)), None) => d.push(ChangeKind::Removed, decl_item(a), &path, Some(summary(a)), None),
+            (None, Some((_, b))) => d.push(ChangeKind::Added, decl_item(b), &path, None, Some(summary(b))),
+            (None, None) => {}
+        }
+    }
+
+    // Statements carrying the same node id are the same statement, whatever
+    // their position. The rest are aligned on their content.
+    let node = |(_, s): &&(usize, &Stmt)| match &s.kind {
+        StmtKind::Expr(e) => e.id,
+        _ => None,
+    };
+    let new_ids: FxHashMap<NodeId, usize> =
+        new_rest.iter().enumerate().filter_map(|(j, s)| Some((node(&s)?, j))).collect();
+    let mut by_id = FxHashMap::default();
+    for (i, s) in old_rest.iter().enumerate() {
+        if let Some(&j) = node(&s).and_then(|id| new_ids.get(&id)) {
+            by_id.insert(i, j);
+        }
+    }
+    let old_free: Vec<_> = (0..old_rest.len()).filter(|i| !by_id.contains_key(i)).collect();
+    let matched_new: Vec<_> = by_id.values().copied().collect();
+    let new_free: Vec<_> = (0..new_rest.len()).filter(|j| !matched_new.contains(j)).collect();
+    let mut pairs: Vec<_> = by_id.into_iter().map(|(i, j)| (Some(i), Some(j))).collect();
+    let old_text: Vec<_> = old_free.iter().map(|&i| stmt(old_rest[i].1)).collect();
+    let new_text: Vec<_> = new_free.iter().map(|&j| stmt(new_rest[j].1)).collect();
+    pairs.extend(
+        align(&old_text, &new_text)
+            .into_iter()
+            .map(|(i, j)| (i.map(|i| old_free[i]), j.map(|j| new_free[j]))),
+    );
+    pairs.sort_by_key(|&(i, j)| j.map(|j| new_rest[j].0).or(i.map(|i| old_rest[i].0)));
+
+    for (i, j) in pairs {
+        let (old_stmt, new_stmt) = (i.map(|i| old_rest[i]), j.map(|j| new_rest[j]));
+        let index = new_stmt.or(old_stmt).map_or(0, |(index, _)| index);
+        d.stmt_pair(&format!("stmt[{}]", index), old_stmt.map(|(_, s)| s), new_stmt.map(|(_, s)| s));
+    }
+
+    ModuleDiff { changes: d.changes }
+}
+
+#[derive(Default)]
+struct Differ {
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn push(&mut self, kind: ChangeKind, item: ItemKind, path: &str, old: Option<String>, new: Option<String>) {
+        self.changes.push(Change { kind, item, path: path.to_string(), old, new });
+    }
+
+    /// Compare two nodes as a whole
+    fn whole(&mut self, item: ItemKind, path: &str, old: String, new: String) {
+        if old != new {
+            self.push(ChangeKind::Changed, item, path, Some(old), Some(new));
+        }
+    }
+
+    /// Compare nodes matched by key, reporting unmatched ones
+    fn matched<T>(
+        &mut self,
+        item: ItemKind,
+        old: &[(String, &T)],
+        new: &[(String, &T)],
+        changed: impl Fn(&mut Self, &str, &T, &T),
+        render: impl Fn(&T) -> String,
+    ) {
+        for (path, a, b) in pair_keyed(old, new) {
+            match (a, b) {
+                (Some(a), Some(b)) => changed(self, &path, a, b),
+                (Some(a), None) => self.push(ChangeKind::Removed, item, &path, Some(render(a)), None),
+                (None, Some(b)) => self.push(ChangeKind::Added, item, &path, None, Some(render(b))),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Compare two declarations with the same kind and name
+    fn decl(&mut self, path: &str, old: &Stmt, new: &Stmt) {
+        match (&old.kind, &new.kind) {
+            (StmtKind::FnDecl(a), StmtKind::FnDecl(b)) => self.function(path, a, b),
+            (
+                StmtKind::Let { ty: ta, init: Some(a), mutable: ma, .. },
+                StmtKind::Let { ty: tb, init: Some(b), mutable: mb, .. },
+            ) if ma == mb && ta.as_ref().map(ty) == tb.as_ref().map(ty) => self.expr(path, a, b),
+            (StmtKind::Const { ty: ta, value: a, .. }, StmtKind::Const { ty: tb, value: b, .. })
+                if ty(ta) == ty(tb) =>
+            {
+                self.expr(path, a, b)
+            }
+            _ => self.whole(decl_item(new), path, stmt(old), stmt(new)),
+        }
+    }
+
+    fn function(&mut self, path: &str, old: &FnDecl, new: &FnDecl) {
+        self.whole(ItemKind::Signature, &format!("{}/signature", path), signature(old), signature(new));
+
+        let contract_path = |c: &Contract| format!("{}/{}", path, contract_keyword(c.kind));
+        self.matched(
+            ItemKind::Contract,
+            &keyed(&old.contracts, contract_path),
+            &keyed(&new.contracts, contract_path),
+            |d, path, a, b| d.whole(ItemKind::Contract, path, contract(a), contract(b)),
+            contract,
+        );
+
+        let body = format!("{}/body", path);
+        match (&old.body, &new.body) {
+            (Some(a), Some(b)) => self.expr(&body, a, b),
+            (Some(a), None) => self.push(ChangeKind::Removed, ItemKind::Expression, &body, Some(expr(a)), None),
+            (None, Some(b)) => self.push(ChangeKind::Added, ItemKind::Expression, &body, None, Some(expr(b))),
+            (None, None) => {}
+        }
+    }
+
+    /// Compare statements paired by [`align`]
+    fn stmt_pair(&mut self, path: &str, old: Option<&Stmt>, new: Option<&Stmt>) {
+        match (old, new) {
+            (Some(a), Some(b)) => self.stmt(path, a, b),
+            (Some(a), None) => self.push(ChangeKind::Removed, ItemKind::Statement, path, Some(stmt(a)), None),
+            (None, Some(b)) => self.push(ChangeKind::Added, ItemKind::Statement, path, None, Some(stmt(b))),
+            (None, None) => {}
+        }
+    }
+
+    fn stmt(&mut self, path: &str, old: &Stmt, new: &Stmt) {
+        match (&old.kind, &new.kind) {
+            (StmtKind::Expr(a), StmtKind::Expr(b)) => self.expr(path, a, b),
+            (
+                StmtKind::If { cond: ca, then_branch: ta, else_branch: ea },
+                StmtKind::If { cond: cb, then_branch: tb, else_branch: eb },
+            ) => {
+                self.expr(&format!("{}/cond", path), ca, cb);
+                self.stmt(&format!("{}/then", path), ta, tb);
+                self.stmt_pair(&format!("{}/else", path), ea.as_deref(), eb.as_deref());
+            }
+            (StmtKind::While { cond: ca, body: ba }, StmtKind::While { cond: cb, body: bb }) => {
+                self.expr(&format!("{}/cond", path), ca, cb);
+                self.stmt(&format!("{}/body", path), ba, bb);
+            }
+            (StmtKind::Loop { body: a }, StmtKind::Loop { body: b }) => self.stmt(&format!("{}/body", path), a, b),
+            (StmtKind::Block(a), StmtKind::Block(b)) => self.stmts(path, a, b),
+            (StmtKind::Return(Some(a)), StmtKind::Return(Some(b)))
+            | (StmtKind::Break(Some(a)), StmtKind::Break(Some(b))) => self.expr(&format!("{}/value", path), a, b),
+            _ if decl_key(old).is_some() && decl_key(old) == decl_key(new) => self.decl(path, old, new),
+            _ => self.whole(ItemKind::Statement, path, stmt(old), stmt(new)),
+        }
+    }
+
+    fn stmts(&mut self, path: &str, old: &[Stmt], new: &[Stmt]) {
+        let old_text: Vec<_> = old.iter().map(stmt).collect();
+        let new_text: Vec<_> = new.iter().map(stmt).collect();
+        for (i, j) in align(&old_text, &new_text) {
+            let index = j.or(i).unwrap_or_default();
+            self.stmt_pair(&format!("{}/stmt[{}]", path, index), i.map(|i| &old[i]), j.map(|j| &new[j]));
+        }
+    }
+
+    fn expr(&mut self, path: &str, old: &Expr, new: &Expr) {
+        let (old_text, new_text) = (expr(old), expr(new));
+        if old_text == new_text {
+            return;
+        }
+        let (old_head, old_parts) = parts(old);
+        let (new_head, new_parts) = parts(new);
+        if old_head != new_head || old_parts.len() != new_parts.len() {
+            self.push(ChangeKind::Changed, ItemKind::Expression, path, Some(old_text), Some(new_text));
+            return;
+        }
+
+        for ((label, a), (_, b)) in old_parts.into_iter().zip(new_parts) {
+            let path = format!("{}/{}", path, label);
+            match (a, b) {
+                (Part::One(a), Part::One(b)) => self.expr(&path, a, b),
+                (Part::Opt(a), Part::Opt(b)) => self.opt_expr(&path, a, b),
+                (Part::Many(a), Part::Many(b)) => {
+                    let old_text: Vec<_> = a.iter().map(|e| expr(e)).collect();
+                    let new_text: Vec<_> = b.iter().map(|e| expr(e)).collect();
+                    for (i, j) in align(&old_text, &new_text) {
+                        let index = j.or(i).unwrap_or_default();
+                        let path = format!("{}[{}]", path, index);
+                        self.opt_expr(&path, i.map(|i| a[i]), j.map(|j| b[j]));
+                    }
+                }
+                (Part::Block(sa, ta), Part::Block(sb, tb)) => {
+                    self.stmts(&path, sa, sb);
+                    self.opt_expr(&format!("{}/value", path), ta, tb);
+                }
+                _ => unreachable!("equal heads have the same parts"),
+            }
+        }
+    }
+
+    fn opt_expr(&mut self, path: &str, old: Option<&Expr>, new: Option<&Expr>) {
+        match (old, new) {
+            (Some(a), Some(b)) => self.expr(path, a, b),
+            (Some(a), None) => self.push(ChangeKind::Removed, ItemKind::Expression, path, Some(expr(a)), None),
+            (None, Some(b)) => self.push(ChangeKind::Added, ItemKind::Expression, path, None, Some(expr(b))),
+            (None, None) => {}
+        }
+    }
+}
+
+/// Child expressions of a node, as compared by [`Differ::expr`]
+enum Part<'a> {
+    One(&'a Expr),
+    Opt(Option<&'a Expr>),
+    Many(Vec<&'a Expr>),
+    Block(&'a [Stmt], Option<&'a Expr>),
+}
+
+/// Split an expression into what must be equal for its children to be
+/// compared one by one, and the labelled children
+fn parts(e: &Expr) -> (String, Vec<(String, Part<'_>)>) {
+    match &e.kind {
+        ExprKind::Unary { op, arg } => (op.token().to_string(), vec![one("arg", arg)]),
+        ExprKind::Binary { op, left, right } => {
+            (op.token().to_string(), vec![one("left", left), one("right", right)])
+        }
+        ExprKind::Compare { op, left, right } => {
+            (op.token().to_string(), vec![one("left", left), one("right", right)])
+        }
+        ExprKind::Call { callee, args } => {
+            ("call".into(), vec![one("callee", callee), ("args".into(), Part::Many(args.iter().collect()))])
+        }
+        ExprKind::MethodCall { object, method, args } => (
+            format!(".call {}", method),
+            vec![one("object", object), ("args".into(), Part::Many(args.iter().collect()))],
+        ),
+        ExprKind::Index { base, index } => (".get".into(), vec![one("base", base), one("index", index)]),
+        ExprKind::Field { base, name } => (format!(".field {}", name), vec![one("base", base)]),
+        ExprKind::Array(items) => ("array".into(), vec![("items".into(), Part::Many(items.iter().collect()))]),
+        ExprKind::Tuple(items) => ("tuple".into(), vec![("items".into(), Part::Many(items.iter().collect()))]),
+        ExprKind::Struct { ty, fields } => {
+            let names: Vec<_> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            let parts = fields.iter().map(|(name, value)| one(name, value)).collect();
+            (format!("{} {}", ty, names.join(" ")), parts)
+        }
+        ExprKind::Lambda { params: ps, effects, body } => {
+            (format!("fn {}", lambda_signature(ps, *effects)), vec![one("body", body)])
+        }
+        ExprKind::Block(stmts, tail) => ("block".into(), vec![(String::new(), Part::Block(stmts, tail.as_deref()))]),
+        ExprKind::If { cond, then_branch, else_branch } => (
+            "if".into(),
+            vec![
+                one("cond", cond),
+                one("then", then_branch),
+                ("else".into(), Part::Opt(else_branch.as_deref())),
+            ],
+        ),
+        ExprKind::Loop { body } => ("loop".into(), vec![one("body", body)]),
+        ExprKind::Break(value) => ("break".into(), vec![("value".into(), Part::Opt(value.as_deref()))]),
+        ExprKind::Return(value) => ("return".into(), vec![("value".into(), Part::Opt(value.as_deref()))]),
+        ExprKind::Match { scrutinee, arms } => {
+            let patterns: Vec<_> = arms.iter().map(|arm| pattern(&arm.pattern)).collect();
+            let mut parts = vec![one("scrutinee", scrutinee)];
+            for (i, arm) in arms.iter().enumerate() {
+                parts.push((format!("arms[{}].guard", i), Part::Opt(arm.guard.as_deref())));
+                parts.push((format!("arms[{}].body", i), Part::One(&arm.body)));
+            }
+            (format!("match {}", patterns.join(" | ")), parts)
+        }
+        ExprKind::Some(inner) => ("some".into(), vec![one("value", inner)]),
+        ExprKind::As { expr: inner, ty: t } => (format!("as {}", ty(t)), vec![one("value", inner)]),
+        ExprKind::Literal(_)
+        | ExprKind::Var { .. }
+        | ExprKind::Continue
+        | ExprKind::None
+        | ExprKind::SizeOf(_)
+        | ExprKind::Error => (expr(e), Vec::new()),
+    }
+}
+
+fn one<'a>(label: &str, e: &'a Expr) -> (String, Part<'a>) {
+    (label.to_string(), Part::One(e))
+}
+
+/// Pair up two sequences of rendered nodes. Equal nodes are paired along a
+/// longest common subsequence; between two such anchors, nodes of the same
+/// form (`(while`, `(let`, ...) are paired the same way, so an edited
+/// statement shows up as changed rather than as removed and added.
+fn align(old: &[String], new: &[String]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut pairs = Vec::new();
+    let (mut gap_old, mut gap_new) = (Vec::new(), Vec::new());
+    let fill_gap = |pairs: &mut Vec<_>, gap_old: &mut Vec<usize>, gap_new: &mut Vec<usize>| {
+        let heads_old: Vec<_> = gap_old.iter().map(|&i| head(&old[i])).collect();
+        let heads_new: Vec<_> = gap_new.iter().map(|&j| head(&new[j])).collect();
+        for (i, j) in lcs(&heads_old, &heads_new) {
+            pairs.push((i.map(|i| gap_old[i]), j.map(|j| gap_new[j])));
+        }
+        gap_old.clear();
+        gap_new.clear();
+    };
+    for pair in lcs(old, new) {
+        match pair {
+            (Some(i), None) => gap_old.push(i),
+            (None, Some(j)) => gap_new.push(j),
+            _ => {
+                fill_gap(&mut pairs, &mut gap_old, &mut gap_new);
+                pairs.push(pair);
+            }
+        }
+    }
+    fill_gap(&mut pairs, &mut gap_old, &mut gap_new);
+    pairs
+}
+
+/// Leading form of a rendered node: `(while` for `(while c body)`
+fn head(text: &str) -> &str {
+    text.split(' ').next().unwrap_or(text)
+}
+
+/// Longest common subsequence of two sequences, as pairs of indices in
+/// order. Elements outside it are paired with `None`, removals first.
+fn lcs<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    let (n, m) = (old.len(), new.len());
+    // len[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut len = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            len[i][j] = if old[i] == new[j] { len[i + 1][j + 1] + 1 } else { len[i + 1][j].max(len[i][j + 1]) };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            pairs.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && len[i + 1][j] >= len[i][j + 1]) {
+            pairs.push((Some(i), None));
+            i += 1;
+        } else {
+            pairs.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Attach a key to each node. Repeated keys get an occurrence suffix, so
+/// the second `let x` is `let x#1`.
+fn keyed<T>(items: &[T], key: impl Fn(&T) -> String) -> Vec<(String, &T)> {
+    let mut seen: FxHashMap<String, usize> = FxHashMap::default();
+    items
+        .iter()
+        .map(|item| {
+            let key = key(item);
+            let n = seen.entry(key.clone()).or_default();
+            *n += 1;
+            match *n {
+                1 => (key, item),
+                n => (format!("{}#{}", key, n - 1), item),
+            }
+        })
+        .collect()
+}
+
+/// Match keyed nodes: old nodes in order, then nodes only in the new module
+fn pair_keyed<'a, T>(old: &[(String, &'a T)], new: &[(String, &'a T)]) -> Vec<(String, Option<&'a T>, Option<&'a T>)> {
+    let new_by_key: FxHashMap<&str, &'a T> = new.iter().map(|(key, item)| (key.as_str(), *item)).collect();
+    let old_keys: FxHashMap<&str, ()> = old.iter().map(|(key, _)| (key.as_str(), ())).collect();
+    let mut pairs: Vec<_> = old
+        .iter()
+        .map(|(key, item)| (key.clone(), Some(*item), new_by_key.get(key.as_str()).copied()))
+        .collect();
+    pairs.extend(
+        new.iter()
+            .filter(|(key, _)| !old_keys.contains_key(key.as_str()))
+            .map(|(key, item)| (key.clone(), None, Some(*item))),
+    );
+    pairs
+}
+
+/// Kind and path of a named declaration
+fn decl_key(s: &Stmt) -> Option<(ItemKind, String)> {
+    match &s.kind {
+        StmtKind::FnDecl(decl) => Some((ItemKind::Function, format!("fn {}", decl.name))),
+        StmtKind::StructDecl(decl) => Some((ItemKind::Struct, format!("struct {}", decl.name))),
+        StmtKind::EnumDecl(decl) => Some((ItemKind::Enum, format!("enum {}", decl.name))),
+        StmtKind::TypeAlias { name, .. } => Some((ItemKind::TypeAlias, format!("type {}", name))),
+        StmtKind::Const { name, .. } => Some((ItemKind::Const, format!("const {}", name))),
+        StmtKind::Let { name, .. } => Some((ItemKind::Variable, format!("let {}", name))),
+        _ => None,
+    }
+}
+
+fn decl_item(s: &Stmt) -> ItemKind {
+    decl_key(s).map_or(ItemKind::Statement, |(item, _)| item)
+}
+
+/// Short form of an added or removed declaration
+fn summary(s: &Stmt) -> String {
+    match &s.kind {
+        StmtKind::FnDecl(decl) => format!("(fn {} {})", decl.name, signature(decl)),
+        _ => stmt(s),
+    }
+}
+
+fn import_name(i: &ImportDecl) -> &str {
+    i.alias.as_deref().unwrap_or(&i.name)
+}
+
+fn import(i: &ImportDecl) -> String {
+    let source = match &i.kind {
+        ImportKind::Hash { algorithm, hash } => format!("hash:{}:{}", algorithm, hash),
+        ImportKind::Semantic { query } => format!("sem:{:?}", query),
+        ImportKind::Path { path } => format!("{:?}", path),
+    };
+    match &i.alias {
+        Some(alias) => format!("(import {} {} as {})", i.name, source, alias),
+        None => format!("(import {} {})", i.name, source),
+    }
+}
+
+fn export(e: &ExportDecl) -> String {
+    match &e.alias {
+        Some(alias) => format!("(export {} as {})", e.name, alias),
+        None => format!("(export {})", e.name),
+    }
+}
+
+// Rendering. Nodes are printed in source syntax without spans or ids, which
+// makes the text both the equality key and what the diff shows.
+
+fn signature(decl: &FnDecl) -> String {
+    let signature = match &decl.ret_type {
+        Some(ret) => format!("{} -> {}", params(&decl.params), ty(ret)),
+        None => params(&decl.params),
+    };
+    match decl.effects {
+        Some(effects) => format!("{} ! {}", signature, effects),
+        None => signature,
+    }
+}
+
+fn lambda_signature(ps: &[Param], effects: Option<Effects>) -> String {
+    match effects {
+        Some(effects) => format!("{} ! {}", params(ps), effects),
+        None => params(ps),
+    }
+}
+
+fn params(params: &[Param]) -> String {
+    let params: Vec<_> = params
+        .iter()
+        .map(|p| match &p.ty {
+            Some(t) => format!("{}: {}", p.name, ty(t)),
+            None => p.name.clone(),
+        })
+        .collect();
+    format!("[{}]", params.join(" "))
+}
+
+fn contract_keyword(kind: ContractKind) -> &'static str {
+    match kind {
+        ContractKind::Pre => "pre",
+        ContractKind::Post => "post",
+        ContractKind::Invariant => "inv",
+        ContractKind::Assert => "assert",
+    }
+}
+
+fn contract(c: &Contract) -> String {
+    format!("@{}({})", contract_keyword(c.kind), expr(&c.expr))
+}
+
+fn list<T>(items: &[T], f: impl Fn(&T) -> String) -> String {
+    items.iter().map(f).collect::<Vec<_>>().join(" ")
+}
+
+fn stmt(s: &Stmt) -> String {
+    match &s.kind {
+        StmtKind::Expr(e) => expr(e),
+        StmtKind::Let { name, ty: t, init, mutable, .. } => {
+            let mut text = format!("(let {}{}", if *mutable { "mut " } else { "" }, name);
+            if let Some(t) = t {
+                text += &format!(": {}", ty(t));
+            }
+            if let Some(init) = init {
+                text += &format!(" = {}", expr(init));
+            }
+            text + ")"
+        }
+        StmtKind::Assign { target, value } => {
+            let target = match target {
+                AssignTarget::Var(name) => name.clone(),
+                AssignTarget::Index { base, index } => format!("(.get {} {})", expr(base), expr(index)),
+                AssignTarget::Field { base, name } => format!("(.field {} {})", expr(base), name),
+                AssignTarget::Deref(e) => format!("(* {})", expr(e)),
+            };
+            format!("(set {} {})", target, expr(value))
+        }
+        StmtKind::Block(stmts) => format!("{{{}}}", list(stmts, stmt)),
+        StmtKind::If { cond, then_branch, else_branch } => match else_branch {
+            Some(e) => format!("(branch {} {} {})", expr(cond), stmt(then_branch), stmt(e)),
+            None => format!("(branch {} {})", expr(cond), stmt(then_branch)),
+        },
+        StmtKind::Loop { body } => format!("(loop {})", stmt(body)),
+        StmtKind::While { cond, body } => format!("(while {} {})", expr(cond), stmt(body)),
+        StmtKind::For { var, iter, body } => format!("(for {} in {} {})", var, expr(iter), stmt(body)),
+        StmtKind::Break(value) => keyword_opt("break", value.as_deref()),
+        StmtKind::Continue => "(continue)".into(),
+        StmtKind::Return(value) => keyword_opt("return", value.as_deref()),
+        StmtKind::FnDecl(decl) => {
+            let mut text = format!("(fn {} {}", decl.name, signature(decl));
+            for c in &decl.contracts {
+                text += &format!(" {}", contract(c));
+            }
+            if let Some(body) = &decl.body {
+                text += &format!(" {}", expr(body));
+            }
+            text + ")"
+        }
+        StmtKind::StructDecl(decl) => {
+            let fields = list(&decl.fields, |f| format!("{}: {}", f.name, ty(&f.ty)));
+            format!("(struct {}{} {})", decl.name, generics(&decl.params), fields)
+        }
+        StmtKind::EnumDecl(decl) => {
+            let variants = list(&decl.variants, |v| match &v.types[..] {
+                [] => v.name.clone(),
+                types => format!("({} {})", v.name, list(types, ty)),
+            });
+            format!("(enum {}{} {})", decl.name, generics(&decl.params), variants)
+        }
+        StmtKind::TypeAlias { name, params, ty: t } => format!("(type {}{} = {})", name, generics(params), ty(t)),
+        StmtKind::Const { name, ty: t, value } => format!("(const {}: {} = {})", name, ty(t), expr(value)),
+        StmtKind::Contract(c) => contract(c),
+        StmtKind::Empty => ";".into(),
+        StmtKind::Error => "<error>".into(),
+    }
+}
+
+fn generics(params: &[String]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", params.join(", "))
+    }
+}
+
+fn keyword_opt(keyword: &str, value: Option<&Expr>) -> String {
+    match value {
+        Some(value) => format!("({} {})", keyword, expr(value)),
+        None => format!("({})", keyword),
+    }
+}
+
+fn expr(e: &Expr) -> String {
+    match &e.kind {
+        ExprKind::Literal(lit) => literal(lit),
+        ExprKind::Var { name, .. } => name.clone(),
+        ExprKind::Unary { op, arg } => format!("({} {})", op.token(), expr(arg)),
+        ExprKind::Binary { op, left, right } => format!("({} {} {})", op.token(), expr(left), expr(right)),
+        ExprKind::Compare { op, left, right } => format!("({} {} {})", op.token(), expr(left), expr(right)),
+        ExprKind::Call { callee, args } if args.is_empty() => format!("(call:{})", expr(callee)),
+        ExprKind::Call { callee, args } => format!("({} {})", expr(callee), list(args, expr)),
+        ExprKind::MethodCall { object, method, args } if args.is_empty() => {
+            format!("(.call {} {})", expr(object), method)
+        }
+        ExprKind::MethodCall { object, method, args } => {
+            format!("(.call {} {} {})", expr(object), method, list(args, expr))
+        }
+        ExprKind::Index { base, index } => format!("(.get {} {})", expr(base), expr(index)),
+        ExprKind::Field { base, name } => format!("(.field {} {})", expr(base), name),
+        ExprKind::Array(items) => format!("[{}]", list(items, expr)),
+        ExprKind::Tuple(items) => format!("({})", items.iter().map(expr).collect::<Vec<_>>().join(", ")),
+        ExprKind::Struct { ty, fields } => {
+            format!("({} {})", ty, list(fields, |(name, value)| format!("{}: {}", name, expr(value))))
+        }
+        ExprKind::Lambda { params: ps, effects, body } => {
+            format!("(fn {} {})", lambda_signature(ps, *effects), expr(body))
+        }
+        ExprKind::Block(stmts, tail) => {
+            let mut items: Vec<_> = stmts.iter().map(stmt).collect();
+            items.extend(tail.as_deref().map(expr));
+            format!("{{{}}}", items.join(" "))
+        }
+        ExprKind::If { cond, then_branch, else_branch } => match else_branch {
+            Some(e) => format!("(if {} {} {})", expr(cond), expr(then_branch), expr(e)),
+            None => format!("(if {} {})", expr(cond), expr(then_branch)),
+        },
+        ExprKind::Loop { body } => format!("(loop {})", expr(body)),
+        ExprKind::Break(value) => keyword_opt("break", value.as_deref()),
+        ExprKind::Continue => "(continue)".into(),
+        ExprKind::Return(value) => keyword_opt("return", value.as_deref()),
+        ExprKind::Match { scrutinee, arms } => format!("(match {} {})", expr(scrutinee), list(arms, arm)),
+        ExprKind::Some(inner) => format!("(some {})", expr(inner)),
+        ExprKind::None => "(none)".into(),
+        ExprKind::As { expr: inner, ty: t } => format!("(as {} {})", expr(inner), ty(t)),
+        ExprKind::SizeOf(t) => format!("(size_of {})", ty(t)),
+        ExprKind::Error => "<error>".into(),
+    }
+}
+
+fn arm(arm: &MatchArm) -> String {
+    match &arm.guard {
+        Some(guard) => format!("{} if {} => {}", pattern(&arm.pattern), expr(guard), expr(&arm.body)),
+        None => format!("{} => {}", pattern(&arm.pattern), expr(&arm.body)),
+    }
+}
+
+fn pattern(p: &Pattern) -> String {
+    match p {
+        Pattern::Wildcard => "_".into(),
+        Pattern::Literal(lit) => literal(lit),
+        Pattern::Bind(name) => name.clone(),
+        Pattern::Or(alternatives) => format!("(| {})", list(alternatives, pattern)),
+        Pattern::Struct { ty, fields } => {
+            format!("({} {})", ty, list(fields, |(name, p)| format!("{}: {}", name, pattern(p))))
+        }
+        Pattern::Tuple(items) => format!("({})", items.iter().map(pattern).collect::<Vec<_>>().join(", ")),
+        Pattern::Slice(items) => format!("[{}]", list(items, pattern)),
+        Pattern::Variant { name, args } if args.is_empty() => name.clone(),
+        Pattern::Variant { name, args } => format!("({} {})", name, list(args, pattern)),
+        Pattern::Some(inner) => format!("(some {})", pattern(inner)),
+        Pattern::None => "(none)".into(),
+    }
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(n) => n.to_string(),
+        Literal::Float(x) => format!("{:?}", x),
+        Literal::TypedInteger(n, t) => format!("{}{}", n, t.name()),
+        Literal::TypedFloat(x, t) => format!("{:?}{}", x, t.name()),
+        Literal::String(s) => format!("{:?}", s),
+        Literal::Char(c) => format!("{:?}", c),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Byte(b) => format!("b{:?}", *b as char),
+        Literal::Bytes(bytes) => format!("b{:?}", String::from_utf8_lossy(bytes)),
+        Literal::Unit => "()".into(),
+    }
+}
+
+fn ty(t: &Type) -> String {
+    t.to_string()
+}
@@ -0,0 +1,125 @@
+//! Effects of evaluating code
+//!
+//! An [`Effects`] value is a set of [`Effect`]s. The empty set is pure:
+//! pure code computes a value and does nothing else, so it can be skipped,
+//! repeated or evaluated by a verifier.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Something evaluating code can do besides computing a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Effect {
+    /// Input or output, such as printing
+    Io,
+    /// Calling a function provided by the host
+    Host,
+    /// Depending on something other than its inputs, such as the time
+    Nondet,
+    /// Failing at run time, such as dividing by zero
+    Panic,
+}
+
+impl Effect {
+    /// Every effect
+    pub const ALL: [Effect; 4] = [Effect::Io, Effect::Host, Effect::Nondet, Effect::Panic];
+
+    /// Name of the effect, as in messages
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Io => "io",
+            Self::Host => "host",
+            Self::Nondet => "nondet",
+            Self::Panic => "panic",
+        }
+    }
+
+    /// Effect with the given name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|effect| effect.name() == name)
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A set of effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Effects(u8);
+
+impl Effects {
+    /// No effects
+    pub const PURE: Effects = Effects(0);
+    /// Every effect, for code whose effects are not known
+    pub const ALL: Effects = Effects(0b1111);
+
+    /// Set of one effect
+    pub const fn only(effect: Effect) -> Self {
+        Self(effect.bit())
+    }
+
+    /// Check if there are no effects
+    pub const fn is_pure(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check if an effect is in the set
+    pub const fn contains(self, effect: Effect) -> bool {
+        self.0 & effect.bit() != 0
+    }
+
+    /// Check if every effect in this set is in `other`
+    pub const fn is_subset(self, other: Effects) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    /// Effects in either set
+    pub const fn union(self, other: Effects) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Effects in this set and not in `other`
+    pub const fn difference(self, other: Effects) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Add an effect
+    pub fn insert(&mut self, effect: Effect) {
+        self.0 |= effect.bit();
+    }
+
+    /// Effects in the set, in the order of [`Effect::ALL`]
+    pub fn iter(self) -> impl Iterator<Item = Effect> {
+        Effect::ALL.into_iter().filter(move |effect| self.contains(*effect))
+    }
+}
+
+impl From<Effect> for Effects {
+    fn from(effect: Effect) -> Self {
+        Self::only(effect)
+    }
+}
+
+impl FromIterator<Effect> for Effects {
+    fn from_iter<I: IntoIterator<Item = Effect>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::PURE, |effects, effect| effects.union(effect.into()))
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_pure() {
+            return f.write_str("pure");
+        }
+        let names: Vec<_> = self.iter().map(Effect::name).collect();
+        f.write_str(&names.join(", "))
+    }
+}
@@ -94,6 +94,9 @@ pub enum ExprKind {
     /// Lambda/anonymous function
     Lambda {
         params: Vec<Param>,
+        /// Effects the body may have, checked by the type checker. `None`
+        /// if not declared.
+        effects: Option<super::Effects>,
         body: Box<Expr>,
     },
 
@@ -158,6 +161,8 @@ pub struct MatchArm {
     pub pattern: Pattern,
     pub guard: Option<Box<Expr>>,
     pub body: Box<Expr>,
+    /// Span of the pattern, which has none of its own
+    pub span: Span,
 }
 
 /// Pattern for match/if-let
@@ -180,6 +185,14 @@ pub enum Pattern {
     Tuple(Vec<Pattern>),
     /// Slice pattern
     Slice(Vec<Pattern>),
+    /// Enum variant: `(Just p)`. A variant without fields is written as a
+    /// bare name, which name resolution tells apart from a binding.
+    Variant {
+        /// Variant name
+        name: String,
+        /// Field patterns, by position
+        args: Vec<Pattern>,
+    },
     /// Some/None patterns
     Some(Box<Pattern>),
     None,
@@ -192,6 +205,10 @@ pub enum Literal {
     Integer(i64),
     /// Float: `3.14`, `-0.5`
     Float(f64),
+    /// Integer with a type suffix: `255u8`, `0xffu64`
+    TypedInteger(i128, super::types::BuiltinType),
+    /// Float with a type suffix: `3.0f32`
+    TypedFloat(f64, super::types::BuiltinType),
     /// String: `"hello"`
     String(String),
     /// Character: `'a'`
@@ -211,6 +228,7 @@ impl Literal {
         match self {
             Self::Integer(_) => super::types::BuiltinType::I32,
             Self::Float(_) => super::types::BuiltinType::F64,
+            Self::TypedInteger(_, ty) | Self::TypedFloat(_, ty) => *ty,
             Self::String(_) => super::types::BuiltinType::String,
             Self::Char(_) => super::types::BuiltinType::Char,
             Self::Bool(_) => super::types::BuiltinType::Bool,
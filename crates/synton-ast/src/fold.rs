@@ -0,0 +1,399 @@
+//! Owned AST transformation
+//!
+//! [`Fold`] takes each node by value and returns its replacement, which
+//! suits passes that change a node's shape, such as desugaring. The default
+//! methods call the matching `walk_*` function, which rebuilds the node
+//! from its folded children.
+//!
+//! ```
+//! use synton_ast::fold::{self, Fold};
+//! use synton_ast::{Expr, ExprKind};
+//!
+//! /// Replace `(some x)` with `x`
+//! struct Unwrap;
+//!
+//! impl Fold for Unwrap {
+//!     fn fold_expr(&mut self, expr: Expr) -> Expr {
+//!         match expr.kind {
+//!             ExprKind::Some(inner) => self.fold_expr(*inner),
+//!             _ => fold::walk_expr(self, expr),
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::{
+    AssignTarget, Contract, EnumDecl, EnumVariant, ExportDecl, Expr, ExprKind, FnDecl, ImportDecl, Literal,
+    MatchArm, Module, Param, Pattern, RefinementType, Stmt, StmtKind, StructDecl, StructField, Type, TypeKind,
+};
+
+/// Transformer over an owned AST
+pub trait Fold {
+    /// Fold a module
+    fn fold_module(&mut self, module: Module) -> Module {
+        walk_module(self, module)
+    }
+
+    /// Fold an import declaration
+    fn fold_import(&mut self, import: ImportDecl) -> ImportDecl {
+        import
+    }
+
+    /// Fold an export declaration
+    fn fold_export(&mut self, export: ExportDecl) -> ExportDecl {
+        export
+    }
+
+    /// Fold a statement
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt(self, stmt)
+    }
+
+    /// Fold a function declaration
+    fn fold_fn_decl(&mut self, decl: FnDecl) -> FnDecl {
+        walk_fn_decl(self, decl)
+    }
+
+    /// Fold a struct declaration
+    fn fold_struct_decl(&mut self, decl: StructDecl) -> StructDecl {
+        walk_struct_decl(self, decl)
+    }
+
+    /// Fold a struct field declaration
+    fn fold_struct_field(&mut self, field: StructField) -> StructField {
+        walk_struct_field(self, field)
+    }
+
+    /// Fold an enum declaration
+    fn fold_enum_decl(&mut self, decl: EnumDecl) -> EnumDecl {
+        walk_enum_decl(self, decl)
+    }
+
+    /// Fold an enum variant declaration
+    fn fold_enum_variant(&mut self, variant: EnumVariant) -> EnumVariant {
+        walk_enum_variant(self, variant)
+    }
+
+    /// Fold a contract
+    fn fold_contract(&mut self, contract: Contract) -> Contract {
+        walk_contract(self, contract)
+    }
+
+    /// Fold the target of an assignment
+    fn fold_assign_target(&mut self, target: AssignTarget) -> AssignTarget {
+        walk_assign_target(self, target)
+    }
+
+    /// Fold an expression
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+
+    /// Fold a function or lambda parameter
+    fn fold_param(&mut self, param: Param) -> Param {
+        walk_param(self, param)
+    }
+
+    /// Fold a match arm
+    fn fold_match_arm(&mut self, arm: MatchArm) -> MatchArm {
+        walk_match_arm(self, arm)
+    }
+
+    /// Fold a pattern
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+
+    /// Fold a literal, in an expression or a pattern
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        literal
+    }
+
+    /// Fold a type
+    fn fold_type(&mut self, ty: Type) -> Type {
+        walk_type(self, ty)
+    }
+}
+
+fn fold_box<F: Fold + ?Sized>(f: &mut F, mut expr: Box<Expr>) -> Box<Expr> {
+    *expr = f.fold_expr(*expr);
+    expr
+}
+
+fn fold_exprs<F: Fold + ?Sized>(f: &mut F, exprs: Vec<Expr>) -> Vec<Expr> {
+    exprs.into_iter().map(|expr| f.fold_expr(expr)).collect()
+}
+
+fn fold_stmts<F: Fold + ?Sized>(f: &mut F, stmts: Vec<Stmt>) -> Vec<Stmt> {
+    stmts.into_iter().map(|stmt| f.fold_stmt(stmt)).collect()
+}
+
+fn fold_patterns<F: Fold + ?Sized>(f: &mut F, patterns: Vec<Pattern>) -> Vec<Pattern> {
+    patterns.into_iter().map(|pattern| f.fold_pattern(pattern)).collect()
+}
+
+fn fold_types<F: Fold + ?Sized>(f: &mut F, types: Vec<Type>) -> Vec<Type> {
+    types.into_iter().map(|ty| f.fold_type(ty)).collect()
+}
+
+fn fold_type_box<F: Fold + ?Sized>(f: &mut F, mut ty: Box<Type>) -> Box<Type> {
+    *ty = f.fold_type(*ty);
+    ty
+}
+
+/// Fold the imports, exports and statements of a module
+pub fn walk_module<F: Fold + ?Sized>(f: &mut F, module: Module) -> Module {
+    Module {
+        id: module.id,
+        imports: module.imports.into_iter().map(|import| f.fold_import(import)).collect(),
+        exports: module.exports.into_iter().map(|export| f.fold_export(export)).collect(),
+        stmts: fold_stmts(f, module.stmts),
+    }
+}
+
+/// Fold the children of a statement
+pub fn walk_stmt<F: Fold + ?Sized>(f: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::Expr(expr) => StmtKind::Expr(fold_box(f, expr)),
+        StmtKind::Let { name, id, ty, init, mutable } => StmtKind::Let {
+            name,
+            id,
+            ty: ty.map(|ty| f.fold_type(ty)),
+            init: init.map(|init| fold_box(f, init)),
+            mutable,
+        },
+        StmtKind::Assign { target, value } => StmtKind::Assign {
+            target: f.fold_assign_target(target),
+            value: fold_box(f, value),
+        },
+        StmtKind::Block(stmts) => StmtKind::Block(fold_stmts(f, stmts)),
+        StmtKind::If { cond, then_branch, else_branch } => StmtKind::If {
+            cond: fold_box(f, cond),
+            then_branch: Box::new(f.fold_stmt(*then_branch)),
+            else_branch: else_branch.map(|stmt| Box::new(f.fold_stmt(*stmt))),
+        },
+        StmtKind::Loop { body } => StmtKind::Loop { body: Box::new(f.fold_stmt(*body)) },
+        StmtKind::While { cond, body } => StmtKind::While {
+            cond: fold_box(f, cond),
+            body: Box::new(f.fold_stmt(*body)),
+        },
+        StmtKind::For { var, iter, body } => StmtKind::For {
+            var,
+            iter: fold_box(f, iter),
+            body: Box::new(f.fold_stmt(*body)),
+        },
+        StmtKind::Break(value) => StmtKind::Break(value.map(|value| fold_box(f, value))),
+        StmtKind::Continue => StmtKind::Continue,
+        StmtKind::Return(value) => StmtKind::Return(value.map(|value| fold_box(f, value))),
+        StmtKind::FnDecl(decl) => StmtKind::FnDecl(f.fold_fn_decl(decl)),
+        StmtKind::StructDecl(decl) => StmtKind::StructDecl(f.fold_struct_decl(decl)),
+        StmtKind::EnumDecl(decl) => StmtKind::EnumDecl(f.fold_enum_decl(decl)),
+        StmtKind::TypeAlias { name, params, ty } => StmtKind::TypeAlias { name, params, ty: f.fold_type(ty) },
+        StmtKind::Const { name, ty, value } => StmtKind::Const {
+            name,
+            ty: f.fold_type(ty),
+            value: fold_box(f, value),
+        },
+        StmtKind::Contract(contract) => StmtKind::Contract(f.fold_contract(contract)),
+        StmtKind::Empty => StmtKind::Empty,
+        StmtKind::Error => StmtKind::Error,
+    };
+    Stmt { kind, ..stmt }
+}
+
+/// Fold the parameters, return type, contracts and body of a function
+pub fn walk_fn_decl<F: Fold + ?Sized>(f: &mut F, decl: FnDecl) -> FnDecl {
+    FnDecl {
+        params: decl.params.into_iter().map(|param| f.fold_param(param)).collect(),
+        ret_type: decl.ret_type.map(|ty| f.fold_type(ty)),
+        contracts: decl.contracts.into_iter().map(|contract| f.fold_contract(contract)).collect(),
+        body: decl.body.map(|body| fold_box(f, body)),
+        ..decl
+    }
+}
+
+/// Fold the fields of a struct declaration
+pub fn walk_struct_decl<F: Fold + ?Sized>(f: &mut F, decl: StructDecl) -> StructDecl {
+    StructDecl {
+        fields: decl.fields.into_iter().map(|field| f.fold_struct_field(field)).collect(),
+        ..decl
+    }
+}
+
+/// Fold the type of a struct field
+pub fn walk_struct_field<F: Fold + ?Sized>(f: &mut F, field: StructField) -> StructField {
+    StructField { ty: f.fold_type(field.ty), ..field }
+}
+
+/// Fold the variants of an enum declaration
+pub fn walk_enum_decl<F: Fold + ?Sized>(f: &mut F, decl: EnumDecl) -> EnumDecl {
+    EnumDecl {
+        variants: decl.variants.into_iter().map(|variant| f.fold_enum_variant(variant)).collect(),
+        ..decl
+    }
+}
+
+/// Fold the payload types of an enum variant
+pub fn walk_enum_variant<F: Fold + ?Sized>(f: &mut F, variant: EnumVariant) -> EnumVariant {
+    EnumVariant { types: fold_types(f, variant.types), ..variant }
+}
+
+/// Fold the condition of a contract
+pub fn walk_contract<F: Fold + ?Sized>(f: &mut F, contract: Contract) -> Contract {
+    Contract { kind: contract.kind, expr: f.fold_expr(contract.expr) }
+}
+
+/// Fold the expressions inside an assignment target
+pub fn walk_assign_target<F: Fold + ?Sized>(f: &mut F, target: AssignTarget) -> AssignTarget {
+    match target {
+        AssignTarget::Var(name) => AssignTarget::Var(name),
+        AssignTarget::Index { base, index } => AssignTarget::Index {
+            base: fold_box(f, base),
+            index: fold_box(f, index),
+        },
+        AssignTarget::Field { base, name } => AssignTarget::Field { base: fold_box(f, base), name },
+        AssignTarget::Deref(expr) => AssignTarget::Deref(fold_box(f, expr)),
+    }
+}
+
+/// Fold the children of an expression
+pub fn walk_expr<F: Fold + ?Sized>(f: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Literal(literal) => ExprKind::Literal(f.fold_literal(literal)),
+        ExprKind::Var { id, name } => ExprKind::Var { id, name },
+        ExprKind::Unary { op, arg } => ExprKind::Unary { op, arg: fold_box(f, arg) },
+        ExprKind::Binary { op, left, right } => ExprKind::Binary {
+            op,
+            left: fold_box(f, left),
+            right: fold_box(f, right),
+        },
+        ExprKind::Compare { op, left, right } => ExprKind::Compare {
+            op,
+            left: fold_box(f, left),
+            right: fold_box(f, right),
+        },
+        ExprKind::Call { callee, args } => ExprKind::Call {
+            callee: fold_box(f, callee),
+            args: fold_exprs(f, args),
+        },
+        ExprKind::MethodCall { object, method, args } => ExprKind::MethodCall {
+            object: fold_box(f, object),
+            method,
+            args: fold_exprs(f, args),
+        },
+        ExprKind::Index { base, index } => ExprKind::Index {
+            base: fold_box(f, base),
+            index: fold_box(f, index),
+        },
+        ExprKind::Field { base, name } => ExprKind::Field { base: fold_box(f, base), name },
+        ExprKind::Array(items) => ExprKind::Array(fold_exprs(f, items)),
+        ExprKind::Tuple(items) => ExprKind::Tuple(fold_exprs(f, items)),
+        ExprKind::Struct { ty, fields } => ExprKind::Struct {
+            ty,
+            fields: fields.into_iter().map(|(name, value)| (name, f.fold_expr(value))).collect(),
+        },
+        ExprKind::Lambda { params, effects, body } => ExprKind::Lambda {
+            params: params.into_iter().map(|param| f.fold_param(param)).collect(),
+            effects,
+            body: fold_box(f, body),
+        },
+        ExprKind::Block(stmts, value) => {
+            ExprKind::Block(fold_stmts(f, stmts), value.map(|value| fold_box(f, value)))
+        }
+        ExprKind::If { cond, then_branch, else_branch } => ExprKind::If {
+            cond: fold_box(f, cond),
+            then_branch: fold_box(f, then_branch),
+            else_branch: else_branch.map(|expr| fold_box(f, expr)),
+        },
+        ExprKind::Loop { body } => ExprKind::Loop { body: fold_box(f, body) },
+        ExprKind::Break(value) => ExprKind::Break(value.map(|value| fold_box(f, value))),
+        ExprKind::Continue => ExprKind::Continue,
+        ExprKind::Return(value) => ExprKind::Return(value.map(|value| fold_box(f, value))),
+        ExprKind::Match { scrutinee, arms } => ExprKind::Match {
+            scrutinee: fold_box(f, scrutinee),
+            arms: arms.into_iter().map(|arm| f.fold_match_arm(arm)).collect(),
+        },
+        ExprKind::Some(value) => ExprKind::Some(fold_box(f, value)),
+        ExprKind::None => ExprKind::None,
+        ExprKind::As { expr: inner, ty } => ExprKind::As { expr: fold_box(f, inner), ty: f.fold_type(ty) },
+        ExprKind::SizeOf(ty) => ExprKind::SizeOf(f.fold_type(ty)),
+        ExprKind::Error => ExprKind::Error,
+    };
+    Expr { kind, ..expr }
+}
+
+/// Fold the type annotation of a parameter
+pub fn walk_param<F: Fold + ?Sized>(f: &mut F, param: Param) -> Param {
+    Param { ty: param.ty.map(|ty| f.fold_type(ty)), ..param }
+}
+
+/// Fold the pattern, guard and body of a match arm
+pub fn walk_match_arm<F: Fold + ?Sized>(f: &mut F, arm: MatchArm) -> MatchArm {
+    MatchArm {
+        pattern: f.fold_pattern(arm.pattern),
+        guard: arm.guard.map(|guard| fold_box(f, guard)),
+        body: fold_box(f, arm.body),
+        span: arm.span,
+    }
+}
+
+/// Fold the children of a pattern
+pub fn walk_pattern<F: Fold + ?Sized>(f: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Wildcard => Pattern::Wildcard,
+        Pattern::Literal(literal) => Pattern::Literal(f.fold_literal(literal)),
+        Pattern::Bind(name) => Pattern::Bind(name),
+        Pattern::Or(items) => Pattern::Or(fold_patterns(f, items)),
+        Pattern::Struct { ty, fields } => Pattern::Struct {
+            ty,
+            fields: fields.into_iter().map(|(name, pattern)| (name, f.fold_pattern(pattern))).collect(),
+        },
+        Pattern::Tuple(items) => Pattern::Tuple(fold_patterns(f, items)),
+        Pattern::Slice(items) => Pattern::Slice(fold_patterns(f, items)),
+        Pattern::Variant { name, args } => Pattern::Variant { name, args: fold_patterns(f, args) },
+        Pattern::Some(inner) => Pattern::Some(Box::new(f.fold_pattern(*inner))),
+        Pattern::None => Pattern::None,
+    }
+}
+
+/// Fold the component types of a type
+pub fn walk_type<F: Fold + ?Sized>(f: &mut F, ty: Type) -> Type {
+    let kind = match ty.kind {
+        TypeKind::Builtin(builtin) => TypeKind::Builtin(builtin),
+        TypeKind::Refinement(refinement) => TypeKind::Refinement(Box::new(RefinementType {
+            base: fold_type_box(f, refinement.base),
+            ..*refinement
+        })),
+        TypeKind::List(inner) => TypeKind::List(fold_type_box(f, inner)),
+        TypeKind::Tuple(items) => TypeKind::Tuple(fold_types(f, items)),
+        TypeKind::Struct(fields) => TypeKind::Struct(
+            fields
+                .into_iter()
+                .map(|field| crate::types::StructField { ty: f.fold_type(field.ty), ..field })
+                .collect(),
+        ),
+        TypeKind::Enum(variants) => TypeKind::Enum(
+            variants
+                .into_iter()
+                .map(|variant| crate::types::EnumVariant { types: fold_types(f, variant.types), ..variant })
+                .collect(),
+        ),
+        TypeKind::Fn { params, ret } => TypeKind::Fn {
+            params: fold_types(f, params),
+            ret: fold_type_box(f, ret),
+        },
+        TypeKind::Maybe(inner) => TypeKind::Maybe(fold_type_box(f, inner)),
+        TypeKind::Result { ok, err } => TypeKind::Result {
+            ok: fold_type_box(f, ok),
+            err: fold_type_box(f, err),
+        },
+        TypeKind::Ref(inner) => TypeKind::Ref(fold_type_box(f, inner)),
+        TypeKind::Inference(var) => TypeKind::Inference(var),
+        TypeKind::Var(name) => TypeKind::Var(name),
+        TypeKind::Named { name, args } => TypeKind::Named { name, args: fold_types(f, args) },
+        TypeKind::Never => TypeKind::Never,
+        TypeKind::Unit => TypeKind::Unit,
+        TypeKind::Error => TypeKind::Error,
+    };
+    Type { kind, ..ty }
+}
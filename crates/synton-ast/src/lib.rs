@@ -10,6 +10,12 @@ pub mod types;
 pub mod expr;
 pub mod stmt;
 pub mod op;
+pub mod effect;
+pub mod binary;
+pub mod visit;
+pub mod visit_mut;
+pub mod fold;
+pub mod diff;
 
 pub use id::{NodeId, VarId, FnId, ModuleId};
 pub use span::{Span, Position};
@@ -17,6 +23,11 @@ pub use types::{Type, TypeKind, BuiltinType, RefinementType};
 pub use expr::{Expr, ExprKind, Literal, Pattern, MatchArm, Param};
 pub use stmt::{Stmt, StmtKind, FnDecl, StructDecl, EnumDecl, Contract, ContractKind, ContractLocation, StructField, EnumVariant, AssignTarget};
 pub use op::{Op, UnaryOp, BinaryOp, CompareOp};
+pub use effect::{Effect, Effects};
+
+// Only the integration tests parse source
+#[cfg(test)]
+use {serde_json as _, synton_parser as _};
 
 /// AST container for a complete Synton module
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -46,6 +57,16 @@ impl Module {
     pub fn is_empty(&self) -> bool {
         self.stmts.is_empty() && self.imports.is_empty() && self.exports.is_empty()
     }
+
+    /// Encode the module in the [binary AST format](binary)
+    pub fn to_binary(&self) -> Result<Vec<u8>, binary::BinaryError> {
+        binary::to_bytes(self)
+    }
+
+    /// Decode a module from the [binary AST format](binary)
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, binary::BinaryError> {
+        binary::from_bytes(bytes)
+    }
 }
 
 /// Import declaration
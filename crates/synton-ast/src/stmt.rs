@@ -131,6 +131,9 @@ pub struct FnDecl {
     pub ret_type: Option<Type>,
     pub body: Option<Box<Expr>>,
     pub contracts: Vec<Contract>,
+    /// Effects the body may have, checked by the type checker. `None` if
+    /// not declared.
+    pub effects: Option<super::Effects>,
     pub span: Span,
 }
 
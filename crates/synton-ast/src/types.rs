@@ -1,6 +1,7 @@
 //! Type system definitions
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use super::Span;
 
 /// A type in Synton
@@ -22,7 +23,7 @@ impl Type {
 
     /// Check if type is numeric
     pub fn is_numeric(&self) -> bool {
-        matches!(self.kind, TypeKind::Builtin(BuiltinType::I32 | BuiltinType::I64 | BuiltinType::F32 | BuiltinType::F64))
+        matches!(self.kind, TypeKind::Builtin(b) if b.is_numeric())
     }
 
     /// Check if type is signed integer
@@ -64,10 +65,20 @@ pub enum TypeKind {
     Inference(u32),
     /// Type variable (generic)
     Var(String),
+    /// Declared struct or enum applied to type arguments: `Pair<i32, bool>`
+    Named {
+        /// Declared name
+        name: String,
+        /// Type arguments, one per declared parameter
+        args: Vec<Type>,
+    },
     /// Never type
     Never,
     /// Unit type
     Unit,
+    /// Type of an expression that failed to check. Compatible with every
+    /// type, so one mistake is reported once.
+    Error,
 }
 
 /// Builtin types
@@ -138,6 +149,111 @@ impl BuiltinType {
     pub const fn is_float(self) -> bool {
         matches!(self, Self::F32 | Self::F64)
     }
+
+    /// Check if this is an integer or float type
+    pub const fn is_numeric(self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// Check if this is a number type with negative values
+    pub const fn is_signed(self) -> bool {
+        matches!(self, Self::I32 | Self::I64 | Self::F32 | Self::F64)
+    }
+
+    /// Smallest and largest value of an integer type
+    pub const fn int_range(self) -> Option<(i128, i128)> {
+        match self {
+            Self::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            Self::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            Self::U8 => Some((0, u8::MAX as i128)),
+            Self::U32 => Some((0, u32::MAX as i128)),
+            Self::U64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    /// Check if every value of this type is also a value of `other`, so
+    /// that it converts implicitly. Signed and unsigned integers never mix.
+    pub const fn widens_to(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::I32, Self::I64) | (Self::U8, Self::U32 | Self::U64) | (Self::U32, Self::U64) | (Self::F32, Self::F64)
+        )
+    }
+}
+
+impl fmt::Display for Type {
+    /// Source syntax of the type. Inference variables, which have none, are
+    /// written `_0`, `_1`, ...
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T>(
+            f: &mut fmt::Formatter<'_>,
+            items: &[T],
+            item: impl Fn(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        ) -> fmt::Result {
+            for (i, t) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                item(f, t)?;
+            }
+            Ok(())
+        }
+
+        match &self.kind {
+            TypeKind::Builtin(b) => f.write_str(b.name()),
+            TypeKind::Refinement(r) => write!(f, "{{{}: {} | {}}}", r.var_name, r.base, r.constraint),
+            TypeKind::List(inner) => write!(f, "list<{}>", inner),
+            TypeKind::Tuple(items) => {
+                f.write_str("(")?;
+                list(f, items, |f, t| write!(f, "{}", t))?;
+                if items.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            TypeKind::Struct(fields) => {
+                f.write_str("struct{")?;
+                list(f, fields, |f, field| write!(f, "{}: {}", field.name, field.ty))?;
+                f.write_str("}")
+            }
+            TypeKind::Enum(variants) => {
+                f.write_str("enum{")?;
+                list(f, variants, |f, variant| {
+                    f.write_str(&variant.name)?;
+                    if !variant.types.is_empty() {
+                        f.write_str("(")?;
+                        list(f, &variant.types, |f, t| write!(f, "{}", t))?;
+                        f.write_str(")")?;
+                    }
+                    Ok(())
+                })?;
+                f.write_str("}")
+            }
+            TypeKind::Fn { params, ret } => {
+                f.write_str("(")?;
+                list(f, params, |f, t| write!(f, "{}", t))?;
+                write!(f, ") -> {}", ret)
+            }
+            TypeKind::Maybe(inner) => write!(f, "maybe<{}>", inner),
+            TypeKind::Result { ok, err } => write!(f, "result<{}, {}>", ok, err),
+            TypeKind::Ref(inner) => write!(f, "&{}", inner),
+            TypeKind::Inference(n) => write!(f, "_{}", n),
+            TypeKind::Var(name) => f.write_str(name),
+            TypeKind::Named { name, args } => {
+                f.write_str(name)?;
+                if !args.is_empty() {
+                    f.write_str("<")?;
+                    list(f, args, |f, t| write!(f, "{}", t))?;
+                    f.write_str(">")?;
+                }
+                Ok(())
+            }
+            TypeKind::Never => f.write_str("!"),
+            TypeKind::Unit => f.write_str("()"),
+            TypeKind::Error => f.write_str("{error}"),
+        }
+    }
 }
 
 /// Refinement type with contract constraint
@@ -145,7 +261,8 @@ impl BuiltinType {
 pub struct RefinementType {
     /// Base type
     pub base: Box<Type>,
-    /// Constraint expression
+    /// Source text of a boolean expression over `var_name`, such as
+    /// `(> val 0)`
     pub constraint: String,
     /// Variable name in constraint (usually "val", "self", or "result")
     pub var_name: String,
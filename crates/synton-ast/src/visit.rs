@@ -0,0 +1,387 @@
+//! Read-only AST traversal
+//!
+//! Implement [`Visitor`] and override the methods for the nodes a pass cares
+//! about. Each default method calls the matching `walk_*` function, which
+//! visits every child; an override calls it too to keep descending.
+//!
+//! ```
+//! use synton_ast::visit::{self, Visitor};
+//! use synton_ast::Expr;
+//!
+//! struct CountCalls(usize);
+//!
+//! impl Visitor for CountCalls {
+//!     fn visit_expr(&mut self, expr: &Expr) {
+//!         if matches!(expr.kind, synton_ast::ExprKind::Call { .. }) {
+//!             self.0 += 1;
+//!         }
+//!         visit::walk_expr(self, expr);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    AssignTarget, Contract, EnumDecl, EnumVariant, ExportDecl, Expr, ExprKind, FnDecl, ImportDecl, Literal,
+    MatchArm, Module, Param, Pattern, Stmt, StmtKind, StructDecl, StructField, Type, TypeKind,
+};
+
+/// Visitor over a borrowed AST
+pub trait Visitor {
+    /// Visit a module
+    fn visit_module(&mut self, module: &Module) {
+        walk_module(self, module);
+    }
+
+    /// Visit an import declaration
+    fn visit_import(&mut self, _import: &ImportDecl) {}
+
+    /// Visit an export declaration
+    fn visit_export(&mut self, _export: &ExportDecl) {}
+
+    /// Visit a statement
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    /// Visit a function declaration
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        walk_fn_decl(self, decl);
+    }
+
+    /// Visit a struct declaration
+    fn visit_struct_decl(&mut self, decl: &StructDecl) {
+        walk_struct_decl(self, decl);
+    }
+
+    /// Visit a struct field declaration
+    fn visit_struct_field(&mut self, field: &StructField) {
+        walk_struct_field(self, field);
+    }
+
+    /// Visit an enum declaration
+    fn visit_enum_decl(&mut self, decl: &EnumDecl) {
+        walk_enum_decl(self, decl);
+    }
+
+    /// Visit an enum variant declaration
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        walk_enum_variant(self, variant);
+    }
+
+    /// Visit a contract
+    fn visit_contract(&mut self, contract: &Contract) {
+        walk_contract(self, contract);
+    }
+
+    /// Visit the target of an assignment
+    fn visit_assign_target(&mut self, target: &AssignTarget) {
+        walk_assign_target(self, target);
+    }
+
+    /// Visit an expression
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    /// Visit a function or lambda parameter
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+
+    /// Visit a match arm
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        walk_match_arm(self, arm);
+    }
+
+    /// Visit a pattern
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    /// Visit a literal, in an expression or a pattern
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    /// Visit a type
+    fn visit_type(&mut self, ty: &Type) {
+        walk_type(self, ty);
+    }
+}
+
+/// Visit the imports, exports and statements of a module
+pub fn walk_module<V: Visitor + ?Sized>(v: &mut V, module: &Module) {
+    for import in &module.imports {
+        v.visit_import(import);
+    }
+    for export in &module.exports {
+        v.visit_export(export);
+    }
+    for stmt in &module.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+/// Visit the children of a statement
+pub fn walk_stmt<V: Visitor + ?Sized>(v: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Expr(expr) => v.visit_expr(expr),
+        StmtKind::Let { ty, init, .. } => {
+            if let Some(ty) = ty {
+                v.visit_type(ty);
+            }
+            if let Some(init) = init {
+                v.visit_expr(init);
+            }
+        }
+        StmtKind::Assign { target, value } => {
+            v.visit_assign_target(target);
+            v.visit_expr(value);
+        }
+        StmtKind::Block(stmts) => {
+            for stmt in stmts {
+                v.visit_stmt(stmt);
+            }
+        }
+        StmtKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr(cond);
+            v.visit_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                v.visit_stmt(else_branch);
+            }
+        }
+        StmtKind::Loop { body } => v.visit_stmt(body),
+        StmtKind::While { cond, body } => {
+            v.visit_expr(cond);
+            v.visit_stmt(body);
+        }
+        StmtKind::For { iter, body, .. } => {
+            v.visit_expr(iter);
+            v.visit_stmt(body);
+        }
+        StmtKind::Break(value) | StmtKind::Return(value) => {
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        StmtKind::FnDecl(decl) => v.visit_fn_decl(decl),
+        StmtKind::StructDecl(decl) => v.visit_struct_decl(decl),
+        StmtKind::EnumDecl(decl) => v.visit_enum_decl(decl),
+        StmtKind::TypeAlias { ty, .. } => v.visit_type(ty),
+        StmtKind::Const { ty, value, .. } => {
+            v.visit_type(ty);
+            v.visit_expr(value);
+        }
+        StmtKind::Contract(contract) => v.visit_contract(contract),
+        StmtKind::Continue | StmtKind::Empty | StmtKind::Error => {}
+    }
+}
+
+/// Visit the parameters, return type, contracts and body of a function
+pub fn walk_fn_decl<V: Visitor + ?Sized>(v: &mut V, decl: &FnDecl) {
+    for param in &decl.params {
+        v.visit_param(param);
+    }
+    if let Some(ret_type) = &decl.ret_type {
+        v.visit_type(ret_type);
+    }
+    for contract in &decl.contracts {
+        v.visit_contract(contract);
+    }
+    if let Some(body) = &decl.body {
+        v.visit_expr(body);
+    }
+}
+
+/// Visit the fields of a struct declaration
+pub fn walk_struct_decl<V: Visitor + ?Sized>(v: &mut V, decl: &StructDecl) {
+    for field in &decl.fields {
+        v.visit_struct_field(field);
+    }
+}
+
+/// Visit the type of a struct field
+pub fn walk_struct_field<V: Visitor + ?Sized>(v: &mut V, field: &StructField) {
+    v.visit_type(&field.ty);
+}
+
+/// Visit the variants of an enum declaration
+pub fn walk_enum_decl<V: Visitor + ?Sized>(v: &mut V, decl: &EnumDecl) {
+    for variant in &decl.variants {
+        v.visit_enum_variant(variant);
+    }
+}
+
+/// Visit the payload types of an enum variant
+pub fn walk_enum_variant<V: Visitor + ?Sized>(v: &mut V, variant: &EnumVariant) {
+    for ty in &variant.types {
+        v.visit_type(ty);
+    }
+}
+
+/// Visit the condition of a contract
+pub fn walk_contract<V: Visitor + ?Sized>(v: &mut V, contract: &Contract) {
+    v.visit_expr(&contract.expr);
+}
+
+/// Visit the expressions inside an assignment target
+pub fn walk_assign_target<V: Visitor + ?Sized>(v: &mut V, target: &AssignTarget) {
+    match target {
+        AssignTarget::Var(_) => {}
+        AssignTarget::Index { base, index } => {
+            v.visit_expr(base);
+            v.visit_expr(index);
+        }
+        AssignTarget::Field { base, .. } => v.visit_expr(base),
+        AssignTarget::Deref(expr) => v.visit_expr(expr),
+    }
+}
+
+/// Visit the children of an expression
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Literal(literal) => v.visit_literal(literal),
+        ExprKind::Var { .. } | ExprKind::Continue | ExprKind::None | ExprKind::Error => {}
+        ExprKind::Unary { arg, .. } => v.visit_expr(arg),
+        ExprKind::Binary { left, right, .. } | ExprKind::Compare { left, right, .. } => {
+            v.visit_expr(left);
+            v.visit_expr(right);
+        }
+        ExprKind::Call { callee, args } => {
+            v.visit_expr(callee);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::MethodCall { object, args, .. } => {
+            v.visit_expr(object);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::Index { base, index } => {
+            v.visit_expr(base);
+            v.visit_expr(index);
+        }
+        ExprKind::Field { base, .. } => v.visit_expr(base),
+        ExprKind::Array(items) | ExprKind::Tuple(items) => {
+            for item in items {
+                v.visit_expr(item);
+            }
+        }
+        ExprKind::Struct { fields, .. } => {
+            for (_, value) in fields {
+                v.visit_expr(value);
+            }
+        }
+        ExprKind::Lambda { params, body, .. } => {
+            for param in params {
+                v.visit_param(param);
+            }
+            v.visit_expr(body);
+        }
+        ExprKind::Block(stmts, value) => {
+            for stmt in stmts {
+                v.visit_stmt(stmt);
+            }
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        ExprKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr(cond);
+            v.visit_expr(then_branch);
+            if let Some(else_branch) = else_branch {
+                v.visit_expr(else_branch);
+            }
+        }
+        ExprKind::Loop { body } => v.visit_expr(body),
+        ExprKind::Break(value) | ExprKind::Return(value) => {
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        ExprKind::Match { scrutinee, arms } => {
+            v.visit_expr(scrutinee);
+            for arm in arms {
+                v.visit_match_arm(arm);
+            }
+        }
+        ExprKind::Some(value) => v.visit_expr(value),
+        ExprKind::As { expr, ty } => {
+            v.visit_expr(expr);
+            v.visit_type(ty);
+        }
+        ExprKind::SizeOf(ty) => v.visit_type(ty),
+    }
+}
+
+/// Visit the type annotation of a parameter
+pub fn walk_param<V: Visitor + ?Sized>(v: &mut V, param: &Param) {
+    if let Some(ty) = &param.ty {
+        v.visit_type(ty);
+    }
+}
+
+/// Visit the pattern, guard and body of a match arm
+pub fn walk_match_arm<V: Visitor + ?Sized>(v: &mut V, arm: &MatchArm) {
+    v.visit_pattern(&arm.pattern);
+    if let Some(guard) = &arm.guard {
+        v.visit_expr(guard);
+    }
+    v.visit_expr(&arm.body);
+}
+
+/// Visit the children of a pattern
+pub fn walk_pattern<V: Visitor + ?Sized>(v: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Bind(_) | Pattern::None => {}
+        Pattern::Literal(literal) => v.visit_literal(literal),
+        Pattern::Or(items)
+        | Pattern::Tuple(items)
+        | Pattern::Slice(items)
+        | Pattern::Variant { args: items, .. } => {
+            for item in items {
+                v.visit_pattern(item);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for (_, pattern) in fields {
+                v.visit_pattern(pattern);
+            }
+        }
+        Pattern::Some(inner) => v.visit_pattern(inner),
+    }
+}
+
+/// Visit the component types of a type
+pub fn walk_type<V: Visitor + ?Sized>(v: &mut V, ty: &Type) {
+    match &ty.kind {
+        TypeKind::Builtin(_) | TypeKind::Inference(_) | TypeKind::Var(_) | TypeKind::Never | TypeKind::Unit | TypeKind::Error => {}
+        TypeKind::Refinement(refinement) => v.visit_type(&refinement.base),
+        TypeKind::List(inner) | TypeKind::Maybe(inner) | TypeKind::Ref(inner) => v.visit_type(inner),
+        TypeKind::Tuple(items) | TypeKind::Named { args: items, .. } => {
+            for item in items {
+                v.visit_type(item);
+            }
+        }
+        TypeKind::Struct(fields) => {
+            for field in fields {
+                v.visit_type(&field.ty);
+            }
+        }
+        TypeKind::Enum(variants) => {
+            for ty in variants.iter().flat_map(|variant| &variant.types) {
+                v.visit_type(ty);
+            }
+        }
+        TypeKind::Fn { params, ret } => {
+            for param in params {
+                v.visit_type(param);
+            }
+            v.visit_type(ret);
+        }
+        TypeKind::Result { ok, err } => {
+            v.visit_type(ok);
+            v.visit_type(err);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! In-place AST traversal
+//!
+//! The mutable counterpart of [`crate::visit`]: [`VisitorMut`] visits the
+//! same nodes in the same order, so a pass can rewrite them where they are.
+//!
+//! ```
+//! use synton_ast::visit_mut::{self, VisitorMut};
+//! use synton_ast::{Expr, ExprKind};
+//!
+//! struct Rename<'a>(&'a str, &'a str);
+//!
+//! impl VisitorMut for Rename<'_> {
+//!     fn visit_expr_mut(&mut self, expr: &mut Expr) {
+//!         if let ExprKind::Var { name, .. } = &mut expr.kind {
+//!             if name == self.0 {
+//!                 *name = self.1.to_string();
+//!             }
+//!         }
+//!         visit_mut::walk_expr_mut(self, expr);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    AssignTarget, Contract, EnumDecl, EnumVariant, ExportDecl, Expr, ExprKind, FnDecl, ImportDecl, Literal,
+    MatchArm, Module, Param, Pattern, Stmt, StmtKind, StructDecl, StructField, Type, TypeKind,
+};
+
+/// Visitor over a mutably borrowed AST
+pub trait VisitorMut {
+    /// Visit a module
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module);
+    }
+
+    /// Visit an import declaration
+    fn visit_import_mut(&mut self, _import: &mut ImportDecl) {}
+
+    /// Visit an export declaration
+    fn visit_export_mut(&mut self, _export: &mut ExportDecl) {}
+
+    /// Visit a statement
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    /// Visit a function declaration
+    fn visit_fn_decl_mut(&mut self, decl: &mut FnDecl) {
+        walk_fn_decl_mut(self, decl);
+    }
+
+    /// Visit a struct declaration
+    fn visit_struct_decl_mut(&mut self, decl: &mut StructDecl) {
+        walk_struct_decl_mut(self, decl);
+    }
+
+    /// Visit a struct field declaration
+    fn visit_struct_field_mut(&mut self, field: &mut StructField) {
+        walk_struct_field_mut(self, field);
+    }
+
+    /// Visit an enum declaration
+    fn visit_enum_decl_mut(&mut self, decl: &mut EnumDecl) {
+        walk_enum_decl_mut(self, decl);
+    }
+
+    /// Visit an enum variant declaration
+    fn visit_enum_variant_mut(&mut self, variant: &mut EnumVariant) {
+        walk_enum_variant_mut(self, variant);
+    }
+
+    /// Visit a contract
+    fn visit_contract_mut(&mut self, contract: &mut Contract) {
+        walk_contract_mut(self, contract);
+    }
+
+    /// Visit the target of an assignment
+    fn visit_assign_target_mut(&mut self, target: &mut AssignTarget) {
+        walk_assign_target_mut(self, target);
+    }
+
+    /// Visit an expression
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    /// Visit a function or lambda parameter
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param);
+    }
+
+    /// Visit a match arm
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        walk_match_arm_mut(self, arm);
+    }
+
+    /// Visit a pattern
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern);
+    }
+
+    /// Visit a literal, in an expression or a pattern
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+
+    /// Visit a type
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty);
+    }
+}
+
+/// Visit the imports, exports and statements of a module
+pub fn walk_module_mut<V: VisitorMut + ?Sized>(v: &mut V, module: &mut Module) {
+    for import in &mut module.imports {
+        v.visit_import_mut(import);
+    }
+    for export in &mut module.exports {
+        v.visit_export_mut(export);
+    }
+    for stmt in &mut module.stmts {
+        v.visit_stmt_mut(stmt);
+    }
+}
+
+/// Visit the children of a statement
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::Expr(expr) => v.visit_expr_mut(expr),
+        StmtKind::Let { ty, init, .. } => {
+            if let Some(ty) = ty {
+                v.visit_type_mut(ty);
+            }
+            if let Some(init) = init {
+                v.visit_expr_mut(init);
+            }
+        }
+        StmtKind::Assign { target, value } => {
+            v.visit_assign_target_mut(target);
+            v.visit_expr_mut(value);
+        }
+        StmtKind::Block(stmts) => {
+            for stmt in stmts {
+                v.visit_stmt_mut(stmt);
+            }
+        }
+        StmtKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr_mut(cond);
+            v.visit_stmt_mut(then_branch);
+            if let Some(else_branch) = else_branch {
+                v.visit_stmt_mut(else_branch);
+            }
+        }
+        StmtKind::Loop { body } => v.visit_stmt_mut(body),
+        StmtKind::While { cond, body } => {
+            v.visit_expr_mut(cond);
+            v.visit_stmt_mut(body);
+        }
+        StmtKind::For { iter, body, .. } => {
+            v.visit_expr_mut(iter);
+            v.visit_stmt_mut(body);
+        }
+        StmtKind::Break(value) | StmtKind::Return(value) => {
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        StmtKind::FnDecl(decl) => v.visit_fn_decl_mut(decl),
+        StmtKind::StructDecl(decl) => v.visit_struct_decl_mut(decl),
+        StmtKind::EnumDecl(decl) => v.visit_enum_decl_mut(decl),
+        StmtKind::TypeAlias { ty, .. } => v.visit_type_mut(ty),
+        StmtKind::Const { ty, value, .. } => {
+            v.visit_type_mut(ty);
+            v.visit_expr_mut(value);
+        }
+        StmtKind::Contract(contract) => v.visit_contract_mut(contract),
+        StmtKind::Continue | StmtKind::Empty | StmtKind::Error => {}
+    }
+}
+
+/// Visit the parameters, return type, contracts and body of a function
+pub fn walk_fn_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut FnDecl) {
+    for param in &mut decl.params {
+        v.visit_param_mut(param);
+    }
+    if let Some(ret_type) = &mut decl.ret_type {
+        v.visit_type_mut(ret_type);
+    }
+    for contract in &mut decl.contracts {
+        v.visit_contract_mut(contract);
+    }
+    if let Some(body) = &mut decl.body {
+        v.visit_expr_mut(body);
+    }
+}
+
+/// Visit the fields of a struct declaration
+pub fn walk_struct_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut StructDecl) {
+    for field in &mut decl.fields {
+        v.visit_struct_field_mut(field);
+    }
+}
+
+/// Visit the type of a struct field
+pub fn walk_struct_field_mut<V: VisitorMut + ?Sized>(v: &mut V, field: &mut StructField) {
+    v.visit_type_mut(&mut field.ty);
+}
+
+/// Visit the variants of an enum declaration
+pub fn walk_enum_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut EnumDecl) {
+    for variant in &mut decl.variants {
+        v.visit_enum_variant_mut(variant);
+    }
+}
+
+/// Visit the payload types of an enum variant
+pub fn walk_enum_variant_mut<V: VisitorMut + ?Sized>(v: &mut V, variant: &mut EnumVariant) {
+    for ty in &mut variant.types {
+        v.visit_type_mut(ty);
+    }
+}
+
+/// Visit the condition of a contract
+pub fn walk_contract_mut<V: VisitorMut + ?Sized>(v: &mut V, contract: &mut Contract) {
+    v.visit_expr_mut(&mut contract.expr);
+}
+
+/// Visit the expressions inside an assignment target
+pub fn walk_assign_target_mut<V: VisitorMut + ?Sized>(v: &mut V, target: &mut AssignTarget) {
+    match target {
+        AssignTarget::Var(_) => {}
+        AssignTarget::Index { base, index } => {
+            v.visit_expr_mut(base);
+            v.visit_expr_mut(index);
+        }
+        AssignTarget::Field { base, .. } => v.visit_expr_mut(base),
+        AssignTarget::Deref(expr) => v.visit_expr_mut(expr),
+    }
+}
+
+/// Visit the children of an expression
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Literal(literal) => v.visit_literal_mut(literal),
+        ExprKind::Var { .. } | ExprKind::Continue | ExprKind::None | ExprKind::Error => {}
+        ExprKind::Unary { arg, .. } => v.visit_expr_mut(arg),
+        ExprKind::Binary { left, right, .. } | ExprKind::Compare { left, right, .. } => {
+            v.visit_expr_mut(left);
+            v.visit_expr_mut(right);
+        }
+        ExprKind::Call { callee, args } => {
+            v.visit_expr_mut(callee);
+            for arg in args {
+                v.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::MethodCall { object, args, .. } => {
+            v.visit_expr_mut(object);
+            for arg in args {
+                v.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Index { base, index } => {
+            v.visit_expr_mut(base);
+            v.visit_expr_mut(index);
+        }
+        ExprKind::Field { base, .. } => v.visit_expr_mut(base),
+        ExprKind::Array(items) | ExprKind::Tuple(items) => {
+            for item in items {
+                v.visit_expr_mut(item);
+            }
+        }
+        ExprKind::Struct { fields, .. } => {
+            for (_, value) in fields {
+                v.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Lambda { params, body, .. } => {
+            for param in params {
+                v.visit_param_mut(param);
+            }
+            v.visit_expr_mut(body);
+        }
+        ExprKind::Block(stmts, value) => {
+            for stmt in stmts {
+                v.visit_stmt_mut(stmt);
+            }
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        ExprKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr_mut(cond);
+            v.visit_expr_mut(then_branch);
+            if let Some(else_branch) = else_branch {
+                v.visit_expr_mut(else_branch);
+            }
+        }
+        ExprKind::Loop { body } => v.visit_expr_mut(body),
+        ExprKind::Break(value) | ExprKind::Return(value) => {
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Match { scrutinee, arms } => {
+            v.visit_expr_mut(scrutinee);
+            for arm in arms {
+                v.visit_match_arm_mut(arm);
+            }
+        }
+        ExprKind::Some(value) => v.visit_expr_mut(value),
+        ExprKind::As { expr, ty } => {
+            v.visit_expr_mut(expr);
+            v.visit_type_mut(ty);
+        }
+        ExprKind::SizeOf(ty) => v.visit_type_mut(ty),
+    }
+}
+
+/// Visit the type annotation of a parameter
+pub fn walk_param_mut<V: VisitorMut + ?Sized>(v: &mut V, param: &mut Param) {
+    if let Some(ty) = &mut param.ty {
+        v.visit_type_mut(ty);
+    }
+}
+
+/// Visit the pattern, guard and body of a match arm
+pub fn walk_match_arm_mut<V: VisitorMut + ?Sized>(v: &mut V, arm: &mut MatchArm) {
+    v.visit_pattern_mut(&mut arm.pattern);
+    if let Some(guard) = &mut arm.guard {
+        v.visit_expr_mut(guard);
+    }
+    v.visit_expr_mut(&mut arm.body);
+}
+
+/// Visit the children of a pattern
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(v: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Wildcard | Pattern::Bind(_) | Pattern::None => {}
+        Pattern::Literal(literal) => v.visit_literal_mut(literal),
+        Pattern::Or(items)
+        | Pattern::Tuple(items)
+        | Pattern::Slice(items)
+        | Pattern::Variant { args: items, .. } => {
+            for item in items {
+                v.visit_pattern_mut(item);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for (_, pattern) in fields {
+                v.visit_pattern_mut(pattern);
+            }
+        }
+        Pattern::Some(inner) => v.visit_pattern_mut(inner),
+    }
+}
+
+/// Visit the component types of a type
+pub fn walk_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut Type) {
+    match &mut ty.kind {
+        TypeKind::Builtin(_) | TypeKind::Inference(_) | TypeKind::Var(_) | TypeKind::Never | TypeKind::Unit | TypeKind::Error => {}
+        TypeKind::Refinement(refinement) => v.visit_type_mut(&mut refinement.base),
+        TypeKind::List(inner) | TypeKind::Maybe(inner) | TypeKind::Ref(inner) => v.visit_type_mut(inner),
+        TypeKind::Tuple(items) | TypeKind::Named { args: items, .. } => {
+            for item in items {
+                v.visit_type_mut(item);
+            }
+        }
+        TypeKind::Struct(fields) => {
+            for field in fields {
+                v.visit_type_mut(&mut field.ty);
+            }
+        }
+        TypeKind::Enum(variants) => {
+            for ty in variants.iter_mut().flat_map(|variant| &mut variant.types) {
+                v.visit_type_mut(ty);
+            }
+        }
+        TypeKind::Fn { params, ret } => {
+            for param in params {
+                v.visit_type_mut(param);
+            }
+            v.visit_type_mut(ret);
+        }
+        TypeKind::Result { ok, err } => {
+            v.visit_type_mut(ok);
+            v.visit_type_mut(err);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Binary AST encoding
+
+use synton_ast::binary::{self, BinaryError, MAX_DEPTH};
+use synton_ast::{FnId, Module, Pattern, StmtKind};
+use synton_parser::SyntonParser;
+
+#[test]
+fn test_binary_roundtrip() {
+    let src = "import \"std/io\" as io\n\
+               (let x = (+ 1 -2i64))\n\
+               (match x (some (| (a, 1) (some [b]))) if (> b 0) => \"caf\\u{e9}\" _ => 2.5f32)\n\
+               (f 'c' b\"\\x00\\xff\" x x x)";
+    let module = SyntonParser::new().parse_module(src).unwrap();
+    let bytes = module.to_binary().unwrap();
+    assert_eq!(&bytes[..4], b"SYNB");
+    let decoded = Module::from_binary(&bytes).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", module));
+
+    // Spans, contracts, effects and resolved ids survive too
+    let src = "(let m = 0)\n\n  (fn f [n] ! pure\n    @pre((>= n m))\n    n)";
+    let mut module = SyntonParser::new().parse_module(src).unwrap();
+    let StmtKind::FnDecl(decl) = &mut module.stmts[1].kind else { panic!("expected fn") };
+    decl.id = Some(FnId::new(7));
+    let decoded = Module::from_binary(&module.to_binary().unwrap()).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", module));
+}
+
+#[test]
+fn test_binary_compression() {
+    let mut src = String::new();
+    for i in 0..50 {
+        src.push_str(&format!(
+            "(let value_{0} = (match input (some (| (a, {0}) (some [b]))) => (+ value b) _ => (* value {0})))\n",
+            i
+        ));
+    }
+    let module = SyntonParser::new().parse_module(&src).unwrap();
+    let json = serde_json::to_string(&module).unwrap();
+    let bytes = module.to_binary().unwrap();
+    let saved = 1.0 - bytes.len() as f64 / json.len() as f64;
+    assert!(saved >= 0.7, "binary {} bytes vs JSON {} bytes", bytes.len(), json.len());
+}
+
+#[test]
+fn test_binary_rejects_corrupt_input() {
+    let module = SyntonParser::new().parse_module("(let x = (+ 1 2))").unwrap();
+    let bytes = module.to_binary().unwrap();
+    let decode = |bytes: &[u8]| Module::from_binary(bytes).map(|_| ());
+
+    assert!(matches!(decode(b"JSON{}"), Err(BinaryError::BadMagic)));
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    assert!(matches!(decode(&newer), Err(BinaryError::UnsupportedVersion(99))));
+    assert!(matches!(decode(&bytes[..bytes.len() - 1]), Err(BinaryError::UnexpectedEof)));
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert!(matches!(decode(&longer), Err(BinaryError::TrailingBytes(1))));
+}
+
+/// Encode an unsigned varint
+fn varint(mut n: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+    bytes
+}
+
+/// Decode a pattern from hand-written nodes, each a type id and payload;
+/// the root refers to node 0. Tuple is variant 5 and Some variant 8, and
+/// 0 is `_`.
+fn patterns(nodes: &[(u8, Vec<u8>)]) -> Result<Pattern, BinaryError> {
+    let mut bytes = [b"SYNB\x01".to_vec(), varint(nodes.len())].concat();
+    for (type_id, payload) in nodes {
+        bytes.push(*type_id);
+        bytes.extend(varint(payload.len()));
+        bytes.extend_from_slice(payload);
+    }
+    bytes.extend_from_slice(&[0, 1, 1]);
+    binary::from_bytes(&bytes)
+}
+
+/// A reference to node `index`
+fn child(index: usize) -> Vec<u8> {
+    varint(index * 2 + 1)
+}
+
+#[test]
+fn test_binary_rejects_shared_nodes() {
+    assert!(patterns(&[(5, [vec![2], child(1), child(2)].concat()), (0, vec![]), (8, vec![0])]).is_ok());
+
+    // A node shared by two parents could make the tree exponentially
+    // larger than its encoding
+    let shared = patterns(&[(5, [vec![2], child(1), child(1)].concat()), (8, vec![0])]);
+    assert!(matches!(shared, Err(BinaryError::InvalidNode(1))));
+}
+
+#[test]
+fn test_binary_recursion_limit() {
+    // A chain of (Some (Some ... _)) as deep as the limit decodes, one node
+    // deeper fails without exhausting the stack. Decoding takes a few
+    // kilobytes of stack per node in debug builds, so this runs on a thread
+    // with a main thread's stack.
+    let chain = |len: usize| {
+        let mut nodes: Vec<_> = (1..len).map(|i| (8, child(i))).collect();
+        nodes.push((8, vec![0]));
+        patterns(&nodes)
+    };
+    std::thread::scope(|scope| {
+        let deep = std::thread::Builder::new().stack_size(8 << 20).spawn_scoped(scope, || {
+            assert!(chain(MAX_DEPTH).is_ok());
+            assert!(matches!(chain(MAX_DEPTH + 1), Err(BinaryError::RecursionLimit(MAX_DEPTH))));
+        });
+        deep.unwrap().join().unwrap();
+    });
+}
@@ -0,0 +1,42 @@
+//! Structural module diffs
+
+use synton_ast::diff::{diff, ChangeKind, ItemKind};
+use synton_parser::SyntonParser;
+
+#[test]
+fn test_module_diff() {
+    let parser = SyntonParser::new();
+    let old = parser
+        .parse_module(
+            "(fn fact [n] @pre((>= n 0)) (if (<= n 1) 1 (* n (fact (- n 1)))))\n\
+             (let x = 1)\n(print x)\n(while (< x 3) (print x))",
+        )
+        .unwrap();
+    let new = parser
+        .parse_module(
+            "(fn fact [n] @pre((> n 0)) (if (<= n 1) 1 (* n (fact (- n 2)))))\n\
+             (print x)\n(print 2)\n(while (< x 5) (print x))",
+        )
+        .unwrap();
+
+    assert!(diff(&old, &old.clone()).is_empty());
+    let changes = diff(&old, &new);
+    let summary: Vec<_> = changes.changes.iter().map(|c| (c.kind, c.item, c.path.as_str())).collect();
+    assert_eq!(
+        summary,
+        [
+            (ChangeKind::Changed, ItemKind::Contract, "fn fact/pre"),
+            (ChangeKind::Changed, ItemKind::Expression, "fn fact/body/else/right/args[0]/right"),
+            (ChangeKind::Removed, ItemKind::Variable, "let x"),
+            (ChangeKind::Added, ItemKind::Statement, "stmt[2]"),
+            (ChangeKind::Changed, ItemKind::Expression, "stmt[3]/cond/right"),
+        ]
+    );
+    assert_eq!(changes.changes[1].to_string(), "~ fn fact/body/else/right/args[0]/right: 1 => 2");
+    assert_eq!(changes.changes[3].to_string(), "+ stmt[2]: (print 2)");
+
+    // Serializes for tools
+    let json = serde_json::to_value(&changes).unwrap();
+    assert_eq!(json["changes"][0]["kind"], "changed");
+    assert_eq!(json["changes"][0]["old"], "@pre((>= n 0))");
+}
@@ -0,0 +1,104 @@
+//! AST traversal
+
+use synton_ast::diff::diff;
+use synton_ast::fold::{self, Fold};
+use synton_ast::visit::{self, Visitor};
+use synton_ast::visit_mut::{self, VisitorMut};
+use synton_ast::{Expr, ExprKind, Literal, Pattern, Stmt, StmtKind};
+use synton_parser::SyntonParser;
+
+#[derive(Default)]
+struct Counts {
+    exprs: usize,
+    patterns: usize,
+    literals: usize,
+}
+
+impl Visitor for Counts {
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.exprs += 1;
+        visit::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        self.patterns += 1;
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {
+        self.literals += 1;
+    }
+}
+
+/// Renames `x` to `y` wherever it is bound or used
+struct Rename;
+
+impl VisitorMut for Rename {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        if let StmtKind::Let { name, .. } = &mut stmt.kind {
+            if name == "x" {
+                *name = "y".to_string();
+            }
+        }
+        visit_mut::walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let ExprKind::Var { name, .. } = &mut expr.kind {
+            if name == "x" {
+                *name = "y".to_string();
+            }
+        }
+        visit_mut::walk_expr_mut(self, expr);
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        if let Pattern::Bind(name) = pattern {
+            if name == "x" {
+                *name = "y".to_string();
+            }
+        }
+        visit_mut::walk_pattern_mut(self, pattern);
+    }
+}
+
+/// Strips `some` wrappers
+struct Unwrap;
+
+impl Fold for Unwrap {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr.kind {
+            ExprKind::Some(inner) => self.fold_expr(*inner),
+            _ => fold::walk_expr(self, expr),
+        }
+    }
+}
+
+const SRC: &str = "(let x = (some (+ x 1)))\n(match (some x) (some (| x 2)) if (> x 0) => [x 3] _ => (size_of i32))";
+
+#[test]
+fn test_visitor() {
+    let module = SyntonParser::new().parse_module(SRC).unwrap();
+    let mut counts = Counts::default();
+    counts.visit_module(&module);
+    assert_eq!((counts.exprs, counts.patterns, counts.literals), (14, 5, 4));
+}
+
+#[test]
+fn test_visitor_mut() {
+    let parser = SyntonParser::new();
+    let mut module = parser.parse_module(SRC).unwrap();
+    Rename.visit_module_mut(&mut module);
+    let renamed = parser.parse_module(&SRC.replace('x', "y")).unwrap();
+    assert!(diff(&renamed, &module).is_empty());
+}
+
+#[test]
+fn test_fold() {
+    let parser = SyntonParser::new();
+    let unwrapped = Unwrap.fold_module(parser.parse_module(SRC).unwrap());
+    let expected = parser
+        .parse_module("(let x = (+ x 1))\n(match x (some (| x 2)) if (> x 0) => [x 3] _ => (size_of i32))")
+        .unwrap();
+    assert!(diff(&expected, &unwrapped).is_empty());
+}
@@ -142,6 +142,8 @@ pub mod error_codes {
     pub const UNDEFINED_REFERENCE: &str = "UNDEFINED_REFERENCE";
     pub const RUNTIME_ERROR: &str = "RUNTIME_ERROR";
     pub const DIVISION_BY_ZERO: &str = "DIVISION_BY_ZERO";
+    /// Integer arithmetic result outside the range of its type
+    pub const INTEGER_OVERFLOW: &str = "INTEGER_OVERFLOW";
     pub const INDEX_OUT_OF_BOUNDS: &str = "INDEX_OUT_OF_BOUNDS";
     pub const INVALID_MEMORY_ACCESS: &str = "INVALID_MEMORY_ACCESS";
 }
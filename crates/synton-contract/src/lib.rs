@@ -16,7 +16,7 @@ pub mod dso;
 pub mod solver;
 
 pub use dso::{DebugStateObject, DsoBuilder};
-pub use solver::{Solver, SolverResult, VerificationResult, DummySolver};
+pub use solver::{Solver, SolverResult, VerificationResult, DummySolver, EvalSolver};
 
 #[cfg(feature = "z3")]
 pub use solver::Z3Solver;
@@ -1,6 +1,12 @@
 //! SMT solver interface
+//!
+//! Constraints are boolean Synton expressions such as `(> x 0)`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
 
 use serde_json::Value;
+use synton_ast::{BinaryOp, CompareOp, Expr, ExprKind, Literal, UnaryOp};
 
 /// Result of verification
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -68,6 +74,255 @@ impl Solver for DummySolver {
     fn reset(&self) {}
 }
 
+/// Solver that works without an SMT backend, by evaluation
+///
+/// A constraint without variables is evaluated. Otherwise, variables
+/// defined by a top-level `(== x e)` conjunct take the value of `e`, and
+/// the others are tried with small integers, the integers in the
+/// constraint and their neighbours, and booleans. A satisfying assignment
+/// makes the constraint [`VerificationResult::Sat`]; not finding one only
+/// makes it [`VerificationResult::Unknown`]. Integers are unbounded and
+/// `/` and `%` by zero are undefined. Asserted constraints accumulate
+/// until the scope they were asserted in is popped.
+#[derive(Debug, Default)]
+pub struct EvalSolver {
+    scopes: Mutex<Vec<Vec<Expr>>>,
+}
+
+/// Largest number of assignments tried
+const MAX_ASSIGNMENTS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Val {
+    Int(i128),
+    Bool(bool),
+}
+
+impl EvalSolver {
+    /// Create a solver with nothing asserted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asserted constraints, split into their top-level conjuncts
+    fn conjuncts(&self) -> Vec<Expr> {
+        fn split(expr: &Expr, out: &mut Vec<Expr>) {
+            match &expr.kind {
+                ExprKind::Binary { op: BinaryOp::And, left, right } => {
+                    split(left, out);
+                    split(right, out);
+                }
+                _ => out.push(expr.clone()),
+            }
+        }
+        let mut out = Vec::new();
+        for expr in self.scopes.lock().unwrap().iter().flatten() {
+            split(expr, &mut out);
+        }
+        out
+    }
+
+    fn solve(&self) -> VerificationResult {
+        let conjuncts = self.conjuncts();
+        let mut vars = BTreeSet::new();
+        let mut ints = BTreeSet::from([-1, 0, 1]);
+        for conjunct in &conjuncts {
+            collect(conjunct, &mut vars, &mut ints);
+        }
+        let defs: Vec<(&str, &Expr)> = conjuncts
+            .iter()
+            .filter_map(|c| match &c.kind {
+                ExprKind::Compare { op: CompareOp::Eq, left, right } => match &left.kind {
+                    ExprKind::Var { name, .. } => Some((name.as_str(), &**right)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        let free: Vec<&str> =
+            vars.iter().map(String::as_str).filter(|var| !defs.iter().any(|(name, _)| name == var)).collect();
+
+        let ints: BTreeSet<i128> = ints.iter().flat_map(|&i| [i - 1, i, i + 1]).collect();
+        let mut candidates: Vec<Val> = ints.into_iter().map(Val::Int).collect();
+        candidates.extend([Val::Bool(true), Val::Bool(false)]);
+        let total = candidates.len().checked_pow(free.len() as u32);
+        if total.is_none_or(|total| total > MAX_ASSIGNMENTS) {
+            return VerificationResult::Unknown;
+        }
+
+        let mut choice = vec![0; free.len()];
+        loop {
+            let mut env: HashMap<&str, Val> = free.iter().zip(&choice).map(|(var, &i)| (*var, candidates[i])).collect();
+            // Definitions may depend on each other, in any order
+            let mut progress = true;
+            while progress {
+                progress = false;
+                for (name, value) in &defs {
+                    if !env.contains_key(name) {
+                        if let Some(value) = eval(value, &env) {
+                            env.insert(name, value);
+                            progress = true;
+                        }
+                    }
+                }
+            }
+            if conjuncts.iter().all(|c| eval(c, &env) == Some(Val::Bool(true))) {
+                return VerificationResult::Sat;
+            }
+            // Next assignment, as a counter in base `candidates.len()`
+            let Some(i) = choice.iter().position(|&c| c + 1 < candidates.len()) else { break };
+            choice[i] += 1;
+            choice[..i].iter_mut().for_each(|c| *c = 0);
+        }
+        if vars.is_empty() {
+            VerificationResult::Unsat
+        } else {
+            VerificationResult::Unknown
+        }
+    }
+}
+
+impl Solver for EvalSolver {
+    fn assert(&self, constraint: &str) -> VerificationResult {
+        let Ok(expr) = synton_parser::parse_expr(constraint) else {
+            return VerificationResult::Unknown;
+        };
+        {
+            let mut scopes = self.scopes.lock().unwrap();
+            if scopes.is_empty() {
+                scopes.push(Vec::new());
+            }
+            scopes.last_mut().unwrap().push(expr);
+        }
+        self.solve()
+    }
+
+    fn check_with_inputs(&self, constraint: &str, inputs: &Value) -> VerificationResult {
+        let Ok(expr) = synton_parser::parse_expr(constraint) else {
+            return VerificationResult::Unknown;
+        };
+        let mut env = HashMap::new();
+        if let Value::Object(inputs) = inputs {
+            for (name, value) in inputs {
+                let value = match value {
+                    Value::Bool(b) => Val::Bool(*b),
+                    Value::Number(n) => match n.as_i64() {
+                        Some(i) => Val::Int(i as i128),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                env.insert(name.as_str(), value);
+            }
+        }
+        match eval(&expr, &env) {
+            Some(Val::Bool(true)) => VerificationResult::Sat,
+            Some(Val::Bool(false)) => VerificationResult::Unsat,
+            _ => VerificationResult::Unknown,
+        }
+    }
+
+    fn push(&self) {
+        self.scopes.lock().unwrap().push(Vec::new());
+    }
+
+    fn pop(&self) {
+        self.scopes.lock().unwrap().pop();
+    }
+
+    fn reset(&self) {
+        self.scopes.lock().unwrap().clear();
+    }
+}
+
+/// Variables and integer literals of a constraint
+fn collect(expr: &Expr, vars: &mut BTreeSet<String>, ints: &mut BTreeSet<i128>) {
+    match &expr.kind {
+        ExprKind::Var { name, .. } => {
+            vars.insert(name.clone());
+        }
+        ExprKind::Literal(Literal::Integer(i)) => {
+            ints.insert(*i as i128);
+        }
+        ExprKind::Literal(Literal::TypedInteger(i, _)) => {
+            ints.insert(*i);
+        }
+        ExprKind::Unary { arg, .. } => collect(arg, vars, ints),
+        ExprKind::Binary { left, right, .. } | ExprKind::Compare { left, right, .. } => {
+            collect(left, vars, ints);
+            collect(right, vars, ints);
+        }
+        ExprKind::If { cond, then_branch, else_branch } => {
+            collect(cond, vars, ints);
+            collect(then_branch, vars, ints);
+            if let Some(else_branch) = else_branch {
+                collect(else_branch, vars, ints);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Value of a constraint, or `None` if it is undefined or not supported
+fn eval(expr: &Expr, env: &HashMap<&str, Val>) -> Option<Val> {
+    use Val::*;
+
+    let int = |e: &Expr| match eval(e, env)? {
+        Int(i) => Some(i),
+        Bool(_) => None,
+    };
+    let bool = |e: &Expr| match eval(e, env)? {
+        Bool(b) => Some(b),
+        Int(_) => None,
+    };
+    match &expr.kind {
+        ExprKind::Literal(Literal::Integer(i)) => Some(Int(*i as i128)),
+        ExprKind::Literal(Literal::TypedInteger(i, _)) => Some(Int(*i)),
+        ExprKind::Literal(Literal::Bool(b)) => Some(Bool(*b)),
+        ExprKind::Var { name, .. } => env.get(name.as_str()).copied(),
+        ExprKind::Unary { op: UnaryOp::Neg, arg } => int(arg)?.checked_neg().map(Int),
+        ExprKind::Unary { op: UnaryOp::Not, arg } => Some(Bool(!bool(arg)?)),
+        ExprKind::Binary { op: BinaryOp::And, left, right } => Some(Bool(bool(left)? && bool(right)?)),
+        ExprKind::Binary { op: BinaryOp::Or, left, right } => Some(Bool(bool(left)? || bool(right)?)),
+        ExprKind::Binary { op, left, right } => {
+            let (a, b) = (int(left)?, int(right)?);
+            let value = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div => a.checked_div(b),
+                BinaryOp::Mod => a.checked_rem(b),
+                _ => None,
+            };
+            value.map(Int)
+        }
+        ExprKind::Compare { op, left, right } => {
+            let ordering = match (eval(left, env)?, eval(right, env)?) {
+                (Int(a), Int(b)) => a.cmp(&b),
+                (Bool(a), Bool(b)) => a.cmp(&b),
+                _ => return None,
+            };
+            let holds = match op {
+                CompareOp::Eq => ordering.is_eq(),
+                CompareOp::NotEq => ordering.is_ne(),
+                CompareOp::Less => ordering.is_lt(),
+                CompareOp::LessEq => ordering.is_le(),
+                CompareOp::Greater => ordering.is_gt(),
+                CompareOp::GreaterEq => ordering.is_ge(),
+            };
+            Some(Bool(holds))
+        }
+        ExprKind::If { cond, then_branch, else_branch: Some(else_branch) } => {
+            if bool(cond)? {
+                eval(then_branch, env)
+            } else {
+                eval(else_branch, env)
+            }
+        }
+        _ => None,
+    }
+}
+
 #[cfg(feature = "z3")]
 /// Z3 solver implementation
 pub struct Z3Solver {
@@ -98,3 +353,77 @@ impl Solver for Z3Solver {
 
     fn reset(&self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use VerificationResult::*;
+
+    fn solve(constraint: &str) -> VerificationResult {
+        EvalSolver::new().assert(constraint)
+    }
+
+    #[test]
+    fn test_eval_solver_results() {
+        // Closed constraints are evaluated
+        assert_eq!(solve("(< (* 2 3) 7)"), Sat);
+        assert_eq!(solve("(&& true (> 1 2))"), Unsat);
+
+        // Candidates include the constraint's integers and their neighbours
+        assert_eq!(solve("(&& (> x 1000) (! b))"), Sat);
+        assert_eq!(solve("(== (if (> x 2) x 0) 3)"), Sat);
+        // Not finding an assignment proves nothing
+        assert_eq!(solve("(== (* x x) 2)"), Unknown);
+        assert_eq!(solve("(&& (> x 0) (< x 0))"), Unknown);
+        assert_eq!(solve("(> x \"s\")"), Unknown);
+        assert_eq!(solve("(> x"), Unknown);
+    }
+
+    #[test]
+    fn test_eval_solver_scopes() {
+        let solver = EvalSolver::new();
+        assert_eq!(solver.assert("(> x 0)"), Sat);
+        solver.push();
+        assert_eq!(solver.assert("(< x 0)"), Unknown);
+        solver.pop();
+        assert_eq!(solver.assert("(< x 5)"), Sat);
+        solver.reset();
+        assert_eq!(solver.assert("(< x 0)"), Sat);
+    }
+
+    #[test]
+    fn test_eval_solver_definitions() {
+        // z is defined in terms of y, which is defined after it; only x is
+        // enumerated
+        assert_eq!(solve("(&& (== z (+ y 1)) (&& (== y (* x 2)) (> z 4)))"), Sat);
+        assert_eq!(solve("(&& (== y (* x 2)) (== y 3))"), Unknown);
+
+        // Defined variables do not count towards the assignments tried
+        let defs = "(&& (== a x) (&& (== b a) (&& (== c b) (&& (== d c) (&& (== e d) (== f e))))))";
+        assert_eq!(solve(&format!("(&& {} (> f 1))", defs)), Sat);
+    }
+
+    #[test]
+    fn test_eval_solver_max_assignments() {
+        // Without other literals each free variable has seven candidates:
+        // -2 to 2, true and false
+        assert!(7usize.pow(5) <= MAX_ASSIGNMENTS && 7usize.pow(6) > MAX_ASSIGNMENTS);
+        assert_eq!(solve("(> (+ a (+ b (+ c (+ d e)))) 0)"), Sat);
+        assert_eq!(solve("(> (+ a (+ b (+ c (+ d (+ e f))))) 0)"), Unknown);
+    }
+
+    #[test]
+    fn test_eval_solver_division_by_zero() {
+        // Assignments that divide by zero are skipped, not evaluated
+        assert_eq!(solve("(== (/ 10 x) 5)"), Sat);
+        assert_eq!(solve("(== (% 7 x) 1)"), Sat);
+        assert_eq!(solve("(== (% x 0) x)"), Unknown);
+
+        let solver = EvalSolver::new();
+        assert_eq!(solver.check_with_inputs("(== (/ x y) 0)", &json!({"x": 1, "y": 2})), Sat);
+        assert_eq!(solver.check_with_inputs("(== (/ x y) 1)", &json!({"x": 1, "y": 2})), Unsat);
+        assert_eq!(solver.check_with_inputs("(== (/ x y) 0)", &json!({"x": 1, "y": 0})), Unknown);
+        assert_eq!(solver.check_with_inputs("(== (% x y) 0)", &json!({"x": 1, "y": 0})), Unknown);
+    }
+}
@@ -1,7 +1,7 @@
 //! Code generation utilities
 
 use synton_ast::{Module, Expr, Stmt, Type, Span};
-use crate::DecompilerResult;
+use crate::{DecompilerError, DecompilerResult};
 
 /// Code generator configuration
 #[derive(Debug, Clone)]
@@ -16,6 +16,8 @@ pub struct CodegenConfig {
     pub pretty: bool,
     /// Line width (for pretty printing)
     pub line_width: usize,
+    /// Maximum expression nesting depth
+    pub max_depth: usize,
 }
 
 impl Default for CodegenConfig {
@@ -26,6 +28,7 @@ impl Default for CodegenConfig {
             comments: true,
             pretty: true,
             line_width: 100,
+            max_depth: 256,
         }
     }
 }
@@ -59,6 +62,18 @@ pub trait Codegen {
     fn gen_stmt(&self, stmt: &Stmt) -> DecompilerResult<String>;
     fn gen_type(&self, ty: &Type) -> DecompilerResult<String>;
 
+    /// Fail once expression nesting goes past the configured maximum depth
+    fn check_depth(&self, depth: usize, expr: &Expr) -> DecompilerResult<()> {
+        let max_depth = self.config().max_depth;
+        if depth < max_depth {
+            return Ok(());
+        }
+        Err(DecompilerError::RecursionLimit {
+            max_depth,
+            span: expr.span.range().into(),
+        })
+    }
+
     /// Helper to join items with separators
     fn join<T, F>(&self, items: &[T], sep: &str, f: F) -> String
     where
@@ -77,6 +77,17 @@ pub enum DecompilerError {
     #[diagnostic(code(synton::decompiler::codegen))]
     Codegen(String, #[label] SourceSpan),
 
+    /// Expression nesting exceeds the configured limit
+    #[error("expression nesting exceeds the maximum depth of {max_depth}")]
+    #[diagnostic(code(synton::decompiler::recursion_limit))]
+    RecursionLimit {
+        /// Configured maximum nesting depth
+        max_depth: usize,
+        /// First expression past the limit
+        #[label("nested too deeply")]
+        span: SourceSpan,
+    },
+
     #[error("invalid AST for decompilation")]
     #[diagnostic(code(synton::decompiler::invalid_ast))]
     InvalidAst {
@@ -160,3 +171,36 @@ pub fn decompile(
 ) -> DecompilerResult<String> {
     Decompiler::new(lang).decompile_module(module)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synton_ast::{BinaryOp, Expr, ExprKind, Literal, Position, Span};
+
+    /// `(+ 1 (+ 1 ... 0))` with `n` additions
+    fn nested_sum(n: usize) -> Expr {
+        let span = Span::new(Position::start(), Position::start());
+        let int = |value| Expr::new(ExprKind::Literal(Literal::Integer(value)), span);
+        (0..n).fold(int(0), |inner, _| {
+            let kind = ExprKind::Binary { op: BinaryOp::Add, left: Box::new(int(1)), right: Box::new(inner) };
+            Expr::new(kind, span)
+        })
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let expr = nested_sum(10);
+        for lang in [TargetLang::Python, TargetLang::TypeScript] {
+            let decompile = |max_depth| {
+                Decompiler::with_config(lang, CodegenConfig { max_depth, ..CodegenConfig::default() })
+                    .decompile_expr(&expr)
+            };
+
+            // The innermost operands sit eleven expressions deep
+            let out = decompile(11).unwrap();
+            assert!(out.starts_with("(1 + (1 + "), "{}: {}", lang.name(), out);
+            assert!(matches!(decompile(10), Err(DecompilerError::RecursionLimit { max_depth: 10, .. })));
+            assert!(matches!(decompile(1), Err(DecompilerError::RecursionLimit { max_depth: 1, .. })));
+        }
+    }
+}
@@ -37,7 +37,7 @@ impl Codegen for TypeScriptCodegen {
 
     fn gen_expr(&self, expr: &Expr) -> DecompilerResult<String> {
         let mut buf = String::new();
-        self.gen_expr_inner(&mut buf, expr)?;
+        self.gen_expr_inner(&mut buf, expr, 0)?;
         Ok(buf)
     }
 
@@ -143,7 +143,8 @@ impl TypeScriptCodegen {
         Ok(())
     }
 
-    fn gen_expr_inner(&self, buf: &mut String, expr: &Expr) -> DecompilerResult<()> {
+    fn gen_expr_inner(&self, buf: &mut String, expr: &Expr, depth: usize) -> DecompilerResult<()> {
+        self.check_depth(depth, expr)?;
         match &expr.kind {
             synton_ast::ExprKind::Literal(lit) => {
                 self.gen_literal(buf, lit);
@@ -153,30 +154,30 @@ impl TypeScriptCodegen {
             }
             synton_ast::ExprKind::Binary { op, left, right } => {
                 buf.push_str("(");
-                self.gen_expr_inner(buf, left)?;
+                self.gen_expr_inner(buf, left, depth + 1)?;
                 buf.push_str(" ");
                 buf.push_str(op.token());
                 buf.push_str(" ");
-                self.gen_expr_inner(buf, right)?;
+                self.gen_expr_inner(buf, right, depth + 1)?;
                 buf.push_str(")");
             }
             synton_ast::ExprKind::Call { callee, args } => {
-                self.gen_expr_inner(buf, callee)?;
+                self.gen_expr_inner(buf, callee, depth + 1)?;
                 buf.push_str("(");
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 { buf.push_str(", "); }
-                    self.gen_expr_inner(buf, arg)?;
+                    self.gen_expr_inner(buf, arg, depth + 1)?;
                 }
                 buf.push_str(")");
             }
             synton_ast::ExprKind::If { cond, then_branch, else_branch } => {
                 buf.push_str("(");
-                self.gen_expr_inner(buf, cond)?;
+                self.gen_expr_inner(buf, cond, depth + 1)?;
                 buf.push_str(" ? ");
-                self.gen_expr_inner(buf, then_branch)?;
+                self.gen_expr_inner(buf, then_branch, depth + 1)?;
                 if let Some(else_br) = else_branch {
                     buf.push_str(" : ");
-                    self.gen_expr_inner(buf, else_br)?;
+                    self.gen_expr_inner(buf, else_br, depth + 1)?;
                 }
                 buf.push_str(")");
             }
@@ -191,6 +192,8 @@ impl TypeScriptCodegen {
         match lit {
             synton_ast::expr::Literal::Integer(i) => buf.push_str(&i.to_string()),
             synton_ast::expr::Literal::Float(f) => buf.push_str(&f.to_string()),
+            synton_ast::expr::Literal::TypedInteger(i, _) => buf.push_str(&i.to_string()),
+            synton_ast::expr::Literal::TypedFloat(f, _) => buf.push_str(&f.to_string()),
             synton_ast::expr::Literal::String(s) => {
                 buf.push_str("\"");
                 buf.push_str(s);
@@ -0,0 +1,347 @@
+//! Core IR definitions
+
+use serde::{Deserialize, Serialize};
+use synton_ast::{
+    BinaryOp, CompareOp, ContractKind, EnumDecl, ExportDecl, FnId, ImportDecl, Literal, ModuleId, NodeId, Span,
+    StructDecl, Type, UnaryOp, VarId,
+};
+
+/// Function that starts an iteration: `(iter collection)` returns an iterator
+pub const ITER_FN: &str = "iter";
+
+/// Function that advances an iteration: `(next iterator)` returns `(some item)`
+/// or `(none)` once the iterator is exhausted
+pub const NEXT_FN: &str = "next";
+
+/// A lowered module
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Program {
+    /// Identifier of the source module
+    pub id: ModuleId,
+    /// Import declarations
+    pub imports: Vec<ImportDecl>,
+    /// Export declarations
+    pub exports: Vec<ExportDecl>,
+    /// Struct, enum and type alias declarations
+    pub types: Vec<TypeDecl>,
+    /// All functions, including lifted lambdas, indexed by [`FnId`]
+    pub functions: Vec<Function>,
+    /// All variables, indexed by [`VarId`]
+    pub vars: Vec<Var>,
+    /// Top-level code, in source order
+    pub body: Block,
+}
+
+impl Program {
+    /// Look up a variable
+    pub fn var(&self, id: VarId) -> &Var {
+        &self.vars[id.0 as usize]
+    }
+
+    /// Look up a function
+    pub fn function(&self, id: FnId) -> &Function {
+        &self.functions[id.0 as usize]
+    }
+}
+
+/// A variable: a `let` or `const` binding, a parameter or a pattern binding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Var {
+    /// Source name. Variables introduced by lowering start with `#`.
+    pub name: String,
+    /// Whether the variable can be assigned to
+    pub mutable: bool,
+    /// Declared type
+    pub ty: Option<Type>,
+    /// Span of the declaration
+    pub span: Span,
+}
+
+/// Type declaration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TypeDecl {
+    /// Struct declaration
+    Struct(StructDecl),
+    /// Enum declaration
+    Enum(EnumDecl),
+    /// Type alias
+    Alias {
+        /// Alias name
+        name: String,
+        /// Type parameters
+        params: Vec<String>,
+        /// Aliased type
+        ty: Type,
+    },
+}
+
+/// A named function or a lifted lambda
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Function {
+    /// Function identifier
+    pub id: FnId,
+    /// Declared name, `None` for lambdas
+    pub name: Option<String>,
+    /// Parameters
+    pub params: Vec<VarId>,
+    /// Declared return type
+    pub ret_type: Option<Type>,
+    /// Pre- and postconditions
+    pub contracts: Vec<Contract>,
+    /// Body, `None` for declarations without one
+    pub body: Option<Block>,
+    /// Variables of enclosing functions used in the body
+    pub captures: Vec<VarId>,
+    /// Span of the declaration
+    pub span: Span,
+}
+
+/// A contract with its condition lowered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contract {
+    /// Kind of contract
+    pub kind: ContractKind,
+    /// Condition
+    pub cond: Expr,
+}
+
+/// A sequence of statements, optionally ending in a value, that opens a scope
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Block {
+    /// Variables declared directly in this scope
+    pub vars: Vec<VarId>,
+    /// Statements
+    pub stmts: Vec<Stmt>,
+    /// Value of the block, unit if `None`
+    pub value: Option<Box<Expr>>,
+}
+
+/// Statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Stmt {
+    /// Variable binding
+    Let {
+        /// Bound variable
+        var: VarId,
+        /// Initial value
+        init: Option<Expr>,
+    },
+    /// Assignment
+    Assign {
+        /// Assigned place
+        place: Place,
+        /// New value
+        value: Expr,
+    },
+    /// Inline contract
+    Contract(Contract),
+    /// Expression evaluated for its effects
+    Expr(Expr),
+}
+
+/// Assignable place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Place {
+    /// Variable
+    Var(VarId),
+    /// Name with no variable declaration in the module
+    Global(String),
+    /// Element of an array
+    Index {
+        /// Indexed value
+        base: Box<Expr>,
+        /// Index
+        index: Box<Expr>,
+    },
+    /// Field of a struct
+    Field {
+        /// Struct value
+        base: Box<Expr>,
+        /// Field name
+        name: String,
+    },
+    /// Target of a reference
+    Deref(Box<Expr>),
+}
+
+/// Expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expr {
+    /// Expression kind
+    pub kind: ExprKind,
+    /// Source span
+    pub span: Span,
+    /// Node of the source expression, if it came from one
+    pub id: Option<NodeId>,
+}
+
+impl Expr {
+    /// Create an expression not tied to a source node
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span, id: None }
+    }
+}
+
+/// Expression kinds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExprKind {
+    /// Literal value
+    Literal(Literal),
+    /// Local or module variable
+    Var(VarId),
+    /// Named function
+    Fn(FnId),
+    /// Name with no declaration in the module, such as a builtin or import
+    Global(String),
+    /// Unary operator
+    Unary {
+        /// Operator
+        op: UnaryOp,
+        /// Operand
+        arg: Box<Expr>,
+    },
+    /// Binary operator
+    Binary {
+        /// Operator
+        op: BinaryOp,
+        /// Left operand
+        left: Box<Expr>,
+        /// Right operand
+        right: Box<Expr>,
+    },
+    /// Comparison
+    Compare {
+        /// Operator
+        op: CompareOp,
+        /// Left operand
+        left: Box<Expr>,
+        /// Right operand
+        right: Box<Expr>,
+    },
+    /// Call, including method calls with the receiver as first argument
+    Call {
+        /// Called function
+        callee: Box<Expr>,
+        /// Arguments
+        args: Vec<Expr>,
+    },
+    /// Indexing
+    Index {
+        /// Indexed value
+        base: Box<Expr>,
+        /// Index
+        index: Box<Expr>,
+    },
+    /// Field access
+    Field {
+        /// Struct value
+        base: Box<Expr>,
+        /// Field name
+        name: String,
+    },
+    /// Array literal
+    Array(Vec<Expr>),
+    /// Tuple literal
+    Tuple(Vec<Expr>),
+    /// Struct literal
+    Struct {
+        /// Struct name
+        ty: String,
+        /// Field values
+        fields: Vec<(String, Expr)>,
+    },
+    /// Closure over a lifted lambda
+    Closure(FnId),
+    /// Block
+    Block(Block),
+    /// Conditional, with an empty else block when the source has none
+    If {
+        /// Condition
+        cond: Box<Expr>,
+        /// Taken when the condition holds
+        then_branch: Block,
+        /// Taken otherwise
+        else_branch: Block,
+    },
+    /// The only loop: runs until a `break` or `return`
+    Loop {
+        /// Loop body
+        body: Block,
+    },
+    /// Leave the innermost loop
+    Break(Option<Box<Expr>>),
+    /// Start the next iteration of the innermost loop
+    Continue,
+    /// Leave the function
+    Return(Option<Box<Expr>>),
+    /// Pattern match
+    Match {
+        /// Matched value
+        scrutinee: Box<Expr>,
+        /// Arms, tried in order
+        arms: Vec<Arm>,
+    },
+    /// `(some value)`
+    Some(Box<Expr>),
+    /// `(none)`
+    None,
+    /// Type cast
+    Cast {
+        /// Converted value
+        expr: Box<Expr>,
+        /// Target type
+        ty: Type,
+    },
+    /// Size of a type
+    SizeOf(Type),
+    /// Error sentinel carried over from the AST
+    Error,
+}
+
+/// Match arm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Arm {
+    /// Variables bound by the pattern
+    pub vars: Vec<VarId>,
+    /// Pattern
+    pub pattern: Pattern,
+    /// Guard
+    pub guard: Option<Expr>,
+    /// Body
+    pub body: Expr,
+}
+
+/// Pattern with resolved bindings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// Literal
+    Literal(Literal),
+    /// Binding. Every alternative of an or-pattern binds the same variables.
+    Bind(VarId),
+    /// Alternatives
+    Or(Vec<Pattern>),
+    /// Struct
+    Struct {
+        /// Struct name
+        ty: String,
+        /// Field patterns
+        fields: Vec<(String, Pattern)>,
+    },
+    /// Tuple
+    Tuple(Vec<Pattern>),
+    /// Slice
+    Slice(Vec<Pattern>),
+    /// Enum variant
+    Variant {
+        /// Variant name
+        name: String,
+        /// Field patterns, by position
+        args: Vec<Pattern>,
+    },
+    /// `(some pattern)`
+    Some(Box<Pattern>),
+    /// `(none)`
+    None,
+}
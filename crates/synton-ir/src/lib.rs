@@ -0,0 +1,37 @@
+//! # Synton IR
+//!
+//! Core intermediate representation for the Synton programming language.
+//!
+//! The AST mirrors the surface syntax, so several constructs overlap: `if`,
+//! `break` and `return` exist as both statements and expressions, there are
+//! three kinds of loop, and methods are called differently from functions.
+//! [`lower`] desugars a module into a smaller core where:
+//!
+//! - everything that computes is an [`Expr`], and the only statements are
+//!   bindings, assignments, contracts and expressions evaluated for effect
+//! - `while` and `for` become [`ExprKind::Loop`], with `for` going through
+//!   the iterator protocol ([`ITER_FN`] and [`NEXT_FN`])
+//! - method calls become plain calls with the receiver as first argument
+//! - every variable has a [`VarId`](synton_ast::VarId) and every function
+//!   an [`FnId`](synton_ast::FnId), and each [`Block`] lists the variables
+//!   it declares
+//! - lambdas are lifted into [`Function`]s that record their captures
+//!
+//! Types are carried only as annotations. Runtime values have a uniform,
+//! tagged representation, so a generic function or a generic struct is
+//! lowered once and used at every instantiation; there is no
+//! monomorphization step.
+
+#![warn(missing_docs, unused_crate_dependencies)]
+
+pub mod ir;
+pub mod lower;
+
+pub use ir::{
+    Arm, Block, Contract, Expr, ExprKind, Function, Pattern, Place, Program, Stmt, TypeDecl, Var, ITER_FN, NEXT_FN,
+};
+pub use lower::lower;
+
+// Only the integration tests parse source
+#[cfg(test)]
+use synton_parser as _;
@@ -0,0 +1,446 @@
+//! Lowering from the AST to the core IR
+
+use std::slice;
+
+use rustc_hash::FxHashMap;
+use synton_ast::{self as ast, FnId, Span, StmtKind, Type, VarId};
+
+use crate::ir::{
+    Arm, Block, Contract, Expr, ExprKind, Function, Pattern, Place, Program, Stmt, TypeDecl, Var, ITER_FN, NEXT_FN,
+};
+
+/// Lower a module to the core IR.
+///
+/// Lowering never fails. A name with no declaration in scope becomes
+/// [`ExprKind::Global`]; whether it exists at runtime is for later passes
+/// to decide.
+pub fn lower(module: &ast::Module) -> Program {
+    let mut lowerer = Lowerer::default();
+    let body = lowerer.block(&module.stmts, None);
+    Program {
+        id: module.id.clone(),
+        imports: module.imports.clone(),
+        exports: module.exports.clone(),
+        types: lowerer.types,
+        functions: lowerer
+            .functions
+            .into_iter()
+            .map(|function| function.expect("every reserved function is lowered"))
+            .collect(),
+        vars: lowerer.vars,
+        body,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Binding {
+    Var(VarId),
+    Fn(FnId),
+}
+
+#[derive(Default)]
+struct Scope {
+    names: FxHashMap<String, Binding>,
+    vars: Vec<VarId>,
+    /// Number of functions enclosing the scope
+    depth: usize,
+}
+
+#[derive(Default)]
+struct Lowerer {
+    vars: Vec<Var>,
+    /// Functions by id, `None` while being lowered
+    functions: Vec<Option<Function>>,
+    types: Vec<TypeDecl>,
+    scopes: Vec<Scope>,
+    /// Captures of the functions being lowered, innermost last
+    captures: Vec<Vec<VarId>>,
+}
+
+impl Lowerer {
+    fn push_scope(&mut self) {
+        let depth = self.captures.len();
+        self.scopes.push(Scope { depth, ..Scope::default() });
+    }
+
+    /// Close the innermost scope, returning the variables declared in it
+    fn pop_scope(&mut self) -> Vec<VarId> {
+        self.scopes.pop().expect("scope is open").vars
+    }
+
+    fn declare(&mut self, name: &str, mutable: bool, ty: Option<Type>, span: Span) -> VarId {
+        let id = VarId::new(self.vars.len() as u32);
+        self.vars.push(Var { name: name.to_string(), mutable, ty, span });
+        let scope = self.scopes.last_mut().expect("scope is open");
+        scope.names.insert(name.to_string(), Binding::Var(id));
+        scope.vars.push(id);
+        id
+    }
+
+    fn reserve_fn(&mut self, name: Option<&str>) -> FnId {
+        let id = FnId::new(self.functions.len() as u32);
+        self.functions.push(None);
+        if let Some(name) = name {
+            let scope = self.scopes.last_mut().expect("scope is open");
+            scope.names.insert(name.to_string(), Binding::Fn(id));
+        }
+        id
+    }
+
+    /// Resolve a name, recording it as a capture of every function between
+    /// the use and a local declaration. Module-level variables are never
+    /// captured.
+    fn resolve(&mut self, name: &str) -> ExprKind {
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
+            match scope.names.get(name) {
+                Some(&Binding::Fn(id)) => return ExprKind::Fn(id),
+                Some(&Binding::Var(id)) => {
+                    if index > 0 {
+                        for captures in &mut self.captures[scope.depth..] {
+                            if !captures.contains(&id) {
+                                captures.push(id);
+                            }
+                        }
+                    }
+                    return ExprKind::Var(id);
+                }
+                None => {}
+            }
+        }
+        ExprKind::Global(name.to_string())
+    }
+
+    fn block(&mut self, stmts: &[ast::Stmt], value: Option<&ast::Expr>) -> Block {
+        self.push_scope();
+        let stmts = self.stmts(stmts);
+        let value = value.map(|value| Box::new(self.expr(value)));
+        Block { vars: self.pop_scope(), stmts, value }
+    }
+
+    /// Lower an expression into a block, reusing the scope of a block expression
+    fn expr_block(&mut self, expr: &ast::Expr) -> Block {
+        match &expr.kind {
+            ast::ExprKind::Block(stmts, value) => self.block(stmts, value.as_deref()),
+            _ => Block { value: Some(Box::new(self.expr(expr))), ..Block::default() },
+        }
+    }
+
+    /// Lower a statement into a block, reusing the scope of a block statement
+    fn stmt_block(&mut self, stmt: &ast::Stmt) -> Block {
+        match &stmt.kind {
+            StmtKind::Block(stmts) => self.block(stmts, None),
+            _ => self.block(slice::from_ref(stmt), None),
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[ast::Stmt]) -> Vec<Stmt> {
+        // Functions are visible in their whole block, so they can call each other
+        let fn_ids: Vec<FnId> = stmts
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::FnDecl(decl) => Some(decl),
+                _ => None,
+            })
+            .map(|decl| self.reserve_fn(Some(&decl.name)))
+            .collect();
+        let mut fn_ids = fn_ids.into_iter();
+
+        let mut out = Vec::new();
+        for stmt in stmts {
+            let span = stmt.span;
+            match &stmt.kind {
+                StmtKind::Expr(expr) => out.push(Stmt::Expr(self.expr(expr))),
+                StmtKind::Let { name, ty, init, mutable, .. } => {
+                    let init = init.as_deref().map(|init| self.expr(init));
+                    let var = self.declare(name, *mutable, ty.clone(), span);
+                    out.push(Stmt::Let { var, init });
+                }
+                StmtKind::Const { name, ty, value } => {
+                    let init = Some(self.expr(value));
+                    let var = self.declare(name, false, Some(ty.clone()), span);
+                    out.push(Stmt::Let { var, init });
+                }
+                StmtKind::Assign { target, value } => {
+                    let place = self.place(target);
+                    let value = self.expr(value);
+                    out.push(Stmt::Assign { place, value });
+                }
+                StmtKind::Contract(contract) => out.push(Stmt::Contract(self.contract(contract))),
+                StmtKind::FnDecl(decl) => {
+                    let id = fn_ids.next().expect("declaration was reserved");
+                    let function = Function {
+                        name: Some(decl.name.clone()),
+                        ret_type: decl.ret_type.clone(),
+                        ..self.function(id, &decl.params, &decl.contracts, decl.body.as_deref(), decl.span)
+                    };
+                    self.functions[id.0 as usize] = Some(function);
+                }
+                StmtKind::StructDecl(decl) => self.types.push(TypeDecl::Struct(decl.clone())),
+                StmtKind::EnumDecl(decl) => self.types.push(TypeDecl::Enum(decl.clone())),
+                StmtKind::TypeAlias { name, params, ty } => self.types.push(TypeDecl::Alias {
+                    name: name.clone(),
+                    params: params.clone(),
+                    ty: ty.clone(),
+                }),
+                StmtKind::Empty => {}
+                StmtKind::Block(_)
+                | StmtKind::If { .. }
+                | StmtKind::Loop { .. }
+                | StmtKind::While { .. }
+                | StmtKind::For { .. }
+                | StmtKind::Break(_)
+                | StmtKind::Continue
+                | StmtKind::Return(_)
+                | StmtKind::Error => out.push(Stmt::Expr(self.stmt_expr(stmt))),
+            }
+        }
+        out
+    }
+
+    /// Lower a control flow statement to the expression it stands for
+    fn stmt_expr(&mut self, stmt: &ast::Stmt) -> Expr {
+        let span = stmt.span;
+        let kind = match &stmt.kind {
+            StmtKind::Block(stmts) => ExprKind::Block(self.block(stmts, None)),
+            StmtKind::If { cond, then_branch, else_branch } => ExprKind::If {
+                cond: Box::new(self.expr(cond)),
+                then_branch: self.stmt_block(then_branch),
+                else_branch: match else_branch {
+                    Some(else_branch) => self.stmt_block(else_branch),
+                    None => Block::default(),
+                },
+            },
+            StmtKind::Loop { body } => ExprKind::Loop { body: self.stmt_block(body) },
+            StmtKind::While { cond, body } => {
+                let cond = self.expr(cond);
+                let body = self.stmt_block(body);
+                while_loop(cond, body, span)
+            }
+            StmtKind::For { var, iter, body } => self.for_loop(var, iter, body, span),
+            StmtKind::Break(value) => ExprKind::Break(self.opt_boxed(value.as_deref())),
+            StmtKind::Continue => ExprKind::Continue,
+            StmtKind::Return(value) => ExprKind::Return(self.opt_boxed(value.as_deref())),
+            StmtKind::Error => ExprKind::Error,
+            _ => ExprKind::Block(self.block(slice::from_ref(stmt), None)),
+        };
+        Expr::new(kind, span)
+    }
+
+    /// `(for x in xs body)` becomes
+    ///
+    /// ```text
+    /// { (let #iter = (iter xs))
+    ///   (loop (match (next #iter) (some x) => body (none) => (break))) }
+    /// ```
+    fn for_loop(&mut self, var: &str, iter: &ast::Expr, body: &ast::Stmt, span: Span) -> ExprKind {
+        let call = |name: &str, arg: Expr| {
+            let callee = Expr::new(ExprKind::Global(name.to_string()), span);
+            Expr::new(ExprKind::Call { callee: Box::new(callee), args: vec![arg] }, span)
+        };
+
+        let init = call(ITER_FN, self.expr(iter));
+        self.push_scope();
+        let iterator = self.declare("#iter", false, None, span);
+
+        self.push_scope();
+        let item = self.declare(var, false, None, span);
+        let body = self.stmt_block(body);
+        let item_vars = self.pop_scope();
+
+        let arms = vec![
+            Arm {
+                vars: item_vars,
+                pattern: Pattern::Some(Box::new(Pattern::Bind(item))),
+                guard: None,
+                body: Expr::new(ExprKind::Block(body), span),
+            },
+            Arm {
+                vars: Vec::new(),
+                pattern: Pattern::None,
+                guard: None,
+                body: Expr::new(ExprKind::Break(None), span),
+            },
+        ];
+        let next = call(NEXT_FN, Expr::new(ExprKind::Var(iterator), span));
+        let step = Expr::new(ExprKind::Match { scrutinee: Box::new(next), arms }, span);
+        let looped = Expr::new(ExprKind::Loop { body: Block { value: Some(Box::new(step)), ..Block::default() } }, span);
+
+        ExprKind::Block(Block {
+            vars: self.pop_scope(),
+            stmts: vec![Stmt::Let { var: iterator, init: Some(init) }],
+            value: Some(Box::new(looped)),
+        })
+    }
+
+    /// Lower a function body in a scope of its own. The caller fills in the
+    /// name and return type.
+    fn function(
+        &mut self,
+        id: FnId,
+        params: &[ast::Param],
+        contracts: &[ast::Contract],
+        body: Option<&ast::Expr>,
+        span: Span,
+    ) -> Function {
+        self.captures.push(Vec::new());
+        self.push_scope();
+        let params = params
+            .iter()
+            .map(|param| self.declare(&param.name, false, param.ty.clone(), param.span))
+            .collect();
+        let contracts = contracts.iter().map(|contract| self.contract(contract)).collect();
+        let body = body.map(|body| self.expr_block(body));
+        self.pop_scope();
+        let captures = self.captures.pop().expect("function is open");
+
+        Function { id, name: None, params, ret_type: None, contracts, body, captures, span }
+    }
+
+    fn contract(&mut self, contract: &ast::Contract) -> Contract {
+        Contract { kind: contract.kind, cond: self.expr(&contract.expr) }
+    }
+
+    fn place(&mut self, target: &ast::AssignTarget) -> Place {
+        match target {
+            ast::AssignTarget::Var(name) => match self.resolve(name) {
+                ExprKind::Var(id) => Place::Var(id),
+                _ => Place::Global(name.clone()),
+            },
+            ast::AssignTarget::Index { base, index } => Place::Index {
+                base: self.boxed(base),
+                index: self.boxed(index),
+            },
+            ast::AssignTarget::Field { base, name } => Place::Field { base: self.boxed(base), name: name.clone() },
+            ast::AssignTarget::Deref(expr) => Place::Deref(self.boxed(expr)),
+        }
+    }
+
+    fn boxed(&mut self, expr: &ast::Expr) -> Box<Expr> {
+        Box::new(self.expr(expr))
+    }
+
+    fn opt_boxed(&mut self, expr: Option<&ast::Expr>) -> Option<Box<Expr>> {
+        expr.map(|expr| self.boxed(expr))
+    }
+
+    fn exprs(&mut self, exprs: &[ast::Expr]) -> Vec<Expr> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) -> Expr {
+        let kind = match &expr.kind {
+            ast::ExprKind::Literal(literal) => ExprKind::Literal(literal.clone()),
+            ast::ExprKind::Var { name, .. } => self.resolve(name),
+            ast::ExprKind::Unary { op, arg } => ExprKind::Unary { op: *op, arg: self.boxed(arg) },
+            ast::ExprKind::Binary { op, left, right } => ExprKind::Binary {
+                op: *op,
+                left: self.boxed(left),
+                right: self.boxed(right),
+            },
+            ast::ExprKind::Compare { op, left, right } => ExprKind::Compare {
+                op: *op,
+                left: self.boxed(left),
+                right: self.boxed(right),
+            },
+            ast::ExprKind::Call { callee, args } => ExprKind::Call {
+                callee: self.boxed(callee),
+                args: self.exprs(args),
+            },
+            ast::ExprKind::MethodCall { object, method, args } => {
+                let callee = Expr::new(self.resolve(method), expr.span);
+                let mut all = vec![self.expr(object)];
+                all.extend(self.exprs(args));
+                ExprKind::Call { callee: Box::new(callee), args: all }
+            }
+            ast::ExprKind::Index { base, index } => ExprKind::Index {
+                base: self.boxed(base),
+                index: self.boxed(index),
+            },
+            ast::ExprKind::Field { base, name } => ExprKind::Field { base: self.boxed(base), name: name.clone() },
+            ast::ExprKind::Array(items) => ExprKind::Array(self.exprs(items)),
+            ast::ExprKind::Tuple(items) => ExprKind::Tuple(self.exprs(items)),
+            ast::ExprKind::Struct { ty, fields } => ExprKind::Struct {
+                ty: ty.clone(),
+                fields: fields.iter().map(|(name, value)| (name.clone(), self.expr(value))).collect(),
+            },
+            ast::ExprKind::Lambda { params, body, .. } => {
+                let id = self.reserve_fn(None);
+                let function = self.function(id, params, &[], Some(body), expr.span);
+                self.functions[id.0 as usize] = Some(function);
+                ExprKind::Closure(id)
+            }
+            ast::ExprKind::Block(stmts, value) => ExprKind::Block(self.block(stmts, value.as_deref())),
+            ast::ExprKind::If { cond, then_branch, else_branch } => ExprKind::If {
+                cond: self.boxed(cond),
+                then_branch: self.expr_block(then_branch),
+                else_branch: match else_branch {
+                    Some(else_branch) => self.expr_block(else_branch),
+                    None => Block::default(),
+                },
+            },
+            ast::ExprKind::Loop { body } => ExprKind::Loop { body: self.expr_block(body) },
+            ast::ExprKind::Break(value) => ExprKind::Break(self.opt_boxed(value.as_deref())),
+            ast::ExprKind::Continue => ExprKind::Continue,
+            ast::ExprKind::Return(value) => ExprKind::Return(self.opt_boxed(value.as_deref())),
+            ast::ExprKind::Match { scrutinee, arms } => ExprKind::Match {
+                scrutinee: self.boxed(scrutinee),
+                arms: arms.iter().map(|arm| self.arm(arm)).collect(),
+            },
+            ast::ExprKind::Some(value) => ExprKind::Some(self.boxed(value)),
+            ast::ExprKind::None => ExprKind::None,
+            ast::ExprKind::As { expr: value, ty } => ExprKind::Cast { expr: self.boxed(value), ty: ty.clone() },
+            ast::ExprKind::SizeOf(ty) => ExprKind::SizeOf(ty.clone()),
+            ast::ExprKind::Error => ExprKind::Error,
+        };
+        Expr { kind, span: expr.span, id: expr.id }
+    }
+
+    fn arm(&mut self, arm: &ast::MatchArm) -> Arm {
+        self.push_scope();
+        let pattern = self.pattern(&arm.pattern, arm.body.span);
+        let guard = arm.guard.as_deref().map(|guard| self.expr(guard));
+        let body = self.expr(&arm.body);
+        Arm { vars: self.pop_scope(), pattern, guard, body }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern, span: Span) -> Pattern {
+        match pattern {
+            ast::Pattern::Wildcard => Pattern::Wildcard,
+            ast::Pattern::Literal(literal) => Pattern::Literal(literal.clone()),
+            ast::Pattern::Bind(name) => {
+                // Alternatives of an or-pattern share their bindings
+                let scope = self.scopes.last().expect("scope is open");
+                match scope.names.get(name) {
+                    Some(&Binding::Var(id)) => Pattern::Bind(id),
+                    _ => Pattern::Bind(self.declare(name, false, None, span)),
+                }
+            }
+            ast::Pattern::Or(items) => Pattern::Or(self.patterns(items, span)),
+            ast::Pattern::Struct { ty, fields } => Pattern::Struct {
+                ty: ty.clone(),
+                fields: fields
+                    .iter()
+                    .map(|(name, pattern)| (name.clone(), self.pattern(pattern, span)))
+                    .collect(),
+            },
+            ast::Pattern::Tuple(items) => Pattern::Tuple(self.patterns(items, span)),
+            ast::Pattern::Slice(items) => Pattern::Slice(self.patterns(items, span)),
+            ast::Pattern::Variant { name, args } => {
+                Pattern::Variant { name: name.clone(), args: self.patterns(args, span) }
+            }
+            ast::Pattern::Some(inner) => Pattern::Some(Box::new(self.pattern(inner, span))),
+            ast::Pattern::None => Pattern::None,
+        }
+    }
+
+    fn patterns(&mut self, patterns: &[ast::Pattern], span: Span) -> Vec<Pattern> {
+        patterns.iter().map(|pattern| self.pattern(pattern, span)).collect()
+    }
+}
+
+/// `(while cond body)` becomes `(loop (branch cond body (break)))`
+fn while_loop(cond: Expr, body: Block, span: Span) -> ExprKind {
+    let exit = Block { value: Some(Box::new(Expr::new(ExprKind::Break(None), span))), ..Block::default() };
+    let test = Expr::new(ExprKind::If { cond: Box::new(cond), then_branch: body, else_branch: exit }, span);
+    ExprKind::Loop { body: Block { value: Some(Box::new(test)), ..Block::default() } }
+}
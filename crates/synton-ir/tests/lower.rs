@@ -0,0 +1,113 @@
+//! Lowering of parsed modules to the core IR
+
+use synton_parser::SyntonParser;
+
+#[test]
+fn test_lower_closures_and_loops() {
+    use synton_ast::VarId;
+    use synton_ir::{ExprKind as Ir, Stmt as IrStmt};
+
+    let src = "(let n = 10)\n(let f = (fn [a] (fn [b] (+ a (+ b n)))))\n(while (< n 20) (.call n add 1))";
+    let module = SyntonParser::new().parse_module(src).unwrap();
+    let program = synton_ir::lower(&module);
+
+    let names: Vec<_> = program.vars.iter().map(|var| var.name.as_str()).collect();
+    // `f` is declared after its initializer is lowered
+    assert_eq!(names, ["n", "a", "b", "f"]);
+    assert_eq!(program.body.vars, [VarId::new(0), VarId::new(3)]);
+
+    // Module variables are not captured, enclosing parameters are
+    let [outer, inner] = &program.functions[..] else { panic!("expected two lambdas") };
+    assert_eq!(outer.params, [VarId::new(1)]);
+    assert!(outer.captures.is_empty());
+    assert_eq!(inner.params, [VarId::new(2)]);
+    assert_eq!(inner.captures, [VarId::new(1)]);
+
+    // (while c body) => (loop (branch c body (break)))
+    let IrStmt::Expr(looped) = &program.body.stmts[2] else { panic!("expected the loop") };
+    let Ir::Loop { body } = &looped.kind else { panic!("expected Loop, got {:?}", looped.kind) };
+    let Ir::If { then_branch, else_branch, .. } = &body.value.as_ref().unwrap().kind else { panic!("expected If") };
+    assert!(matches!(else_branch.value.as_deref().map(|e| &e.kind), Some(Ir::Break(None))));
+
+    // (.call n add 1) => (add n 1)
+    let IrStmt::Expr(call) = &then_branch.stmts[0] else { panic!("expected the call") };
+    let Ir::Call { callee, args } = &call.kind else { panic!("expected Call, got {:?}", call.kind) };
+    assert!(matches!(&callee.kind, Ir::Global(name) if name == "add"));
+    assert!(matches!(args[0].kind, Ir::Var(id) if id == VarId::new(0)));
+}
+
+#[test]
+fn test_lower_functions_and_for() {
+    use synton_ast::{Expr, ExprKind, FnId, Position, Span, Stmt, StmtKind};
+    use synton_ir::{ExprKind as Ir, Pattern as IrPattern, Stmt as IrStmt};
+
+    // (fn f [xs] { (for x in xs (g x)) })  (fn g [y] (f y)), with the loop
+    // built by hand as there is no syntax for it
+    let parser = SyntonParser::new();
+    let mut module = parser.parse_module("(fn f [xs] {})\n(fn g [y] (f y))").unwrap();
+    let span = Span::new(Position::start(), Position::start());
+    let expr = |src: &str| Box::new(parser.parse_expr(src).unwrap());
+    let call_g = Stmt::new(StmtKind::Expr(expr("(g x)")), span);
+    let for_loop = Stmt::new(StmtKind::For { var: "x".to_string(), iter: expr("xs"), body: Box::new(call_g) }, span);
+    let StmtKind::FnDecl(f) = &mut module.stmts[0].kind else { panic!("expected fn") };
+    f.body = Some(Box::new(Expr::new(ExprKind::Block(vec![for_loop], None), span)));
+    let program = synton_ir::lower(&module);
+
+    let [f, g] = &program.functions[..] else { panic!("expected two functions") };
+    assert_eq!((f.name.as_deref(), g.name.as_deref()), (Some("f"), Some("g")));
+
+    // Functions see each other regardless of order
+    let g_body = g.body.as_ref().unwrap().value.as_ref().unwrap();
+    let Ir::Call { callee, .. } = &g_body.kind else { panic!("expected Call") };
+    assert!(matches!(callee.kind, Ir::Fn(id) if id == FnId::new(0)));
+
+    // (for x in xs body) => { (let #iter = (iter xs)) (loop (match (next #iter) (some x) => body (none) => (break))) }
+    let f_body = f.body.as_ref().unwrap();
+    let IrStmt::Expr(lowered) = &f_body.stmts[0] else { panic!("expected the loop") };
+    let Ir::Block(block) = &lowered.kind else { panic!("expected Block, got {:?}", lowered.kind) };
+    let iterator = block.vars[0];
+    assert_eq!(program.var(iterator).name, "#iter");
+    assert!(matches!(&block.stmts[0], IrStmt::Let { var, init: Some(init) }
+        if *var == iterator && matches!(&init.kind, Ir::Call { callee, .. } if matches!(&callee.kind, Ir::Global(name) if name == synton_ir::ITER_FN))));
+
+    let Ir::Loop { body } = &block.value.as_ref().unwrap().kind else { panic!("expected Loop") };
+    let Ir::Match { scrutinee, arms } = &body.value.as_ref().unwrap().kind else { panic!("expected Match") };
+    assert!(matches!(&scrutinee.kind, Ir::Call { args, .. } if matches!(args[0].kind, Ir::Var(id) if id == iterator)));
+    let item = arms[0].vars[0];
+    assert_eq!(program.var(item).name, "x");
+    assert!(matches!(&arms[0].pattern, IrPattern::Some(inner) if matches!(**inner, IrPattern::Bind(id) if id == item)));
+    assert!(matches!(arms[1].pattern, IrPattern::None));
+    assert!(matches!(arms[1].body.kind, Ir::Break(None)));
+
+    // The loop body calls g with the item
+    let Ir::Block(item_body) = &arms[0].body.kind else { panic!("expected Block") };
+    let IrStmt::Expr(call) = &item_body.stmts[0] else { panic!("expected call") };
+    let Ir::Call { callee, args } = &call.kind else { panic!("expected Call") };
+    assert!(matches!(callee.kind, Ir::Fn(id) if id == FnId::new(1)));
+    assert!(matches!(args[0].kind, Ir::Var(id) if id == item));
+}
+
+#[test]
+fn test_lower_generic_declarations() {
+    use synton_ir::{Stmt as IrStmt, TypeDecl};
+
+    let src = "(struct Pair<A, B> first: A second: B)\n(enum Opt<T> (Just T) Nothing)\n\
+               (let swap = (fn [p: Pair<A, B>] (Pair first: (.field p second) second: (.field p first))))\n\
+               (swap (Pair first: 1 second: true))\n(swap (Pair first: \"a\" second: 2))";
+    let module = SyntonParser::new().parse_module(src).unwrap();
+    let program = synton_ir::lower(&module);
+
+    // Declarations keep their type parameters and leave no code behind
+    let [TypeDecl::Struct(pair), TypeDecl::Enum(opt)] = &program.types[..] else { panic!("expected two types") };
+    assert_eq!((pair.name.as_str(), &pair.params[..]), ("Pair", &["A".to_string(), "B".to_string()][..]));
+    let fields: Vec<_> = pair.fields.iter().map(|field| (field.name.as_str(), field.ty.to_string())).collect();
+    assert_eq!(fields, [("first", "A".to_string()), ("second", "B".to_string())]);
+    assert_eq!((opt.name.as_str(), &opt.params[..]), ("Opt", &["T".to_string()][..]));
+    let variants: Vec<_> = opt.variants.iter().map(|variant| (variant.name.as_str(), variant.types.len())).collect();
+    assert_eq!(variants, [("Just", 1), ("Nothing", 0)]);
+
+    // The generic function is lowered once and shared by both calls
+    assert_eq!(program.functions.len(), 1);
+    assert_eq!(program.body.stmts.len(), 3);
+    assert!(matches!(&program.body.stmts[0], IrStmt::Let { .. }));
+}
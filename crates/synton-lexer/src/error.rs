@@ -6,33 +6,44 @@ use std::ops::Range;
 pub type LexResult<T> = Result<T, LexError>;
 
 /// Lexer error
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum LexError {
     /// Invalid token encountered
-    #[error("invalid token: '{text}' at offset {offset}")]
+    #[error("invalid token: '{text}' at offset {}", span.start)]
     InvalidToken {
         text: String,
-        offset: usize,
+        span: Range<usize>,
     },
 
     /// Unterminated string literal
-    #[error("unterminated string literal at offset {start}")]
+    #[error("unterminated string literal at offset {}", span.start)]
     UnterminatedString {
-        start: usize,
+        span: Range<usize>,
     },
 
     /// Invalid escape sequence
-    #[error("invalid escape sequence: '\\{seq}' at offset {offset}")]
+    #[error("invalid escape sequence: '{seq}' at offset {}", span.start)]
     InvalidEscape {
         seq: String,
-        offset: usize,
+        span: Range<usize>,
     },
 
     /// Invalid character literal
-    #[error("invalid character literal: '{text}' at offset {offset}")]
+    #[error("invalid character literal: {text} at offset {}", span.start)]
     InvalidChar {
         text: String,
-        offset: usize,
+        span: Range<usize>,
+    },
+
+    /// Numeric literal out of range for its type
+    #[error("numeric literal `{text}` does not fit in {ty} at offset {}", span.start)]
+    NumberOverflow {
+        /// The literal as written
+        text: String,
+        /// Name of the literal's type
+        ty: String,
+        /// Location of the literal
+        span: Range<usize>,
     },
 
     /// Unexpected end of file
@@ -47,12 +58,60 @@ pub enum LexError {
 impl LexError {
     pub fn span(&self) -> Range<usize> {
         match self {
-            Self::InvalidToken { offset, .. } => *offset..(*offset + 1),
-            Self::UnterminatedString { start, .. } => *start..(*start + 1),
-            Self::InvalidEscape { offset, .. } => *offset..(*offset + 2),
-            Self::InvalidChar { offset, .. } => *offset..(*offset + 1),
+            Self::InvalidToken { span, .. }
+            | Self::UnterminatedString { span }
+            | Self::InvalidEscape { span, .. }
+            | Self::InvalidChar { span, .. }
+            | Self::NumberOverflow { span, .. } => span.clone(),
             Self::UnexpectedEof => 0..0,
             Self::Other(_) => 0..0,
         }
     }
 }
+
+/// Why a token failed to lex, produced by the token callbacks.
+///
+/// Offsets are relative to the start of the token; [`crate::Lexer`] turns
+/// these into [`LexError`]s with absolute spans.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LexErrorKind {
+    /// No token matches the input
+    #[default]
+    InvalidToken,
+    /// String or byte string without a closing quote
+    UnterminatedString,
+    /// Unknown or malformed escape sequence
+    InvalidEscape {
+        /// The escape sequence as written
+        seq: String,
+        /// Offset of the backslash within the token
+        offset: usize,
+    },
+    /// Character literal that is empty or holds more than one character
+    InvalidChar,
+    /// Numeric literal out of range for its type
+    NumberOverflow {
+        /// Name of the literal's type
+        ty: &'static str,
+    },
+}
+
+impl LexErrorKind {
+    /// Attach the source position of the offending token
+    pub fn at(self, span: Range<usize>, text: &str) -> LexError {
+        match self {
+            Self::InvalidToken => LexError::InvalidToken { text: text.to_string(), span },
+            Self::UnterminatedString => LexError::UnterminatedString { span },
+            Self::InvalidEscape { seq, offset } => {
+                let start = span.start + offset;
+                LexError::InvalidEscape { span: start..start + seq.len(), seq }
+            }
+            Self::InvalidChar => LexError::InvalidChar { text: text.to_string(), span },
+            Self::NumberOverflow { ty } => LexError::NumberOverflow {
+                text: text.to_string(),
+                ty: ty.to_string(),
+                span,
+            },
+        }
+    }
+}
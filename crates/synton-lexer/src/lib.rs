@@ -4,14 +4,18 @@
 
 #![warn(missing_docs, unused_crate_dependencies)]
 
+use std::fmt;
+
 use logos::Logos;
+use synton_ast::BuiltinType;
 
 pub mod error;
 
-pub use error::{LexError, LexResult};
+pub use error::{LexError, LexErrorKind, LexResult};
 
 /// Token in Synton source code
-#[derive(Logos, Debug, Clone, PartialEq, Eq)]
+#[derive(Logos, Debug, Clone, PartialEq)]
+#[logos(error = LexErrorKind)]
 pub enum Token {
     // Whitespace (skipped via skip attributes on the enum)
     #[regex(r"[ \t\r\n]+", logos::skip)]
@@ -110,20 +114,32 @@ pub enum Token {
     #[token("result")] KwResult,
 
     // Literals
-    #[regex(r"[0-9]+", |lex| lex.slice().parse::<i64>().ok())]
-    Integer(i64),
-
-    #[regex(r"[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?")]
-    Float,
-
-    #[regex(r#"'[^']'"#, |lex| lex.slice().chars().nth(1))]
+    // Numbers may be negative, contain `_` separators and end in a type suffix
+    #[regex(r"-?[0-9][0-9_]*(i32|i64|u8|u32|u64)?", lex_integer)]
+    #[regex(r"-?0x[0-9a-fA-F_]+(i32|i64|u8|u32|u64)?", lex_integer)]
+    #[regex(r"-?0o[0-7_]+(i32|i64|u8|u32|u64)?", lex_integer)]
+    #[regex(r"-?0b[01_]+(i32|i64|u8|u32|u64)?", lex_integer)]
+    Integer(IntLiteral),
+
+    #[regex(r"-?[0-9][0-9_]*\.[0-9][0-9_]*([eE][+-]?[0-9_]+)?(f32|f64)?", lex_float)]
+    #[regex(r"-?[0-9][0-9_]*[eE][+-]?[0-9_]+(f32|f64)?", lex_float)]
+    #[regex(r"-?[0-9][0-9_]*(f32|f64)", lex_float)]
+    Float(FloatLiteral),
+
+    #[regex(r#"'([^'\\\n]|\\[^\n])*'"#, lex_char)]
     Char(char),
 
-    #[regex(r#""[^"]*""#, |lex| Some(lex.slice().to_string()))]
+    // String bodies are scanned by the callback so that an unterminated
+    // string is reported up to the end of its line
+    #[token("\"", lex_string)]
     String(String),
 
-    #[regex(r#"b"[^"]*""#, |lex| Some(lex.slice().to_string()))]
-    ByteString(String),
+    #[token("b\"", lex_byte_string)]
+    ByteString(Vec<u8>),
+
+    // Content hash reference: `hash:sha256:e3b0c442...`
+    #[regex(r"hash:[a-zA-Z0-9]+:[0-9a-fA-F]+", |lex| Some(lex.slice().to_string()))]
+    HashRef(String),
 
     // Boolean literals
     #[token("true")] True,
@@ -132,12 +148,98 @@ pub enum Token {
     // Special tokens
     #[token("=>")] FatArrow,
 
-    // Identifiers (must come after underscore)
+    // Identifiers (must come after underscore). `%1` is the short form of a
+    // local produced by the compact printer.
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_-]*", |lex| Some(lex.slice().to_string()))]
+    #[regex(r"%[0-9]+", |lex| Some(lex.slice().to_string()))]
     Identifier(String),
 }
 
 impl Token {
+    /// Source text of a fixed token (delimiter, operator or keyword)
+    pub fn text(&self) -> Option<&'static str> {
+        let text = match self {
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::Semi => ";",
+            Self::Comma => ",",
+            Self::Colon => ":",
+            Self::ColonColon => "::",
+            Self::Arrow => "->",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::Caret => "^",
+            Self::Amp => "&",
+            Self::Pipe => "|",
+            Self::Bang => "!",
+            Self::Eq => "=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Tilde => "~",
+            Self::Dot => ".",
+            Self::EqEq => "==",
+            Self::NotEq => "!=",
+            Self::LtEq => "<=",
+            Self::GtEq => ">=",
+            Self::AndAnd => "&&",
+            Self::OrOr => "||",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::StarStar => "**",
+            Self::Question => "?",
+            Self::Dollar => "$",
+            Self::At => "@",
+            Self::KwFn => "fn",
+            Self::KwLet => "let",
+            Self::KwSet => "set",
+            Self::KwIf => "if",
+            Self::KwElse => "else",
+            Self::KwBranch => "branch",
+            Self::KwMatch => "match",
+            Self::KwLoop => "loop",
+            Self::KwWhile => "while",
+            Self::KwFor => "for",
+            Self::KwIn => "in",
+            Self::KwBreak => "break",
+            Self::KwContinue => "continue",
+            Self::KwReturn => "return",
+            Self::KwStruct => "struct",
+            Self::KwEnum => "enum",
+            Self::KwType => "type",
+            Self::KwConst => "const",
+            Self::KwImport => "import",
+            Self::KwExport => "export",
+            Self::KwAs => "as",
+            Self::KwMod => "mod",
+            Self::KwI32 => "i32",
+            Self::KwI64 => "i64",
+            Self::KwU32 => "u32",
+            Self::KwU64 => "u64",
+            Self::KwF32 => "f32",
+            Self::KwF64 => "f64",
+            Self::KwBool => "bool",
+            Self::KwString => "string",
+            Self::KwStr => "str",
+            Self::KwChar => "char",
+            Self::KwList => "list",
+            Self::KwMap => "map",
+            Self::KwMaybe => "maybe",
+            Self::KwResult => "result",
+            Self::True => "true",
+            Self::False => "false",
+            Self::FatArrow => "=>",
+            _ => return None,
+        };
+        Some(text)
+    }
+
     /// Check if this is a keyword
     pub fn is_keyword(&self) -> bool {
         matches!(
@@ -192,7 +294,13 @@ impl Token {
     pub fn is_literal(&self) -> bool {
         matches!(
             self,
-            Self::Integer(_) | Self::Float | Self::Char(_) | Self::String(_) | Self::True | Self::False
+            Self::Integer(_)
+                | Self::Float(_)
+                | Self::Char(_)
+                | Self::String(_)
+                | Self::ByteString(_)
+                | Self::True
+                | Self::False
         )
     }
 
@@ -201,8 +309,10 @@ impl Token {
         matches!(
             self,
             Self::Integer(_)
-                | Self::Float
+                | Self::Float(_)
                 | Self::String(_)
+                | Self::Char(_)
+                | Self::ByteString(_)
                 | Self::True
                 | Self::False
                 | Self::Identifier(_)
@@ -220,8 +330,243 @@ impl Token {
     }
 }
 
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(n) => write!(f, "{}", n),
+            Self::Float(x) => write!(f, "{}", x),
+            Self::Char(c) => write!(f, "{:?}", c),
+            Self::String(s) => write!(f, "{:?}", s),
+            Self::ByteString(bytes) => write!(f, "b\"{}\"", bytes.escape_ascii()),
+            Self::HashRef(s) | Self::Identifier(s) => f.write_str(s),
+            Self::Whitespace => f.write_str("whitespace"),
+            Self::LineComment | Self::BlockComment => f.write_str("comment"),
+            fixed => f.write_str(fixed.text().unwrap_or_default()),
+        }
+    }
+}
+
+/// Integer literal with its optional type suffix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntLiteral {
+    /// Value, already checked against the range of its type
+    pub value: i128,
+    /// Type suffix, as in `255u8`
+    pub suffix: Option<BuiltinType>,
+}
+
+/// Float literal with its optional type suffix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloatLiteral {
+    /// Value, already checked against the range of its type
+    pub value: f64,
+    /// Type suffix, as in `3.0f32`
+    pub suffix: Option<BuiltinType>,
+}
+
+impl fmt::Display for IntLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.suffix.map_or("", BuiltinType::name))
+    }
+}
+
+impl fmt::Display for FloatLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}{}", self.value, self.suffix.map_or("", BuiltinType::name))
+    }
+}
+
+/// Split a numeric literal into its sign, digits and type suffix
+fn split_number<'s>(text: &'s str, suffixes: &[BuiltinType]) -> (bool, &'s str, Option<BuiltinType>) {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let suffix = suffixes.iter().copied().find(|ty| text.ends_with(ty.name()));
+    let digits = &text[..text.len() - suffix.map_or(0, |ty| ty.name().len())];
+    (negative, digits, suffix)
+}
+
+fn lex_integer(lex: &mut logos::Lexer<Token>) -> Result<IntLiteral, LexErrorKind> {
+    use BuiltinType::*;
+
+    let (negative, digits, suffix) = split_number(lex.slice(), &[I32, I64, U8, U32, U64]);
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return Err(LexErrorKind::InvalidToken);
+    }
+
+    let ty = suffix.unwrap_or(I64);
+    let (min, max) = ty.int_range().unwrap_or((i64::MIN as i128, i64::MAX as i128));
+    let overflow = LexErrorKind::NumberOverflow { ty: ty.name() };
+    let magnitude = u128::from_str_radix(&digits, radix)
+        .ok()
+        .and_then(|m| i128::try_from(m).ok())
+        .ok_or_else(|| overflow.clone())?;
+    let value = if negative { -magnitude } else { magnitude };
+    if value < min || value > max {
+        return Err(overflow);
+    }
+    Ok(IntLiteral { value, suffix })
+}
+
+fn lex_float(lex: &mut logos::Lexer<Token>) -> Result<FloatLiteral, LexErrorKind> {
+    let (negative, digits, suffix) = split_number(lex.slice(), &[BuiltinType::F32, BuiltinType::F64]);
+    let magnitude: f64 = digits.replace('_', "").parse().map_err(|_| LexErrorKind::InvalidToken)?;
+    let value = if negative { -magnitude } else { magnitude };
+
+    let fits = match suffix {
+        Some(BuiltinType::F32) => (value as f32).is_finite(),
+        _ => value.is_finite(),
+    };
+    if !fits {
+        let ty = suffix.unwrap_or(BuiltinType::F64);
+        return Err(LexErrorKind::NumberOverflow { ty: ty.name() });
+    }
+    Ok(FloatLiteral { value, suffix })
+}
+
+fn lex_char(lex: &mut logos::Lexer<Token>) -> Result<char, LexErrorKind> {
+    let slice = lex.slice();
+    let decoded = unescape(&slice[1..slice.len() - 1], 1, false)?;
+    let text = String::from_utf8(decoded).map_err(|_| LexErrorKind::InvalidChar)?;
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(LexErrorKind::InvalidChar),
+    }
+}
+
+fn lex_string(lex: &mut logos::Lexer<Token>) -> Result<String, LexErrorKind> {
+    let body = string_body(lex)?;
+    let decoded = unescape(body, 1, false)?;
+    String::from_utf8(decoded).map_err(|_| LexErrorKind::InvalidToken)
+}
+
+fn lex_byte_string(lex: &mut logos::Lexer<Token>) -> Result<Vec<u8>, LexErrorKind> {
+    let body = string_body(lex)?;
+    unescape(body, 2, true)
+}
+
+/// Consume a string body after its opening quote, returning it without the
+/// closing quote. Without a closing quote the token extends to the end of
+/// the line and is reported as unterminated.
+fn string_body<'s>(lex: &mut logos::Lexer<'s, Token>) -> Result<&'s str, LexErrorKind> {
+    let rest = lex.remainder();
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '"' if !escaped => {
+                lex.bump(i + 1);
+                return Ok(&rest[..i]);
+            }
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    lex.bump(rest.find('\n').unwrap_or(rest.len()));
+    Err(LexErrorKind::UnterminatedString)
+}
+
+/// Decode the escape sequences in the body of a string, char or byte string
+/// literal. `offset` is the position of the body within the token, used to
+/// locate a bad escape.
+///
+/// Supported escapes: `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, `\xNN` (ASCII
+/// only outside byte strings) and `\u{N..}` (not in byte strings).
+fn unescape(body: &str, offset: usize, bytes: bool) -> Result<Vec<u8>, LexErrorKind> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while let Some(c) = body[i..].chars().next() {
+        if c != '\\' {
+            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            i += c.len_utf8();
+            continue;
+        }
+
+        let rest = &body[i..];
+        let (decoded, len) = escape(rest, bytes).map_err(|len| LexErrorKind::InvalidEscape {
+            seq: rest[..len].to_string(),
+            offset: offset + i,
+        })?;
+        match decoded {
+            Escaped::Byte(b) => out.push(b),
+            Escaped::Char(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+        i += len;
+    }
+    Ok(out)
+}
+
+enum Escaped {
+    Byte(u8),
+    Char(char),
+}
+
+/// Decode the escape at the start of `rest`, returning it with its length in
+/// bytes, or the length of the malformed sequence
+fn escape(rest: &str, bytes: bool) -> Result<(Escaped, usize), usize> {
+    let mut chars = rest.chars().skip(1);
+    let Some(kind) = chars.next() else {
+        return Err(rest.len());
+    };
+    let simple = match kind {
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        '0' => Some(b'\0'),
+        '\\' => Some(b'\\'),
+        '"' => Some(b'"'),
+        '\'' => Some(b'\''),
+        _ => None,
+    };
+    if let Some(b) = simple {
+        return Ok((Escaped::Byte(b), 2));
+    }
+
+    match kind {
+        'x' => {
+            let digits = rest.get(2..4).ok_or(rest.len())?;
+            // `from_str_radix` alone would also take a sign
+            if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(4);
+            }
+            let value = u8::from_str_radix(digits, 16).map_err(|_| 4_usize)?;
+            if !bytes && value > 0x7f {
+                return Err(4);
+            }
+            Ok((Escaped::Byte(value), 4))
+        }
+        'u' if !bytes => {
+            if !rest[2..].starts_with('{') {
+                return Err(2);
+            }
+            // The closing brace must follow the digits, not just come later
+            // in the string
+            let close = 3 + rest[3..].bytes().take_while(u8::is_ascii_hexdigit).count();
+            if rest.as_bytes().get(close) != Some(&b'}') {
+                return Err(close);
+            }
+            let digits = &rest[3..close];
+            let c = u32::from_str_radix(digits, 16)
+                .ok()
+                .filter(|_| (1..=6).contains(&digits.len()))
+                .and_then(char::from_u32)
+                .ok_or(close + 1)?;
+            Ok((Escaped::Char(c), close + 1))
+        }
+        other => Err(1 + other.len_utf8()),
+    }
+}
+
 /// A token with location information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TokenKind {
     pub token: Token,
     pub span: std::ops::Range<usize>,
@@ -249,17 +594,41 @@ impl<'a> Lexer<'a> {
         self.inner.remainder()
     }
 
+    /// Tokenize the whole input, failing with the first error
     pub fn tokenize_all(&mut self) -> LexResult<Vec<TokenKind>> {
+        let (tokens, mut errors) = self.tokenize_recovering();
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Tokenize the whole input, skipping bad tokens and reporting each of
+    /// them instead of stopping at the first one
+    pub fn tokenize_recovering(&mut self) -> (Vec<TokenKind>, Vec<LexError>) {
         let mut tokens = Vec::new();
+        let mut errors: Vec<LexError> = Vec::new();
         while let Some(result) = self.inner.next() {
-            let token = result.map_err(|_| LexError::Other("Lexer error".to_string()))?;
             let span = self.inner.span();
-            tokens.push(TokenKind {
-                token,
-                span,
-            });
+            match result {
+                Ok(token) => tokens.push(TokenKind { token, span }),
+                Err(kind) => {
+                    // Runs of unrecognised characters are reported once
+                    if let (LexErrorKind::InvalidToken, Some(LexError::InvalidToken { text, span: prev })) =
+                        (&kind, errors.last_mut())
+                    {
+                        if prev.end == span.start {
+                            prev.end = span.end;
+                            text.push_str(self.inner.slice());
+                            continue;
+                        }
+                    }
+                    errors.push(kind.at(span, self.inner.slice()));
+                }
+            }
         }
-        Ok(tokens)
+        (tokens, errors)
     }
 }
 
@@ -267,3 +636,104 @@ impl<'a> Lexer<'a> {
 pub fn tokenize(source: &str) -> LexResult<Vec<TokenKind>> {
     Lexer::new(source).tokenize_all()
 }
+
+/// Convenience function to tokenize a string, collecting every error
+pub fn tokenize_recovering(source: &str) -> (Vec<TokenKind>, Vec<LexError>) {
+    Lexer::new(source).tokenize_recovering()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(source: &str) -> LexResult<Token> {
+        let mut tokens = tokenize(source)?;
+        assert_eq!(tokens.len(), 1, "expected one token in {:?}", source);
+        Ok(tokens.remove(0).token)
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        use BuiltinType::*;
+
+        let int = |src: &str| match single(src) {
+            Ok(Token::Integer(n)) => (n.value, n.suffix),
+            other => panic!("Expected integer for {:?}, got {:?}", src, other),
+        };
+        assert_eq!(int("1_000"), (1000, None));
+        assert_eq!(int("0xff"), (255, None));
+        assert_eq!(int("0b1010"), (10, None));
+        assert_eq!(int("0o17"), (15, None));
+        assert_eq!(int("-7"), (-7, None));
+        assert_eq!(int("-9223372036854775808"), (i64::MIN as i128, None));
+        assert_eq!(int("42u8"), (42, Some(U8)));
+        assert_eq!(int("0xffff_ffff_ffff_ffffu64"), (u64::MAX as i128, Some(U64)));
+
+        let float = |src: &str| match single(src) {
+            Ok(Token::Float(x)) => (x.value, x.suffix),
+            other => panic!("Expected float for {:?}, got {:?}", src, other),
+        };
+        assert_eq!(float("3.0f32"), (3.0, Some(F32)));
+        assert_eq!(float("1e3"), (1000.0, None));
+        assert_eq!(float("-2.5e-1"), (-0.25, None));
+
+        // Subtraction still needs the operator to stand alone
+        let tokens: Vec<_> = tokenize("- 1").unwrap().into_iter().map(|t| t.token).collect();
+        assert!(matches!(tokens[..], [Token::Minus, Token::Integer(IntLiteral { value: 1, .. })]));
+    }
+
+    #[test]
+    fn test_numeric_overflow() {
+        let overflow = |src: &str| match single(src) {
+            Err(LexError::NumberOverflow { text, ty, span }) => {
+                assert_eq!(text, src);
+                assert_eq!(span, 0..src.len());
+                ty
+            }
+            other => panic!("Expected NumberOverflow for {:?}, got {:?}", src, other),
+        };
+        assert_eq!(overflow("9223372036854775808"), "i64");
+        assert_eq!(overflow("256u8"), "u8");
+        assert_eq!(overflow("-1u32"), "u32");
+        assert_eq!(overflow("0x1_0000_0000i32"), "i32");
+        assert_eq!(overflow("1e39f32"), "f32");
+        assert_eq!(overflow("1e400"), "f64");
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(single(r#""\x41\u{e9}""#), Ok(Token::String("A\u{e9}".into())));
+
+        // Escapes take hex digits only, and `\u` ends at its own brace
+        let bad_escape = |src: &str| match single(src) {
+            Err(LexError::InvalidEscape { seq, span }) => {
+                assert_eq!(&src[span], seq);
+                seq
+            }
+            other => panic!("Expected InvalidEscape for {:?}, got {:?}", src, other),
+        };
+        assert_eq!(bad_escape(r#""\x+f""#), r"\x+f");
+        assert_eq!(bad_escape(r#""\x-1""#), r"\x-1");
+        assert_eq!(bad_escape(r#""\u{+41}""#), r"\u{");
+        assert_eq!(bad_escape(r#""\u{41 }""#), r"\u{41");
+        assert_eq!(bad_escape(r#""\u{41" "}""#), r"\u{41");
+        assert_eq!(bad_escape(r#""\u41}""#), r"\u");
+        assert_eq!(bad_escape(r#""\u{}""#), r"\u{}");
+        assert_eq!(bad_escape(r#"'\u{1234567}'"#), r"\u{1234567}");
+    }
+
+    #[test]
+    fn test_tokenize_recovering() {
+        let src = "(f x §§) (g\n\"oops\n1)";
+        let (tokens, errors) = tokenize_recovering(src);
+        let spans: Vec<_> = errors.iter().map(|e| &src[e.span()]).collect();
+        assert_eq!(spans, ["§§", "\"oops"]);
+        assert!(matches!(errors[0], LexError::InvalidToken { .. }));
+        assert!(matches!(errors[1], LexError::UnterminatedString { .. }));
+
+        // Lexing goes on after each bad token
+        let text: Vec<_> = tokens.iter().map(|t| &src[t.span.clone()]).collect();
+        assert_eq!(text, ["(", "f", "x", ")", "(", "g", "1", ")"]);
+        assert_eq!(tokenize(src), Err(errors[0].clone()));
+    }
+}
@@ -1,6 +1,8 @@
 //! LSP diagnostics
 
 use tower_lsp::lsp_types::*;
+use synton_parser::ParseError;
+use synton_typeck::{ResolveError, TypeDiagnostic};
 use crate::DocumentState;
 
 pub struct Diagnostics;
@@ -9,4 +11,85 @@ impl Diagnostics {
     pub fn from_document(state: &DocumentState) -> Vec<Diagnostic> {
         state.diagnostics.clone()
     }
+
+    /// Convert a parse error into a diagnostic covering its source range
+    pub fn from_parse_error(source: &str, error: &ParseError) -> Diagnostic {
+        let span = error.span();
+        Diagnostic {
+            range: Range {
+                start: position_at(source, span.start),
+                end: position_at(source, span.end),
+            },
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("synton".to_string()),
+            message: error.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Convert a name resolution error or warning into a diagnostic
+    pub fn from_resolve_error(source: &str, error: &ResolveError) -> Diagnostic {
+        let span = error.span();
+        Diagnostic {
+            range: Range {
+                start: position_at(source, span.start.offset as usize),
+                end: position_at(source, span.end.offset as usize),
+            },
+            severity: Some(if error.is_warning() { DiagnosticSeverity::WARNING } else { DiagnosticSeverity::ERROR }),
+            source: Some("synton".to_string()),
+            message: error.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Convert a type error into a diagnostic, with its labels as related
+    /// information
+    pub fn from_type_error(uri: &str, source: &str, error: &TypeDiagnostic) -> Diagnostic {
+        let range = |span: &synton_ast::Span| Range {
+            start: position_at(source, span.start.offset as usize),
+            end: position_at(source, span.end.offset as usize),
+        };
+        let related = Url::parse(uri).ok().map(|uri| {
+            error
+                .labels
+                .iter()
+                .map(|label| DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), range(&label.span)),
+                    message: label.message.clone(),
+                })
+                .collect::<Vec<_>>()
+        });
+        Diagnostic {
+            range: range(&error.span),
+            severity: Some(if error.is_warning() { DiagnosticSeverity::WARNING } else { DiagnosticSeverity::ERROR }),
+            code: Some(NumberOrString::String(error.code().to_string())),
+            source: Some("synton".to_string()),
+            message: error.to_string(),
+            related_information: related.filter(|related| !related.is_empty()),
+            ..Default::default()
+        }
+    }
+}
+
+/// LSP position (UTF-16 columns) of a byte offset
+pub(crate) fn position_at(source: &str, offset: usize) -> Position {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// Byte offset of an LSP position, clamped to its line
+pub(crate) fn offset_at(source: &str, position: Position) -> usize {
+    let line_start: usize = source.split_inclusive('\n').take(position.line as usize).map(str::len).sum();
+    let line = source[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
 }
@@ -1,20 +1,51 @@
 //! LSP hover information
 
+use synton_ast::visit::{self, Visitor};
+use synton_ast::{Expr, NodeId, Span};
 use tower_lsp::lsp_types::*;
+use crate::diagnostics::{offset_at, position_at};
 use crate::DocumentState;
 
 pub struct HoverImpl;
 
 impl HoverImpl {
+    /// Type of the innermost expression under the cursor
     pub fn hover(state: &DocumentState, pos: Position) -> Option<Hover> {
-        let _line = state.content.lines().nth(pos.line as usize)?;
-        // TODO: Implement actual hover logic
+        let (module, typed) = (state.ast.as_ref()?, state.types.as_ref()?);
+        let mut innermost = Innermost { offset: offset_at(&state.content, pos), found: None };
+        innermost.visit_module(module);
+        let (node, span) = innermost.found?;
+        let ty = typed.type_of(node)?;
         Some(Hover {
             contents: HoverContents::Markup(MarkupContent {
                 kind: MarkupKind::Markdown,
-                value: "Hover info".to_string(),
+                value: format!("```synton\n{}\n```", ty),
+            }),
+            range: Some(Range {
+                start: position_at(&state.content, span.start.offset as usize),
+                end: position_at(&state.content, span.end.offset as usize),
             }),
-            range: None,
         })
     }
 }
+
+/// Finds the smallest expression with a node id whose span holds an offset
+struct Innermost {
+    offset: usize,
+    found: Option<(NodeId, Span)>,
+}
+
+impl Visitor for Innermost {
+    fn visit_expr(&mut self, expr: &Expr) {
+        let range = expr.span.range();
+        if !range.contains(&self.offset) {
+            return;
+        }
+        if let Some(id) = expr.id {
+            if self.found.is_none_or(|(_, span)| range.len() <= span.range().len()) {
+                self.found = Some((id, expr.span));
+            }
+        }
+        visit::walk_expr(self, expr);
+    }
+}
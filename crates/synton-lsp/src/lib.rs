@@ -51,9 +51,66 @@ struct DocumentState {
     version: i32,
     content: String,
     ast: Option<synton_ast::Module>,
+    types: Option<synton_typeck::TypedModule>,
     diagnostics: Vec<Diagnostic>,
 }
 
+impl DocumentState {
+    /// Parse, resolve and type check a document
+    fn analyze(uri: String, version: i32, content: String) -> Self {
+        let mut state = DocumentState {
+            uri,
+            version,
+            content,
+            ast: None,
+            types: None,
+            diagnostics: Vec::new(),
+        };
+
+        // Parse the document, keeping the partial AST and every syntax error
+        let parsed = synton_parser::parse_module_recovering(&state.content);
+        state.diagnostics.extend(
+            parsed.errors
+                .iter()
+                .map(|e| Diagnostics::from_parse_error(&state.content, e)),
+        );
+        let parsed_cleanly = !parsed.has_errors();
+        state.ast = parsed.output;
+        let (true, Some(module)) = (parsed_cleanly, &mut state.ast) else {
+            return state;
+        };
+
+        // Resolve names, which also numbers the expressions the type table
+        // is keyed on, then type check if every name was found
+        let stdlib = synton_runtime::StdLib::new();
+        let resolution = synton_typeck::Resolver::new()
+            .with_globals(stdlib.functions())
+            .resolve(module);
+        state.diagnostics.extend(
+            resolution.diagnostics
+                .iter()
+                .map(|e| Diagnostics::from_resolve_error(&state.content, e)),
+        );
+        if resolution.errors().next().is_some() {
+            return state;
+        }
+
+        let mut checker = synton_typeck::TypeChecker::new()
+            .with_globals(stdlib.functions())
+            .with_effects(stdlib.effects());
+        let diagnostics = match checker.check_module(module) {
+            Ok(typed) => state.types.insert(typed).warnings.clone(),
+            Err(errors) => errors,
+        };
+        state.diagnostics.extend(
+            diagnostics
+                .iter()
+                .map(|e| Diagnostics::from_type_error(&state.uri, &state.content, e)),
+        );
+        state
+    }
+}
+
 impl SyntonServer {
     /// Create a new LSP server
     pub fn new(client: Client) -> Self {
@@ -65,30 +122,7 @@ impl SyntonServer {
 
     /// Update document content and re-analyze
     async fn update_document(&self, uri: String, version: i32, content: String) {
-        let mut state = DocumentState {
-            uri: uri.clone(),
-            version,
-            content: content.clone(),
-            ast: None,
-            diagnostics: Vec::new(),
-        };
-
-        // Parse the document
-        match synton_parser::parse_module(&content) {
-            Ok(module) => {
-                state.ast = Some(module);
-            }
-            Err(e) => {
-                state.diagnostics.push(to_lsp_diagnostic(&e));
-            }
-        }
-
-        // Type check if parsing succeeded
-        if let Some(ref module) = state.ast {
-            if let Err(e) = synton_typeck::check(module) {
-                state.diagnostics.push(to_lsp_diagnostic(&e));
-            }
-        }
+        let state = DocumentState::analyze(uri.clone(), version, content);
 
         self.documents.write().await.insert(uri.clone(), state);
 
@@ -119,6 +153,7 @@ impl LanguageServer for SyntonServer {
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 rename_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
@@ -234,6 +269,34 @@ impl LanguageServer for SyntonServer {
 
         Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.as_str();
+
+        let docs = self.documents.read().await;
+        let Some(state) = docs.get(uri) else {
+            return Ok(None);
+        };
+
+        let config = synton_parser::format::FormatConfig {
+            indent_width: params.options.tab_size as usize,
+            ..Default::default()
+        };
+        // Documents with syntax errors are left alone; the errors are
+        // already published as diagnostics
+        let Ok(formatted) = synton_parser::format::format_source(&state.content, &config) else {
+            return Ok(None);
+        };
+        if formatted == state.content {
+            return Ok(Some(Vec::new()));
+        }
+
+        let range = Range {
+            start: Position::new(0, 0),
+            end: diagnostics::position_at(&state.content, state.content.len()),
+        };
+        Ok(Some(vec![TextEdit::new(range, formatted)]))
+    }
 }
 
 fn semantic_token_types() -> Vec<SemanticTokenType> {
@@ -252,14 +315,34 @@ fn semantic_token_types() -> Vec<SemanticTokenType> {
     ]
 }
 
-fn to_lsp_diagnostic(e: &dyn std::error::Error) -> Diagnostic {
-    Diagnostic {
-        range: Range {
-            start: Position::new(0, 0),
-            end: Position::new(0, 0),
-        },
-        severity: Some(DiagnosticSeverity::ERROR),
-        message: e.to_string(),
-        ..Default::default()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(content: &str) -> DocumentState {
+        DocumentState::analyze("file:///test.syn".to_string(), 1, content.to_string())
+    }
+
+    #[test]
+    fn test_analyze_resolves_before_checking() {
+        let state = analyze("(let n = (+ 1 2))\n(print n)");
+        assert!(state.diagnostics.is_empty(), "{:?}", state.diagnostics);
+        assert!(!state.types.as_ref().unwrap().types.is_empty());
+
+        let Some(hover) = HoverImpl::hover(&state, Position::new(1, 7)) else {
+            panic!("Expected hover information");
+        };
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup, got {:?}", hover.contents);
+        };
+        assert_eq!(markup.value, "```synton\ni32\n```");
+        assert_eq!(hover.range, Some(Range::new(Position::new(1, 7), Position::new(1, 8))));
+
+        // Undefined names are reported once, by the resolver
+        let state = analyze("(print m)");
+        assert_eq!(state.diagnostics.len(), 1);
+        assert_eq!(state.diagnostics[0].message, "undefined name: 'm'");
+        assert_eq!(state.diagnostics[0].range, Range::new(Position::new(0, 7), Position::new(0, 8)));
+        assert!(state.types.is_none());
     }
 }
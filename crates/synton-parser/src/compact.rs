@@ -0,0 +1,356 @@
+//! Compact printer for LLM context windows
+//!
+//! Prints the shortest source text that lexes to the same tokens: comments
+//! are dropped and a space is kept only where two tokens would otherwise
+//! run together. Local variables can also be renamed to short IDs such as
+//! `%1` (the [`VarId`] display form), with a [`NameMap`] recording the
+//! original names so that [`restore_names`] can undo the renaming.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use synton_ast::visit::{self, Visitor};
+use synton_ast::{
+    AssignTarget, EnumDecl, EnumVariant, ExportDecl, Expr, ExprKind, FnDecl, ImportDecl, Module, Param, Pattern,
+    Stmt, StmtKind, StructDecl, StructField, Type, TypeKind, VarId,
+};
+use synton_lexer::{Token, TokenKind};
+
+use crate::{ParseResult, SyntonParser};
+
+/// Compact printer configuration
+#[derive(Debug, Clone, Default)]
+pub struct CompactConfig {
+    /// Rename local variables to short IDs
+    pub rename_locals: bool,
+}
+
+/// Compact source with the names it replaced
+#[derive(Debug, Clone)]
+pub struct CompactOutput {
+    /// Compact source text
+    pub text: String,
+    /// Original names of the renamed locals
+    pub names: NameMap,
+}
+
+/// Mapping from short local names back to the original names
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameMap {
+    entries: Vec<(String, String)>,
+}
+
+impl NameMap {
+    /// Original name of a short name
+    pub fn original(&self, short: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(s, _)| s == short)
+            .map(|(_, original)| original.as_str())
+    }
+
+    /// Pairs of short and original names, in order of first use
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(short, original)| (short.as_str(), original.as_str()))
+    }
+
+    /// Number of renamed locals
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if nothing was renamed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Read a mapping written by the [`fmt::Display`] impl: one
+    /// `short original` pair per line
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let entries = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(short), Some(original), None) => Ok((short.to_string(), original.to_string())),
+                    _ => Err(format!("line {}: expected `short original`, found `{}`", i + 1, line)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries })
+    }
+}
+
+impl fmt::Display for NameMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (short, original) in self.iter() {
+            writeln!(f, "{} {}", short, original)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts tokens the way a particular model tokenizer would
+pub trait TokenCounter {
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+}
+
+impl<F: Fn(&str) -> usize> TokenCounter for F {
+    fn count(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Counts Synton lexer tokens
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LexTokenCounter;
+
+impl TokenCounter for LexTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        synton_lexer::tokenize_recovering(text).0.len()
+    }
+}
+
+/// Estimates tokens from the character count, as a fixed number of
+/// characters per token
+#[derive(Debug, Clone, Copy)]
+pub struct CharTokenCounter(pub usize);
+
+impl Default for CharTokenCounter {
+    fn default() -> Self {
+        Self(4)
+    }
+}
+
+impl TokenCounter for CharTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(self.0.max(1))
+    }
+}
+
+/// Print a module in compact form. Fails with the first error if the
+/// source does not parse.
+pub fn compact_source(source: &str, config: &CompactConfig) -> ParseResult<CompactOutput> {
+    let module = SyntonParser::new().parse_module(source)?;
+    let tokens = synton_lexer::tokenize(source)?;
+    let mut texts: Vec<String> = tokens.iter().map(|t| source[t.span.clone()].to_string()).collect();
+
+    let mut names = NameMap::default();
+    if config.rename_locals {
+        let locals = renameable_locals(&module);
+        let mut next_id = first_free_id(&tokens);
+        let mut short_names: FxHashMap<String, String> = FxHashMap::default();
+        for (token, text) in tokens.iter().zip(&mut texts) {
+            let Token::Identifier(name) = &token.token else { continue };
+            // Names already in short form stay as they are
+            if !locals.contains(name.as_str()) || name.starts_with('%') {
+                continue;
+            }
+            let short = short_names.entry(name.clone()).or_insert_with(|| {
+                let short = VarId::new(next_id).to_string();
+                next_id += 1;
+                names.entries.push((short.clone(), name.clone()));
+                short
+            });
+            *text = short.clone();
+        }
+    }
+
+    Ok(CompactOutput { text: join_tokens(&texts), names })
+}
+
+/// Undo short-ID renaming, printing the result in compact form
+pub fn restore_names(source: &str, names: &NameMap) -> ParseResult<String> {
+    let tokens = synton_lexer::tokenize(source)?;
+    let texts: Vec<String> = tokens
+        .iter()
+        .map(|t| {
+            let text = &source[t.span.clone()];
+            match &t.token {
+                Token::Identifier(name) => names.original(name).unwrap_or(text).to_string(),
+                _ => text.to_string(),
+            }
+        })
+        .collect();
+    Ok(join_tokens(&texts))
+}
+
+/// Join token texts, separating two tokens only where they would otherwise
+/// lex differently
+fn join_tokens(texts: &[String]) -> String {
+    let mut out = String::new();
+    for (i, text) in texts.iter().enumerate() {
+        if i > 0 && needs_space(&texts[i - 1], text) {
+            out.push(' ');
+        }
+        out.push_str(text);
+    }
+    out
+}
+
+fn needs_space(left: &str, right: &str) -> bool {
+    let joined = format!("{}{}", left, right);
+    match synton_lexer::tokenize(&joined) {
+        Ok(tokens) => !matches!(&tokens[..], [a, b] if a.span.end == left.len() && b.span.start == left.len()),
+        Err(_) => true,
+    }
+}
+
+/// First ID not already used by a `%N` name in the source
+fn first_free_id(tokens: &[TokenKind]) -> u32 {
+    tokens
+        .iter()
+        .filter_map(|t| match &t.token {
+            Token::Identifier(name) => name.strip_prefix('%')?.parse::<u32>().ok(),
+            _ => None,
+        })
+        .max()
+        .map_or(1, |max| max + 1)
+}
+
+/// Names bound by locals that can be renamed throughout the file. A name
+/// that is also used for a function, type, field, method or import/export
+/// is left alone, since those are not renamed with it.
+fn renameable_locals(module: &Module) -> HashSet<String> {
+    let mut names = Names::default();
+    names.visit_module(module);
+    // Identifiers with a meaning of their own in the grammar
+    names.fixed.extend(["_", "none", "some", "sem", "call", "get", "field", "size_of"].map(String::from));
+
+    names.locals.retain(|name| !names.fixed.contains(name));
+    names.locals
+}
+
+#[derive(Default)]
+struct Names {
+    locals: HashSet<String>,
+    fixed: HashSet<String>,
+}
+
+impl Visitor for Names {
+    fn visit_import(&mut self, import: &ImportDecl) {
+        self.fixed.insert(import.name.clone());
+        self.fixed.extend(import.alias.clone());
+    }
+
+    fn visit_export(&mut self, export: &ExportDecl) {
+        self.fixed.insert(export.name.clone());
+        self.fixed.extend(export.alias.clone());
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { name, .. } | StmtKind::For { var: name, .. } => {
+                self.locals.insert(name.clone());
+            }
+            StmtKind::TypeAlias { name, params, .. } => {
+                self.fixed.insert(name.clone());
+                self.fixed.extend(params.iter().cloned());
+            }
+            StmtKind::Const { name, .. } => {
+                self.fixed.insert(name.clone());
+            }
+            _ => {}
+        }
+        visit::walk_stmt(self, stmt);
+    }
+
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        self.fixed.insert(decl.name.clone());
+        visit::walk_fn_decl(self, decl);
+    }
+
+    fn visit_struct_decl(&mut self, decl: &StructDecl) {
+        self.fixed.insert(decl.name.clone());
+        self.fixed.extend(decl.params.iter().cloned());
+        visit::walk_struct_decl(self, decl);
+    }
+
+    fn visit_struct_field(&mut self, field: &StructField) {
+        self.fixed.insert(field.name.clone());
+        visit::walk_struct_field(self, field);
+    }
+
+    fn visit_enum_decl(&mut self, decl: &EnumDecl) {
+        self.fixed.insert(decl.name.clone());
+        self.fixed.extend(decl.params.iter().cloned());
+        visit::walk_enum_decl(self, decl);
+    }
+
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.fixed.insert(variant.name.clone());
+        visit::walk_enum_variant(self, variant);
+    }
+
+    fn visit_assign_target(&mut self, target: &AssignTarget) {
+        if let AssignTarget::Field { name, .. } = target {
+            self.fixed.insert(name.clone());
+        }
+        visit::walk_assign_target(self, target);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::MethodCall { method: name, .. } | ExprKind::Field { name, .. } => {
+                self.fixed.insert(name.clone());
+            }
+            ExprKind::Struct { ty, fields } => {
+                self.fixed.insert(ty.clone());
+                self.fixed.extend(fields.iter().map(|(name, _)| name.clone()));
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        self.locals.insert(param.name.clone());
+        visit::walk_param(self, param);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Bind(name) => {
+                self.locals.insert(name.clone());
+            }
+            Pattern::Struct { ty, fields } => {
+                self.fixed.insert(ty.clone());
+                self.fixed.extend(fields.iter().map(|(name, _)| name.clone()));
+            }
+            Pattern::Variant { name, .. } => {
+                self.fixed.insert(name.clone());
+            }
+            _ => {}
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    /// Names inside types are never renamed
+    fn visit_type(&mut self, ty: &Type) {
+        match &ty.kind {
+            TypeKind::Var(name) | TypeKind::Named { name, .. } => {
+                self.fixed.insert(name.clone());
+            }
+            TypeKind::Refinement(refinement) => {
+                self.fixed.insert(refinement.var_name.clone());
+                let (tokens, _) = synton_lexer::tokenize_recovering(&refinement.constraint);
+                self.fixed.extend(tokens.into_iter().filter_map(|t| match t.token {
+                    Token::Identifier(name) => Some(name),
+                    _ => None,
+                }));
+            }
+            TypeKind::Struct(fields) => {
+                self.fixed.extend(fields.iter().map(|field| field.name.clone()));
+            }
+            TypeKind::Enum(variants) => {
+                self.fixed.extend(variants.iter().map(|variant| variant.name.clone()));
+            }
+            _ => {}
+        }
+        visit::walk_type(self, ty);
+    }
+}
@@ -0,0 +1,280 @@
+//! Lossless concrete syntax tree
+//!
+//! The CST keeps every byte of the source. Tokens carry the whitespace,
+//! comments and rejected text around them as trivia, and delimited groups
+//! nest the way the S-expressions they come from do. Printing a [`Cst`]
+//! reproduces the original source exactly, so formatters and refactorings
+//! can rewrite a file without dropping its comments.
+
+use std::fmt;
+use std::ops::Range;
+
+use synton_lexer::{Token, TokenKind};
+
+/// Kind of trivia
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// Spaces, tabs and newlines
+    Whitespace,
+    /// `// ...` up to the end of the line
+    LineComment,
+    /// `/* ... */`
+    BlockComment,
+    /// Text the lexer rejected, split at whitespace
+    Skipped,
+}
+
+/// Source text that carries no meaning for the parser
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    /// What the text is
+    pub kind: TriviaKind,
+    /// The text as written
+    pub text: String,
+}
+
+impl Trivia {
+    /// Check if this is a comment
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, TriviaKind::LineComment | TriviaKind::BlockComment)
+    }
+}
+
+/// A token with its source text and surrounding trivia.
+///
+/// Trailing trivia runs up to the end of the token's line, so a comment
+/// after a token stays with it; everything else leads the next token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstToken {
+    /// The lexed token
+    pub token: Token,
+    /// Source text of the token
+    pub text: String,
+    /// Byte range of the token in the source
+    pub span: Range<usize>,
+    /// Trivia before the token
+    pub leading: Vec<Trivia>,
+    /// Trivia after the token on the same line
+    pub trailing: Vec<Trivia>,
+}
+
+/// Delimiter of a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// `( ... )`
+    Paren,
+    /// `[ ... ]`
+    Bracket,
+    /// `{ ... }`
+    Brace,
+}
+
+impl Delimiter {
+    fn open(token: &Token) -> Option<Self> {
+        match token {
+            Token::LParen => Some(Self::Paren),
+            Token::LBracket => Some(Self::Bracket),
+            Token::LBrace => Some(Self::Brace),
+            _ => None,
+        }
+    }
+
+    fn closes(self, token: &Token) -> bool {
+        matches!(
+            (self, token),
+            (Self::Paren, Token::RParen) | (Self::Bracket, Token::RBracket) | (Self::Brace, Token::RBrace)
+        )
+    }
+}
+
+/// A delimited group
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstNode {
+    /// Kind of delimiter
+    pub delimiter: Delimiter,
+    /// Opening delimiter
+    pub open: CstToken,
+    /// Contents of the group
+    pub children: Vec<CstElement>,
+    /// Closing delimiter, missing if the group is never closed
+    pub close: Option<CstToken>,
+}
+
+/// A token or a group
+#[derive(Debug, Clone, PartialEq)]
+pub enum CstElement {
+    /// Single token
+    Token(CstToken),
+    /// Delimited group
+    Node(CstNode),
+}
+
+/// Lossless syntax tree of a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cst {
+    /// Top-level elements
+    pub elements: Vec<CstElement>,
+    /// Trivia after the last token
+    pub trailing: Vec<Trivia>,
+}
+
+impl Cst {
+    /// Build the CST of a source file. Never fails: rejected text becomes
+    /// skipped trivia, stray closers plain tokens and unclosed groups run
+    /// to the end of the file.
+    pub fn parse(source: &str) -> Self {
+        let (tokens, _) = synton_lexer::tokenize_recovering(source);
+        Self::from_tokens(source, &tokens)
+    }
+
+    /// Build the CST from tokens already lexed from `source`
+    pub fn from_tokens(source: &str, tokens: &[TokenKind]) -> Self {
+        let mut stack: Vec<(CstToken, Delimiter, Vec<CstElement>)> = Vec::new();
+        let mut elements = Vec::new();
+        let mut pending: Option<CstToken> = None;
+        let mut pos = 0;
+
+        for kind in tokens {
+            let mut leading = split_trivia(&source[pos..kind.span.start]);
+            if let Some(prev) = pending.take() {
+                let prev = attach_trailing(prev, &mut leading);
+                push(&mut stack, &mut elements, prev);
+            }
+            pending = Some(CstToken {
+                token: kind.token.clone(),
+                text: source[kind.span.clone()].to_string(),
+                span: kind.span.clone(),
+                leading,
+                trailing: Vec::new(),
+            });
+            pos = kind.span.end;
+        }
+
+        let mut trailing = split_trivia(&source[pos..]);
+        if let Some(prev) = pending.take() {
+            let prev = attach_trailing(prev, &mut trailing);
+            push(&mut stack, &mut elements, prev);
+        }
+        while let Some((open, delimiter, children)) = stack.pop() {
+            let node = CstElement::Node(CstNode { delimiter, open, children, close: None });
+            children_of(&mut stack, &mut elements).push(node);
+        }
+
+        Self { elements, trailing }
+    }
+
+    /// All tokens in source order
+    pub fn tokens(&self) -> Vec<&CstToken> {
+        let mut out = Vec::new();
+        for element in &self.elements {
+            element.collect_tokens(&mut out);
+        }
+        out
+    }
+}
+
+impl CstElement {
+    fn collect_tokens<'a>(&'a self, out: &mut Vec<&'a CstToken>) {
+        match self {
+            Self::Token(token) => out.push(token),
+            Self::Node(node) => {
+                out.push(&node.open);
+                for child in &node.children {
+                    child.collect_tokens(out);
+                }
+                out.extend(node.close.as_ref());
+            }
+        }
+    }
+}
+
+/// Move the trivia up to the first line break from the start of `next`
+/// onto the token before it
+fn attach_trailing(mut token: CstToken, next: &mut Vec<Trivia>) -> CstToken {
+    let same_line = next
+        .iter()
+        .position(|trivia| trivia.text.contains('\n'))
+        .unwrap_or(next.len());
+    token.trailing = next.drain(..same_line).collect();
+    token
+}
+
+/// Place a finished token, opening or closing groups as needed
+fn push(stack: &mut Vec<(CstToken, Delimiter, Vec<CstElement>)>, elements: &mut Vec<CstElement>, token: CstToken) {
+    if let Some(delimiter) = Delimiter::open(&token.token) {
+        stack.push((token, delimiter, Vec::new()));
+        return;
+    }
+    match stack.last() {
+        Some((_, delimiter, _)) if delimiter.closes(&token.token) => {
+            let (open, delimiter, children) = stack.pop().expect("checked above");
+            let node = CstElement::Node(CstNode { delimiter, open, children, close: Some(token) });
+            children_of(stack, elements).push(node);
+        }
+        _ => children_of(stack, elements).push(CstElement::Token(token)),
+    }
+}
+
+fn children_of<'a>(
+    stack: &'a mut [(CstToken, Delimiter, Vec<CstElement>)],
+    elements: &'a mut Vec<CstElement>,
+) -> &'a mut Vec<CstElement> {
+    match stack.last_mut() {
+        Some((_, _, children)) => children,
+        None => elements,
+    }
+}
+
+/// Split the text between two tokens into trivia
+fn split_trivia(mut text: &str) -> Vec<Trivia> {
+    let is_space = |c: char| matches!(c, ' ' | '\t' | '\r' | '\n');
+    let mut out = Vec::new();
+    while let Some(first) = text.chars().next() {
+        let (kind, len) = if text.starts_with("//") {
+            (TriviaKind::LineComment, text.find('\n').unwrap_or(text.len()))
+        } else if text.starts_with("/*") {
+            (TriviaKind::BlockComment, text.find("*/").map_or(text.len(), |i| i + 2))
+        } else if is_space(first) {
+            (TriviaKind::Whitespace, text.find(|c| !is_space(c)).unwrap_or(text.len()))
+        } else {
+            (TriviaKind::Skipped, text.find(is_space).unwrap_or(text.len()))
+        };
+        out.push(Trivia { kind, text: text[..len].to_string() });
+        text = &text[len..];
+    }
+    out
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl fmt::Display for CstToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.leading.iter().try_for_each(|trivia| trivia.fmt(f))?;
+        f.write_str(&self.text)?;
+        self.trailing.iter().try_for_each(|trivia| trivia.fmt(f))
+    }
+}
+
+impl fmt::Display for CstElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Token(token) => token.fmt(f),
+            Self::Node(node) => {
+                node.open.fmt(f)?;
+                node.children.iter().try_for_each(|child| child.fmt(f))?;
+                node.close.iter().try_for_each(|close| close.fmt(f))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.elements.iter().try_for_each(|element| element.fmt(f))?;
+        self.trailing.iter().try_for_each(|trivia| trivia.fmt(f))
+    }
+}
@@ -1,5 +1,9 @@
 //! Parser error types
 
+use std::ops::Range;
+
+use chumsky::error::{Rich, RichPattern, RichReason};
+use synton_lexer::TokenKind;
 use thiserror::Error;
 
 /// Result type for parser operations
@@ -13,22 +17,32 @@ pub enum ParseError {
     LexError(#[from] synton_lexer::LexError),
 
     /// Unexpected token
-    #[error("unexpected token: expected {expected}, found {found}")]
+    #[error("unexpected token `{found}`, expected {}", one_of(expected))]
     UnexpectedToken {
-        expected: String,
+        /// Tokens or constructs that would have been accepted
+        expected: Vec<String>,
+        /// Source text of the offending token
         found: String,
+        /// Byte range of the offending token
+        span: Range<usize>,
     },
 
     /// Unexpected end of input
-    #[error("unexpected end of input, expected {expected}")]
+    #[error("unexpected end of input, expected {}", one_of(expected))]
     UnexpectedEof {
-        expected: String,
+        /// Tokens or constructs that would have been accepted
+        expected: Vec<String>,
+        /// Empty range at the end of the source
+        span: Range<usize>,
     },
 
     /// Invalid syntax
     #[error("invalid syntax: {message}")]
     InvalidSyntax {
+        /// Description of the problem
         message: String,
+        /// Byte range of the malformed construct
+        span: Range<usize>,
     },
 
     /// Type error during parsing
@@ -37,9 +51,72 @@ pub enum ParseError {
         message: String,
     },
 
-    /// Recursion limit exceeded
+    /// Nesting deeper than the configured maximum depth
     #[error("recursion limit exceeded (max depth: {max_depth})")]
     RecursionLimit {
+        /// Configured maximum nesting depth
         max_depth: usize,
+        /// Byte range of the token that opens the first group past the limit
+        span: Range<usize>,
     },
 }
+
+impl ParseError {
+    /// Byte range in the source the error points at
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::LexError(e) => e.span(),
+            Self::UnexpectedToken { span, .. }
+            | Self::UnexpectedEof { span, .. }
+            | Self::InvalidSyntax { span, .. }
+            | Self::RecursionLimit { span, .. } => span.clone(),
+            Self::TypeError { .. } => 0..0,
+        }
+    }
+
+    /// Convert a chumsky error over token indices into a parse error over
+    /// byte offsets into `source`
+    pub(crate) fn from_rich(err: Rich<'_, TokenKind>, tokens: &[TokenKind], source: &str) -> Self {
+        let span = byte_span(err.span().into_range(), tokens, source.len());
+
+        let mut expected: Vec<String> = err.expected()
+            .filter_map(|pattern| match pattern {
+                RichPattern::Token(t) => Some(format!("`{}`", t.token)),
+                RichPattern::Label(label) => Some(label.to_string()),
+                RichPattern::Identifier(name) => Some(format!("`{}`", name)),
+                RichPattern::EndOfInput => Some("end of input".to_string()),
+                _ => None,
+            })
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        match err.into_reason() {
+            RichReason::Custom(message) => Self::InvalidSyntax { message, span },
+            RichReason::ExpectedFound { found: Some(found), .. } => Self::UnexpectedToken {
+                expected,
+                found: found.token.to_string(),
+                span,
+            },
+            RichReason::ExpectedFound { found: None, .. } => Self::UnexpectedEof { expected, span },
+        }
+    }
+}
+
+/// Map a range of token indices to the byte range those tokens cover
+pub(crate) fn byte_span(range: Range<usize>, tokens: &[TokenKind], len: usize) -> Range<usize> {
+    let start = tokens.get(range.start).map_or(len, |t| t.span.start);
+    let end = match range.end.checked_sub(1).and_then(|last| tokens.get(last)) {
+        Some(t) if range.end > range.start => t.span.end,
+        _ => start,
+    };
+    start..end.max(start)
+}
+
+fn one_of(expected: &[String]) -> String {
+    match expected {
+        [] => "something else".to_string(),
+        [single] => single.clone(),
+        _ => format!("one of {}", expected.join(", ")),
+    }
+}
@@ -1,94 +1,429 @@
 //! Expression parser for Synton - Polish notation
 
+use chumsky::input::MapExtra;
 use chumsky::prelude::*;
 use synton_lexer::{Token, TokenKind};
-use synton_ast::{Expr, ExprKind, Literal, BinaryOp, CompareOp, Span, Position};
+use synton_ast::{
+    BinaryOp, CompareOp, Effect, Effects, Expr, ExprKind, Literal, MatchArm, Op, Param, Stmt, StmtKind, UnaryOp,
+};
+
+use super::grammar::{balanced, ident, keyword, span_of, token, Extra};
+use super::pattern_parser::pattern_parser;
+use super::stmt_parser::stmt_body;
+use super::type_parser::type_parser;
 
 /// Expression parser
-pub fn expr_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Expr> + Clone + 'a {
+pub fn expr_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Expr, Extra<'a>> + Clone + 'a {
     recursive(|expr| {
-        // Integer literal
-        let int_lit = select!(TokenKind { token: Token::Integer(n), .. } => n)
-            .map(|n| Expr::new(ExprKind::Literal(Literal::Integer(n)), Span::new(Position::start(), Position::start())));
-
-        // Boolean literals
-        let bool_lit = select!(
-            TokenKind { token: Token::True, .. } => true,
-            TokenKind { token: Token::False, .. } => false
+        let stmt = recursive(|stmt| stmt_body(expr.clone(), stmt));
+        expr_body(expr, stmt)
+    })
+}
+
+/// Literal parser shared by expressions and patterns
+pub(crate) fn literal_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Literal, Extra<'a>> + Clone + 'a {
+    select!(
+        TokenKind { token: Token::Integer(n), .. } => match n.suffix {
+            // Unsuffixed integers are range-checked against i64 by the lexer
+            None => Literal::Integer(n.value as i64),
+            Some(ty) => Literal::TypedInteger(n.value, ty),
+        },
+        TokenKind { token: Token::Float(f), .. } => match f.suffix {
+            None => Literal::Float(f.value),
+            Some(ty) => Literal::TypedFloat(f.value, ty),
+        },
+        TokenKind { token: Token::True, .. } => Literal::Bool(true),
+        TokenKind { token: Token::False, .. } => Literal::Bool(false),
+        TokenKind { token: Token::Char(c), .. } => Literal::Char(c),
+        TokenKind { token: Token::String(s), .. } => Literal::String(s),
+        TokenKind { token: Token::ByteString(b), .. } => Literal::Bytes(b),
+    )
+    .labelled("literal")
+}
+
+/// Parameter list shared by lambdas and function declarations:
+/// `[x y: i32]`
+pub(crate) fn params_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Vec<Param>, Extra<'a>> + Clone + 'a {
+    let param = ident()
+        .then(
+            token(Token::Colon)
+                .ignore_then(type_parser())
+                .or_not()
         )
-        .map(|b| Expr::new(ExprKind::Literal(Literal::Bool(b)), Span::new(Position::start(), Position::start())));
-
-        // Variable reference (excluding special call: syntax)
-        let var = select!(TokenKind { token: Token::Identifier(s), .. } => s.clone())
-            .map(|name| Expr::new(ExprKind::Var { id: None, name }, Span::new(Position::start(), Position::start())));
-
-        // Atomic expressions (literals and variables)
-        let atom = int_lit.or(bool_lit).or(var).boxed();
-
-        // Binary operation in Polish notation: (+ 1 2)
-        // Parse each operator explicitly with select!
-        let op_plus = select!(TokenKind { token: Token::Plus, span: _ } => BinaryOp::Add);
-        let op_minus = select!(TokenKind { token: Token::Minus, span: _ } => BinaryOp::Sub);
-        let op_star = select!(TokenKind { token: Token::Star, span: _ } => BinaryOp::Mul);
-        let op_slash = select!(TokenKind { token: Token::Slash, span: _ } => BinaryOp::Div);
-        let op_percent = select!(TokenKind { token: Token::Percent, span: _ } => BinaryOp::Mod);
-        let op_caret = select!(TokenKind { token: Token::Caret, span: _ } => BinaryOp::BitXor);
-        let op_amp = select!(TokenKind { token: Token::Amp, span: _ } => BinaryOp::BitAnd);
-        let op_pipe = select!(TokenKind { token: Token::Pipe, span: _ } => BinaryOp::BitOr);
-        let op_shl = select!(TokenKind { token: Token::Shl, span: _ } => BinaryOp::Shl);
-        let op_shr = select!(TokenKind { token: Token::Shr, span: _ } => BinaryOp::Shr);
-        let op_and = select!(TokenKind { token: Token::AndAnd, span: _ } => BinaryOp::And);
-        let op_or = select!(TokenKind { token: Token::OrOr, span: _ } => BinaryOp::Or);
-        let op_pow = select!(TokenKind { token: Token::StarStar, span: _ } => BinaryOp::Pow);
-
-        let binary_op = op_plus
-            .or(op_minus)
-            .or(op_star)
-            .or(op_slash)
-            .or(op_percent)
-            .or(op_caret)
-            .or(op_amp)
-            .or(op_pipe)
-            .or(op_shl)
-            .or(op_shr)
-            .or(op_and)
-            .or(op_or)
-            .or(op_pow)
-            .boxed();
-
-        let binary = select!(TokenKind { token: Token::LParen, span: _ })
-            .ignore_then(
-                // Parse operator
-                binary_op
-                // Parse left operand
-                .then(expr.clone())
-                // Parse right operand
-                .then(expr.clone())
-                // Parse closing paren
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
-            )
-            .map(|((op, left), right)| {
-                Expr::new(
-                    ExprKind::Binary {
-                        op,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    },
-                    Span::new(Position::start(), Position::start()),
-                )
+        .map_with(|(name, ty), e| Param { name, ty, span: span_of(e) });
+
+    token(Token::LBracket)
+        .ignore_then(param.separated_by(token(Token::Comma).or_not()).allow_trailing().collect::<Vec<_>>())
+        .then_ignore(token(Token::RBracket))
+}
+
+/// Declared effects shared by lambdas and function declarations: `! io`,
+/// or `! pure` for none
+pub(crate) fn effects_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Effects, Extra<'a>> + Clone + 'a {
+    let effect = ident().try_map_with(|name, e| match name.as_str() {
+        "pure" => Ok(Effects::PURE),
+        _ => Effect::from_name(&name)
+            .map(Effects::from)
+            .ok_or_else(|| Rich::custom(e.span(), format!("unknown effect '{}'", name))),
+    });
+    token(Token::Bang).ignore_then(
+        effect
+            .separated_by(token(Token::Comma))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map(|effects| effects.into_iter().fold(Effects::PURE, Effects::union)),
+    )
+}
+
+/// Expression grammar, parameterised over the recursive expression and
+/// statement parsers so that blocks can contain statements.
+pub(crate) fn expr_body<'a, E, S>(expr: E, stmt: S) -> impl Parser<'a, &'a [TokenKind], Expr, Extra<'a>> + Clone + 'a
+where
+    E: Parser<'a, &'a [TokenKind], Expr, Extra<'a>> + Clone + 'a,
+    S: Parser<'a, &'a [TokenKind], Stmt, Extra<'a>> + Clone + 'a,
+{
+    let lparen = token(Token::LParen);
+    let rparen = token(Token::RParen);
+    let comma = token(Token::Comma);
+    let ident = ident();
+
+    // Literals and `()`
+    let unit = lparen
+        .then(rparen)
+        .to(Literal::Unit);
+    let literal = literal_parser()
+        .or(unit)
+        .map_with(|lit, e| node(e, ExprKind::Literal(lit)))
+        .boxed();
+
+    // Variable reference; `none` is the empty maybe
+    let var = ident
+        .map_with(|name, e| match name.as_str() {
+            "none" => node(e, ExprKind::None),
+            _ => node(e, ExprKind::Var { id: None, name }),
+        })
+        .boxed();
+
+    // Operator in Polish notation: (+ 1 2), (== a b), (! x), (- x)
+    let operator = select!(
+        TokenKind { token: Token::Plus, .. } => Op::Binary(BinaryOp::Add),
+        TokenKind { token: Token::Minus, .. } => Op::Binary(BinaryOp::Sub),
+        TokenKind { token: Token::Star, .. } => Op::Binary(BinaryOp::Mul),
+        TokenKind { token: Token::Slash, .. } => Op::Binary(BinaryOp::Div),
+        TokenKind { token: Token::Percent, .. } => Op::Binary(BinaryOp::Mod),
+        TokenKind { token: Token::Caret, .. } => Op::Binary(BinaryOp::BitXor),
+        TokenKind { token: Token::Amp, .. } => Op::Binary(BinaryOp::BitAnd),
+        TokenKind { token: Token::Pipe, .. } => Op::Binary(BinaryOp::BitOr),
+        TokenKind { token: Token::Shl, .. } => Op::Binary(BinaryOp::Shl),
+        TokenKind { token: Token::Shr, .. } => Op::Binary(BinaryOp::Shr),
+        TokenKind { token: Token::AndAnd, .. } => Op::Binary(BinaryOp::And),
+        TokenKind { token: Token::OrOr, .. } => Op::Binary(BinaryOp::Or),
+        TokenKind { token: Token::StarStar, .. } => Op::Binary(BinaryOp::Pow),
+        TokenKind { token: Token::EqEq, .. } => Op::Compare(CompareOp::Eq),
+        TokenKind { token: Token::NotEq, .. } => Op::Compare(CompareOp::NotEq),
+        TokenKind { token: Token::Lt, .. } => Op::Compare(CompareOp::Less),
+        TokenKind { token: Token::LtEq, .. } => Op::Compare(CompareOp::LessEq),
+        TokenKind { token: Token::Gt, .. } => Op::Compare(CompareOp::Greater),
+        TokenKind { token: Token::GtEq, .. } => Op::Compare(CompareOp::GreaterEq),
+        TokenKind { token: Token::Bang, .. } => Op::Unary(UnaryOp::Not),
+        TokenKind { token: Token::Tilde, .. } => Op::Unary(UnaryOp::BitNot),
+    )
+    .labelled("operator");
+
+    let operation = lparen
+        .ignore_then(operator)
+        .then(expr.clone())
+        .then(expr.clone().or_not())
+        .then_ignore(rparen)
+        .try_map_with(|((op, left), right), e| {
+            let kind = match (op, right) {
+                (Op::Binary(op), Some(right)) => ExprKind::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                (Op::Compare(op), Some(right)) => ExprKind::Compare {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                (Op::Unary(op), None) => ExprKind::Unary { op, arg: Box::new(left) },
+                (Op::Binary(op), None) => match unary_form(op) {
+                    Some(op) => ExprKind::Unary { op, arg: Box::new(left) },
+                    None => return Err(Rich::custom(e.span(), "binary operator requires two operands")),
+                },
+                (Op::Compare(_), None) => {
+                    return Err(Rich::custom(e.span(), "comparison requires two operands"));
+                }
+                (Op::Unary(_), Some(_)) => {
+                    return Err(Rich::custom(e.span(), "unary operator takes a single operand"));
+                }
+            };
+            Ok(node(e, kind))
+        })
+        .boxed();
+
+    // Function call shorthands: ($ f a b) and (call:f a b)
+    let dollar_call = lparen
+        .ignore_then(token(Token::Dollar))
+        .ignore_then(expr.clone())
+        .then(expr.clone().repeated().collect::<Vec<_>>())
+        .then_ignore(rparen)
+        .map_with(|(callee, args), e| node(e, ExprKind::Call { callee: Box::new(callee), args }))
+        .boxed();
+
+    let named_call = lparen
+        .ignore_then(keyword("call"))
+        .ignore_then(token(Token::Colon))
+        .ignore_then(ident.map_with(|name, e| node(e, ExprKind::Var { id: None, name })))
+        .then(expr.clone().repeated().collect::<Vec<_>>())
+        .then_ignore(rparen)
+        .map_with(|(callee, args), e| node(e, ExprKind::Call { callee: Box::new(callee), args }))
+        .boxed();
+
+    // Accessors: (.call obj method args...), (.get base index), (.field base name)
+    let dot = token(Token::Dot);
+    let method_call = lparen
+        .ignore_then(dot)
+        .ignore_then(keyword("call"))
+        .ignore_then(expr.clone())
+        .then(ident)
+        .then(expr.clone().repeated().collect::<Vec<_>>())
+        .then_ignore(rparen)
+        .map_with(|((object, method), args), e| {
+            node(e, ExprKind::MethodCall { object: Box::new(object), method, args })
+        })
+        .boxed();
+
+    let index = lparen
+        .ignore_then(dot)
+        .ignore_then(keyword("get"))
+        .ignore_then(expr.clone())
+        .then(expr.clone())
+        .then_ignore(rparen)
+        .map_with(|(base, index), e| node(e, ExprKind::Index { base: Box::new(base), index: Box::new(index) }))
+        .boxed();
+
+    let field = lparen
+        .ignore_then(dot)
+        .ignore_then(keyword("field"))
+        .ignore_then(expr.clone())
+        .then(ident)
+        .then_ignore(rparen)
+        .map_with(|(base, name), e| node(e, ExprKind::Field { base: Box::new(base), name }))
+        .boxed();
+
+    // Lambda: (fn [x y: i32] body), or (fn [s] ! io (print s))
+    let lambda = lparen
+        .ignore_then(token(Token::KwFn))
+        .ignore_then(params_parser())
+        .then(effects_parser().or_not())
+        .then(expr.clone())
+        .then_ignore(rparen)
+        .map_with(|((params, effects), body), e| node(e, ExprKind::Lambda { params, effects, body: Box::new(body) }))
+        .boxed();
+
+    // If expression: (? cond then [else]), also spelled `branch` or `if`
+    let if_expr = lparen
+        .ignore_then(choice((
+            token(Token::Question),
+            token(Token::KwBranch),
+            token(Token::KwIf),
+        )))
+        .ignore_then(expr.clone())
+        .then(expr.clone())
+        .then(expr.clone().or_not())
+        .then_ignore(rparen)
+        .map_with(|((cond, then_branch), else_branch), e| {
+            node(e, ExprKind::If {
+                cond: Box::new(cond),
+                then_branch: Box::new(then_branch),
+                else_branch: else_branch.map(Box::new),
             })
-            .boxed();
-
-        // Parenthesized expression for grouping
-        let parenthesized = select!(TokenKind { token: Token::LParen, span: _ })
-            .ignore_then(expr.clone())
-            .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
-            .boxed();
-
-        // Combine all expression types
-        binary
-            .or(parenthesized)
-            .or(atom)
-            .boxed()
-    })
+        })
+        .boxed();
+
+    // Control flow: (loop body), (break [value]), (continue), (return [value])
+    let loop_expr = lparen
+        .ignore_then(token(Token::KwLoop))
+        .ignore_then(expr.clone())
+        .then_ignore(rparen)
+        .map_with(|body, e| node(e, ExprKind::Loop { body: Box::new(body) }))
+        .boxed();
+
+    let break_expr = lparen
+        .ignore_then(token(Token::KwBreak))
+        .ignore_then(expr.clone().or_not())
+        .then_ignore(rparen)
+        .map_with(|value, e| node(e, ExprKind::Break(value.map(Box::new))))
+        .boxed();
+
+    let continue_expr = lparen
+        .ignore_then(token(Token::KwContinue))
+        .then_ignore(rparen)
+        .map_with(|_, e| node(e, ExprKind::Continue))
+        .boxed();
+
+    let return_expr = lparen
+        .ignore_then(token(Token::KwReturn))
+        .ignore_then(expr.clone().or_not())
+        .then_ignore(rparen)
+        .map_with(|value, e| node(e, ExprKind::Return(value.map(Box::new))))
+        .boxed();
+
+    // Match expression: (match scrutinee pattern [if guard] => body ...)
+    let match_arm = pattern_parser()
+        .map_with(|pattern, e| (pattern, span_of(e)))
+        .then(
+            token(Token::KwIf)
+                .ignore_then(expr.clone())
+                .or_not()
+        )
+        .then_ignore(token(Token::FatArrow))
+        .then(expr.clone())
+        .map(|(((pattern, span), guard), body)| MatchArm {
+            pattern,
+            guard: guard.map(Box::new),
+            body: Box::new(body),
+            span,
+        });
+
+    let match_expr = lparen
+        .ignore_then(token(Token::KwMatch))
+        .ignore_then(expr.clone())
+        .then(match_arm.repeated().at_least(1).collect::<Vec<_>>())
+        .then_ignore(rparen)
+        .map_with(|(scrutinee, arms), e| {
+            node(e, ExprKind::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            })
+        })
+        .boxed();
+
+    // Types in expression position: (as expr type), (size_of type)
+    let cast = lparen
+        .ignore_then(token(Token::KwAs))
+        .ignore_then(expr.clone())
+        .then(type_parser())
+        .then_ignore(rparen)
+        .map_with(|(expr, ty), e| node(e, ExprKind::As { expr: Box::new(expr), ty }))
+        .boxed();
+
+    let size_of = lparen
+        .ignore_then(keyword("size_of"))
+        .ignore_then(type_parser())
+        .then_ignore(rparen)
+        .map_with(|ty, e| node(e, ExprKind::SizeOf(ty)))
+        .boxed();
+
+    // Struct literal: (Point x: 1 y: 2)
+    let struct_field = ident
+        .then_ignore(token(Token::Colon))
+        .then(expr.clone());
+    let struct_lit = lparen
+        .ignore_then(ident)
+        .then(struct_field.repeated().at_least(1).collect::<Vec<_>>())
+        .then_ignore(rparen)
+        .map_with(|(ty, fields), e| node(e, ExprKind::Struct { ty, fields }))
+        .boxed();
+
+    // Head-first forms sharing the `(expr` prefix:
+    // (a, b) tuple, (some x) maybe, (f a b) call, (x) grouping
+    let tail = comma
+        .ignore_then(expr.clone().separated_by(comma).allow_trailing().collect::<Vec<_>>())
+        .map(Tail::Tuple)
+        .or(expr.clone().repeated().collect::<Vec<_>>().map(Tail::Args));
+
+    let application = lparen
+        .ignore_then(expr.clone())
+        .then(tail)
+        .then_ignore(rparen)
+        .map_with(|(head, tail), e| match tail {
+            Tail::Tuple(rest) => {
+                let mut items = vec![head];
+                items.extend(rest);
+                node(e, ExprKind::Tuple(items))
+            }
+            Tail::Args(args) if args.is_empty() => head,
+            Tail::Args(mut args) => match &head.kind {
+                ExprKind::Var { name, .. } if name == "some" && args.len() == 1 => {
+                    node(e, ExprKind::Some(Box::new(args.remove(0))))
+                }
+                _ => node(e, ExprKind::Call { callee: Box::new(head), args }),
+            },
+        })
+        .boxed();
+
+    // Array literal: [a b c] (commas optional)
+    let array = token(Token::LBracket)
+        .ignore_then(expr.clone().separated_by(comma.or_not()).allow_trailing().collect::<Vec<_>>())
+        .then_ignore(token(Token::RBracket))
+        .map_with(|items, e| node(e, ExprKind::Array(items)))
+        .boxed();
+
+    // Block expression: { stmts... [tail] }
+    let block = token(Token::LBrace)
+        .ignore_then(stmt.repeated().collect::<Vec<_>>())
+        .then_ignore(token(Token::RBrace))
+        .map_with(|mut stmts: Vec<Stmt>, e| {
+            let tail = match stmts.last().map(|s| &s.kind) {
+                Some(StmtKind::Expr(_)) => match stmts.pop().map(|s| s.kind) {
+                    Some(StmtKind::Expr(expr)) => Some(expr),
+                    _ => None,
+                },
+                _ => None,
+            };
+            node(e, ExprKind::Block(stmts, tail))
+        })
+        .boxed();
+
+    choice((
+        operation,
+        dollar_call,
+        named_call,
+        method_call,
+        index,
+        field,
+        lambda,
+        if_expr,
+        loop_expr,
+        break_expr,
+        continue_expr,
+        return_expr,
+        match_expr,
+        cast,
+        size_of,
+        struct_lit,
+    ))
+    .or(application)
+    .or(array)
+    .or(block)
+    .or(literal)
+    .or(var)
+    // A parenthesized group that fails to parse becomes an error node, so
+    // parsing resumes after its closing paren
+    .recover_with(via_parser(balanced().map_with(|_, e| node(e, ExprKind::Error))))
+    .boxed()
+}
+
+/// What follows the head expression inside `( ... )`
+#[derive(Clone)]
+enum Tail {
+    Tuple(Vec<Expr>),
+    Args(Vec<Expr>),
+}
+
+/// Unary reading of a binary operator token used with a single operand
+fn unary_form(op: BinaryOp) -> Option<UnaryOp> {
+    match op {
+        BinaryOp::Sub => Some(UnaryOp::Neg),
+        BinaryOp::Mul => Some(UnaryOp::Deref),
+        BinaryOp::BitAnd => Some(UnaryOp::Ref),
+        _ => None,
+    }
+}
+
+fn node<'a>(e: &mut MapExtra<'a, '_, &'a [TokenKind], Extra<'a>>, kind: ExprKind) -> Expr {
+    Expr::new(kind, span_of(e))
 }
@@ -0,0 +1,338 @@
+//! Canonical formatter for Synton source
+//!
+//! Formatting works on the [`Cst`] so that comments survive. A group is
+//! printed on one line when it fits within the line width; otherwise its
+//! head stays on the opening line and every other element gets a line of
+//! its own, indented from the opening delimiter. Infix joiners such as
+//! `=`, `=>` and `:` keep their operands on the same line.
+//!
+//! Only whitespace changes: the tokens, and therefore the parsed module,
+//! are the same before and after formatting, and formatting is idempotent.
+
+use synton_lexer::Token;
+
+use crate::cst::{Cst, CstElement, CstNode, CstToken, Delimiter, Trivia, TriviaKind};
+use crate::{ParseResult, SyntonParser};
+
+/// Formatter configuration
+#[derive(Debug, Clone)]
+pub struct FormatConfig {
+    /// Preferred maximum line width
+    pub line_width: usize,
+    /// Spaces per indentation level
+    pub indent_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            line_width: 80,
+            indent_width: 2,
+        }
+    }
+}
+
+/// Format a source file. Fails with the first error if the source does not
+/// parse, since whitespace cannot be normalized safely around broken syntax.
+pub fn format_source(source: &str, config: &FormatConfig) -> ParseResult<String> {
+    let (output, tree) = SyntonParser::new().parse_module_lossless(source);
+    output.into_result()?;
+    Ok(format_cst(&tree, config))
+}
+
+/// Format an already parsed syntax tree
+pub fn format_cst(tree: &Cst, config: &FormatConfig) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        config,
+        break_pending: false,
+    };
+
+    let mut prev: Option<&CstElement> = None;
+    for element in &tree.elements {
+        if let Some(prev) = prev {
+            if starts_line(prev, element) {
+                printer.newline(0);
+                if blank_line_before(first_token(element)) {
+                    printer.newline(0);
+                }
+            } else {
+                printer.space(0);
+            }
+        }
+        printer.element(element, 0);
+        prev = Some(element);
+    }
+    for comment in tree.trailing.iter().filter(|t| t.is_comment()) {
+        if !printer.out.is_empty() {
+            printer.newline(0);
+        }
+        printer.out.push_str(&comment.text);
+    }
+
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+struct Printer<'c> {
+    out: String,
+    config: &'c FormatConfig,
+    /// A line comment was just printed, so the next token needs a new line
+    break_pending: bool,
+}
+
+impl Printer<'_> {
+    fn column(&self) -> usize {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..].chars().count()
+    }
+
+    fn newline(&mut self, indent: usize) {
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+        self.break_pending = false;
+    }
+
+    /// Change the indentation of the current, still empty, line
+    fn reindent(&mut self, indent: usize) {
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+
+    /// Separate two elements by a space, or a line break after a comment
+    fn space(&mut self, indent: usize) {
+        if self.break_pending {
+            self.newline(indent);
+        } else {
+            self.out.push(' ');
+        }
+    }
+
+    fn element(&mut self, element: &CstElement, indent: usize) {
+        match element {
+            CstElement::Token(token) => self.token(token, indent),
+            CstElement::Node(node) => self.node(node, indent),
+        }
+    }
+
+    fn token(&mut self, token: &CstToken, indent: usize) {
+        self.leading(&token.leading, indent);
+        self.out.push_str(&token.text);
+        self.trailing(&token.trailing);
+    }
+
+    /// Comments before a token, each on a line of its own. Blank lines
+    /// after a comment are kept, collapsed to one.
+    fn leading(&mut self, trivia: &[Trivia], indent: usize) {
+        let mut after_comment = false;
+        let mut blank = false;
+        for trivia in trivia {
+            if trivia.kind == TriviaKind::Whitespace {
+                blank |= after_comment && trivia.text.matches('\n').count() >= 2;
+                continue;
+            }
+            if !trivia.is_comment() {
+                continue;
+            }
+            if self.out.trim_end_matches(' ').rsplit('\n').next().is_some_and(|line| !line.is_empty()) {
+                self.newline(indent);
+            }
+            if blank {
+                self.newline(indent);
+            }
+            self.out.push_str(&trivia.text);
+            self.newline(indent);
+            after_comment = true;
+            blank = false;
+        }
+        if blank {
+            self.newline(indent);
+        }
+        if self.break_pending {
+            self.newline(indent);
+        }
+    }
+
+    /// Comments after a token on the same line
+    fn trailing(&mut self, trivia: &[Trivia]) {
+        for comment in trivia.iter().filter(|t| t.is_comment()) {
+            self.out.push(' ');
+            self.out.push_str(&comment.text);
+            if comment.kind == TriviaKind::LineComment || comment.text.contains('\n') {
+                self.break_pending = true;
+            }
+        }
+    }
+
+    fn node(&mut self, node: &CstNode, indent: usize) {
+        self.leading(&node.open.leading, indent);
+        let open_column = self.column();
+
+        if let Some(flat) = flat_body(node) {
+            if open_column + flat.chars().count() <= self.config.line_width {
+                self.out.push_str(&flat);
+                if let Some(close) = &node.close {
+                    self.trailing(&close.trailing);
+                }
+                return;
+            }
+        }
+
+        let child_indent = open_column + self.config.indent_width;
+        self.out.push_str(&node.open.text);
+        self.trailing(&node.open.trailing);
+
+        let segments = segments(&node.children);
+        // Keep the keyword and its subject together: `(match x`, `(.get xs`
+        let keyword = |segment: &Vec<(bool, &CstElement)>| {
+            segment.iter().all(|(_, element)| matches!(element, CstElement::Token(_)))
+        };
+        let head = match node.delimiter {
+            Delimiter::Paren if segments.first().is_some_and(keyword) => 2,
+            _ => 1,
+        };
+        for (i, segment) in segments.iter().enumerate() {
+            if i == 0 {
+                if self.break_pending {
+                    self.newline(child_indent);
+                }
+            } else if i < head {
+                self.space(child_indent);
+            } else {
+                self.newline(child_indent);
+            }
+            self.segment(segment, child_indent);
+        }
+
+        if let Some(close) = &node.close {
+            if close.leading.iter().any(Trivia::is_comment) {
+                self.leading(&close.leading, child_indent);
+                self.reindent(open_column);
+            } else if self.break_pending {
+                self.newline(open_column);
+            }
+            self.out.push_str(&close.text);
+            self.trailing(&close.trailing);
+        }
+    }
+
+    fn segment(&mut self, segment: &[(bool, &CstElement)], indent: usize) {
+        for (i, (spaced, element)) in segment.iter().enumerate() {
+            if i > 0 && (*spaced || self.break_pending) {
+                self.space(indent);
+            }
+            self.element(element, indent);
+        }
+    }
+}
+
+/// Group children into segments, joining the operands of infix joiners.
+/// Each element comes with whether a space separates it from the one
+/// before: `:` sticks to what it annotates, `.` to the accessor after it
+/// (`.get`), and the callee to `call:`.
+fn segments(children: &[CstElement]) -> Vec<Vec<(bool, &CstElement)>> {
+    let mut segments: Vec<Vec<(bool, &CstElement)>> = Vec::new();
+    let (mut join_next, mut glue_next) = (false, false);
+    for (i, child) in children.iter().enumerate() {
+        let token = match child {
+            CstElement::Token(token) => Some(&token.token),
+            CstElement::Node(_) => None,
+        };
+        let joiner = matches!(token, Some(Token::Eq | Token::FatArrow | Token::Colon | Token::Arrow));
+        let spaced = !glue_next && !matches!(token, Some(Token::Colon));
+        match segments.last_mut() {
+            Some(segment) if joiner || join_next || glue_next => segment.push((spaced, child)),
+            _ => segments.push(vec![(true, child)]),
+        }
+        join_next = joiner;
+        glue_next = match token {
+            Some(Token::Dot) => true,
+            Some(Token::Colon) => i == 1 && matches!(&children[0], CstElement::Token(head) if head.text == "call"),
+            _ => false,
+        };
+    }
+    segments
+}
+
+/// Single-line rendering of a group, without the comments around it, or
+/// `None` if a comment inside forces it onto several lines
+fn flat_body(node: &CstNode) -> Option<String> {
+    let mut out = String::new();
+    flat_node(node, &mut out)?;
+    Some(out)
+}
+
+fn flat_node(node: &CstNode, out: &mut String) -> Option<()> {
+    let close_leading = node.close.as_ref().map_or(&[][..], |close| &close.leading[..]);
+    if has_comment(&node.open.trailing) || has_comment(close_leading) {
+        return None;
+    }
+    out.push_str(&node.open.text);
+    for (i, segment) in segments(&node.children).iter().enumerate() {
+        for (j, (spaced, element)) in segment.iter().enumerate() {
+            if (i > 0 || j > 0) && *spaced {
+                out.push(' ');
+            }
+            flat_element(element, out)?;
+        }
+    }
+    if let Some(close) = &node.close {
+        out.push_str(&close.text);
+    }
+    Some(())
+}
+
+fn flat_element(element: &CstElement, out: &mut String) -> Option<()> {
+    match element {
+        CstElement::Token(token) => {
+            if has_comment(&token.leading) || has_comment(&token.trailing) {
+                return None;
+            }
+            out.push_str(&token.text);
+            Some(())
+        }
+        CstElement::Node(node) => {
+            let close_trailing = node.close.as_ref().map_or(&[][..], |close| &close.trailing[..]);
+            if has_comment(&node.open.leading) || has_comment(close_trailing) {
+                return None;
+            }
+            flat_node(node, out)
+        }
+    }
+}
+
+fn has_comment(trivia: &[Trivia]) -> bool {
+    trivia.iter().any(Trivia::is_comment)
+}
+
+fn first_token(element: &CstElement) -> &CstToken {
+    match element {
+        CstElement::Token(token) => token,
+        CstElement::Node(node) => &node.open,
+    }
+}
+
+/// Whether a top-level element goes on a new line. Groups each get their
+/// own line, while bare declarations such as `import "a" as b` stay on one.
+fn starts_line(prev: &CstElement, element: &CstElement) -> bool {
+    match (prev, element) {
+        (CstElement::Node(_), _) | (_, CstElement::Node(_)) => true,
+        (_, CstElement::Token(token)) => matches!(token.token, Token::KwImport | Token::KwExport),
+    }
+}
+
+/// Whether the source had a blank line before a top-level element, or
+/// before the comments leading it
+fn blank_line_before(token: &CstToken) -> bool {
+    token
+        .leading
+        .first()
+        .is_some_and(|t| t.kind == TriviaKind::Whitespace && t.text.matches('\n').count() >= 2)
+}
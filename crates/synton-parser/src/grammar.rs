@@ -1,24 +1,266 @@
 //! Grammar module for Synton parser
 
+use std::borrow::Cow;
+use std::mem;
+
+use chumsky::error::RichPattern;
+use chumsky::extra::SimpleState;
+use chumsky::input::MapExtra;
 use chumsky::prelude::*;
+use sha2::{Digest, Sha256};
 use synton_lexer::{Token, TokenKind};
-use synton_ast::{Module, ModuleId, Stmt, StmtKind, Span, Position};
+use synton_ast::{Module, ModuleId, Stmt, ImportDecl, ImportKind, ExportDecl, Span};
 
+use super::ast_builder::AstBuilder;
+use super::error::{byte_span, ParseError};
 use super::stmt_parser::stmt_parser;
 
+/// Parser extra carrying rich errors, so every failure keeps its span and
+/// the set of tokens that would have been accepted, and the [`Source`] the
+/// tokens come from
+pub type Extra<'a> = extra::Full<Rich<'a, TokenKind>, SimpleState<Source<'a>>, ()>;
+
+/// Tokens being parsed and the source they were lexed from, so that parsed
+/// nodes get source spans
+pub struct Source<'a> {
+    tokens: &'a [TokenKind],
+    len: usize,
+    builder: AstBuilder,
+}
+
+impl<'a> Source<'a> {
+    /// Source of `tokens`, lexed from `source`
+    pub fn new(tokens: &'a [TokenKind], source: &str) -> Self {
+        Self { tokens, len: source.len(), builder: AstBuilder::new(source.to_string()) }
+    }
+}
+
+/// Source span of the tokens a parser consumed. A parser that consumed
+/// nothing gets an empty span where the next token starts.
+pub fn span_of<'a>(e: &mut MapExtra<'a, '_, &'a [TokenKind], Extra<'a>>) -> Span {
+    let tokens = e.span().into_range();
+    let source = &e.state().0;
+    let bytes = byte_span(tokens, source.tokens, source.len);
+    source.builder.span(bytes.start, bytes.end)
+}
+
+/// Expected fixed token or contextual keyword, reported by its source text
+#[derive(Debug, Clone, Copy)]
+pub struct Expected(&'static str);
+
+impl From<Expected> for RichPattern<'_, TokenKind> {
+    fn from(expected: Expected) -> Self {
+        RichPattern::Label(Cow::Owned(format!("`{}`", expected.0)))
+    }
+}
+
+/// Match a single fixed token (delimiter, operator or keyword)
+pub fn token<'a>(expected: Token) -> impl Parser<'a, &'a [TokenKind], (), Extra<'a>> + Copy + 'a {
+    let kind = mem::discriminant(&expected);
+    let text = expected.text().unwrap_or("token");
+    any()
+        .filter(move |t: &TokenKind| mem::discriminant(&t.token) == kind)
+        .ignored()
+        .labelled(Expected(text))
+}
+
+/// Identifier
+pub fn ident<'a>() -> impl Parser<'a, &'a [TokenKind], String, Extra<'a>> + Copy + 'a {
+    select!(TokenKind { token: Token::Identifier(s), .. } => s).labelled("identifier")
+}
+
+/// Contextual keyword spelled as an identifier, such as `some` or `sem`
+pub fn keyword<'a>(name: &'static str) -> impl Parser<'a, &'a [TokenKind], (), Extra<'a>> + Copy + 'a {
+    select!(TokenKind { token: Token::Identifier(s), .. } if s == name => ())
+        .labelled(Expected(name))
+}
+
+/// A balanced parenthesized group, skipped wholesale during error recovery
+pub fn balanced<'a>() -> impl Parser<'a, &'a [TokenKind], (), Extra<'a>> + Clone + 'a {
+    recursive(|group| {
+        let other = any()
+            .filter(|t: &TokenKind| !matches!(t.token, Token::LParen | Token::RParen))
+            .ignored();
+
+        token(Token::LParen)
+            .ignore_then(group.or(other).repeated())
+            .then_ignore(token(Token::RParen))
+    })
+}
+
+/// Top-level module item
+#[derive(Debug, Clone)]
+enum Item {
+    Import(ImportDecl),
+    Export(Vec<ExportDecl>),
+    Stmt(Stmt),
+}
+
 /// Module parser
-pub fn module_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Module> + Clone + 'a {
-    // Parse a sequence of statements
-    let stmts = stmt_parser()
-        .repeated()
-        .collect();
-
-    stmts.then_ignore(end())
-        .map(|stmts: Vec<Stmt>| Module {
-            id: ModuleId::new("placeholder_hash".to_string()),
-            stmts,
-            imports: vec![],
-            exports: vec![],
+///
+/// Items that fail to parse are skipped token by token until the next item
+/// parses, so a single pass reports every error in the module.
+pub fn module_parser<'a>(id: ModuleId) -> impl Parser<'a, &'a [TokenKind], Module, Extra<'a>> + Clone + 'a {
+    let item = import_parser()
+        .map(Item::Import)
+        .or(export_parser().map(Item::Export))
+        .or(stmt_parser().map(Item::Stmt))
+        .recover_with(skip_then_retry_until(any().ignored(), end()));
+
+    item.repeated()
+        .collect::<Vec<_>>()
+        .then_ignore(end())
+        .map(move |items| {
+            let mut module = Module::new(id.clone());
+            for item in items {
+                match item {
+                    Item::Import(import) => module.imports.push(import),
+                    Item::Export(exports) => module.exports.extend(exports),
+                    Item::Stmt(stmt) => module.stmts.push(stmt),
+                }
+            }
+            module
         })
         .boxed()
 }
+
+/// Import declaration, bare or parenthesized:
+///
+/// - `import hash:sha256:e3b0c442... as util`
+/// - `import sem:"sort algorithm fast" as sort`
+/// - `(import "std/list" as list)`
+pub fn import_parser<'a>() -> impl Parser<'a, &'a [TokenKind], ImportDecl, Extra<'a>> + Clone + 'a {
+    let hash = select!(TokenKind { token: Token::HashRef(s), .. } => s)
+        .labelled("hash reference")
+        .map(|s| hash_import(&s));
+
+    let semantic = keyword("sem")
+        .ignore_then(token(Token::Colon))
+        .ignore_then(select!(TokenKind { token: Token::String(s), .. } => s))
+        .map(|s| ImportKind::Semantic { query: s });
+
+    // A quoted `"hash:..."` is accepted as well as the bare form
+    let path = select!(TokenKind { token: Token::String(s), .. } => s)
+        .labelled("module path")
+        .map(|path| {
+            if path.starts_with("hash:") {
+                hash_import(&path)
+            } else {
+                ImportKind::Path { path }
+            }
+        });
+
+    let decl = token(Token::KwImport)
+        .ignore_then(hash.or(semantic).or(path))
+        .then(alias())
+        .map_with(|(kind, alias), e| ImportDecl {
+            name: import_name(&kind),
+            kind,
+            alias,
+            span: span_of(e),
+        });
+
+    parenthesized(decl)
+}
+
+/// Export declaration with one or more names, each optionally aliased:
+///
+/// - `export add sub`
+/// - `(export add sub as minus)`
+pub fn export_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Vec<ExportDecl>, Extra<'a>> + Clone + 'a {
+    let entry = ident()
+        .then(alias())
+        .map_with(|(name, alias), e| ExportDecl { name, alias, span: span_of(e) });
+
+    let decl = token(Token::KwExport)
+        .ignore_then(entry.repeated().at_least(1).collect::<Vec<_>>());
+
+    parenthesized(decl)
+}
+
+/// Reject input nested deeper than `max_depth` before it reaches the
+/// recursive parsers.
+///
+/// Every nested construct is delimited by `()`, `[]` or `{}`, except the
+/// prefix type operators `?T` and `&T`, so counting those bounds the
+/// recursion depth of the parse.
+pub fn check_nesting(tokens: &[TokenKind], max_depth: usize) -> Result<(), ParseError> {
+    let mut depth = 0usize;
+    let mut prefix = 0usize;
+    for token in tokens {
+        match token.token {
+            Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+            Token::RParen | Token::RBracket | Token::RBrace => depth = depth.saturating_sub(1),
+            Token::Question | Token::Amp => prefix += 1,
+            _ => {}
+        }
+        if !matches!(token.token, Token::Question | Token::Amp) {
+            prefix = 0;
+        }
+        if depth + prefix > max_depth {
+            return Err(ParseError::RecursionLimit {
+                max_depth,
+                span: token.span.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Compute the content-addressed identifier of a module.
+///
+/// The hash covers the token texts only, so whitespace and comments do not
+/// change the identity of a module.
+pub fn module_hash(source: &str, tokens: &[TokenKind]) -> ModuleId {
+    let mut hasher = Sha256::new();
+    for token in tokens {
+        hasher.update(source[token.span.clone()].as_bytes());
+        hasher.update([0u8]);
+    }
+    let hash = hasher.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    ModuleId::new(hash)
+}
+
+/// Optional `as alias`
+fn alias<'a>() -> impl Parser<'a, &'a [TokenKind], Option<String>, Extra<'a>> + Clone + 'a {
+    token(Token::KwAs)
+        .ignore_then(ident())
+        .or_not()
+}
+
+/// Accept a declaration either bare or wrapped in parentheses
+fn parenthesized<'a, T, P>(decl: P) -> impl Parser<'a, &'a [TokenKind], T, Extra<'a>> + Clone + 'a
+where
+    P: Parser<'a, &'a [TokenKind], T, Extra<'a>> + Clone + 'a,
+    T: 'a,
+{
+    token(Token::LParen)
+        .ignore_then(decl.clone())
+        .then_ignore(token(Token::RParen))
+        .or(decl)
+        .boxed()
+}
+
+fn hash_import(reference: &str) -> ImportKind {
+    let mut parts = reference.splitn(3, ':').skip(1);
+    ImportKind::Hash {
+        algorithm: parts.next().unwrap_or_default().to_string(),
+        hash: parts.next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Default local binding name of an import
+fn import_name(kind: &ImportKind) -> String {
+    match kind {
+        ImportKind::Hash { hash, .. } => hash.clone(),
+        ImportKind::Semantic { query } => query.clone(),
+        ImportKind::Path { path } => path
+            .rsplit('/')
+            .next()
+            .unwrap_or(path)
+            .to_string(),
+    }
+}
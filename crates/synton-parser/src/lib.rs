@@ -7,13 +7,20 @@
 pub mod error;
 pub mod ast_builder;
 pub mod expr_parser;
+pub mod pattern_parser;
+pub mod type_parser;
 pub mod stmt_parser;
 pub mod grammar;
+pub mod cst;
+pub mod format;
+pub mod compact;
 
+use chumsky::extra::SimpleState;
 use chumsky::Parser;
 use synton_lexer::TokenKind;
-use synton_ast::{Module, Span, Position, Expr, Stmt};
+use synton_ast::{Module, Expr, Stmt};
 pub use error::{ParseError, ParseResult};
+use grammar::Extra;
 
 /// Parser configuration
 #[derive(Debug, Clone)]
@@ -33,6 +40,35 @@ impl Default for ParserConfig {
     }
 }
 
+/// Output of a recovering parse
+#[derive(Debug)]
+pub struct ParseOutput<T> {
+    /// Parsed output, possibly containing `Error` sentinel nodes
+    pub output: Option<T>,
+    /// Syntax errors in source order
+    pub errors: Vec<ParseError>,
+}
+
+impl<T> ParseOutput<T> {
+    fn failed(error: ParseError) -> Self {
+        Self { output: None, errors: vec![error] }
+    }
+
+    /// Check if any errors were reported
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Convert into a result, failing with the first error
+    pub fn into_result(mut self) -> ParseResult<T> {
+        match self.output {
+            Some(output) if self.errors.is_empty() => Ok(output),
+            _ if !self.errors.is_empty() => Err(self.errors.remove(0)),
+            _ => Err(ParseError::UnexpectedEof { expected: Vec::new(), span: 0..0 }),
+        }
+    }
+}
+
 /// Parser for Synton source code
 pub struct SyntonParser {
     config: ParserConfig,
@@ -51,38 +87,80 @@ impl SyntonParser {
 
     /// Parse a complete module from source
     pub fn parse_module(&self, source: &str) -> ParseResult<Module> {
-        let token_kinds = synton_lexer::tokenize(source)?;
-        let parser = grammar::module_parser();
-        parser.parse(&token_kinds)
-            .into_result()
-            .map_err(|err| ParseError::InvalidSyntax { message: format!("{:?}", err) })
+        self.parse_module_recovering(source).into_result()
+    }
+
+    /// Parse a module, recovering from syntax errors.
+    ///
+    /// Returns the partial module, with `Error` sentinels in place of the
+    /// groups that failed to parse, together with every error found. Bad
+    /// tokens are reported and skipped, so parsing continues past them. With
+    /// recovery disabled only the first error is reported.
+    pub fn parse_module_recovering(&self, source: &str) -> ParseOutput<Module> {
+        let (token_kinds, lex_errors) = synton_lexer::tokenize_recovering(source);
+        self.run_module(&token_kinds, lex_errors, source)
+    }
+
+    /// Parse a module with recovery, also building its lossless syntax tree
+    pub fn parse_module_lossless(&self, source: &str) -> (ParseOutput<Module>, cst::Cst) {
+        let (token_kinds, lex_errors) = synton_lexer::tokenize_recovering(source);
+        let tree = cst::Cst::from_tokens(source, &token_kinds);
+        (self.run_module(&token_kinds, lex_errors, source), tree)
     }
 
     /// Parse an expression
     pub fn parse_expr(&self, source: &str) -> ParseResult<Expr> {
-        let token_kinds = synton_lexer::tokenize(source)?;
-        let parser = expr_parser::expr_parser();
-        parser.parse(&token_kinds)
-            .into_result()
-            .map_err(|err| ParseError::InvalidSyntax { message: format!("{:?}", err) })
+        let (token_kinds, lex_errors) = synton_lexer::tokenize_recovering(source);
+        self.run(expr_parser::expr_parser(), &token_kinds, lex_errors, source).into_result()
     }
 
     /// Parse a type
-    pub fn parse_type(&self, _source: &str) -> ParseResult<synton_ast::Type> {
-        use synton_ast::{Type, TypeKind, BuiltinType};
-        Ok(Type::new(TypeKind::Builtin(BuiltinType::I32), Span::new(
-            Position::start(),
-            Position::start(),
-        )))
+    pub fn parse_type(&self, source: &str) -> ParseResult<synton_ast::Type> {
+        let (token_kinds, lex_errors) = synton_lexer::tokenize_recovering(source);
+        self.run(type_parser::type_parser(), &token_kinds, lex_errors, source).into_result()
     }
 
     /// Parse a statement
     pub fn parse_stmt(&self, source: &str) -> ParseResult<Stmt> {
-        let token_kinds = synton_lexer::tokenize(source)?;
-        let parser = stmt_parser::stmt_parser();
-        parser.parse(&token_kinds)
-            .into_result()
-            .map_err(|err| ParseError::InvalidSyntax { message: format!("{:?}", err) })
+        let (token_kinds, lex_errors) = synton_lexer::tokenize_recovering(source);
+        self.run(stmt_parser::stmt_parser(), &token_kinds, lex_errors, source).into_result()
+    }
+
+    fn run_module(
+        &self,
+        tokens: &[TokenKind],
+        lex_errors: Vec<synton_lexer::LexError>,
+        source: &str,
+    ) -> ParseOutput<Module> {
+        let id = grammar::module_hash(source, tokens);
+        self.run(grammar::module_parser(id), tokens, lex_errors, source)
+    }
+
+    fn run<'a, T>(
+        &self,
+        parser: impl Parser<'a, &'a [TokenKind], T, Extra<'a>>,
+        tokens: &'a [TokenKind],
+        lex_errors: Vec<synton_lexer::LexError>,
+        source: &str,
+    ) -> ParseOutput<T> {
+        if let Err(err) = grammar::check_nesting(tokens, self.config.max_depth) {
+            return ParseOutput::failed(err);
+        }
+
+        let mut state = SimpleState(grammar::Source::new(tokens, source));
+        let (output, errors) = parser.parse_with_state(tokens, &mut state).into_output_errors();
+        let mut errors: Vec<ParseError> = lex_errors
+            .into_iter()
+            .map(ParseError::from)
+            .chain(errors.into_iter().map(|err| ParseError::from_rich(err, tokens, source)))
+            .collect();
+        errors.sort_by_key(|err| err.span().start);
+
+        if !self.config.recovery && !errors.is_empty() {
+            errors.truncate(1);
+            return ParseOutput { output: None, errors };
+        }
+        ParseOutput { output, errors }
     }
 }
 
@@ -97,6 +175,11 @@ pub fn parse_module(source: &str) -> ParseResult<Module> {
     SyntonParser::new().parse_module(source)
 }
 
+/// Convenience function to parse a module, recovering from syntax errors
+pub fn parse_module_recovering(source: &str) -> ParseOutput<Module> {
+    SyntonParser::new().parse_module_recovering(source)
+}
+
 /// Convenience function to parse an expression
 pub fn parse_expr(source: &str) -> ParseResult<Expr> {
     SyntonParser::new().parse_expr(source)
@@ -278,4 +361,730 @@ mod tests {
         let result = parse_module(source);
         assert!(result.is_ok());
     }
+
+    fn match_arms(source: &str) -> Vec<synton_ast::MatchArm> {
+        let expr = parse_expr(source).expect("match should parse");
+        match expr.kind {
+            synton_ast::ExprKind::Match { arms, .. } => arms,
+            other => panic!("Expected Match expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_maybe() {
+        let arms = match_arms("(match x (some v) => v (none) => 0)");
+        assert_eq!(arms.len(), 2);
+        match &arms[0].pattern {
+            synton_ast::Pattern::Some(inner) => {
+                assert!(matches!(inner.as_ref(), synton_ast::Pattern::Bind(name) if name == "v"));
+            }
+            other => panic!("Expected Some pattern, got {:?}", other),
+        }
+        assert!(matches!(arms[1].pattern, synton_ast::Pattern::None));
+    }
+
+    #[test]
+    fn test_match_bare_none() {
+        let arms = match_arms("(match x none => 0 _ => 1)");
+        assert!(matches!(arms[0].pattern, synton_ast::Pattern::None));
+    }
+
+    #[test]
+    fn test_match_wildcard() {
+        let arms = match_arms("(match x _ => 1)");
+        assert!(matches!(arms[0].pattern, synton_ast::Pattern::Wildcard));
+    }
+
+    #[test]
+    fn test_match_literal() {
+        let arms = match_arms("(match x 0 => 1 -1 => 2 true => 3 'c' => 4 \"s\" => 5 _ => 6)");
+        assert!(matches!(arms[0].pattern, synton_ast::Pattern::Literal(synton_ast::Literal::Integer(0))));
+        assert!(matches!(arms[1].pattern, synton_ast::Pattern::Literal(synton_ast::Literal::Integer(-1))));
+        assert!(matches!(arms[2].pattern, synton_ast::Pattern::Literal(synton_ast::Literal::Bool(true))));
+        assert!(matches!(arms[3].pattern, synton_ast::Pattern::Literal(synton_ast::Literal::Char('c'))));
+        assert!(matches!(&arms[4].pattern, synton_ast::Pattern::Literal(synton_ast::Literal::String(s)) if s == "s"));
+    }
+
+    #[test]
+    fn test_match_bind() {
+        let arms = match_arms("(match x n => (+ n 1))");
+        assert!(matches!(&arms[0].pattern, synton_ast::Pattern::Bind(name) if name == "n"));
+    }
+
+    #[test]
+    fn test_match_or() {
+        let arms = match_arms("(match x (| 1 2 3) => true _ => false)");
+        match &arms[0].pattern {
+            synton_ast::Pattern::Or(alts) => assert_eq!(alts.len(), 3),
+            other => panic!("Expected Or pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_struct() {
+        let arms = match_arms("(match p (Point x: 0 y: y) => y)");
+        match &arms[0].pattern {
+            synton_ast::Pattern::Struct { ty, fields } => {
+                assert_eq!(ty, "Point");
+                assert_eq!(fields.len(), 2);
+                assert_eq!(fields[0].0, "x");
+                assert!(matches!(&fields[1].1, synton_ast::Pattern::Bind(name) if name == "y"));
+            }
+            other => panic!("Expected Struct pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_variant() {
+        let arms = match_arms("(match o (Just (some v)) => v (Point) => 0)");
+        match &arms[0].pattern {
+            synton_ast::Pattern::Variant { name, args } => {
+                assert_eq!(name, "Just");
+                assert!(matches!(args.as_slice(), [synton_ast::Pattern::Some(_)]));
+            }
+            other => panic!("Expected Variant pattern, got {:?}", other),
+        }
+        // Without fields it is a struct pattern until names are resolved
+        assert!(matches!(&arms[1].pattern, synton_ast::Pattern::Struct { fields, .. } if fields.is_empty()));
+    }
+
+    #[test]
+    fn test_match_tuple() {
+        let arms = match_arms("(match t (a, _, 3) => a)");
+        match &arms[0].pattern {
+            synton_ast::Pattern::Tuple(items) => {
+                assert_eq!(items.len(), 3);
+                assert!(matches!(items[1], synton_ast::Pattern::Wildcard));
+            }
+            other => panic!("Expected Tuple pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_slice() {
+        let arms = match_arms("(match xs [] => 0 [head _] => head)");
+        assert!(matches!(&arms[0].pattern, synton_ast::Pattern::Slice(items) if items.is_empty()));
+        assert!(matches!(&arms[1].pattern, synton_ast::Pattern::Slice(items) if items.len() == 2));
+    }
+
+    #[test]
+    fn test_match_guard() {
+        let arms = match_arms("(match x (some v) if (> v 0) => v _ => 0)");
+        assert!(arms[0].guard.is_some());
+        assert!(arms[1].guard.is_none());
+    }
+
+    #[test]
+    fn test_match_nested_patterns() {
+        let arms = match_arms("(match x (some (| (a, 1) (some [b]))) => 1 _ => 0)");
+        match &arms[0].pattern {
+            synton_ast::Pattern::Some(inner) => match inner.as_ref() {
+                synton_ast::Pattern::Or(alts) => {
+                    assert!(matches!(alts[0], synton_ast::Pattern::Tuple(_)));
+                    assert!(matches!(&alts[1], synton_ast::Pattern::Some(p) if matches!(p.as_ref(), synton_ast::Pattern::Slice(_))));
+                }
+                other => panic!("Expected Or pattern, got {:?}", other),
+            },
+            other => panic!("Expected Some pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_requires_arm() {
+        assert!(parse_expr("(match x)").is_err());
+    }
+
+    fn expr_kind(source: &str) -> synton_ast::ExprKind {
+        match parse_expr(source) {
+            Ok(expr) => expr.kind,
+            Err(e) => panic!("failed to parse {:?}: {:?}", source, e),
+        }
+    }
+
+    #[test]
+    fn test_scalar_literals() {
+        use synton_ast::{BuiltinType, ExprKind, Literal};
+        assert!(matches!(expr_kind("3.5"), ExprKind::Literal(Literal::Float(f)) if f == 3.5));
+        assert!(matches!(expr_kind("\"hi\""), ExprKind::Literal(Literal::String(s)) if s == "hi"));
+        assert!(matches!(expr_kind("'x'"), ExprKind::Literal(Literal::Char('x'))));
+        assert!(matches!(expr_kind("b\"ab\""), ExprKind::Literal(Literal::Bytes(b)) if b == b"ab"));
+        assert!(matches!(expr_kind("()"), ExprKind::Literal(Literal::Unit)));
+        assert!(matches!(expr_kind("-7"), ExprKind::Literal(Literal::Integer(-7))));
+        assert!(matches!(expr_kind("42u8"), ExprKind::Literal(Literal::TypedInteger(42, BuiltinType::U8))));
+        assert!(matches!(expr_kind("3.0f32"), ExprKind::Literal(Literal::TypedFloat(f, BuiltinType::F32)) if f == 3.0));
+    }
+
+    #[test]
+    fn test_source_spans() {
+        use synton_ast::{ExprKind, StmtKind};
+        let src = "// add\n(let f = (fn [a b: i32] (+ a b)))\n(match (f 1 2) 3 => true _ => false)";
+        let module = parse_module(src).unwrap();
+        let text = |span: &synton_ast::Span| &src[span.range()];
+
+        assert_eq!(text(&module.stmts[0].span), "(let f = (fn [a b: i32] (+ a b)))");
+        assert_eq!((module.stmts[0].span.start.line, module.stmts[0].span.start.column), (2, 1));
+        let StmtKind::Let { init: Some(lambda), .. } = &module.stmts[0].kind else {
+            panic!("Expected a let, got {:?}", module.stmts[0].kind);
+        };
+        let ExprKind::Lambda { params, body, .. } = &lambda.kind else {
+            panic!("Expected a lambda, got {:?}", lambda.kind);
+        };
+        assert_eq!(text(&lambda.span), "(fn [a b: i32] (+ a b))");
+        assert_eq!(text(&params[1].span), "b: i32");
+        assert_eq!(text(&params[1].ty.as_ref().unwrap().span), "i32");
+        assert_eq!(text(&body.span), "(+ a b)");
+
+        let StmtKind::Expr(expr) = &module.stmts[1].kind else {
+            panic!("Expected an expression, got {:?}", module.stmts[1].kind);
+        };
+        let ExprKind::Match { scrutinee, arms } = &expr.kind else {
+            panic!("Expected a match, got {:?}", expr.kind);
+        };
+        assert_eq!(text(&scrutinee.span), "(f 1 2)");
+        assert_eq!(arms.iter().map(|arm| text(&arm.span)).collect::<Vec<_>>(), ["3", "_"]);
+        assert_eq!(text(&arms[1].body.span), "false");
+        assert_eq!(arms[1].body.span.start.line, 3);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        use synton_ast::{CompareOp, ExprKind};
+        for (src, expected) in [
+            ("(== a b)", CompareOp::Eq),
+            ("(!= a b)", CompareOp::NotEq),
+            ("(< a b)", CompareOp::Less),
+            ("(<= a b)", CompareOp::LessEq),
+            ("(> a b)", CompareOp::Greater),
+            ("(>= a b)", CompareOp::GreaterEq),
+        ] {
+            assert!(matches!(expr_kind(src), ExprKind::Compare { op, .. } if op == expected), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_unary_ops() {
+        use synton_ast::{ExprKind, UnaryOp};
+        for (src, expected) in [
+            ("(! ok)", UnaryOp::Not),
+            ("(~ bits)", UnaryOp::BitNot),
+            ("(- x)", UnaryOp::Neg),
+            ("(& x)", UnaryOp::Ref),
+            ("(* p)", UnaryOp::Deref),
+        ] {
+            assert!(matches!(expr_kind(src), ExprKind::Unary { op, .. } if op == expected), "{}", src);
+        }
+        assert!(parse_expr("(! a b)").is_err());
+    }
+
+    #[test]
+    fn test_calls() {
+        use synton_ast::ExprKind;
+        for src in ["($ f 1 2)", "(call:f 1 2)", "(f 1 2)"] {
+            match expr_kind(src) {
+                ExprKind::Call { callee, args } => {
+                    assert!(matches!(callee.kind, ExprKind::Var { ref name, .. } if name == "f"));
+                    assert_eq!(args.len(), 2);
+                }
+                other => panic!("Expected Call for {}, got {:?}", src, other),
+            }
+        }
+        assert!(matches!(expr_kind("($ now)"), ExprKind::Call { args, .. } if args.is_empty()));
+    }
+
+    #[test]
+    fn test_accessors() {
+        use synton_ast::ExprKind;
+        assert!(matches!(expr_kind("(.call xs push 1)"), ExprKind::MethodCall { method, args, .. } if method == "push" && args.len() == 1));
+        assert!(matches!(expr_kind("(.get xs 0)"), ExprKind::Index { .. }));
+        assert!(matches!(expr_kind("(.field p x)"), ExprKind::Field { name, .. } if name == "x"));
+    }
+
+    #[test]
+    fn test_lambda() {
+        use synton_ast::ExprKind;
+        match expr_kind("(fn [x y: i32] (+ x y))") {
+            ExprKind::Lambda { params, .. } => {
+                assert_eq!(params.len(), 2);
+                assert!(params[0].ty.is_none());
+                assert!(params[1].ty.is_some());
+            }
+            other => panic!("Expected Lambda, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_aggregates() {
+        use synton_ast::ExprKind;
+        assert!(matches!(expr_kind("[1 2 3]"), ExprKind::Array(items) if items.len() == 3));
+        assert!(matches!(expr_kind("[1, 2]"), ExprKind::Array(items) if items.len() == 2));
+        assert!(matches!(expr_kind("(1, x, (+ 1 2))"), ExprKind::Tuple(items) if items.len() == 3));
+        assert!(matches!(expr_kind("(Point x: 1 y: 2)"), ExprKind::Struct { ty, fields } if ty == "Point" && fields.len() == 2));
+    }
+
+    #[test]
+    fn test_maybe_constructors() {
+        use synton_ast::ExprKind;
+        assert!(matches!(expr_kind("(some 1)"), ExprKind::Some(_)));
+        assert!(matches!(expr_kind("none"), ExprKind::None));
+        assert!(matches!(expr_kind("(none)"), ExprKind::None));
+    }
+
+    #[test]
+    fn test_casts() {
+        use synton_ast::{BuiltinType, ExprKind, TypeKind};
+        assert!(matches!(expr_kind("(as x i64)"), ExprKind::As { ty, .. } if ty.kind == TypeKind::Builtin(BuiltinType::I64)));
+        assert!(matches!(expr_kind("(size_of u8)"), ExprKind::SizeOf(ty) if ty.kind == TypeKind::Builtin(BuiltinType::U8)));
+    }
+
+    #[test]
+    fn test_control_flow_exprs() {
+        use synton_ast::ExprKind;
+        assert!(matches!(expr_kind("(? c 1 2)"), ExprKind::If { else_branch: Some(_), .. }));
+        assert!(matches!(expr_kind("(loop (break 1))"), ExprKind::Loop { .. }));
+        assert!(matches!(expr_kind("(break)"), ExprKind::Break(None)));
+        assert!(matches!(expr_kind("(continue)"), ExprKind::Continue));
+        assert!(matches!(expr_kind("(return x)"), ExprKind::Return(Some(_))));
+    }
+
+    #[test]
+    fn test_block_expr() {
+        use synton_ast::ExprKind;
+        match expr_kind("{ (let x = 1) (+ x 1) }") {
+            ExprKind::Block(stmts, tail) => {
+                assert_eq!(stmts.len(), 1);
+                assert!(tail.is_some());
+            }
+            other => panic!("Expected Block, got {:?}", other),
+        }
+        assert!(matches!(expr_kind("{ (f x) ; }"), ExprKind::Block(_, None)));
+    }
+
+    #[test]
+    fn test_parse_types() {
+        use synton_ast::{BuiltinType, TypeKind};
+        let ty = |src: &str| parse_type(src).unwrap_or_else(|e| panic!("{}: {:?}", src, e)).kind;
+        assert_eq!(ty("i32"), TypeKind::Builtin(BuiltinType::I32));
+        assert_eq!(ty("u8"), TypeKind::Builtin(BuiltinType::U8));
+        assert!(matches!(ty("list<i32>"), TypeKind::List(_)));
+        assert!(matches!(ty("list<list<i32>>"), TypeKind::List(inner) if matches!(inner.kind, TypeKind::List(_))));
+        assert!(matches!(ty("result<i32, string>"), TypeKind::Result { .. }));
+        assert!(matches!(ty("maybe<list<maybe<bool>>>"), TypeKind::Maybe(_)));
+        assert!(matches!(ty("?i32"), TypeKind::Maybe(_)));
+        assert!(matches!(ty("&i32"), TypeKind::Ref(_)));
+        assert!(matches!(ty("(i32, bool)"), TypeKind::Tuple(items) if items.len() == 2));
+        assert!(matches!(ty("(i32, bool) -> i32"), TypeKind::Fn { params, .. } if params.len() == 2));
+        assert_eq!(ty("()"), TypeKind::Unit);
+        assert_eq!(ty("T"), TypeKind::Var("T".to_string()));
+        assert!(parse_type("list<i32, i32>").is_err());
+    }
+
+    #[test]
+    fn test_import_forms() {
+        use synton_ast::ImportKind;
+
+        let module = parse_module(
+            "import hash:sha256:e3b0c442 as util\n\
+             (import sem:\"sort algorithm fast\" as sort)\n\
+             import \"std/list\"",
+        ).unwrap();
+        assert_eq!(module.imports.len(), 3);
+
+        let hash = &module.imports[0];
+        assert!(matches!(&hash.kind, ImportKind::Hash { algorithm, hash }
+            if algorithm == "sha256" && hash == "e3b0c442"));
+        assert_eq!(hash.alias.as_deref(), Some("util"));
+
+        let semantic = &module.imports[1];
+        assert!(matches!(&semantic.kind, ImportKind::Semantic { query } if query == "sort algorithm fast"));
+        assert_eq!(semantic.alias.as_deref(), Some("sort"));
+
+        let path = &module.imports[2];
+        assert!(matches!(&path.kind, ImportKind::Path { path } if path == "std/list"));
+        assert_eq!(path.name, "list");
+        assert_eq!(path.alias, None);
+    }
+
+    #[test]
+    fn test_exports_and_stmts() {
+        let module = parse_module("(export add sub as minus)\nexport mul\n(+ 1 2)").unwrap();
+        let exports: Vec<_> = module.exports.iter()
+            .map(|e| (e.name.as_str(), e.alias.as_deref()))
+            .collect();
+        assert_eq!(exports, vec![("add", None), ("sub", Some("minus")), ("mul", None)]);
+        assert_eq!(module.stmts.len(), 1);
+        assert!(parse_module("(export)").is_err());
+    }
+
+    #[test]
+    fn test_type_declarations() {
+        use synton_ast::StmtKind;
+        let src = "(struct Pair<A, B> first: A second: list<B>)\n(enum Opt<T> (Just T) Nothing)";
+        let module = parse_module(src).unwrap();
+
+        let StmtKind::StructDecl(pair) = &module.stmts[0].kind else {
+            panic!("Expected a struct, got {:?}", module.stmts[0].kind);
+        };
+        assert_eq!((pair.name.as_str(), &pair.params[..]), ("Pair", &["A".to_string(), "B".to_string()][..]));
+        let fields: Vec<_> = pair.fields.iter().map(|f| (f.name.as_str(), f.ty.to_string())).collect();
+        assert_eq!(fields, vec![("first", "A".to_string()), ("second", "list<B>".to_string())]);
+
+        let StmtKind::EnumDecl(opt) = &module.stmts[1].kind else {
+            panic!("Expected an enum, got {:?}", module.stmts[1].kind);
+        };
+        assert_eq!((opt.name.as_str(), &opt.params[..]), ("Opt", &["T".to_string()][..]));
+        let variants: Vec<_> = opt.variants.iter().map(|v| (v.name.as_str(), v.types.len())).collect();
+        assert_eq!(variants, vec![("Just", 1), ("Nothing", 0)]);
+
+        assert!(parse_module("(struct Empty)").is_ok());
+        assert!(parse_module("(struct Pair<> first: i32)").is_err());
+        assert!(parse_module("(struct Pair first second: i32)").is_err());
+    }
+
+    #[test]
+    fn test_fn_declarations() {
+        use synton_ast::{ContractKind, Effect, Effects, ExprKind, StmtKind};
+        let src = "(fn abs [n: i32] -> i32 ! pure @pre((> n -100)) (if (< n 0) (- 0 n) n))\n\
+                   (fn log [s] ! io, host)\n(fn id [x] x)";
+        let module = parse_module(src).unwrap();
+        let decls: Vec<_> = module.stmts.iter().map(|stmt| match &stmt.kind {
+            StmtKind::FnDecl(decl) => decl,
+            kind => panic!("Expected a function, got {:?}", kind),
+        }).collect();
+
+        let abs = decls[0];
+        assert_eq!(abs.name, "abs");
+        assert_eq!(&src[abs.span.range()], src.lines().next().unwrap());
+        assert_eq!(abs.params[0].ty.as_ref().unwrap().to_string(), "i32");
+        assert_eq!(abs.ret_type.as_ref().unwrap().to_string(), "i32");
+        assert_eq!(abs.effects, Some(Effects::PURE));
+        assert_eq!(abs.contracts.len(), 1);
+        assert_eq!(abs.contracts[0].kind, ContractKind::Pre);
+        assert!(matches!(abs.body.as_deref().map(|body| &body.kind), Some(ExprKind::If { .. })));
+
+        // Without a body only the signature is declared
+        let log = decls[1];
+        assert!(log.body.is_none() && log.ret_type.is_none());
+        assert_eq!(log.effects, Some(Effects::from(Effect::Io).union(Effects::from(Effect::Host))));
+        assert!(decls[2].effects.is_none() && decls[2].body.is_some());
+
+        // A lambda is still an expression
+        assert!(matches!(&parse_module("(fn [x] x)").unwrap().stmts[0].kind, StmtKind::Expr(_)));
+        assert!(parse_module("(fn f [x] -> i32 x x)").is_err());
+        assert!(parse_module("(fn f [x] @when((> x 0)) x)").is_err());
+    }
+
+    #[test]
+    fn test_module_hash() {
+        let a = parse_module("(let x = 1)\n(+ x 2)").unwrap();
+        let b = parse_module("  (let x = 1) // binding\n/* sum */ (+ x   2)").unwrap();
+        let c = parse_module("(let x = 1)\n(+ x 3)").unwrap();
+
+        assert_eq!(a.id.as_str().len(), 64);
+        assert_eq!(a.id, b.id);
+        assert_ne!(a.id, c.id);
+    }
+
+    #[test]
+    fn test_recovery_reports_all_errors() {
+        use synton_ast::StmtKind;
+
+        let src = "(let x = 1) (+ 1 2 3) (let y = 2) (foo ]) (+ x y)";
+        let out = parse_module_recovering(src);
+        assert_eq!(out.errors.len(), 2);
+
+        match &out.errors[0] {
+            ParseError::UnexpectedToken { expected, found, span } => {
+                assert_eq!(expected, &vec!["`)`".to_string()]);
+                assert_eq!(found, "3");
+                assert_eq!(&src[span.clone()], "3");
+            }
+            other => panic!("Expected UnexpectedToken, got {:?}", other),
+        }
+        assert_eq!(&src[out.errors[1].span()], "]");
+
+        let module = out.output.expect("partial module");
+        let kinds: Vec<_> = module.stmts.iter()
+            .map(|s| matches!(s.kind, StmtKind::Error))
+            .collect();
+        assert_eq!(kinds, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_recovery_nested_error_expr() {
+        use synton_ast::{ExprKind, StmtKind};
+
+        let out = parse_module_recovering("(let x = (+ 1 2 3)) (+ 1 2)");
+        assert_eq!(out.errors.len(), 1);
+
+        let module = out.output.unwrap();
+        assert_eq!(module.stmts.len(), 2);
+        match &module.stmts[0].kind {
+            StmtKind::Let { init: Some(init), .. } => assert!(matches!(init.kind, ExprKind::Error)),
+            other => panic!("Expected Let, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let out = parse_module_recovering("(+ 1 2");
+        match &out.errors[..] {
+            [ParseError::UnexpectedEof { expected, span }] => {
+                assert_eq!(expected, &vec!["`)`".to_string()]);
+                assert_eq!(span, &(6..6));
+            }
+            other => panic!("Expected a single UnexpectedEof, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recovery_disabled() {
+        let parser = SyntonParser::with_config(ParserConfig {
+            recovery: false,
+            ..ParserConfig::default()
+        });
+        let out = parser.parse_module_recovering("(+ 1 2 3) (foo ])");
+        assert!(out.output.is_none());
+        assert_eq!(out.errors.len(), 1);
+
+        let err = parse_module("(+ 1 2 3)").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected token `3`, expected `)`");
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |depth: usize| format!("{}1{}", "(- ".repeat(depth), ")".repeat(depth));
+
+        assert!(parse_expr(&nested(200)).is_ok());
+
+        let src = nested(300);
+        match parse_module(&src) {
+            Err(ParseError::RecursionLimit { max_depth, span }) => {
+                assert_eq!(max_depth, 256);
+                assert_eq!(span, 3 * 256..3 * 256 + 1);
+            }
+            other => panic!("Expected RecursionLimit, got {:?}", other),
+        }
+
+        let parser = SyntonParser::with_config(ParserConfig {
+            max_depth: 4,
+            ..ParserConfig::default()
+        });
+        assert!(parser.parse_type("list<list<i32>>").is_ok());
+        assert!(matches!(
+            parser.parse_type("(?????i32)"),
+            Err(ParseError::RecursionLimit { max_depth: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_literal_escapes() {
+        use synton_ast::{ExprKind, Literal};
+        assert!(matches!(
+            expr_kind(r#""a\tb\n\"q\" \\ \u{e9}\x41""#),
+            ExprKind::Literal(Literal::String(s)) if s == "a\tb\n\"q\" \\ \u{e9}A"
+        ));
+        assert!(matches!(expr_kind(r"'\n'"), ExprKind::Literal(Literal::Char('\n'))));
+        assert!(matches!(expr_kind(r"'\''"), ExprKind::Literal(Literal::Char('\''))));
+        assert!(matches!(expr_kind(r"'\u{1F600}'"), ExprKind::Literal(Literal::Char('\u{1F600}'))));
+        assert!(matches!(
+            expr_kind(r#"b"\xff\0\"""#),
+            ExprKind::Literal(Literal::Bytes(b)) if b == b"\xff\0\""
+        ));
+    }
+
+    #[test]
+    fn test_lex_errors() {
+        use synton_lexer::LexError;
+
+        let bad_escape = |src: &str| match parse_expr(src) {
+            Err(ParseError::LexError(LexError::InvalidEscape { seq, span })) => {
+                assert_eq!(&src[span], seq);
+                seq
+            }
+            other => panic!("Expected InvalidEscape for {:?}, got {:?}", src, other),
+        };
+        assert_eq!(bad_escape(r#""ok \q""#), r"\q");
+        assert_eq!(bad_escape(r#""\x80""#), r"\x80");
+        assert_eq!(bad_escape(r#""\u{110000}""#), r"\u{110000}");
+        assert_eq!(bad_escape(r#"b"\u{41}""#), r"\u");
+
+        assert!(matches!(
+            parse_expr("'ab'"),
+            Err(ParseError::LexError(LexError::InvalidChar { span, .. })) if span == (0..4)
+        ));
+    }
+
+    #[test]
+    fn test_lex_error_recovery() {
+        use synton_ast::StmtKind;
+        use synton_lexer::LexError;
+
+        let src = "(let x = 1) (f x §§) (let y = 2)\n\"oops\n(+ x y)";
+        let out = parse_module_recovering(src);
+        let spans: Vec<_> = out.errors.iter().map(|e| &src[e.span()]).collect();
+        assert_eq!(spans, ["§§", "\"oops"]);
+        assert!(matches!(out.errors[0], ParseError::LexError(LexError::InvalidToken { .. })));
+        assert!(matches!(out.errors[1], ParseError::LexError(LexError::UnterminatedString { .. })));
+
+        // The statements around the bad tokens still parse
+        let module = out.output.expect("partial module");
+        let lets = module.stmts.iter()
+            .filter(|s| matches!(s.kind, StmtKind::Let { .. }))
+            .count();
+        assert_eq!(lets, 2);
+        assert!(matches!(module.stmts.last().map(|s| &s.kind), Some(StmtKind::Expr(_))));
+    }
+
+    #[test]
+    fn test_cst_roundtrip() {
+        let sources = [
+            "// header\n(import \"std/io\")\n\n(let x = 1) // one\n/* block\n comment */ (+ x  2)\n",
+            "(f [1 2] {3})",
+            "(let s = \"oops\n§ (g ]) (h",
+            "",
+            "  // only trivia",
+        ];
+        for src in sources {
+            assert_eq!(cst::Cst::parse(src).to_string(), src);
+        }
+    }
+
+    #[test]
+    fn test_cst_trivia_and_groups() {
+        use cst::{CstElement, Delimiter, TriviaKind};
+
+        let src = "// lead\n(let x = 1) // trailing\n(f [1 2]\n";
+        let (out, tree) = SyntonParser::new().parse_module_lossless(src);
+        assert!(out.has_errors());
+        assert_eq!(tree.elements.len(), 2);
+
+        let CstElement::Node(first) = &tree.elements[0] else {
+            panic!("Expected a group, got {:?}", tree.elements[0]);
+        };
+        assert_eq!(first.delimiter, Delimiter::Paren);
+        assert_eq!(first.open.leading[0].kind, TriviaKind::LineComment);
+        let close = first.close.as_ref().expect("closed group");
+        assert!(close.trailing.iter().any(|t| t.text == "// trailing"));
+
+        // An unclosed group runs to the end of the file
+        let CstElement::Node(second) = &tree.elements[1] else {
+            panic!("Expected a group, got {:?}", tree.elements[1]);
+        };
+        assert!(second.close.is_none());
+        assert!(matches!(&second.children[1], CstElement::Node(n) if n.delimiter == Delimiter::Bracket));
+        assert_eq!(tree.tokens().len(), 12);
+    }
+
+    fn assert_formats(src: &str, config: &format::FormatConfig) -> String {
+        let formatted = format::format_source(src, config).expect("formats");
+        let (before, after) = (parse_module(src).expect("parses"), parse_module(&formatted).expect("reparses"));
+        let changes = synton_ast::diff::diff(&before, &after);
+        assert!(changes.is_empty(), "formatting changed the module:\n{}\n{}", formatted, changes);
+        assert_eq!(format::format_source(&formatted, config).expect("formats"), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_format_layout() {
+        let config = format::FormatConfig::default();
+        assert_eq!(assert_formats("  (let   x =\n 1)(+ x\t2)", &config), "(let x = 1)\n(+ x 2)\n");
+        assert_eq!(
+            assert_formats("import \"std/io\" as io\n\n\n(f [1 2]   {3})", &config),
+            "import \"std/io\" as io\n\n(f [1 2] {3})\n"
+        );
+
+        let narrow = format::FormatConfig { line_width: 20, ..config };
+        assert_eq!(
+            assert_formats("(match value 0 => (f 1 2 3) 1 => 2 _ => 3)", &narrow),
+            "(match value\n  0 => (f 1 2 3)\n  1 => 2\n  _ => 3)\n"
+        );
+        assert_eq!(
+            assert_formats("(let total = (f first second third))", &narrow),
+            "(let total = (f first\n               second\n               third))\n"
+        );
+    }
+
+    #[test]
+    fn test_format_annotations_and_accessors() {
+        let config = format::FormatConfig::default();
+        assert_eq!(assert_formats("(let f = (fn [x : i32 y] x))", &config), "(let f = (fn [x: i32 y] x))\n");
+        assert_eq!(assert_formats("(let n :i64 = 1)", &config), "(let n: i64 = 1)\n");
+        assert_eq!(assert_formats("(call : f 1 2)", &config), "(call:f 1 2)\n");
+        assert_eq!(assert_formats("(. call xs push 1)", &config), "(.call xs push 1)\n");
+        assert_eq!(assert_formats("(.get (.field p items) 0)", &config), "(.get (.field p items) 0)\n");
+
+        let narrow = format::FormatConfig { line_width: 16, ..config };
+        assert_eq!(assert_formats("(.call items push first)", &narrow), "(.call items\n  push\n  first)\n");
+        assert_eq!(assert_formats("(call:f first second)", &narrow), "(call:f first\n  second)\n");
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let config = format::FormatConfig::default();
+        let src = "// header\n\n(let x = 1) // one\n(f x /* inline */ 2\n  // before close\n)\n// end\n";
+        let formatted = assert_formats(src, &config);
+        assert_eq!(
+            formatted,
+            "// header\n\n(let x = 1) // one\n(f x /* inline */\n  2\n  // before close\n)\n// end\n"
+        );
+
+        assert_eq!(assert_formats("(f ( // empty\n))", &config), "(f ( // empty\n   ))\n");
+
+        let src = "(match x // subject\n 0 => 1 _ => 2)";
+        assert_eq!(assert_formats(src, &config), "(match x // subject\n  0 => 1\n  _ => 2)\n");
+    }
+
+    #[test]
+    fn test_format_rejects_errors() {
+        let config = format::FormatConfig::default();
+        assert!(format::format_source("(let x = 1", &config).is_err());
+        assert_eq!(format::format_source("", &config).unwrap(), "");
+    }
+
+    #[test]
+    fn test_compact_printing() {
+        use compact::{compact_source, CompactConfig};
+
+        let src = "// sum\n(let total = (- 10 -1))\n(let add = (fn [a b: i32] (+ a b)))\n(print \"a b\" 'c')\n";
+        let out = compact_source(src, &CompactConfig::default()).unwrap();
+        assert_eq!(out.text, "(let total=(- 10-1))(let add=(fn[a b:i32](+a b)))(print\"a b\"'c')");
+        assert!(out.names.is_empty());
+        assert!(synton_ast::diff::diff(&parse_module(src).unwrap(), &parse_module(&out.text).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_compact_renaming() {
+        use compact::{compact_source, restore_names, CompactConfig, NameMap};
+
+        let config = CompactConfig { rename_locals: true };
+        let src = "(let total = 1) (let %3 = 2)\n\
+                   (let add = (fn [total x] (+ total x)))\n\
+                   (match total n => (add n (.field p x)) _ => 0)";
+        let out = compact_source(src, &config).unwrap();
+
+        // `x` is also a field name, `p` is never bound and `%3` is taken
+        assert_eq!(
+            out.text,
+            "(let%4=1)(let%3=2)(let%5=(fn[%4x](+%4x)))(match%4%6=>(%5%6(.field p x))_=>0)"
+        );
+        let names: Vec<_> = out.names.iter().collect();
+        assert_eq!(names, [("%4", "total"), ("%5", "add"), ("%6", "n")]);
+        assert!(parse_module(&out.text).is_ok());
+
+        let map = NameMap::parse(&out.names.to_string()).unwrap();
+        assert_eq!(map, out.names);
+        let plain = compact_source(src, &CompactConfig::default()).unwrap();
+        assert_eq!(restore_names(&out.text, &map).unwrap(), plain.text);
+        assert!(NameMap::parse("%1").is_err());
+    }
+
+    #[test]
+    fn test_token_counters() {
+        use compact::{CharTokenCounter, LexTokenCounter, TokenCounter};
+
+        assert_eq!(LexTokenCounter.count("(+ 1 2) // c"), 5);
+        assert_eq!(CharTokenCounter::default().count("(+ 1 2)"), 2);
+        let words = |text: &str| text.split_whitespace().count();
+        assert_eq!(words.count("(+ 1 2)"), 3);
+    }
 }
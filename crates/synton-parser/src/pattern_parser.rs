@@ -0,0 +1,117 @@
+//! Pattern parser for Synton `match` arms
+
+use chumsky::prelude::*;
+use synton_lexer::{Token, TokenKind};
+use synton_ast::Pattern;
+
+use super::expr_parser::literal_parser;
+use super::grammar::{ident, keyword, token, Extra};
+
+/// Pattern parser
+///
+/// Supported forms:
+/// - `_` wildcard
+/// - literals: `42`, `-1`, `true`, `'c'`, `"text"`
+/// - bindings: `x`
+/// - `(some p)`, `(none)` or bare `none`
+/// - or-patterns: `(| p1 p2 ...)`
+/// - tuples: `(p1, p2, ...)`
+/// - slices: `[p1 p2 ...]`
+/// - structs: `(Point x: p1 y: p2)`
+/// - enum variants: `(Just p1 ...)`; without fields, a bare name
+pub fn pattern_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Pattern, Extra<'a>> + Clone + 'a {
+    recursive(|pattern| {
+        let lparen = token(Token::LParen);
+        let rparen = token(Token::RParen);
+
+        // Literal pattern; negative numbers such as `-1` are single tokens
+        let literal = literal_parser()
+            .map(Pattern::Literal)
+            .boxed();
+
+        // Identifiers: `_` is a wildcard, `none` the empty maybe, anything else binds
+        let binding = ident()
+            .map(|name| match name.as_str() {
+                "_" => Pattern::Wildcard,
+                "none" => Pattern::None,
+                _ => Pattern::Bind(name),
+            })
+            .boxed();
+
+        // (some p)
+        let some = lparen
+            .ignore_then(keyword("some"))
+            .ignore_then(pattern.clone())
+            .then_ignore(rparen)
+            .map(|inner| Pattern::Some(Box::new(inner)))
+            .boxed();
+
+        // (none)
+        let none = lparen
+            .ignore_then(keyword("none"))
+            .then_ignore(rparen)
+            .to(Pattern::None)
+            .boxed();
+
+        // (| p1 p2 ...)
+        let or = lparen
+            .ignore_then(token(Token::Pipe))
+            .ignore_then(pattern.clone().repeated().at_least(2).collect::<Vec<_>>())
+            .then_ignore(rparen)
+            .map(Pattern::Or)
+            .boxed();
+
+        // (p1, p2, ...) - at least one comma is required
+        let comma = token(Token::Comma);
+        let tuple = lparen
+            .ignore_then(
+                pattern.clone()
+                    .then_ignore(comma)
+                    .then(pattern.clone().separated_by(comma).allow_trailing().collect::<Vec<_>>())
+            )
+            .then_ignore(rparen)
+            .map(|(first, rest)| {
+                let mut items = vec![first];
+                items.extend(rest);
+                Pattern::Tuple(items)
+            })
+            .boxed();
+
+        // [p1 p2 ...]
+        let slice = token(Token::LBracket)
+            .ignore_then(pattern.clone().repeated().collect::<Vec<_>>())
+            .then_ignore(token(Token::RBracket))
+            .map(Pattern::Slice)
+            .boxed();
+
+        // (Name field: p ...)
+        let field = ident()
+            .then_ignore(token(Token::Colon))
+            .then(pattern.clone());
+        let structure = lparen
+            .ignore_then(ident())
+            .then(field.repeated().collect::<Vec<_>>())
+            .then_ignore(rparen)
+            .map(|(ty, fields)| Pattern::Struct { ty, fields })
+            .boxed();
+
+        // (Name p1 p2 ...)
+        let variant = lparen
+            .ignore_then(ident())
+            .then(pattern.clone().repeated().at_least(1).collect::<Vec<_>>())
+            .then_ignore(rparen)
+            .map(|(name, args)| Pattern::Variant { name, args })
+            .boxed();
+
+        some
+            .or(none)
+            .or(or)
+            .or(tuple)
+            .or(structure)
+            .or(variant)
+            .or(slice)
+            .or(literal)
+            .or(binding)
+            .boxed()
+    })
+}
@@ -2,165 +2,289 @@
 
 use chumsky::prelude::*;
 use synton_lexer::{Token, TokenKind};
-use synton_ast::{Stmt, StmtKind, Span, Position, Expr};
+use synton_ast::{
+    AssignTarget, Contract, ContractKind, EnumDecl, EnumVariant, Expr, ExprKind, FnDecl, Stmt, StmtKind, StructDecl,
+    StructField, UnaryOp,
+};
 
-/// Statement parser
-pub fn stmt_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Stmt> + Clone + 'a {
-    // Import the expression parser
-    use super::expr_parser::expr_parser;
+use super::expr_parser::{effects_parser, expr_body, params_parser};
+use super::grammar::{ident, keyword, span_of, token, Extra};
+use super::type_parser::type_parser;
 
+/// Statement parser
+pub fn stmt_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Stmt, Extra<'a>> + Clone + 'a {
     recursive(|stmt| {
-        // Empty statement (semicolon)
-        let empty = select!(TokenKind { token: Token::Semi, span: _ })
-            .map(|_| Stmt::new(StmtKind::Empty, Span::new(Position::start(), Position::start())));
+        let expr = recursive(|expr| expr_body(expr, stmt.clone()));
+        stmt_body(expr, stmt)
+    })
+}
 
-        // Let binding: (let x [= expr])
-        // Simplified syntax for now
-        let let_stmt = select!(TokenKind { token: Token::LParen, span: _ })
+/// Statement grammar, parameterised over the recursive expression and
+/// statement parsers.
+pub(crate) fn stmt_body<'a, E, S>(expr: E, stmt: S) -> impl Parser<'a, &'a [TokenKind], Stmt, Extra<'a>> + Clone + 'a
+where
+    E: Parser<'a, &'a [TokenKind], Expr, Extra<'a>> + Clone + 'a,
+    S: Parser<'a, &'a [TokenKind], Stmt, Extra<'a>> + Clone + 'a,
+{
+    // Empty statement (semicolon)
+    let empty = token(Token::Semi)
+        .map_with(|_, e| Stmt::new(StmtKind::Empty, span_of(e)));
+
+    // Let binding: (let [mut] x [: type] [= expr])
+    let let_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwLet)
             .ignore_then(
-                select!(TokenKind { token: Token::KwLet, span: _ })
-                .ignore_then(
-                    // Variable name
-                    select!(TokenKind { token: Token::Identifier(s), .. } => s.clone())
-                    // Optional initialization
-                    .then(
-                        select!(TokenKind { token: Token::Eq, span: _ })
-                        .ignore_then(
-                            expr_parser()
-                        )
-                        .or_not()
+                // Variable name, which may itself be `mut`
+                keyword("mut")
+                .ignore_then(ident())
+                .map(|name| (name, true))
+                .or(ident().map(|name| (name, false)))
+                // Optional annotation
+                .then(
+                    token(Token::Colon)
+                    .ignore_then(type_parser())
+                    .or_not()
+                )
+                // Optional initialization
+                .then(
+                    token(Token::Eq)
+                    .ignore_then(
+                        expr.clone()
                     )
+                    .or_not()
                 )
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
             )
-            .map(|(name, init): (String, Option<Expr>)| {
-                Stmt::new(
-                    StmtKind::Let {
-                        name,
-                        id: None,
-                        ty: None,
-                        init: init.map(Box::new),
-                        mutable: false,
-                    },
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|(((name, mutable), ty), init), e| {
+            Stmt::new(
+                StmtKind::Let {
+                    name,
+                    id: None,
+                    ty,
+                    init: init.map(Box::new),
+                    mutable,
+                },
+                span_of(e),
+            )
+        })
+        .boxed();
 
-        // If/branch statement: (branch cond then_branch [else_branch])
-        let if_stmt = select!(TokenKind { token: Token::LParen, span: _ })
+    // Assignment: (set target value), where the target is a variable,
+    // (* ref), (.get base index) or (.field base name)
+    let set_stmt = token(Token::LParen)
+        .ignore_then(token(Token::KwSet))
+        .ignore_then(expr.clone())
+        .then(expr.clone())
+        .then_ignore(token(Token::RParen))
+        .try_map_with(|(target, value): (Expr, Expr), e| {
+            let target = match target.kind {
+                ExprKind::Var { name, .. } => AssignTarget::Var(name),
+                ExprKind::Unary { op: UnaryOp::Deref, arg } => AssignTarget::Deref(arg),
+                ExprKind::Index { base, index } => AssignTarget::Index { base, index },
+                ExprKind::Field { base, name } => AssignTarget::Field { base, name },
+                _ => return Err(Rich::custom(e.span(), "cannot assign to this expression")),
+            };
+            Ok(Stmt::new(
+                StmtKind::Assign { target, value: Box::new(value) },
+                span_of(e),
+            ))
+        })
+        .boxed();
+
+    // If/branch statement: (branch cond then_branch [else_branch])
+    let if_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwBranch)
+            .or(token(Token::KwIf))
             .ignore_then(
-                select!(TokenKind { token: Token::KwBranch, span: _ })
-                .or(select!(TokenKind { token: Token::KwIf, span: _ }))
-                .ignore_then(
-                    expr_parser()
-                    .then(stmt.clone())
-                    .then(
-                        stmt.clone().or_not()
-                    )
+                expr.clone()
+                .then(stmt.clone())
+                .then(
+                    stmt.clone().or_not()
                 )
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
             )
-            .map(|((cond, then_branch), else_branch): ((Expr, Stmt), Option<Stmt>)| {
-                Stmt::new(
-                    StmtKind::If {
-                        cond: Box::new(cond),
-                        then_branch: Box::new(then_branch),
-                        else_branch: else_branch.map(Box::new),
-                    },
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|((cond, then_branch), else_branch): ((Expr, Stmt), Option<Stmt>), e| {
+            Stmt::new(
+                StmtKind::If {
+                    cond: Box::new(cond),
+                    then_branch: Box::new(then_branch),
+                    else_branch: else_branch.map(Box::new),
+                },
+                span_of(e),
+            )
+        })
+        .boxed();
 
-        // While loop: (while cond body)
-        let while_stmt = select!(TokenKind { token: Token::LParen, span: _ })
+    // While loop: (while cond body)
+    let while_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwWhile)
             .ignore_then(
-                select!(TokenKind { token: Token::KwWhile, span: _ })
-                .ignore_then(
-                    expr_parser()
-                    .then(stmt.clone())
-                )
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
+                expr.clone()
+                .then(stmt.clone())
             )
-            .map(|(cond, body): (Expr, Stmt)| {
-                Stmt::new(
-                    StmtKind::While {
-                        cond: Box::new(cond),
-                        body: Box::new(body),
-                    },
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|(cond, body): (Expr, Stmt), e| {
+            Stmt::new(
+                StmtKind::While {
+                    cond: Box::new(cond),
+                    body: Box::new(body),
+                },
+                span_of(e),
+            )
+        })
+        .boxed();
 
-        // Loop statement: (loop body)
-        let loop_stmt = select!(TokenKind { token: Token::LParen, span: _ })
-            .ignore_then(
-                select!(TokenKind { token: Token::KwLoop, span: _ })
-                .ignore_then(stmt.clone())
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
+    // Loop statement: (loop body)
+    let loop_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwLoop)
+            .ignore_then(stmt.clone())
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|body: Stmt, e| {
+            Stmt::new(
+                StmtKind::Loop {
+                    body: Box::new(body),
+                },
+                span_of(e),
             )
-            .map(|body: Stmt| {
-                Stmt::new(
-                    StmtKind::Loop {
-                        body: Box::new(body),
-                    },
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
+        })
+        .boxed();
 
-        // Break statement: (break) or (break expr)
-        let break_stmt = select!(TokenKind { token: Token::LParen, span: _ })
+    // Break statement: (break) or (break expr)
+    let break_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwBreak)
             .ignore_then(
-                select!(TokenKind { token: Token::KwBreak, span: _ })
-                .ignore_then(
-                    expr_parser().or_not()
-                )
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
+                expr.clone().or_not()
             )
-            .map(|value: Option<Expr>| {
-                Stmt::new(
-                    StmtKind::Break(value.map(Box::new)),
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|value: Option<Expr>, e| {
+            Stmt::new(
+                StmtKind::Break(value.map(Box::new)),
+                span_of(e),
+            )
+        })
+        .boxed();
 
-        // Continue statement: (continue)
-        let continue_stmt = select!(TokenKind { token: Token::LParen, span: _ })
-            .ignore_then(
-                select!(TokenKind { token: Token::KwContinue, span: _ })
-                .then_ignore(select!(TokenKind { token: Token::RParen, span: _ }))
+    // Continue statement: (continue)
+    let continue_stmt = token(Token::LParen)
+        .ignore_then(
+            token(Token::KwContinue)
+            .then_ignore(token(Token::RParen))
+        )
+        .map_with(|_, e| {
+            Stmt::new(
+                StmtKind::Continue,
+                span_of(e),
             )
-            .map(|_| {
-                Stmt::new(
-                    StmtKind::Continue,
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
-
-        // Expression statement: just an expression
-        let expr_stmt = expr_parser()
-            .map(|expr: Expr| {
-                Stmt::new(
-                    StmtKind::Expr(Box::new(expr)),
-                    Span::new(Position::start(), Position::start()),
-                )
-            })
-            .boxed();
-
-        // Combine all statement types
-        let_stmt
-            .or(if_stmt)
-            .or(while_stmt)
-            .or(loop_stmt)
-            .or(break_stmt)
-            .or(continue_stmt)
-            .or(expr_stmt)
-            .or(empty)
-            .boxed()
-    })
+        })
+        .boxed();
+
+    // Function declaration: (fn abs [n: i32] -> i32 ! pure @pre((> n 0)) body).
+    // Without a body it only declares the signature.
+    let contract = token(Token::At)
+        .ignore_then(choice((
+            keyword("pre").to(ContractKind::Pre),
+            keyword("post").to(ContractKind::Post),
+            keyword("inv").to(ContractKind::Invariant),
+            keyword("assert").to(ContractKind::Assert),
+        )))
+        .then(expr.clone().delimited_by(token(Token::LParen), token(Token::RParen)))
+        .map(|(kind, expr)| Contract { kind, expr });
+    let fn_stmt = token(Token::LParen)
+        .ignore_then(token(Token::KwFn))
+        .ignore_then(ident())
+        .then(params_parser())
+        .then(token(Token::Arrow).ignore_then(type_parser()).or_not())
+        .then(effects_parser().or_not())
+        .then(contract.repeated().collect::<Vec<_>>())
+        .then(expr.clone().or_not())
+        .then_ignore(token(Token::RParen))
+        .map_with(|(((((name, params), ret_type), effects), contracts), body), e| {
+            let body = body.map(Box::new);
+            let decl = FnDecl { name, id: None, params, ret_type, body, contracts, effects, span: span_of(e) };
+            Stmt::new(StmtKind::FnDecl(decl), span_of(e))
+        })
+        .boxed();
+
+    // Type parameters of a declaration: <T, U>
+    let generics = ident()
+        .separated_by(token(Token::Comma))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(token(Token::Lt), token(Token::Gt))
+        .or_not()
+        .map(Option::unwrap_or_default);
+
+    // Struct declaration: (struct Pair<A, B> first: A second: B)
+    let field = ident()
+        .then_ignore(token(Token::Colon))
+        .then(type_parser())
+        .map_with(|(name, ty), e| StructField { name, ty, span: span_of(e) });
+    let struct_stmt = token(Token::LParen)
+        .ignore_then(token(Token::KwStruct))
+        .ignore_then(ident())
+        .then(generics)
+        .then(field.repeated().collect::<Vec<_>>())
+        .then_ignore(token(Token::RParen))
+        .map_with(|((name, params), fields), e| {
+            let decl = StructDecl { name, params, fields, span: span_of(e) };
+            Stmt::new(StmtKind::StructDecl(decl), span_of(e))
+        })
+        .boxed();
+
+    // Enum declaration: (enum Opt<T> (Just T) Nothing)
+    let variant = ident()
+        .map(|name| (name, Vec::new()))
+        .or(token(Token::LParen)
+            .ignore_then(ident())
+            .then(type_parser().repeated().collect::<Vec<_>>())
+            .then_ignore(token(Token::RParen)))
+        .map_with(|(name, types), e| EnumVariant { name, types, span: span_of(e) });
+    let enum_stmt = token(Token::LParen)
+        .ignore_then(token(Token::KwEnum))
+        .ignore_then(ident())
+        .then(generics)
+        .then(variant.repeated().collect::<Vec<_>>())
+        .then_ignore(token(Token::RParen))
+        .map_with(|((name, params), variants), e| {
+            let decl = EnumDecl { name, params, variants, span: span_of(e) };
+            Stmt::new(StmtKind::EnumDecl(decl), span_of(e))
+        })
+        .boxed();
+
+    // Expression statement: just an expression. A statement whose whole
+    // group failed to parse is recovered as an error statement.
+    let expr_stmt = expr.clone()
+        .map_with(|expr: Expr, e| {
+            let kind = match expr.kind {
+                ExprKind::Error => StmtKind::Error,
+                _ => StmtKind::Expr(Box::new(expr)),
+            };
+            Stmt::new(kind, span_of(e))
+        })
+        .boxed();
+
+    // Combine all statement types
+    let_stmt
+        .or(set_stmt)
+        .or(if_stmt)
+        .or(while_stmt)
+        .or(loop_stmt)
+        .or(break_stmt)
+        .or(continue_stmt)
+        .or(fn_stmt)
+        .or(struct_stmt)
+        .or(enum_stmt)
+        .or(expr_stmt)
+        .or(empty)
+        .boxed()
 }
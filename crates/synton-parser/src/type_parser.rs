@@ -0,0 +1,201 @@
+//! Type parser for Synton
+
+use chumsky::prelude::*;
+use synton_lexer::{Token, TokenKind};
+use synton_ast::{Type, TypeKind, BuiltinType, RefinementType};
+
+use super::grammar::{ident, span_of, token, Extra};
+
+/// Generic type constructors that accept `<...>` arguments
+#[derive(Debug, Clone)]
+enum Generic {
+    List,
+    Maybe,
+    Result,
+    /// User-declared struct or enum; its arity is checked by the type checker
+    Named(String),
+}
+
+impl Generic {
+    fn apply(self, mut args: Vec<Type>) -> Option<TypeKind> {
+        match (self, args.len()) {
+            (Self::List, 1) => Some(TypeKind::List(Box::new(args.remove(0)))),
+            (Self::Maybe, 1) => Some(TypeKind::Maybe(Box::new(args.remove(0)))),
+            (Self::Result, 2) => {
+                let err = args.remove(1);
+                let ok = args.remove(0);
+                Some(TypeKind::Result {
+                    ok: Box::new(ok),
+                    err: Box::new(err),
+                })
+            }
+            (Self::Named(name), _) => Some(TypeKind::Named { name, args }),
+            _ => None,
+        }
+    }
+}
+
+/// Type parser
+///
+/// Supported forms:
+/// - builtins: `i32`, `u8`, `string`, `bool`, ...
+/// - generics: `list<T>`, `maybe<T>`, `result<T, E>`, `Pair<A, B>`
+/// - shorthands: `?T` (maybe), `&T` (reference), `[T]` (list), `!` (never)
+/// - tuples and unit: `(T1, T2)`, `()`
+/// - functions: `(T1, T2) -> R`
+/// - refinements: `i32 {> val 0}`, a type with a constraint on `val`,
+///   written like an operation with braces instead of parentheses
+/// - any other identifier is a named or generic type variable
+pub fn type_parser<'a>() -> impl Parser<'a, &'a [TokenKind], Type, Extra<'a>> + Clone + 'a {
+    recursive(|ty| {
+        let comma = token(Token::Comma);
+        let lparen = token(Token::LParen);
+        let rparen = token(Token::RParen);
+
+        let builtin = select!(
+            TokenKind { token: Token::KwI32, .. } => BuiltinType::I32,
+            TokenKind { token: Token::KwI64, .. } => BuiltinType::I64,
+            TokenKind { token: Token::KwU32, .. } => BuiltinType::U32,
+            TokenKind { token: Token::KwU64, .. } => BuiltinType::U64,
+            TokenKind { token: Token::KwF32, .. } => BuiltinType::F32,
+            TokenKind { token: Token::KwF64, .. } => BuiltinType::F64,
+            TokenKind { token: Token::KwBool, .. } => BuiltinType::Bool,
+            TokenKind { token: Token::KwString, .. } => BuiltinType::String,
+            TokenKind { token: Token::KwStr, .. } => BuiltinType::String,
+            TokenKind { token: Token::KwChar, .. } => BuiltinType::Char,
+        )
+        .labelled("type")
+        .map(TypeKind::Builtin);
+
+        // Identifiers: builtin aliases (`u8`, `byte`, `dyn`) or named types
+        let named = ident()
+            .map(|name| match BuiltinType::from_name(&name) {
+                Some(b) => TypeKind::Builtin(b),
+                None => TypeKind::Var(name),
+            });
+
+        let never = token(Token::Bang).to(TypeKind::Never);
+
+        let maybe_short = token(Token::Question)
+            .ignore_then(ty.clone())
+            .map(|t| TypeKind::Maybe(Box::new(t)));
+
+        let reference = token(Token::Amp)
+            .ignore_then(ty.clone())
+            .map(|t| TypeKind::Ref(Box::new(t)));
+
+        let list_short = token(Token::LBracket)
+            .ignore_then(ty.clone())
+            .then_ignore(token(Token::RBracket))
+            .map(|t| TypeKind::List(Box::new(t)));
+
+        // (T1, T2) tuple, () unit, (T) grouping, (T1, T2) -> R function
+        let paren = lparen
+            .ignore_then(ty.clone().separated_by(comma).collect::<Vec<_>>())
+            .then(comma.or_not())
+            .then_ignore(rparen)
+            .then(
+                token(Token::Arrow)
+                    .ignore_then(ty.clone())
+                    .or_not()
+            )
+            .map(|((mut items, trailing), ret)| match ret {
+                Some(ret) => TypeKind::Fn {
+                    params: items,
+                    ret: Box::new(ret),
+                },
+                None if items.is_empty() => TypeKind::Unit,
+                None if items.len() == 1 && trailing.is_none() => items.remove(0).kind,
+                None => TypeKind::Tuple(items),
+            })
+            .boxed();
+
+        // Generic application. A `>>` token closes two nested generics at once,
+        // so `list<list<i32>>` is handled by the second alternative.
+        let generic_name = select!(
+            TokenKind { token: Token::KwList, .. } => Generic::List,
+            TokenKind { token: Token::KwMaybe, .. } => Generic::Maybe,
+            TokenKind { token: Token::KwResult, .. } => Generic::Result,
+        )
+        .or(ident().filter(|name| BuiltinType::from_name(name).is_none()).map(Generic::Named));
+        let lt = token(Token::Lt);
+        let gt = token(Token::Gt);
+        let shr = token(Token::Shr);
+        let leading_args = ty.clone().then_ignore(comma).repeated().collect::<Vec<_>>();
+
+        let generic = generic_name
+            .then_ignore(lt)
+            .then(ty.clone().separated_by(comma).at_least(1).collect::<Vec<_>>())
+            .then_ignore(gt)
+            .try_map(|(generic, args), span| generic.apply(args).ok_or_else(|| arity_error(span)))
+            .boxed();
+
+        let nested_generic = generic_name
+            .then_ignore(lt)
+            .then(leading_args.clone())
+            .then(generic_name)
+            .then_ignore(lt)
+            .then(ty.clone().separated_by(comma).at_least(1).collect::<Vec<_>>())
+            .then_ignore(shr)
+            .try_map_with(|(((outer, mut outer_args), inner), inner_args), e| {
+                let span = e.span();
+                let inner = inner.apply(inner_args).ok_or_else(|| arity_error(span))?;
+                // The inner type ends inside the `>>` token, so it gets the
+                // span of the whole type
+                outer_args.push(Type::new(inner, span_of(e)));
+                outer.apply(outer_args).ok_or_else(|| arity_error(span))
+            })
+            .boxed();
+
+        // The constraint is kept as source text, in parentheses
+        let constraint = token(Token::LBrace)
+            .ignore_then(
+                any()
+                    .filter(|t: &TokenKind| !matches!(t.token, Token::LBrace | Token::RBrace))
+                    .repeated()
+                    .at_least(1)
+                    .to_slice(),
+            )
+            .then_ignore(token(Token::RBrace))
+            .map(|tokens: &[TokenKind]| format!("({})", source_text(tokens)));
+
+        nested_generic
+            .or(generic)
+            .or(builtin)
+            .or(paren)
+            .or(list_short)
+            .or(maybe_short)
+            .or(reference)
+            .or(never)
+            .or(named)
+            .map_with(|kind, e| Type::new(kind, span_of(e)))
+            .then(constraint.or_not())
+            .map_with(|(base, constraint), e| match constraint {
+                Some(constraint) => {
+                    let refinement = RefinementType { base: Box::new(base), constraint, var_name: "val".into() };
+                    Type::new(TypeKind::Refinement(Box::new(refinement)), span_of(e))
+                }
+                None => base,
+            })
+            .boxed()
+    })
+}
+
+/// Source text of a token sequence, with a space between tokens except
+/// inside brackets
+fn source_text(tokens: &[TokenKind]) -> String {
+    let mut out = String::new();
+    for (i, t) in tokens.iter().enumerate() {
+        let opens = |t: &TokenKind| matches!(t.token, Token::LParen | Token::LBracket);
+        let closes = matches!(t.token, Token::RParen | Token::RBracket);
+        if i > 0 && !opens(&tokens[i - 1]) && !closes {
+            out.push(' ');
+        }
+        out.push_str(&t.token.to_string());
+    }
+    out
+}
+
+fn arity_error<'a>(span: SimpleSpan) -> Rich<'a, TokenKind> {
+    Rich::custom(span, "wrong number of type arguments")
+}
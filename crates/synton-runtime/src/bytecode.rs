@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use synton_ast::BuiltinType;
 
 /// A compiled bytecode program
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,6 +113,15 @@ pub enum OpKind {
     Shl,
     Shr,
 
+    // Numeric conversions
+    /// Convert to a type as `as` does: integers wrap to the width of the
+    /// target, floats saturate when converted to integers. Traps with
+    /// `INTEGER_OVERFLOW` for a `u64` that does not fit in an `i64`.
+    Cast(BuiltinType),
+    /// Trap with `INTEGER_OVERFLOW` if an integer does not fit in a type.
+    /// Follows arithmetic on types narrower than `i64`.
+    CheckRange(BuiltinType),
+
     // Comparison
     Eq,
     NotEq,
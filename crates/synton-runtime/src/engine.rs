@@ -101,6 +101,14 @@ impl Engine {
                 let a = self.stack.pop()?;
                 self.stack.push(a.div(b)?);
             }
+            super::OpKind::Cast(ty) => {
+                let a = self.stack.pop()?;
+                self.stack.push(a.cast(*ty)?)?;
+            }
+            super::OpKind::CheckRange(ty) => {
+                let a = self.stack.pop()?;
+                self.stack.push(a.check_range(*ty)?)?;
+            }
             super::OpKind::Return => {
                 let val = self.stack.pop().unwrap_or(StackValue::Unit);
                 return Ok(ControlFlow::Halt(val));
@@ -133,6 +141,7 @@ enum ControlFlow {
 fn error_code(e: &RuntimeError) -> String {
     match e {
         RuntimeError::DivisionByZero => "DIVISION_BY_ZERO".to_string(),
+        RuntimeError::IntegerOverflow(_) => "INTEGER_OVERFLOW".to_string(),
         RuntimeError::IndexOutOfBounds { .. } => "INDEX_OUT_OF_BOUNDS".to_string(),
         RuntimeError::StackOverflow => "STACK_OVERFLOW".to_string(),
         RuntimeError::StackUnderflow => "STACK_UNDERFLOW".to_string(),
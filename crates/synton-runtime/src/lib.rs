@@ -101,6 +101,10 @@ pub enum RuntimeError {
     #[error("division by zero")]
     DivisionByZero,
 
+    /// Integer arithmetic result outside the range of its type
+    #[error("integer overflow: {0}")]
+    IntegerOverflow(String),
+
     #[error("index out of bounds: {index} >= {len}")]
     IndexOutOfBounds { index: usize, len: usize },
 
@@ -163,3 +167,98 @@ impl ExecutionResult {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use synton_ast::BuiltinType;
+
+    fn overflows(result: Result<StackValue, RuntimeError>) -> bool {
+        matches!(result, Err(RuntimeError::IntegerOverflow(_)))
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        use StackValue::Integer;
+
+        assert_eq!(Integer(2).add(Integer(3)).unwrap(), Integer(5));
+        assert_eq!(Integer(2).sub(Integer(3)).unwrap(), Integer(-1));
+        assert_eq!(Integer(-4).mul(Integer(3)).unwrap(), Integer(-12));
+        assert_eq!(Integer(7).div(Integer(2)).unwrap(), Integer(3));
+
+        assert!(overflows(Integer(i64::MAX).add(Integer(1))));
+        assert!(overflows(Integer(i64::MIN).sub(Integer(1))));
+        assert!(overflows(Integer(i64::MAX).mul(Integer(2))));
+        assert!(overflows(Integer(i64::MIN).div(Integer(-1))));
+        assert!(matches!(Integer(1).div(Integer(0)), Err(RuntimeError::DivisionByZero)));
+        assert!(matches!(StackValue::Float(1.0).div(StackValue::Float(0.0)), Err(RuntimeError::DivisionByZero)));
+        assert!(matches!(Integer(1).add(StackValue::Bool(true)), Err(RuntimeError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_cast() {
+        use BuiltinType::*;
+        use StackValue::{Bool, Float, Integer};
+
+        // Integers wrap to the width of the target
+        assert_eq!(Integer(300).cast(U8).unwrap(), Integer(44));
+        assert_eq!(Integer(-1).cast(U8).unwrap(), Integer(255));
+        assert_eq!(Integer(-1).cast(U32).unwrap(), Integer(u32::MAX as i64));
+        assert_eq!(Integer(1 << 31).cast(I32).unwrap(), Integer(i32::MIN as i64));
+        assert_eq!(Integer(7).cast(F64).unwrap(), Float(7.0));
+
+        // Floats truncate and saturate
+        assert_eq!(Float(-2.7).cast(I64).unwrap(), Integer(-2));
+        assert_eq!(Float(1e10).cast(I32).unwrap(), Integer(i32::MAX as i64));
+        assert_eq!(Float(-5.0).cast(U8).unwrap(), Integer(0));
+        assert_eq!(Float(f64::NAN).cast(I32).unwrap(), Integer(0));
+        assert_eq!(Bool(true).cast(I32).unwrap(), Integer(1));
+
+        // A u64 above i64::MAX cannot be held
+        assert_eq!(Integer(5).cast(U64).unwrap(), Integer(5));
+        assert!(overflows(Integer(-1).cast(U64)));
+        assert!(overflows(Float(1e19).cast(U64)));
+        assert!(matches!(StackValue::String("1".into()).cast(I32), Err(RuntimeError::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_check_range() {
+        use StackValue::Integer;
+
+        assert_eq!(Integer(255).check_range(BuiltinType::U8).unwrap(), Integer(255));
+        assert!(overflows(Integer(256).check_range(BuiltinType::U8)));
+        assert!(overflows(Integer(-1).check_range(BuiltinType::U32)));
+        assert!(overflows(Integer(i32::MAX as i64 + 1).check_range(BuiltinType::I32)));
+        assert!(overflows(Integer(-1).check_range(BuiltinType::U64)));
+        assert_eq!(Integer(i64::MAX).check_range(BuiltinType::I64).unwrap(), Integer(i64::MAX));
+        // Only integers are checked
+        assert_eq!(StackValue::Float(1e300).check_range(BuiltinType::U8).unwrap(), StackValue::Float(1e300));
+    }
+
+    #[test]
+    fn test_overflow_error_code() {
+        let run = |constants: &[i64], ops: Vec<OpKind>| {
+            let mut bytecode = Bytecode::new();
+            for &value in constants {
+                let idx = bytecode.add_constant(Constant::Integer(value));
+                bytecode.push(Instruction::new(OpKind::Const(idx)));
+            }
+            for op in ops {
+                bytecode.push(Instruction::new(op));
+            }
+            bytecode.push(Instruction::new(OpKind::Return));
+            Runtime::new().execute(&bytecode)
+        };
+        let code = |result: ExecutionResult| match result {
+            ExecutionResult::Error { code, .. } => code,
+            other => panic!("Expected an error, got {:?}", other),
+        };
+
+        assert_eq!(run(&[2, 3], vec![OpKind::Add]), ExecutionResult::Success(StackValue::Integer(5)));
+        assert_eq!(code(run(&[i64::MAX, 1], vec![OpKind::Add])), "INTEGER_OVERFLOW");
+        assert_eq!(code(run(&[i64::MIN, 1], vec![OpKind::Sub])), "INTEGER_OVERFLOW");
+        assert_eq!(code(run(&[300], vec![OpKind::CheckRange(BuiltinType::U8)])), "INTEGER_OVERFLOW");
+        assert_eq!(code(run(&[-1], vec![OpKind::Cast(BuiltinType::U64)])), "INTEGER_OVERFLOW");
+        assert_eq!(code(run(&[1, 0], vec![OpKind::Div])), "DIVISION_BY_ZERO");
+    }
+}
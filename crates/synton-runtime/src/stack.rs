@@ -1,6 +1,14 @@
 //! Value stack
+//!
+//! Every integer type is held in an `i64`. Arithmetic traps with
+//! [`RuntimeError::IntegerOverflow`] when the result does not fit in an
+//! `i64`; for narrower types, [`StackValue::check_range`] traps when it does
+//! not fit in the type. Only [`StackValue::cast`] wraps, as `as` does. A
+//! `u64` above `i64::MAX` cannot be held, so producing one traps. Chars are
+//! held as their code point.
 
 use serde::{Deserialize, Serialize};
+use synton_ast::BuiltinType;
 use super::RuntimeError;
 
 /// Stack value
@@ -58,7 +66,7 @@ impl StackValue {
 
     pub fn add(self, other: StackValue) -> Result<StackValue, RuntimeError> {
         match (self, other) {
-            (Self::Integer(a), Self::Integer(b)) => Ok(Self::Integer(a + b)),
+            (Self::Integer(a), Self::Integer(b)) => checked(a.checked_add(b), || format!("{} + {}", a, b)),
             (Self::Float(a), Self::Float(b)) => Ok(Self::Float(a + b)),
             (Self::String(a), Self::String(b)) => Ok(Self::String(format!("{}{}", a, b))),
             _ => Err(RuntimeError::TypeMismatch {
@@ -70,7 +78,7 @@ impl StackValue {
 
     pub fn sub(self, other: StackValue) -> Result<StackValue, RuntimeError> {
         match (self, other) {
-            (Self::Integer(a), Self::Integer(b)) => Ok(Self::Integer(a - b)),
+            (Self::Integer(a), Self::Integer(b)) => checked(a.checked_sub(b), || format!("{} - {}", a, b)),
             (Self::Float(a), Self::Float(b)) => Ok(Self::Float(a - b)),
             _ => Err(RuntimeError::TypeMismatch {
                 expected: "numeric".to_string(),
@@ -81,7 +89,7 @@ impl StackValue {
 
     pub fn mul(self, other: StackValue) -> Result<StackValue, RuntimeError> {
         match (self, other) {
-            (Self::Integer(a), Self::Integer(b)) => Ok(Self::Integer(a * b)),
+            (Self::Integer(a), Self::Integer(b)) => checked(a.checked_mul(b), || format!("{} * {}", a, b)),
             (Self::Float(a), Self::Float(b)) => Ok(Self::Float(a * b)),
             _ => Err(RuntimeError::TypeMismatch {
                 expected: "numeric".to_string(),
@@ -96,7 +104,7 @@ impl StackValue {
                 if b == 0 {
                     Err(RuntimeError::DivisionByZero)
                 } else {
-                    Ok(Self::Integer(a / b))
+                    checked(a.checked_div(b), || format!("{} / {}", a, b))
                 }
             }
             (Self::Float(a), Self::Float(b)) => {
@@ -114,6 +122,64 @@ impl StackValue {
     }
 }
 
+impl StackValue {
+    /// Convert to `ty` as `as` does. Integers wrap to the width of `ty`,
+    /// floats are truncated and saturate at its bounds (NaN becomes 0), and
+    /// `bool` becomes 0 or 1. The exception is `u64`: a negative integer
+    /// would wrap, and a float would saturate, above `i64::MAX`, so those
+    /// trap with [`RuntimeError::IntegerOverflow`] instead. The type
+    /// checker rejects negative constants cast to any unsigned type.
+    pub fn cast(self, ty: BuiltinType) -> Result<StackValue, RuntimeError> {
+        use BuiltinType::*;
+
+        // A u64 above i64::MAX cannot be held
+        let too_large = |value: &dyn std::fmt::Display| {
+            RuntimeError::IntegerOverflow(format!("{} as u64 does not fit in an i64", value))
+        };
+        let value = match (self, ty) {
+            (Self::Integer(i), U64) if i < 0 => return Err(too_large(&i)),
+            (Self::Integer(i), I64 | U64 | Char) => i,
+            (Self::Integer(i), I32) => i as i32 as i64,
+            (Self::Integer(i), U32) => i as u32 as i64,
+            (Self::Integer(i), U8) => i as u8 as i64,
+            (Self::Integer(i), F32) => return Ok(Self::Float(i as f32 as f64)),
+            (Self::Integer(i), F64) => return Ok(Self::Float(i as f64)),
+            (Self::Float(f), I64) => f as i64,
+            (Self::Float(f), U64) => i64::try_from(f as u64).map_err(|_| too_large(&f))?,
+            (Self::Float(f), I32) => f as i32 as i64,
+            (Self::Float(f), U32) => f as u32 as i64,
+            (Self::Float(f), U8) => f as u8 as i64,
+            (Self::Float(f), F32) => return Ok(Self::Float(f as f32 as f64)),
+            (Self::Float(f), F64) => return Ok(Self::Float(f)),
+            (Self::Bool(b), ty) if ty.is_integer() => b as i64,
+            (value @ Self::Bool(_), Bool) | (value @ Self::String(_), String) | (value, Dyn) => return Ok(value),
+            (value, ty) => {
+                return Err(RuntimeError::TypeMismatch {
+                    expected: format!("value convertible to {}", ty.name()),
+                    found: format!("{:?}", value),
+                })
+            }
+        };
+        Ok(Self::Integer(value))
+    }
+
+    /// Trap if an integer does not fit in `ty`
+    pub fn check_range(self, ty: BuiltinType) -> Result<StackValue, RuntimeError> {
+        match (&self, ty.int_range()) {
+            (Self::Integer(i), Some((min, max))) if !(min..=max).contains(&(*i as i128)) => {
+                Err(RuntimeError::IntegerOverflow(format!("{} does not fit in {}", i, ty.name())))
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+/// Result of checked integer arithmetic, or an overflow error describing
+/// the operation
+fn checked(result: Option<i64>, operation: impl FnOnce() -> String) -> Result<StackValue, RuntimeError> {
+    result.map(StackValue::Integer).ok_or_else(|| RuntimeError::IntegerOverflow(operation()))
+}
+
 impl std::fmt::Display for StackValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -2,34 +2,36 @@
 
 use super::{StackValue, RuntimeError};
 use rustc_hash::FxHashMap;
+use synton_ast::{Effect, Effects};
 use std::sync::Arc;
 
 /// Standard library
 #[derive(Clone)]
 pub struct StdLib {
-    functions: FxHashMap<String, StdLibFn>,
+    functions: FxHashMap<String, (StdLibFn, Effects)>,
 }
 
 type StdLibFn = Arc<dyn Fn(&[StackValue]) -> Result<StackValue, RuntimeError> + Send + Sync>;
 
 impl StdLib {
     pub fn new() -> Self {
-        let mut functions: FxHashMap<String, StdLibFn> = FxHashMap::default();
+        let mut functions: FxHashMap<String, (StdLibFn, Effects)> = FxHashMap::default();
 
-        // Register built-in functions
+        // Register built-in functions, with what they do besides returning
+        // a value. `len` and `abs` fail on arguments of the wrong type.
         functions.insert(
             "print".to_string(),
-            Arc::new(stdlib_print) as StdLibFn,
+            (Arc::new(stdlib_print) as StdLibFn, Effects::only(Effect::Io)),
         );
 
         functions.insert(
             "len".to_string(),
-            Arc::new(stdlib_len) as StdLibFn,
+            (Arc::new(stdlib_len) as StdLibFn, Effects::only(Effect::Panic)),
         );
 
         functions.insert(
             "abs".to_string(),
-            Arc::new(stdlib_abs) as StdLibFn,
+            (Arc::new(stdlib_abs) as StdLibFn, Effects::only(Effect::Panic)),
         );
 
         Self { functions }
@@ -39,7 +41,7 @@ impl StdLib {
     pub fn call(&self, name: &str, args: &[StackValue]) -> Result<StackValue, RuntimeError> {
         self.functions.get(name)
             .ok_or_else(|| RuntimeError::UndefinedFunction(name.to_string()))
-            .and_then(|f| f(args))
+            .and_then(|(f, _)| f(args))
     }
 
     /// Check if a function exists
@@ -51,6 +53,11 @@ impl StdLib {
     pub fn functions(&self) -> impl Iterator<Item = &str> {
         self.functions.keys().map(|s| s.as_str())
     }
+
+    /// Effects of each registered function, for the type checker
+    pub fn effects(&self) -> impl Iterator<Item = (&str, Effects)> {
+        self.functions.iter().map(|(name, (_, effects))| (name.as_str(), *effects))
+    }
 }
 
 impl Default for StdLib {
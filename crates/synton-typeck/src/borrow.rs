@@ -0,0 +1,443 @@
+//! Borrow checking
+//!
+//! References are second-class. `(& place)` borrows the variable the place
+//! is rooted in, and a value holding the reference can be bound, passed to
+//! calls and dereferenced, but it may not leave the scope of the variable
+//! it borrows: not as the value of a block, the `break` value of a loop or
+//! the result of a function, and not by assignment to something declared
+//! outside that scope. A lambda that uses a reference is held to the scope
+//! of what the reference borrows. A call may return a reference into any
+//! of its arguments, if the type of its result can hold one.
+//!
+//! A reference to a mutable variable may be assigned through. Any other
+//! reference, such as one passed as a parameter, is shared, and
+//! `(set (* r) v)` through it is an error. Assigning to a variable, or to
+//! an element or field of one, requires it to be declared with `let mut`.
+
+use rustc_hash::FxHashMap;
+use synton_ast::{AssignTarget, Expr, ExprKind, FnDecl, NodeId, Param, Pattern, Span, Stmt, StmtKind, UnaryOp};
+
+use crate::error::{TypeDiagnostic, TypeError};
+
+/// What the check needs to know about the types of expressions
+pub(crate) trait Types {
+    /// Check if the value of an expression can hold a reference. True if
+    /// its type is not known.
+    fn may_hold_reference(&self, node: Option<NodeId>) -> bool;
+}
+
+/// Check the statements of a module, returning the violations found
+pub(crate) fn check(stmts: &[Stmt], types: &dyn Types) -> Vec<TypeDiagnostic> {
+    let scopes = vec![FxHashMap::default()];
+    let mut cx = Cx { types, scopes, frames: Vec::new(), loops: Vec::new(), diagnostics: Vec::new() };
+    cx.stmts(stmts);
+    cx.diagnostics
+}
+
+/// What a value borrows
+#[derive(Debug, Clone)]
+struct Borrow {
+    /// Variable borrowed
+    name: String,
+    /// Depth of the scope it is declared in
+    depth: usize,
+    /// Whether it may be assigned through
+    mutable: bool,
+}
+
+/// Of two borrows, the one that ends first
+fn shortest(a: Option<Borrow>, b: Option<Borrow>) -> Option<Borrow> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let mutable = a.mutable && b.mutable;
+            let shorter = if b.depth > a.depth { b } else { a };
+            Some(Borrow { mutable, ..shorter })
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Var {
+    depth: usize,
+    mutable: bool,
+    /// What the value of the variable may borrow
+    borrow: Option<Borrow>,
+}
+
+/// A function or lambda being checked
+struct Frame {
+    /// Depth of the scope of its parameters
+    depth: usize,
+    /// Borrows of variables outside it that it uses
+    captured: Option<Borrow>,
+}
+
+struct Cx<'a> {
+    types: &'a dyn Types,
+    scopes: Vec<FxHashMap<String, Var>>,
+    frames: Vec<Frame>,
+    /// Depth outside each enclosing loop and what its `break` values
+    /// borrow, innermost last
+    loops: Vec<(usize, Option<Borrow>)>,
+    diagnostics: Vec<TypeDiagnostic>,
+}
+
+impl Cx<'_> {
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+            }
+            StmtKind::Let { name, init, mutable, .. } => {
+                let borrow = init.as_deref().and_then(|init| self.expr(init));
+                self.bind(name, *mutable, borrow);
+            }
+            StmtKind::Const { name, value, .. } => {
+                let borrow = self.expr(value);
+                self.bind(name, false, borrow);
+            }
+            StmtKind::Assign { target, value } => {
+                let value = self.expr(value);
+                self.assign(target, value, stmt.span);
+            }
+            StmtKind::Block(stmts) => self.scoped(|cx| cx.stmts(stmts)),
+            StmtKind::If { cond, then_branch, else_branch } => {
+                self.expr(cond);
+                self.scoped(|cx| cx.stmt(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.scoped(|cx| cx.stmt(else_branch));
+                }
+            }
+            StmtKind::Loop { body } => {
+                self.in_loop(|cx| cx.scoped(|cx| cx.stmt(body)));
+            }
+            StmtKind::While { cond, body } => {
+                self.expr(cond);
+                self.in_loop(|cx| cx.scoped(|cx| cx.stmt(body)));
+            }
+            StmtKind::For { var, iter, body } => {
+                let items = self.expr(iter);
+                self.in_loop(|cx| {
+                    cx.scoped(|cx| {
+                        cx.bind(var, false, items);
+                        cx.stmt(body);
+                    })
+                });
+            }
+            StmtKind::Break(value) => self.exit_loop(value.as_deref()),
+            StmtKind::Return(value) => self.exit_fn(value.as_deref()),
+            StmtKind::FnDecl(decl) => {
+                let captured = self.fn_decl(decl);
+                self.bind(&decl.name, false, captured);
+            }
+            StmtKind::Contract(contract) => {
+                self.expr(&contract.expr);
+            }
+            StmtKind::Continue
+            | StmtKind::StructDecl(_)
+            | StmtKind::EnumDecl(_)
+            | StmtKind::TypeAlias { .. }
+            | StmtKind::Empty
+            | StmtKind::Error => {}
+        }
+    }
+
+    /// Check an assignment of a value borrowing `value`
+    fn assign(&mut self, target: &AssignTarget, value: Option<Borrow>, span: Span) {
+        match target {
+            AssignTarget::Var(name) => {
+                let Some(var) = self.lookup(name) else { return };
+                if !var.mutable {
+                    self.report(TypeError::AssignImmutable { name: name.clone() }, span, None);
+                }
+                self.store(name, var.depth, value, span);
+            }
+            AssignTarget::Index { base, index } => {
+                self.expr(index);
+                self.assign_in(base, value, span);
+            }
+            AssignTarget::Field { base, .. } => self.assign_in(base, value, span),
+            AssignTarget::Deref(reference) => self.assign_through(reference, value, span),
+        }
+    }
+
+    /// Check an assignment to an element or field of `place`
+    fn assign_in(&mut self, place: &Expr, value: Option<Borrow>, span: Span) {
+        match &place.kind {
+            ExprKind::Var { name, .. } => self.assign(&AssignTarget::Var(name.clone()), value, span),
+            ExprKind::Index { base, index } => {
+                self.expr(index);
+                self.assign_in(base, value, span);
+            }
+            ExprKind::Field { base, .. } => self.assign_in(base, value, span),
+            ExprKind::Unary { op: UnaryOp::Deref, arg } => self.assign_through(arg, value, span),
+            // A temporary, which nothing else sees
+            _ => {
+                self.expr(place);
+            }
+        }
+    }
+
+    /// Check an assignment through the reference `reference` evaluates to
+    fn assign_through(&mut self, reference: &Expr, value: Option<Borrow>, span: Span) {
+        match self.expr(reference) {
+            Some(target) if target.mutable => self.store(&target.name, target.depth, value, span),
+            _ => self.report(TypeError::AssignThroughShared, span, None),
+        }
+    }
+
+    /// Store a value borrowing `value` in the variable `name`, declared at
+    /// `depth`
+    fn store(&mut self, name: &str, depth: usize, value: Option<Borrow>, span: Span) {
+        let Some(value) = value else { return };
+        if value.depth > depth {
+            self.report(TypeError::EscapingReference { name: value.name.clone() }, span, None);
+            return;
+        }
+        if let Some(var) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            var.borrow = shortest(var.borrow.take(), Some(value));
+        }
+    }
+
+    /// What the value of an expression may borrow
+    fn expr(&mut self, expr: &Expr) -> Option<Borrow> {
+        match &expr.kind {
+            ExprKind::Literal(_)
+            | ExprKind::None
+            | ExprKind::SizeOf(_)
+            | ExprKind::Continue
+            | ExprKind::Error => None,
+            ExprKind::Var { name, .. } => self.lookup(name).and_then(|var| var.borrow),
+            ExprKind::Unary { op: UnaryOp::Ref, arg } => Some(self.place(arg)),
+            ExprKind::Unary { arg, .. } => {
+                self.expr(arg);
+                None
+            }
+            ExprKind::Binary { left, right, .. } | ExprKind::Compare { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+                None
+            }
+            ExprKind::Call { callee, args } => {
+                let callee = self.expr(callee);
+                let args = self.exprs(args);
+                shortest(callee, args).filter(|_| self.types.may_hold_reference(expr.id))
+            }
+            ExprKind::MethodCall { object, args, .. } => {
+                let object = self.expr(object);
+                let args = self.exprs(args);
+                shortest(object, args).filter(|_| self.types.may_hold_reference(expr.id))
+            }
+            ExprKind::Index { base, index } => {
+                self.expr(index);
+                self.expr(base)
+            }
+            ExprKind::Field { base, .. } => self.expr(base),
+            ExprKind::Array(items) | ExprKind::Tuple(items) => self.exprs(items),
+            ExprKind::Struct { fields, .. } => {
+                fields.iter().fold(None, |borrow, (_, value)| shortest(borrow, self.expr(value)))
+            }
+            ExprKind::Some(inner) | ExprKind::As { expr: inner, .. } => self.expr(inner),
+            ExprKind::Lambda { params, body, .. } => self.function(params, &[], Some(body)),
+            ExprKind::Block(stmts, tail) => {
+                let depth = self.scopes.len();
+                let borrow = self.scoped(|cx| {
+                    cx.stmts(stmts);
+                    tail.as_deref().and_then(|tail| cx.expr(tail))
+                });
+                self.leave(borrow, depth, expr)
+            }
+            ExprKind::If { cond, then_branch, else_branch } => {
+                self.expr(cond);
+                let then_borrow = self.expr(then_branch);
+                let else_borrow = else_branch.as_deref().and_then(|else_branch| self.expr(else_branch));
+                shortest(then_borrow, else_borrow)
+            }
+            ExprKind::Loop { body } => self.in_loop(|cx| {
+                cx.expr(body);
+            }),
+            ExprKind::Break(value) => {
+                self.exit_loop(value.as_deref());
+                None
+            }
+            ExprKind::Return(value) => {
+                self.exit_fn(value.as_deref());
+                None
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let matched = self.expr(scrutinee);
+                let mut borrow = None;
+                for arm in arms {
+                    let arm_borrow = self.scoped(|cx| {
+                        cx.bind_pattern(&arm.pattern, &matched);
+                        if let Some(guard) = &arm.guard {
+                            cx.expr(guard);
+                        }
+                        cx.expr(&arm.body)
+                    });
+                    borrow = shortest(borrow, arm_borrow);
+                }
+                borrow
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Option<Borrow> {
+        exprs.iter().fold(None, |borrow, expr| shortest(borrow, self.expr(expr)))
+    }
+
+    /// What `(& place)` borrows
+    fn place(&mut self, place: &Expr) -> Borrow {
+        match &place.kind {
+            ExprKind::Var { name, .. } => match self.lookup(name) {
+                Some(var) => {
+                    let borrow = Borrow { name: name.clone(), depth: var.depth, mutable: var.mutable };
+                    self.capture(&borrow);
+                    borrow
+                }
+                // A global, or a name that is already reported
+                None => Borrow { name: name.clone(), depth: 0, mutable: false },
+            },
+            ExprKind::Index { base, index } => {
+                self.expr(index);
+                self.place(base)
+            }
+            ExprKind::Field { base, .. } => self.place(base),
+            // A reborrow of what the reference borrows
+            ExprKind::Unary { op: UnaryOp::Deref, arg } => self.expr(arg).unwrap_or_else(|| Borrow {
+                name: "a reference parameter".into(),
+                depth: 0,
+                mutable: false,
+            }),
+            // A temporary, which lives as long as the current scope
+            _ => {
+                self.expr(place);
+                Borrow { name: "a temporary".into(), depth: self.scopes.len() - 1, mutable: true }
+            }
+        }
+    }
+
+    /// Check a function declaration, returning what it captures
+    fn fn_decl(&mut self, decl: &FnDecl) -> Option<Borrow> {
+        let contracts: Vec<_> = decl.contracts.iter().map(|contract| &contract.expr).collect();
+        self.function(&decl.params, &contracts, decl.body.as_deref())
+    }
+
+    /// Check a function, returning what it captures
+    fn function(&mut self, params: &[Param], contracts: &[&Expr], body: Option<&Expr>) -> Option<Borrow> {
+        let loops = std::mem::take(&mut self.loops);
+        self.frames.push(Frame { depth: self.scopes.len(), captured: None });
+        self.scoped(|cx| {
+            for param in params {
+                cx.bind(&param.name, false, None);
+            }
+            for contract in contracts {
+                cx.expr(contract);
+            }
+            if let Some(body) = body {
+                cx.exit_fn(Some(body));
+            }
+        });
+        self.loops = loops;
+        self.frames.pop().and_then(|frame| frame.captured)
+    }
+
+    /// Check a value leaving the innermost function
+    fn exit_fn(&mut self, value: Option<&Expr>) {
+        let Some(value) = value else { return };
+        let borrow = self.expr(value);
+        if let Some(depth) = self.frames.last().map(|frame| frame.depth) {
+            self.leave(borrow, depth, value);
+        }
+    }
+
+    /// Check a value leaving the innermost loop
+    fn exit_loop(&mut self, value: Option<&Expr>) {
+        let Some(value) = value else { return };
+        let borrow = self.expr(value);
+        let Some(depth) = self.loops.last().map(|(depth, _)| *depth) else { return };
+        let borrow = self.leave(borrow, depth + 1, value);
+        if let Some((_, broken)) = self.loops.last_mut() {
+            *broken = shortest(broken.take(), borrow);
+        }
+    }
+
+    /// Check the value of `expr` leaving the scopes from `depth` on,
+    /// returning what it borrows outside them
+    fn leave(&mut self, borrow: Option<Borrow>, depth: usize, expr: &Expr) -> Option<Borrow> {
+        match borrow {
+            Some(borrow) if borrow.depth >= depth => {
+                self.report(TypeError::EscapingReference { name: borrow.name }, expr.span, expr.id);
+                None
+            }
+            borrow => borrow,
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, matched: &Option<Borrow>) {
+        match pattern {
+            Pattern::Bind(name) => self.bind(name, false, matched.clone()),
+            Pattern::Or(alternatives) => alternatives.iter().for_each(|p| self.bind_pattern(p, matched)),
+            Pattern::Struct { fields, .. } => fields.iter().for_each(|(_, p)| self.bind_pattern(p, matched)),
+            Pattern::Tuple(items) | Pattern::Slice(items) | Pattern::Variant { args: items, .. } => {
+                items.iter().for_each(|p| self.bind_pattern(p, matched))
+            }
+            Pattern::Some(inner) => self.bind_pattern(inner, matched),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::None => {}
+        }
+    }
+
+    fn bind(&mut self, name: &str, mutable: bool, borrow: Option<Borrow>) {
+        let depth = self.scopes.len() - 1;
+        self.scopes.last_mut().expect("borrows have a global scope").insert(name.to_string(), Var {
+            depth,
+            mutable,
+            borrow,
+        });
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<Var> {
+        let var = self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()?;
+        if let Some(borrow) = &var.borrow {
+            if var.depth < self.frames.last().map_or(0, |frame| frame.depth) {
+                self.capture(&borrow.clone());
+            }
+        }
+        Some(var)
+    }
+
+    /// Record that the innermost function uses `borrow`, if it is of a
+    /// variable outside it
+    fn capture(&mut self, borrow: &Borrow) {
+        if let Some(frame) = self.frames.last_mut() {
+            if borrow.depth < frame.depth {
+                frame.captured = shortest(frame.captured.take(), Some(borrow.clone()));
+            }
+        }
+    }
+
+    fn report(&mut self, error: TypeError, span: Span, node: Option<NodeId>) {
+        self.diagnostics.push(TypeDiagnostic::new(error, span, node));
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(FxHashMap::default());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Check a loop, returning what its `break` values borrow
+    fn in_loop(&mut self, f: impl FnOnce(&mut Self)) -> Option<Borrow> {
+        self.loops.push((self.scopes.len() - 1, None));
+        f(self);
+        self.loops.pop().and_then(|(_, borrow)| borrow)
+    }
+}
@@ -0,0 +1,70 @@
+//! Nesting depth
+//!
+//! Every pass recurses over the AST, so a module is checked for nesting
+//! deeper than the configured limit before any of them runs, the way the
+//! parser checks brackets before parsing. Statements, expressions, patterns
+//! and types each count as one level.
+
+use synton_ast::visit::{self, Visitor};
+use synton_ast::{Expr, NodeId, Pattern, Position, Span, Stmt, Type};
+
+/// Default maximum nesting depth
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// Finds the first node nested deeper than a limit, without recursing any
+/// deeper itself
+pub(crate) struct Nesting {
+    max_depth: usize,
+    depth: usize,
+    /// Span of the innermost statement, expression or type, which patterns
+    /// are reported at
+    span: Span,
+    first: Option<(Span, Option<NodeId>)>,
+}
+
+impl Nesting {
+    pub(crate) fn new(max_depth: usize) -> Self {
+        Self { max_depth, depth: 0, span: Span::single(Position::start()), first: None }
+    }
+
+    /// Span of the first node past the limit, and its node if it is an
+    /// expression
+    pub(crate) fn too_deep(self) -> Option<(Span, Option<NodeId>)> {
+        self.first
+    }
+
+    /// Visit a node with `walk`, unless it is past the limit
+    fn enter(&mut self, span: Option<Span>, node: Option<NodeId>, walk: impl FnOnce(&mut Self)) {
+        if self.first.is_some() {
+            return;
+        }
+        let outer = self.span;
+        self.span = span.unwrap_or(outer);
+        if self.depth >= self.max_depth {
+            self.first = Some((self.span, node));
+        } else {
+            self.depth += 1;
+            walk(self);
+            self.depth -= 1;
+        }
+        self.span = outer;
+    }
+}
+
+impl Visitor for Nesting {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.enter(Some(stmt.span), None, |v| visit::walk_stmt(v, stmt));
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.enter(Some(expr.span), expr.id, |v| visit::walk_expr(v, expr));
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        self.enter(None, None, |v| visit::walk_pattern(v, pattern));
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        self.enter(Some(ty.span), None, |v| visit::walk_type(v, ty));
+    }
+}
@@ -0,0 +1,397 @@
+//! Effects of functions
+//!
+//! The effects of a function are inferred from its body: those of the
+//! standard library and host functions it calls, as declared by the host,
+//! and those of the functions it calls in turn. Division, remainder and
+//! indexing can panic. Integer overflow and out-of-range casts trap too,
+//! but are not counted, as almost any arithmetic could.
+//!
+//! A function declared with effects may not have others, so one declared
+//! pure can only call pure functions. Contracts must be pure. Calling a
+//! function value whose effects are not known, such as a parameter, may
+//! have any effect.
+//!
+//! Functions may call each other before their declaration, so the pass is
+//! repeated until the effects of every function declaration settle.
+
+use rustc_hash::FxHashMap;
+use synton_ast::{
+    AssignTarget, BinaryOp, Effect, Effects, Expr, ExprKind, FnDecl, Literal, Param, Pattern, Stmt, StmtKind,
+};
+
+use crate::error::{TypeDiagnostic, TypeError};
+
+/// Result of checking the effects of a module
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    /// Effects of each top-level function, by name
+    pub effects: FxHashMap<String, Effects>,
+    /// Effects where they are not allowed
+    pub diagnostics: Vec<TypeDiagnostic>,
+}
+
+/// Check the effects of the statements of a module, given the effects of
+/// the names the host provides
+pub(crate) fn check(stmts: &[Stmt], host: &FxHashMap<String, Effects>) -> Report {
+    let mut cx = Cx {
+        host,
+        decls: FxHashMap::default(),
+        scopes: vec![FxHashMap::default()],
+        allowed: None,
+        changed: false,
+        diagnostics: Vec::new(),
+    };
+    loop {
+        cx.scopes.truncate(1);
+        cx.scopes[0].clear();
+        cx.diagnostics.clear();
+        cx.changed = false;
+        cx.stmts(stmts);
+        if !cx.changed {
+            break;
+        }
+    }
+
+    let effects = cx.scopes[0]
+        .iter()
+        .filter(|(name, _)| stmts.iter().any(|stmt| declares_fn(stmt, name)))
+        .filter_map(|(name, latent)| Some((name.clone(), (*latent)?)))
+        .collect();
+    Report { effects, diagnostics: cx.diagnostics }
+}
+
+/// Check if a statement declares a function named `name`
+fn declares_fn(stmt: &Stmt, name: &str) -> bool {
+    match &stmt.kind {
+        StmtKind::FnDecl(decl) => decl.name == name,
+        StmtKind::Let { name: bound, init: Some(init), .. } | StmtKind::Const { name: bound, value: init, .. } => {
+            bound == name && matches!(init.kind, ExprKind::Lambda { .. })
+        }
+        _ => false,
+    }
+}
+
+/// What evaluating an expression does
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    /// Effects of evaluating it
+    effects: Effects,
+    /// If it is a function, the effects of calling it, if known
+    latent: Option<Effects>,
+}
+
+impl Value {
+    fn of(effects: Effects) -> Self {
+        Self { effects, latent: None }
+    }
+}
+
+struct Cx<'a> {
+    host: &'a FxHashMap<String, Effects>,
+    /// Inferred effects of each function declaration, by address, which
+    /// is stable while the module is borrowed. They only grow from pass to
+    /// pass.
+    decls: FxHashMap<*const FnDecl, Effects>,
+    /// Effects of calling the names in scope, if they are known functions
+    scopes: Vec<FxHashMap<String, Option<Effects>>>,
+    /// Effects allowed where evaluation is, and what allows them
+    allowed: Option<(Effects, String)>,
+    /// Whether the effects of a declaration grew in this pass
+    changed: bool,
+    diagnostics: Vec<TypeDiagnostic>,
+}
+
+impl Cx<'_> {
+    fn stmts(&mut self, stmts: &[Stmt]) -> Effects {
+        // Functions and variants can be used before their declaration
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::FnDecl(decl) => {
+                    let inferred = self.decls.get(&(decl as *const _)).copied().unwrap_or_default();
+                    self.bind(&decl.name, Some(decl.effects.unwrap_or(inferred)));
+                }
+                StmtKind::EnumDecl(decl) => {
+                    for variant in &decl.variants {
+                        self.bind(&variant.name, Some(Effects::PURE));
+                    }
+                }
+                _ => {}
+            }
+        }
+        stmts.iter().fold(Effects::PURE, |effects, stmt| effects.union(self.stmt(stmt)))
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Effects {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => self.expr(expr).effects,
+            StmtKind::Let { name, init, mutable, .. } => {
+                let value = init.as_deref().map(|init| self.named(name, init));
+                // A mutable variable may be given another function later
+                let latent = value.and_then(|value| value.latent).filter(|_| !mutable);
+                self.bind(name, latent);
+                value.map_or(Effects::PURE, |value| value.effects)
+            }
+            StmtKind::Const { name, value, .. } => {
+                let value = self.named(name, value);
+                self.bind(name, value.latent);
+                value.effects
+            }
+            StmtKind::Assign { target, value } => {
+                let target = match target {
+                    AssignTarget::Var(_) => Effects::PURE,
+                    AssignTarget::Index { base, index } => {
+                        let effects = self.expr(base).effects.union(self.expr(index).effects);
+                        effects.union(self.effect(Effect::Panic, index))
+                    }
+                    AssignTarget::Field { base, .. } => self.expr(base).effects,
+                    AssignTarget::Deref(inner) => self.expr(inner).effects,
+                };
+                target.union(self.expr(value).effects)
+            }
+            StmtKind::Block(stmts) => self.scoped(|cx| cx.stmts(stmts)),
+            StmtKind::If { cond, then_branch, else_branch } => {
+                let mut effects = self.expr(cond).effects;
+                effects = effects.union(self.scoped(|cx| cx.stmt(then_branch)));
+                if let Some(else_branch) = else_branch {
+                    effects = effects.union(self.scoped(|cx| cx.stmt(else_branch)));
+                }
+                effects
+            }
+            StmtKind::Loop { body } => self.scoped(|cx| cx.stmt(body)),
+            StmtKind::While { cond, body } => {
+                let effects = self.expr(cond).effects;
+                effects.union(self.scoped(|cx| cx.stmt(body)))
+            }
+            StmtKind::For { var, iter, body } => {
+                let effects = self.expr(iter).effects;
+                effects.union(self.scoped(|cx| {
+                    cx.bind(var, None);
+                    cx.stmt(body)
+                }))
+            }
+            StmtKind::Break(value) | StmtKind::Return(value) => {
+                value.as_deref().map_or(Effects::PURE, |value| self.expr(value).effects)
+            }
+            StmtKind::FnDecl(decl) => {
+                let latent = self.fn_decl(decl);
+                self.bind(&decl.name, Some(latent));
+                Effects::PURE
+            }
+            StmtKind::Contract(contract) => {
+                self.contract(&contract.expr);
+                Effects::PURE
+            }
+            StmtKind::Continue
+            | StmtKind::StructDecl(_)
+            | StmtKind::EnumDecl(_)
+            | StmtKind::TypeAlias { .. }
+            | StmtKind::Empty
+            | StmtKind::Error => Effects::PURE,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Value {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::None | ExprKind::SizeOf(_) | ExprKind::Continue | ExprKind::Error => {
+                Value::of(Effects::PURE)
+            }
+            ExprKind::Var { name, .. } => Value { effects: Effects::PURE, latent: self.lookup(name) },
+            ExprKind::Unary { arg, .. } => Value::of(self.expr(arg).effects),
+            ExprKind::Binary { op, left, right } => {
+                let effects = self.expr(left).effects.union(self.expr(right).effects);
+                // Dividing by a nonzero literal cannot fail
+                let nonzero = match right.kind {
+                    ExprKind::Literal(Literal::Integer(n)) => n != 0,
+                    ExprKind::Literal(Literal::TypedInteger(n, _)) => n != 0,
+                    _ => false,
+                };
+                match op {
+                    BinaryOp::Div | BinaryOp::Mod if !nonzero => {
+                        Value::of(effects.union(self.effect(Effect::Panic, expr)))
+                    }
+                    _ => Value::of(effects),
+                }
+            }
+            ExprKind::Compare { left, right, .. } => {
+                Value::of(self.expr(left).effects.union(self.expr(right).effects))
+            }
+            ExprKind::Call { callee, args } => {
+                let callee_value = self.expr(callee);
+                let effects = self.exprs(args).union(callee_value.effects);
+                let call = self.effects(callee_value.latent.unwrap_or(Effects::ALL), expr);
+                Value::of(effects.union(call))
+            }
+            ExprKind::MethodCall { object, args, .. } => {
+                let effects = self.expr(object).effects.union(self.exprs(args));
+                Value::of(effects.union(self.effects(Effects::ALL, expr)))
+            }
+            ExprKind::Index { base, index } => {
+                let effects = self.expr(base).effects.union(self.expr(index).effects);
+                Value::of(effects.union(self.effect(Effect::Panic, expr)))
+            }
+            ExprKind::Field { base, .. } => Value::of(self.expr(base).effects),
+            ExprKind::Array(items) | ExprKind::Tuple(items) => Value::of(self.exprs(items)),
+            ExprKind::Struct { fields, .. } => {
+                let values = fields.iter().map(|(_, value)| value);
+                Value::of(values.fold(Effects::PURE, |effects, value| effects.union(self.expr(value).effects)))
+            }
+            ExprKind::Lambda { params, effects, body } => self.lambda("a function", params, *effects, body),
+            ExprKind::Block(stmts, tail) => self.scoped(|cx| {
+                let effects = cx.stmts(stmts);
+                match tail {
+                    Some(tail) => {
+                        let value = cx.expr(tail);
+                        Value { effects: effects.union(value.effects), latent: value.latent }
+                    }
+                    None => Value::of(effects),
+                }
+            }),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                let cond = self.expr(cond).effects;
+                let then_value = self.expr(then_branch);
+                let else_value = match else_branch {
+                    Some(else_branch) => self.expr(else_branch),
+                    None => Value::of(Effects::PURE),
+                };
+                let latent = match (then_value.latent, else_value.latent) {
+                    (Some(a), Some(b)) => Some(a.union(b)),
+                    _ => None,
+                };
+                Value { effects: cond.union(then_value.effects).union(else_value.effects), latent }
+            }
+            ExprKind::Loop { body } => Value::of(self.expr(body).effects),
+            ExprKind::Break(value) | ExprKind::Return(value) => {
+                Value::of(value.as_deref().map_or(Effects::PURE, |value| self.expr(value).effects))
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let mut effects = self.expr(scrutinee).effects;
+                for arm in arms {
+                    effects = effects.union(self.scoped(|cx| {
+                        cx.bind_pattern(&arm.pattern);
+                        let guard = arm.guard.as_deref().map_or(Effects::PURE, |guard| cx.expr(guard).effects);
+                        guard.union(cx.expr(&arm.body).effects)
+                    }));
+                }
+                Value::of(effects)
+            }
+            ExprKind::Some(inner) | ExprKind::As { expr: inner, .. } => Value::of(self.expr(inner).effects),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Effects {
+        exprs.iter().fold(Effects::PURE, |effects, expr| effects.union(self.expr(expr).effects))
+    }
+
+    /// Effects of calling a function declaration. Its declared effects if
+    /// it has them, after checking its body against them.
+    fn fn_decl(&mut self, decl: &FnDecl) -> Effects {
+        let context = decl.effects.map(|declared| (declared, format!("`{}`, declared {}", decl.name, declared)));
+        let allowed = std::mem::replace(&mut self.allowed, context);
+        let inferred = self.scoped(|cx| {
+            cx.bind_params(&decl.params);
+            for contract in &decl.contracts {
+                cx.contract(&contract.expr);
+            }
+            decl.body.as_deref().map_or(Effects::PURE, |body| cx.expr(body).effects)
+        });
+        self.allowed = allowed;
+
+        let known = self.decls.entry(decl as *const _).or_default();
+        if !inferred.is_subset(*known) {
+            *known = known.union(inferred);
+            self.changed = true;
+        }
+        decl.effects.unwrap_or(*known)
+    }
+
+    /// Value of an expression bound to `name`, which names it in messages
+    /// if it is a lambda
+    fn named(&mut self, name: &str, expr: &Expr) -> Value {
+        match &expr.kind {
+            ExprKind::Lambda { params, effects, body } => {
+                self.lambda(&format!("`{}`", name), params, *effects, body)
+            }
+            _ => self.expr(expr),
+        }
+    }
+
+    /// Value of a lambda. Its effects are allowed or not where it is
+    /// called, once checked against the effects it declares.
+    fn lambda(&mut self, name: &str, params: &[Param], effects: Option<Effects>, body: &Expr) -> Value {
+        let context = effects.map(|declared| (declared, format!("{}, declared {}", name, declared)));
+        let allowed = std::mem::replace(&mut self.allowed, context);
+        let inferred = self.function(params, body);
+        self.allowed = allowed;
+        Value { effects: Effects::PURE, latent: Some(effects.unwrap_or(inferred)) }
+    }
+
+    /// Effects of calling a function with the given parameters and body
+    fn function(&mut self, params: &[Param], body: &Expr) -> Effects {
+        self.scoped(|cx| {
+            cx.bind_params(params);
+            cx.expr(body).effects
+        })
+    }
+
+    /// Check that a contract is pure
+    fn contract(&mut self, expr: &Expr) {
+        let allowed = self.allowed.replace((Effects::PURE, "a contract".into()));
+        self.expr(expr);
+        self.allowed = allowed;
+    }
+
+    /// Report the effects of `expr` that are not allowed where it is
+    fn effects(&mut self, effects: Effects, expr: &Expr) -> Effects {
+        if let Some((allowed, context)) = &self.allowed {
+            let disallowed = effects.difference(*allowed);
+            if !disallowed.is_pure() {
+                let error = TypeError::DisallowedEffect { effects: disallowed.to_string(), context: context.clone() };
+                self.diagnostics.push(TypeDiagnostic::new(error, expr.span, expr.id));
+            }
+        }
+        effects
+    }
+
+    fn effect(&mut self, effect: Effect, expr: &Expr) -> Effects {
+        self.effects(effect.into(), expr)
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Bind(name) => self.bind(name, None),
+            Pattern::Or(alternatives) => alternatives.iter().for_each(|p| self.bind_pattern(p)),
+            Pattern::Struct { fields, .. } => fields.iter().for_each(|(_, p)| self.bind_pattern(p)),
+            Pattern::Tuple(items) | Pattern::Slice(items) | Pattern::Variant { args: items, .. } => {
+                items.iter().for_each(|p| self.bind_pattern(p))
+            }
+            Pattern::Some(inner) => self.bind_pattern(inner),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::None => {}
+        }
+    }
+
+    /// Parameters are function values whose effects are not known
+    fn bind_params(&mut self, params: &[Param]) {
+        for param in params {
+            self.bind(&param.name, None);
+        }
+    }
+
+    fn bind(&mut self, name: &str, latent: Option<Effects>) {
+        self.scopes.last_mut().expect("effects have a global scope").insert(name.to_string(), latent);
+    }
+
+    /// Effects of calling a name, if it is a known function
+    fn lookup(&self, name: &str) -> Option<Effects> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(latent) => *latent,
+            None => self.host.get(name).copied(),
+        }
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(FxHashMap::default());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+}
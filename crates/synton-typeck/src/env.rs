@@ -10,6 +10,9 @@ pub struct Binding {
     pub name: String,
     pub ty: Type,
     pub id: Option<VarId>,
+    /// Inference variables in `ty` that are generalized, and replaced by
+    /// fresh ones at each use
+    pub quantified: Vec<u32>,
 }
 
 /// Type environment
@@ -41,14 +44,34 @@ impl TypeEnv {
 
     /// Declare a variable in the current scope
     pub fn decl(&mut self, name: String, ty: Type) {
+        self.decl_generic(name, Vec::new(), ty);
+    }
+
+    /// Declare a variable whose type is generic over some inference variables
+    pub fn decl_generic(&mut self, name: String, quantified: Vec<u32>, ty: Type) {
         let id = VarId(self.next_var_id);
         self.next_var_id += 1;
 
         if let Some(scope) = self.vars.last_mut() {
-            scope.insert(name.clone(), Binding { name, ty, id: Some(id) });
+            scope.insert(name.clone(), Binding { name, ty, id: Some(id), quantified });
         }
     }
 
+    /// Check if a name is declared in the innermost scope
+    pub fn in_current_scope(&self, name: &str) -> bool {
+        self.vars.last().is_some_and(|scope| scope.contains_key(name))
+    }
+
+    /// All visible and shadowed bindings
+    pub fn bindings(&self) -> impl Iterator<Item = &Binding> {
+        self.vars.iter().flat_map(|scope| scope.values())
+    }
+
+    /// Remove a variable from the innermost scope
+    pub fn remove(&mut self, name: &str) -> Option<Binding> {
+        self.vars.last_mut().and_then(|scope| scope.remove(name))
+    }
+
     /// Look up a variable
     pub fn get(&self, name: &str) -> Option<&Binding> {
         for scope in self.vars.iter().rev() {
@@ -1,12 +1,16 @@
 //! Type checker errors
 
+use std::fmt;
+
+use miette::{LabeledSpan, SourceSpan};
+use synton_ast::{NodeId, Span};
 use thiserror::Error;
 
 /// Result type for type checker
 pub type TResult<T> = Result<T, TypeError>;
 
 /// Type error
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum TypeError {
     /// Type mismatch
     #[error("type mismatch: expected {expected}, found {found}")]
@@ -39,6 +43,13 @@ pub enum TypeError {
         name: String,
     },
 
+    /// Call of a value that is not a function
+    #[error("'{ty}' is not a function")]
+    NotCallable {
+        /// Type of the called value
+        ty: String,
+    },
+
     /// Wrong number of arguments
     #[error("wrong number of arguments: expected {expected}, found {found}")]
     ArgCount {
@@ -46,6 +57,36 @@ pub enum TypeError {
         found: usize,
     },
 
+    /// Declared type applied to the wrong number of type arguments
+    #[error("wrong number of type arguments for '{name}': expected {expected}, found {found}")]
+    TypeArgCount {
+        /// Struct, enum or alias name
+        name: String,
+        /// Number of declared type parameters
+        expected: usize,
+        /// Number of type arguments given
+        found: usize,
+    },
+
+    /// Pattern naming something that is not an enum variant
+    #[error("undefined enum variant: '{name}'")]
+    UndefinedVariant {
+        /// Name used in the pattern
+        name: String,
+    },
+
+    /// `match` that some value of the matched type gets through
+    #[error("non-exhaustive match, missing: {}", missing.join(", "))]
+    NonExhaustive {
+        /// Patterns for the values no arm matches
+        missing: Vec<String>,
+    },
+
+    /// Match arm that no value reaches, because the arms before it match
+    /// everything it would
+    #[error("unreachable match arm")]
+    UnreachableArm,
+
     /// Cannot infer type
     #[error("cannot infer type for this expression")]
     CannotInfer,
@@ -56,7 +97,278 @@ pub enum TypeError {
         constraint: String,
     },
 
-    /// Cyclic type dependency
-    #[error("cyclic type dependency")]
-    Cycle,
+    /// Refinement constraint that is not an expression
+    #[error("invalid refinement constraint `{constraint}`: {message}")]
+    InvalidRefinement {
+        /// Constraint as written
+        constraint: String,
+        /// Why it does not parse
+        message: String,
+    },
+
+    /// Effects where they are not allowed, such as printing in a function
+    /// declared pure
+    #[error("{effects} not allowed in {context}")]
+    DisallowedEffect {
+        /// Effects that are not allowed
+        effects: String,
+        /// Where they are not allowed
+        context: String,
+    },
+
+    /// Assignment to a variable not declared with `let mut`, or to an
+    /// element or field of one
+    #[error("cannot assign to immutable variable `{name}`")]
+    AssignImmutable {
+        /// Variable assigned
+        name: String,
+    },
+
+    /// Assignment through a reference that is not to a mutable variable
+    #[error("cannot assign through a shared reference")]
+    AssignThroughShared,
+
+    /// Reference that outlives the variable it borrows
+    #[error("reference to `{name}` escapes its scope")]
+    EscapingReference {
+        /// Variable borrowed
+        name: String,
+    },
+
+    /// A type would have to contain itself, as in `_0 = list<_0>`
+    #[error("cyclic type: {var} occurs in {ty}")]
+    Cycle {
+        /// Inference variable
+        var: String,
+        /// Type it would be bound to
+        ty: String,
+    },
+
+    /// Field access or struct literal with a field the struct does not have
+    #[error("no field '{field}' on type {ty}")]
+    NoField {
+        /// Struct type
+        ty: String,
+        /// Field name
+        field: String,
+    },
+
+    /// Operator applied to a type it is not defined for
+    #[error("operator '{op}' cannot be applied to type {ty}")]
+    InvalidOperand {
+        /// Operator token
+        op: String,
+        /// Type of the operand
+        ty: String,
+    },
+
+    /// `as` between types that do not convert
+    #[error("cannot cast {from} as {to}")]
+    InvalidCast {
+        /// Type of the cast value
+        from: String,
+        /// Target type
+        to: String,
+    },
+
+    /// Integer literal that its type cannot represent
+    #[error("literal {value} out of range for {ty}")]
+    LiteralOutOfRange {
+        /// Value of the literal
+        value: i128,
+        /// Type the literal has
+        ty: String,
+    },
+
+    /// Statements, expressions, patterns or types nested deeper than the
+    /// configured limit
+    #[error("nesting exceeds the maximum depth of {max_depth}")]
+    RecursionLimit {
+        /// Configured maximum nesting depth
+        max_depth: usize,
+    },
+}
+
+impl TypeError {
+    /// Machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::Mismatch { .. } => "synton::typeck::mismatch",
+            TypeError::Uninitialized { .. } => "synton::typeck::uninitialized",
+            TypeError::UndefinedVar { .. } => "synton::typeck::undefined_var",
+            TypeError::UndefinedType { .. } => "synton::typeck::undefined_type",
+            TypeError::UndefinedFn { .. } => "synton::typeck::undefined_fn",
+            TypeError::NotCallable { .. } => "synton::typeck::not_callable",
+            TypeError::ArgCount { .. } => "synton::typeck::arg_count",
+            TypeError::TypeArgCount { .. } => "synton::typeck::type_arg_count",
+            TypeError::UndefinedVariant { .. } => "synton::typeck::undefined_variant",
+            TypeError::NonExhaustive { .. } => "synton::typeck::non_exhaustive",
+            TypeError::UnreachableArm => "synton::typeck::unreachable_arm",
+            TypeError::CannotInfer => "synton::typeck::cannot_infer",
+            TypeError::RefinementViolation { .. } => "synton::typeck::refinement",
+            TypeError::InvalidRefinement { .. } => "synton::typeck::invalid_refinement",
+            TypeError::DisallowedEffect { .. } => "synton::typeck::effect",
+            TypeError::AssignImmutable { .. } => "synton::typeck::assign_immutable",
+            TypeError::AssignThroughShared => "synton::typeck::shared_reference",
+            TypeError::EscapingReference { .. } => "synton::typeck::escaping_reference",
+            TypeError::Cycle { .. } => "synton::typeck::cycle",
+            TypeError::NoField { .. } => "synton::typeck::no_field",
+            TypeError::InvalidOperand { .. } => "synton::typeck::invalid_operand",
+            TypeError::InvalidCast { .. } => "synton::typeck::invalid_cast",
+            TypeError::LiteralOutOfRange { .. } => "synton::typeck::literal_out_of_range",
+            TypeError::RecursionLimit { .. } => "synton::typeck::recursion_limit",
+        }
+    }
+
+    /// Check if this is a warning rather than an error
+    pub fn is_warning(&self) -> bool {
+        matches!(self, TypeError::UnreachableArm)
+    }
+}
+
+/// Secondary location of a diagnostic, such as the annotation that made a
+/// type expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// Location
+    pub span: Span,
+    /// What it has to do with the error
+    pub message: String,
+}
+
+/// A type error and where it happened
+#[derive(Debug, Clone)]
+pub struct TypeDiagnostic {
+    /// The error
+    pub error: TypeError,
+    /// Span of the offending expression or statement
+    pub span: Span,
+    /// Node of the offending expression, if it has one
+    pub node: Option<NodeId>,
+    /// Related locations
+    pub labels: Vec<Label>,
+}
+
+impl TypeDiagnostic {
+    /// Create a diagnostic without labels
+    pub fn new(error: TypeError, span: Span, node: Option<NodeId>) -> Self {
+        Self { error, span, node, labels: Vec::new() }
+    }
+
+    /// Add a secondary label
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    /// Machine-readable error code
+    pub fn code(&self) -> &'static str {
+        self.error.code()
+    }
+
+    /// Check if this is a warning rather than an error
+    pub fn is_warning(&self) -> bool {
+        self.error.is_warning()
+    }
+}
+
+impl fmt::Display for TypeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for TypeDiagnostic {}
+
+impl miette::Diagnostic for TypeDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.error.code()))
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(if self.is_warning() { miette::Severity::Warning } else { miette::Severity::Error })
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let source_span = |span: &Span| SourceSpan::from(span.range());
+        let primary = LabeledSpan::new_primary_with_span(None, source_span(&self.span));
+        let secondary = self
+            .labels
+            .iter()
+            .map(move |label| LabeledSpan::new_with_span(Some(label.message.clone()), source_span(&label.span)));
+        Some(Box::new(std::iter::once(primary).chain(secondary)))
+    }
+}
+
+/// Name resolution error or warning
+#[derive(Error, Debug, Clone)]
+pub enum ResolveError {
+    /// Name with no declaration in scope
+    #[error("undefined name: '{name}'")]
+    Undefined {
+        /// Unresolved name
+        name: String,
+        /// Span of the use
+        span: Span,
+    },
+
+    /// Name declared twice in the same scope
+    #[error("duplicate definition of '{name}'")]
+    Duplicate {
+        /// Declared name
+        name: String,
+        /// Span of the second declaration
+        span: Span,
+        /// Span of the first declaration
+        previous: Span,
+    },
+
+    /// Name used before its declaration in the same block
+    #[error("'{name}' is used before it is declared")]
+    UseBeforeDeclare {
+        /// Used name
+        name: String,
+        /// Span of the use
+        span: Span,
+        /// Span of the later declaration
+        declared: Span,
+    },
+
+    /// Declaration hiding an earlier variable of the same name
+    #[error("'{name}' shadows an earlier variable")]
+    Shadowed {
+        /// Declared name
+        name: String,
+        /// Span of the new declaration
+        span: Span,
+        /// Span of the hidden declaration
+        previous: Span,
+    },
+
+    /// Module nested deeper than the configured limit
+    #[error("nesting exceeds the maximum depth of {max_depth}")]
+    RecursionLimit {
+        /// Configured maximum nesting depth
+        max_depth: usize,
+        /// Span of the first node past the limit
+        span: Span,
+    },
+}
+
+impl ResolveError {
+    /// Check if this is a warning rather than an error
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ResolveError::Shadowed { .. })
+    }
+
+    /// Span the diagnostic points at
+    pub fn span(&self) -> Span {
+        match self {
+            ResolveError::Undefined { span, .. }
+            | ResolveError::Duplicate { span, .. }
+            | ResolveError::UseBeforeDeclare { span, .. }
+            | ResolveError::Shadowed { span, .. }
+            | ResolveError::RecursionLimit { span, .. } => *span,
+        }
+    }
 }
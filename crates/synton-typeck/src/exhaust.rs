@@ -0,0 +1,289 @@
+//! Exhaustiveness and reachability of `match` arms
+//!
+//! Uses the usefulness algorithm of Maranget, "Warnings for pattern
+//! matching" (2007). Patterns are reduced to constructors applied to
+//! sub-patterns; a row of patterns is useful against the rows above it if
+//! some value matches it and none of them. An arm is unreachable if its
+//! pattern is not useful against the unguarded arms before it, and a match
+//! is exhaustive if a wildcard is not useful against its unguarded arms.
+//! When it is not, the values it misses are rebuilt as patterns.
+//!
+//! Which constructors make up a type is read off the patterns themselves:
+//! `true` is one of two booleans, `(some p)` one of two `maybe`s, and a
+//! variant one of the variants of its enum. Integers, strings and slices
+//! have unboundedly many constructors, so only a wildcard covers them.
+
+use synton_ast::{Literal, Pattern};
+
+use crate::resolve::fieldless_variant;
+
+/// What the check needs to know about declared types
+pub(crate) trait Declarations {
+    /// Variants of the enum a variant belongs to, with their number of
+    /// fields, in declaration order
+    fn siblings(&self, variant: &str) -> Option<Vec<(String, usize)>>;
+
+    /// Field names of a struct, in declaration order
+    fn fields(&self, name: &str) -> Option<Vec<String>>;
+}
+
+/// Result of checking the arms of a `match`
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    /// Patterns for the values no arm matches
+    pub missing: Vec<String>,
+    /// Indices of the arms no value reaches
+    pub unreachable: Vec<usize>,
+}
+
+/// Head of a pattern that tells values apart
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Some,
+    None,
+    Variant(String),
+    Tuple(usize),
+    Struct(String),
+    Slice(usize),
+    /// Any other literal, compared by its source form
+    Literal(String),
+}
+
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+/// Check the arms of a `match`, given as their pattern and whether they
+/// have a guard
+pub(crate) fn check(decls: &dyn Declarations, arms: &[(&Pattern, bool)]) -> Report {
+    let cx = Cx { decls };
+    let mut report = Report::default();
+    let mut rows: Vec<Vec<Pat>> = Vec::new();
+    for (i, (pattern, guarded)) in arms.iter().enumerate() {
+        let row = vec![cx.lower(pattern)];
+        if !cx.useful(&rows, &row) {
+            report.unreachable.push(i);
+        }
+        // A guard may fail, so a guarded arm covers nothing for the arms
+        // after it
+        if !guarded {
+            rows.push(row);
+        }
+    }
+    report.missing = cx.witnesses(&rows, 1).into_iter().map(|mut witness| cx.show(&witness.remove(0))).collect();
+    report
+}
+
+struct Cx<'a> {
+    decls: &'a dyn Declarations,
+}
+
+impl Cx<'_> {
+    fn lower(&self, pattern: &Pattern) -> Pat {
+        let ctor = |ctor, args: &[Pattern]| Pat::Ctor(ctor, args.iter().map(|arg| self.lower(arg)).collect());
+        // The module may not have been resolved
+        if let Some(name) = fieldless_variant(pattern, |name| self.decls.siblings(name).is_some()) {
+            return self.lower(&Pattern::Variant { name: name.clone(), args: Vec::new() });
+        }
+        match pattern {
+            Pattern::Wildcard | Pattern::Bind(_) => Pat::Wild,
+            Pattern::Literal(Literal::Bool(b)) => Pat::Ctor(Ctor::Bool(*b), Vec::new()),
+            Pattern::Literal(literal) => Pat::Ctor(Ctor::Literal(format!("{:?}", literal)), Vec::new()),
+            Pattern::Or(alternatives) => Pat::Or(alternatives.iter().map(|p| self.lower(p)).collect()),
+            Pattern::Struct { ty, fields } => match self.decls.fields(ty) {
+                Some(declared) => {
+                    let args = declared
+                        .iter()
+                        .map(|name| match fields.iter().find(|(field, _)| field == name) {
+                            Some((_, pattern)) => self.lower(pattern),
+                            None => Pat::Wild,
+                        })
+                        .collect();
+                    Pat::Ctor(Ctor::Struct(ty.clone()), args)
+                }
+                // Already reported
+                None => Pat::Wild,
+            },
+            Pattern::Tuple(items) => ctor(Ctor::Tuple(items.len()), items),
+            Pattern::Slice(items) => ctor(Ctor::Slice(items.len()), items),
+            Pattern::Variant { name, args } => {
+                match self.decls.siblings(name).and_then(|siblings| siblings.into_iter().find(|(v, _)| v == name)) {
+                    Some((_, arity)) => {
+                        // A wrong number of fields is already reported
+                        let mut args: Vec<_> = args.iter().take(arity).map(|arg| self.lower(arg)).collect();
+                        args.resize(arity, Pat::Wild);
+                        Pat::Ctor(Ctor::Variant(name.clone()), args)
+                    }
+                    None => Pat::Wild,
+                }
+            }
+            Pattern::Some(inner) => ctor(Ctor::Some, std::slice::from_ref(inner)),
+            Pattern::None => Pat::Ctor(Ctor::None, Vec::new()),
+        }
+    }
+
+    /// Every constructor of the type `ctor` belongs to, with its arity, or
+    /// `None` if there are too many to list
+    fn all_ctors(&self, ctor: &Ctor) -> Option<Vec<(Ctor, usize)>> {
+        match ctor {
+            Ctor::Bool(_) => Some(vec![(Ctor::Bool(true), 0), (Ctor::Bool(false), 0)]),
+            Ctor::Some | Ctor::None => Some(vec![(Ctor::Some, 1), (Ctor::None, 0)]),
+            Ctor::Variant(name) => self.decls.siblings(name).map(|siblings| {
+                siblings.into_iter().map(|(variant, arity)| (Ctor::Variant(variant), arity)).collect()
+            }),
+            Ctor::Tuple(n) => Some(vec![(Ctor::Tuple(*n), *n)]),
+            Ctor::Struct(name) => Some(vec![(ctor.clone(), self.decls.fields(name).map_or(0, |fields| fields.len()))]),
+            Ctor::Slice(_) | Ctor::Literal(_) => None,
+        }
+    }
+
+    /// Constructors at the head of some row
+    fn head_ctors(&self, rows: &[Vec<Pat>]) -> Vec<Ctor> {
+        fn collect(pat: &Pat, out: &mut Vec<Ctor>) {
+            match pat {
+                Pat::Wild => {}
+                Pat::Ctor(ctor, _) => {
+                    if !out.contains(ctor) {
+                        out.push(ctor.clone());
+                    }
+                }
+                Pat::Or(alternatives) => alternatives.iter().for_each(|alt| collect(alt, out)),
+            }
+        }
+        let mut out = Vec::new();
+        for row in rows {
+            collect(&row[0], &mut out);
+        }
+        out
+    }
+
+    /// The constructors of the head column, if the heads use all of them
+    fn complete(&self, heads: &[Ctor]) -> Option<Vec<(Ctor, usize)>> {
+        let all = self.all_ctors(heads.first()?)?;
+        all.iter().all(|(ctor, _)| heads.contains(ctor)).then_some(all)
+    }
+
+    /// Check if some value matches `row` and none of `rows`
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+        match head {
+            Pat::Or(alternatives) => alternatives.iter().any(|alt| self.useful(rows, &cons(alt.clone(), rest))),
+            Pat::Ctor(ctor, args) => {
+                let row: Vec<_> = args.iter().chain(rest).cloned().collect();
+                self.useful(&specialize(rows, ctor, args.len()), &row)
+            }
+            Pat::Wild => match self.complete(&self.head_ctors(rows)) {
+                Some(all) => all.iter().any(|(ctor, arity)| {
+                    let row: Vec<_> = std::iter::repeat_n(Pat::Wild, *arity).chain(rest.iter().cloned()).collect();
+                    self.useful(&specialize(rows, ctor, *arity), &row)
+                }),
+                None => self.useful(&default(rows), rest),
+            },
+        }
+    }
+
+    /// Rows of `arity` patterns matching values that no row of `rows`
+    /// matches
+    fn witnesses(&self, rows: &[Vec<Pat>], arity: usize) -> Vec<Vec<Pat>> {
+        if arity == 0 {
+            return if rows.is_empty() { vec![Vec::new()] } else { Vec::new() };
+        }
+        let heads = self.head_ctors(rows);
+        if let Some(all) = self.complete(&heads) {
+            let mut out = Vec::new();
+            for (ctor, n) in all {
+                for mut witness in self.witnesses(&specialize(rows, &ctor, n), n + arity - 1) {
+                    let rest = witness.split_off(n);
+                    out.push(cons(Pat::Ctor(ctor.clone(), witness), &rest));
+                }
+            }
+            return out;
+        }
+
+        let rest = self.witnesses(&default(rows), arity - 1);
+        if rest.is_empty() {
+            return rest;
+        }
+        // Name the constructors that are missing, if they can be listed
+        let missing: Vec<Pat> = match heads.first().and_then(|ctor| self.all_ctors(ctor)) {
+            Some(all) => all
+                .into_iter()
+                .filter(|(ctor, _)| !heads.contains(ctor))
+                .map(|(ctor, n)| Pat::Ctor(ctor, vec![Pat::Wild; n]))
+                .collect(),
+            None => vec![Pat::Wild],
+        };
+        rest.iter().flat_map(|rest| missing.iter().map(|head| cons(head.clone(), rest))).collect()
+    }
+
+    /// Source form of a witness
+    fn show(&self, pat: &Pat) -> String {
+        let list = |pats: &[Pat], sep: &str| pats.iter().map(|p| self.show(p)).collect::<Vec<_>>().join(sep);
+        match pat {
+            Pat::Wild => "_".into(),
+            Pat::Or(alternatives) => format!("(| {})", list(alternatives, " ")),
+            Pat::Ctor(ctor, args) => match ctor {
+                Ctor::Bool(b) => b.to_string(),
+                Ctor::Some => format!("(some {})", list(args, " ")),
+                Ctor::None => "(none)".into(),
+                Ctor::Variant(name) if args.is_empty() => name.clone(),
+                Ctor::Variant(name) => format!("({} {})", name, list(args, " ")),
+                Ctor::Tuple(1) => format!("({},)", list(args, "")),
+                Ctor::Tuple(_) => format!("({})", list(args, ", ")),
+                Ctor::Struct(name) => {
+                    let fields = self.decls.fields(name).unwrap_or_default();
+                    let fields: Vec<_> =
+                        fields.iter().zip(args).map(|(field, p)| format!(" {}: {}", field, self.show(p))).collect();
+                    format!("({}{})", name, fields.concat())
+                }
+                Ctor::Slice(_) => format!("[{}]", list(args, " ")),
+                Ctor::Literal(literal) => literal.clone(),
+            },
+        }
+    }
+}
+
+fn cons(head: Pat, rest: &[Pat]) -> Vec<Pat> {
+    std::iter::once(head).chain(rest.iter().cloned()).collect()
+}
+
+/// Rows that match values built with `ctor`, with its fields in place of
+/// the head
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    fn row(head: &Pat, rest: &[Pat], ctor: &Ctor, arity: usize, out: &mut Vec<Vec<Pat>>) {
+        match head {
+            Pat::Wild => out.push(std::iter::repeat_n(Pat::Wild, arity).chain(rest.iter().cloned()).collect()),
+            Pat::Ctor(head, args) if head == ctor => out.push(args.iter().chain(rest).cloned().collect()),
+            Pat::Ctor(..) => {}
+            Pat::Or(alternatives) => alternatives.iter().for_each(|alt| row(alt, rest, ctor, arity, out)),
+        }
+    }
+    let mut out = Vec::new();
+    for r in rows {
+        row(&r[0], &r[1..], ctor, arity, &mut out);
+    }
+    out
+}
+
+/// Rows that match values built with a constructor no row names, without
+/// the head
+fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    fn row(head: &Pat, rest: &[Pat], out: &mut Vec<Vec<Pat>>) {
+        match head {
+            Pat::Wild => out.push(rest.to_vec()),
+            Pat::Ctor(..) => {}
+            Pat::Or(alternatives) => alternatives.iter().for_each(|alt| row(alt, rest, out)),
+        }
+    }
+    let mut out = Vec::new();
+    for r in rows {
+        row(&r[0], &r[1..], &mut out);
+    }
+    out
+}
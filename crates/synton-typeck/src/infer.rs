@@ -1,52 +1,1401 @@
 //! Type inference
+//!
+//! Hindley–Milner inference in the style of algorithm W. Every expression
+//! gets a type, usually containing inference variables, and the constraints
+//! between types are solved as they arise by unification into a
+//! [`Substitution`]. When a `let` binds a function, the variables its type
+//! does not share with the environment are generalized, so
+//! `(let id = (fn [x] x))` can be used at several types.
+//!
+//! Type names in annotations that are not declared structs, enums or
+//! aliases are generic parameters: `(fn [x: T] x)` has the same type as
+//! `(fn [x] x)`. Generic structs and enums are instantiated at each use:
+//! a struct literal or a bare `Pair` in an annotation gets fresh inference
+//! variables for the type arguments, and enum variants are generalized
+//! constructor functions, so `(Just 1)` is an `Option<i32>`.
+//!
+//! Unsuffixed number literals get an inference variable that can only be
+//! solved as an integer or a float type, and defaults to `i32` or `f64`
+//! once checking is done; only then are integer literals checked against
+//! the range of their type. Operands of an arithmetic operator have the
+//! same type, except that the narrower of two integer types of the same
+//! signedness, or `f32` next to `f64`, is widened implicitly. Anything
+//! else needs an `as`, which is checked: numbers convert to each other,
+//! `bool` and `char` to integers and `u8` to `char`.
+//!
+//! A refinement type is checked like its base type. Where a value is
+//! expected to have a declared refinement type, its constraint is checked
+//! too, if a solver is set (see [`crate::refine`]).
+//!
+//! After its arms are checked, a `match` is checked for exhaustiveness and
+//! for arms that cannot be reached (see [`crate::exhaust`]).
+//!
+//! Errors do not stop inference. Each one is recorded as a
+//! [`TypeDiagnostic`] at the innermost expression or statement it concerns,
+//! which then gets the error type. The error type is compatible with every
+//! type, so the mistake is not reported again by the code around it.
 
-use crate::{TResult, TypeChecker, TypeEnv, type_from_literal};
-use synton_ast::{Expr, Type};
+use rustc_hash::{FxHashMap, FxHashSet};
+use synton_ast::types::RefinementType;
+use synton_ast::{
+    AssignTarget, BinaryOp, BuiltinType, Contract, Expr, ExprKind, FnDecl, Literal, MatchArm, NodeId, Param, Pattern,
+    Position, Span, Stmt, StmtKind, Type, TypeKind, UnaryOp,
+};
+
+use crate::error::{Label, TypeDiagnostic, TypeError};
+use crate::borrow::Types;
+use crate::exhaust::{self, Declarations};
+use crate::refine::{Body, Constraint, Refiner};
+use crate::resolve::{fieldless_variant, DeclKind, Symbol};
+use crate::subst::{free_vars, map_children, NumberKind, Substitution};
+use crate::typed::{TypedBinding, TypedModule};
+use crate::{type_from_literal, unit_type, TResult, TypeEnv};
+use synton_contract::Solver;
+
+/// A declared struct
+#[derive(Debug, Clone)]
+struct StructInfo {
+    params: Vec<String>,
+    fields: Vec<(String, Type)>,
+}
+
+/// A declared enum
+#[derive(Debug, Clone)]
+struct EnumInfo {
+    params: Vec<String>,
+    /// Variants and their field types, as written
+    variants: Vec<(String, Vec<Type>)>,
+}
 
 /// Type inference engine
 pub struct TypeInfer {
     env: TypeEnv,
+    subst: Substitution,
     next_ty_var: u32,
+    structs: FxHashMap<String, StructInfo>,
+    enums: FxHashMap<String, EnumInfo>,
+    /// Enum of each variant
+    variants: FxHashMap<String, String>,
+    aliases: FxHashMap<String, (Vec<String>, Type)>,
+    /// Result types of the enclosing loops, innermost last
+    loops: Vec<Type>,
+    /// Return types of the enclosing functions, innermost last
+    returns: Vec<Type>,
+    diagnostics: Vec<TypeDiagnostic>,
+    /// Type of each expression with a node id
+    types: FxHashMap<NodeId, Type>,
+    /// Unsuffixed integer literals, checked against their type once it is
+    /// known: variable, value, span and node
+    literals: Vec<(u32, i64, Span, Option<NodeId>)>,
+    bindings: Vec<TypedBinding>,
+    /// Checks refinement constraints, if they are checked
+    refiner: Option<Refiner>,
+    /// Parsed refinement constraints, by variable and source text. `None`
+    /// if the constraint does not parse.
+    constraints: FxHashMap<(String, String), Option<Expr>>,
+    depth: usize,
+    max_depth: usize,
 }
 
 impl TypeInfer {
     pub fn new() -> Self {
         Self {
             env: TypeEnv::new(),
+            subst: Substitution::new(),
             next_ty_var: 0,
+            structs: FxHashMap::default(),
+            enums: FxHashMap::default(),
+            variants: FxHashMap::default(),
+            aliases: FxHashMap::default(),
+            loops: Vec::new(),
+            returns: Vec::new(),
+            diagnostics: Vec::new(),
+            types: FxHashMap::default(),
+            literals: Vec::new(),
+            bindings: Vec::new(),
+            refiner: None,
+            constraints: FxHashMap::default(),
+            depth: 0,
+            max_depth: crate::depth::MAX_DEPTH,
         }
     }
 
+    /// Set the maximum nesting depth of statements and expressions
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Check refinement constraints, deciding them with `solver`
+    pub fn with_solver(mut self, solver: Box<dyn Solver>) -> Self {
+        self.refiner = Some(Refiner::new(solver));
+        self
+    }
+
     /// Fresh type variable
     pub fn fresh_var(&mut self) -> Type {
+        self.fresh(Span::new(Position::start(), Position::start()))
+    }
+
+    fn fresh(&mut self, span: Span) -> Type {
         let id = self.next_ty_var;
         self.next_ty_var += 1;
-        Type::new(synton_ast::TypeKind::Inference(id), synton_ast::Span::new(
-            synton_ast::Position::start(),
-            synton_ast::Position::start(),
-        ))
+        Type::new(TypeKind::Inference(id), span)
+    }
+
+    /// Fresh type variable that can only be solved as a number of `kind`
+    fn fresh_number(&mut self, kind: NumberKind, span: Span) -> (u32, Type) {
+        let id = self.next_ty_var;
+        self.subst.mark_number(id, kind);
+        (id, self.fresh(span))
+    }
+
+    /// Give the number literals whose type is still open their default
+    /// type, and report the integer literals that do not fit their type.
+    /// Done at the end of checking a module, statement or expression.
+    pub fn default_literals(&mut self) {
+        self.subst.default_numbers();
+        for (var, value, span, node) in std::mem::take(&mut self.literals) {
+            let ty = self.apply(&Type::new(TypeKind::Inference(var), span));
+            let Some(builtin) = builtin_of(&ty) else { continue };
+            if let Some((min, max)) = builtin.int_range() {
+                if !(min..=max).contains(&(value as i128)) {
+                    let error = TypeError::LiteralOutOfRange { value: value as i128, ty: builtin.name().to_string() };
+                    self.report(TypeDiagnostic::new(error, span, node));
+                }
+            }
+        }
+    }
+
+    /// Bindings in scope
+    pub fn env(&self) -> &TypeEnv {
+        &self.env
+    }
+
+    /// Errors found so far, in the order they were found
+    pub fn diagnostics(&self) -> &[TypeDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Add errors found by other passes
+    pub(crate) fn extend_diagnostics(&mut self, diagnostics: Vec<TypeDiagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Take the errors found so far
+    pub fn take_diagnostics(&mut self) -> Vec<TypeDiagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Types and warnings found so far, with every solved inference
+    /// variable replaced
+    pub fn typed_module(&self) -> TypedModule {
+        let types = self.types.iter().map(|(node, ty)| (*node, self.apply(ty))).collect();
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|binding| TypedBinding { ty: self.apply(&binding.ty), ..binding.clone() })
+            .collect();
+        let warnings = self.diagnostics.iter().filter(|d| d.is_warning()).cloned().collect();
+        TypedModule { types, bindings, warnings, effects: FxHashMap::default() }
+    }
+
+    /// Declare a name with a type, generalizing it
+    pub fn declare(&mut self, name: impl Into<String>, ty: Type) {
+        let quantified = self.generalize(&ty);
+        self.env.decl_generic(name.into(), quantified, ty);
+    }
+
+    /// Replace solved inference variables in a type
+    pub fn apply(&self, ty: &Type) -> Type {
+        self.subst.apply(ty)
+    }
+
+    /// Unify two types
+    pub fn unify(&mut self, expected: &Type, found: &Type) -> TResult<()> {
+        self.subst.unify(expected, found)
     }
 
     /// Infer type of expression
-    pub fn infer(&mut self, expr: &Expr) -> TResult<Type> {
-        match &expr.kind {
-            synton_ast::ExprKind::Literal(lit) => {
-                Ok(type_from_literal(lit, expr.span))
-            }
-            synton_ast::ExprKind::Var { name, .. } => {
-                self.env.get(name)
-                    .map(|b| b.ty.clone())
-                    .ok_or_else(|| crate::error::TypeError::UndefinedVar {
-                        name: name.clone(),
+    pub fn infer(&mut self, expr: &Expr) -> Type {
+        if self.too_deep(expr.span, expr.id) {
+            return Type::new(TypeKind::Error, expr.span);
+        }
+
+        self.depth += 1;
+        let result = self.infer_kind(expr);
+        self.depth -= 1;
+        let ty = match result {
+            Ok(ty) => self.subst.apply(&ty),
+            Err(error) => {
+                self.report(TypeDiagnostic::new(error, expr.span, expr.id));
+                Type::new(TypeKind::Error, expr.span)
+            }
+        };
+        if let Some(id) = expr.id {
+            self.types.insert(id, ty.clone());
+        }
+        ty
+    }
+
+    /// Infer the types in a statement. Expression statements have the type
+    /// of their expression, `return`, `break` and `continue` are `!`, and
+    /// everything else is unit.
+    pub fn infer_stmt(&mut self, stmt: &Stmt) -> Type {
+        if self.too_deep(stmt.span, stmt_node(stmt)) {
+            return Type::new(TypeKind::Error, stmt.span);
+        }
+
+        self.depth += 1;
+        let result = self.infer_stmt_kind(stmt);
+        self.depth -= 1;
+        match result {
+            Ok(ty) => ty,
+            Err(error) => {
+                self.report(TypeDiagnostic::new(error, stmt.span, stmt_node(stmt)));
+                Type::new(TypeKind::Error, stmt.span)
+            }
+        }
+    }
+
+    fn infer_stmt_kind(&mut self, stmt: &Stmt) -> TResult<Type> {
+        let span = stmt.span;
+        match &stmt.kind {
+            StmtKind::Expr(expr) => return Ok(self.infer(expr)),
+            StmtKind::Let { name, id, ty: annotation, init, mutable } => {
+                let declared = annotation.as_ref().map(|ty| self.annotation(ty, &mut FxHashMap::default()));
+                let ty = match (init, declared) {
+                    (Some(init), Some(declared)) => {
+                        let because = annotation.as_ref().map(|ty| Label {
+                            span: ty.span,
+                            message: "expected because of this annotation".into(),
+                        });
+                        self.expect_refined(&declared, init, because);
+                        declared
+                    }
+                    (Some(init), None) => self.infer(init),
+                    (None, Some(declared)) => declared,
+                    (None, None) => {
+                        // Declare it anyway, so its uses are not reported too
+                        self.env.decl(name.clone(), Type::new(TypeKind::Error, span));
+                        return Err(TypeError::Uninitialized { name: name.clone() });
+                    }
+                };
+                // Only syntactic functions are generalized: a generic value
+                // computed at run time, or one that can be reassigned, could
+                // be used at two incompatible types
+                let is_fn = matches!(init.as_deref().map(|init| &init.kind), Some(ExprKind::Lambda { .. }));
+                let refinement = self.refinement(&ty);
+                if let Some(refiner) = &mut self.refiner {
+                    let value = init.as_deref().filter(|_| !mutable).map(|init| refiner.term(init));
+                    refiner.bind(name, value, refinement);
+                }
+                self.record(name, DeclKind::Let, id.map(Symbol::Var), span, &ty);
+                if is_fn && !mutable {
+                    self.declare(name.clone(), ty);
+                } else {
+                    self.env.decl(name.clone(), ty);
+                }
+            }
+            StmtKind::Const { name, ty, value } => {
+                let declared = self.annotation(ty, &mut FxHashMap::default());
+                let because = Label { span: ty.span, message: "expected because of this annotation".into() };
+                self.expect_refined(&declared, value, Some(because));
+                let refinement = self.refinement(&declared);
+                if let Some(refiner) = &mut self.refiner {
+                    let value = refiner.term(value);
+                    refiner.bind(name, Some(value), refinement);
+                }
+                self.record(name, DeclKind::Const, None, span, &declared);
+                self.declare(name.clone(), declared);
+            }
+            StmtKind::Assign { target: target_expr, value } => {
+                let target = match target_expr {
+                    AssignTarget::Var(name) => self.lookup(name, span)?,
+                    AssignTarget::Index { base, index } => self.infer_index(base, index, span)?,
+                    AssignTarget::Field { base, name } => self.infer_field(base, name, span)?,
+                    AssignTarget::Deref(inner) => self.infer_deref(inner, span)?,
+                };
+                self.expect_refined(&target, value, None);
+                if let (AssignTarget::Var(name), Some(refiner)) = (target_expr, &mut self.refiner) {
+                    refiner.forget(name);
+                }
+            }
+            StmtKind::Block(stmts) => {
+                return Ok(self.infer_block(stmts, None, span));
+            }
+            StmtKind::If { cond, then_branch, else_branch } => {
+                self.expect_bool(cond);
+                self.scoped(|infer| infer.infer_stmt(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.scoped(|infer| infer.infer_stmt(else_branch));
+                }
+            }
+            StmtKind::Loop { body } => {
+                self.forget_assigned(Body::Stmt(body));
+                self.in_loop(span, |infer| {
+                    infer.scoped(|infer| infer.infer_stmt(body));
+                });
+            }
+            StmtKind::While { cond, body } => {
+                self.forget_assigned(Body::Stmt(body));
+                self.expect_bool(cond);
+                self.in_loop(span, |infer| {
+                    infer.scoped(|infer| infer.infer_stmt(body));
+                });
+            }
+            StmtKind::For { var, iter, body } => {
+                let item = self.fresh(span);
+                self.expect(&Type::new(TypeKind::List(Box::new(item.clone())), span), iter);
+                self.forget_assigned(Body::Stmt(body));
+                self.in_loop(span, |infer| {
+                    infer.scoped(|infer| {
+                        infer.record(var, DeclKind::Loop, None, span, &item);
+                        infer.env.decl(var.clone(), item);
+                        infer.infer_stmt(body);
                     })
+                });
+            }
+            StmtKind::Break(value) => {
+                self.infer_break(value.as_deref(), span)?;
+                return Ok(Type::new(TypeKind::Never, span));
+            }
+            StmtKind::Continue => return Ok(Type::new(TypeKind::Never, span)),
+            StmtKind::Return(value) => {
+                self.infer_return(value.as_deref(), span)?;
+                return Ok(Type::new(TypeKind::Never, span));
+            }
+            StmtKind::FnDecl(decl) => {
+                let ty = self.infer_fn(decl)?;
+                // The forward declaration is monomorphic; drop it so it does
+                // not keep the function's own variables from generalizing
+                self.env.remove(&decl.name);
+                self.record(&decl.name, DeclKind::Fn, decl.id.map(Symbol::Fn), decl.span, &ty);
+                self.declare(decl.name.clone(), ty);
+            }
+            StmtKind::Contract(contract) => {
+                self.expect_bool(&contract.expr);
+            }
+            StmtKind::StructDecl(_) | StmtKind::EnumDecl(_) | StmtKind::TypeAlias { .. } => {
+                self.declare_items(std::slice::from_ref(stmt));
+            }
+            StmtKind::Empty | StmtKind::Error => {}
+        }
+        Ok(unit_type(span))
+    }
+
+    /// Infer a statement sequence in a new scope
+    pub fn infer_block(&mut self, stmts: &[Stmt], tail: Option<&Expr>, span: Span) -> Type {
+        self.scoped(|infer| {
+            infer.declare_items(stmts);
+            let mut last = unit_type(span);
+            for stmt in stmts {
+                last = infer.infer_stmt(stmt);
+            }
+            match tail {
+                Some(tail) => infer.infer(tail),
+                // A block that always leaves early has no value of its own
+                None if matches!(last.kind, TypeKind::Never) => last,
+                None => unit_type(span),
+            }
+        })
+    }
+
+    /// Record the declarations among some statements, so that they can be
+    /// used before the point where they are declared. Types come first, so
+    /// that signatures can mention them. Functions get their annotated
+    /// signature, with inference variables for what is left out, and enum
+    /// variants a generalized constructor.
+    pub fn declare_items(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::StructDecl(decl) => {
+                    let fields = decl.fields.iter().map(|field| (field.name.clone(), field.ty.clone())).collect();
+                    self.structs.insert(decl.name.clone(), StructInfo { params: decl.params.clone(), fields });
+                }
+                StmtKind::EnumDecl(decl) => {
+                    let variants = decl.variants.iter().map(|variant| (variant.name.clone(), variant.types.clone()));
+                    let info = EnumInfo { params: decl.params.clone(), variants: variants.collect() };
+                    for (variant, _) in &info.variants {
+                        self.variants.insert(variant.clone(), decl.name.clone());
+                    }
+                    self.enums.insert(decl.name.clone(), info);
+                }
+                StmtKind::TypeAlias { name, params, ty } => {
+                    self.aliases.insert(name.clone(), (params.clone(), ty.clone()));
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::FnDecl(decl) => {
+                    let signature = self.signature(decl);
+                    self.env.decl(decl.name.clone(), signature);
+                }
+                StmtKind::EnumDecl(decl) => {
+                    let args: Vec<_> = decl.params.iter().map(|_| self.fresh(decl.span)).collect();
+                    let mut generics: FxHashMap<_, _> = decl.params.iter().cloned().zip(args.iter().cloned()).collect();
+                    let enum_ty = Type::new(TypeKind::Named { name: decl.name.clone(), args }, decl.span);
+                    for variant in &decl.variants {
+                        let ty = if variant.types.is_empty() {
+                            enum_ty.clone()
+                        } else {
+                            let params = variant.types.iter().map(|ty| self.annotation(ty, &mut generics)).collect();
+                            Type::new(TypeKind::Fn { params, ret: Box::new(enum_ty.clone()) }, variant.span)
+                        };
+                        self.declare(variant.name.clone(), ty);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn infer_kind(&mut self, expr: &Expr) -> TResult<Type> {
+        let span = expr.span;
+        let builtin = |ty| Type::builtin(ty, span);
+        match &expr.kind {
+            ExprKind::Literal(lit) => Ok(self.literal(lit, span, expr.id)),
+            ExprKind::Var { name, .. } => self.lookup(name, span),
+            ExprKind::Unary { op, arg } => match op {
+                UnaryOp::Not => {
+                    self.expect_bool(arg);
+                    Ok(builtin(BuiltinType::Bool))
+                }
+                UnaryOp::Neg => {
+                    let ty = self.infer(arg);
+                    self.check_operand(op.token(), &ty, |b| b.is_numeric() && b.is_signed())?;
+                    Ok(ty)
+                }
+                UnaryOp::BitNot => {
+                    let ty = self.infer(arg);
+                    self.check_operand(op.token(), &ty, |b| b.is_integer() || b == BuiltinType::Bool)?;
+                    Ok(ty)
+                }
+                UnaryOp::Ref => Ok(Type::new(TypeKind::Ref(Box::new(self.infer(arg))), span)),
+                UnaryOp::Deref => self.infer_deref(arg, span),
+            },
+            ExprKind::Binary { op: BinaryOp::And | BinaryOp::Or, left, right } => {
+                self.expect_bool(left);
+                self.expect_bool(right);
+                Ok(builtin(BuiltinType::Bool))
+            }
+            ExprKind::Binary { op, left, right } => {
+                let ty = self.infer_operands(left, right);
+                let valid: fn(BuiltinType) -> bool = match op {
+                    BinaryOp::Add => |b| b.is_numeric() || b == BuiltinType::String,
+                    BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+                        |b| b.is_integer() || b == BuiltinType::Bool
+                    }
+                    BinaryOp::Shl | BinaryOp::Shr => BuiltinType::is_integer,
+                    _ => BuiltinType::is_numeric,
+                };
+                self.check_operand(op.token(), &ty, valid)?;
+                Ok(ty)
+            }
+            ExprKind::Compare { left, right, .. } => {
+                self.infer_operands(left, right);
+                Ok(builtin(BuiltinType::Bool))
+            }
+            ExprKind::Call { callee, args } => self.infer_call(callee, args, span),
+            ExprKind::MethodCall { object, args, .. } => {
+                // Methods are not declared anywhere yet, so only the
+                // receiver and the arguments are checked
+                self.infer(object);
+                for arg in args {
+                    self.infer(arg);
+                }
+                Ok(self.fresh(span))
+            }
+            ExprKind::Index { base, index } => self.infer_index(base, index, span),
+            ExprKind::Field { base, name } => self.infer_field(base, name, span),
+            ExprKind::Array(items) => {
+                let item = self.fresh(span);
+                for value in items {
+                    self.expect(&item, value);
+                }
+                Ok(Type::new(TypeKind::List(Box::new(item)), span))
+            }
+            ExprKind::Tuple(items) => {
+                let items = items.iter().map(|item| self.infer(item)).collect();
+                Ok(Type::new(TypeKind::Tuple(items), span))
+            }
+            ExprKind::Struct { ty, fields } => {
+                let (instance, declared) = match self.struct_instance(ty, span) {
+                    Ok(instance) => instance,
+                    Err(error) => {
+                        for (_, value) in fields {
+                            self.infer(value);
+                        }
+                        return Err(error);
+                    }
+                };
+                for (name, value) in fields {
+                    match declared.get(name) {
+                        Some(expected) => {
+                            self.expect(expected, value);
+                        }
+                        None => {
+                            self.infer(value);
+                            let error = TypeError::NoField { ty: ty.clone(), field: name.clone() };
+                            self.report(TypeDiagnostic::new(error, value.span, value.id));
+                        }
+                    }
+                }
+                Ok(instance)
+            }
+            ExprKind::Lambda { params, body, .. } => {
+                let mut generics = FxHashMap::default();
+                Ok(self.infer_function(params, None, &mut generics, span, &[], Some(body)))
+            }
+            ExprKind::Block(stmts, tail) => Ok(self.infer_block(stmts, tail.as_deref(), span)),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                self.expect_bool(cond);
+                let then_ty = self.infer(then_branch);
+                match else_branch {
+                    Some(else_branch) => {
+                        let because = Label {
+                            span: then_branch.span,
+                            message: format!("then branch has type {}", self.subst.display(&then_ty)),
+                        };
+                        let else_ty = self.expect_because(&then_ty, else_branch, Some(because));
+                        // A branch that leaves early does not decide the type
+                        match then_ty.kind {
+                            TypeKind::Never => Ok(else_ty),
+                            _ => Ok(then_ty),
+                        }
+                    }
+                    // Without an else branch there is no value
+                    None => Ok(unit_type(span)),
+                }
+            }
+            ExprKind::Loop { body } => {
+                self.forget_assigned(Body::Expr(body));
+                Ok(self.in_loop(span, |infer| {
+                    infer.infer(body);
+                }))
+            }
+            ExprKind::Break(value) => {
+                self.infer_break(value.as_deref(), span)?;
+                Ok(Type::new(TypeKind::Never, span))
+            }
+            ExprKind::Continue => Ok(Type::new(TypeKind::Never, span)),
+            ExprKind::Return(value) => {
+                self.infer_return(value.as_deref(), span)?;
+                Ok(Type::new(TypeKind::Never, span))
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.infer(scrutinee);
+                let result = self.fresh(span);
+                for arm in arms {
+                    self.scoped(|infer| {
+                        infer.bind_pattern(&arm.pattern, &scrutinee, expr);
+                        if let Some(guard) = &arm.guard {
+                            infer.expect_bool(guard);
+                        }
+                        infer.expect(&result, &arm.body);
+                    });
+                }
+                self.check_arms(arms, expr);
+                Ok(result)
+            }
+            ExprKind::Some(inner) => Ok(Type::new(TypeKind::Maybe(Box::new(self.infer(inner))), span)),
+            ExprKind::None => Ok(Type::new(TypeKind::Maybe(Box::new(self.fresh(span))), span)),
+            ExprKind::As { expr, ty } => {
+                let from = self.infer(expr);
+                let to = self.annotation(ty, &mut FxHashMap::default());
+                self.check_cast(&from, &to)?;
+                // At run time a negative integer wraps to the width of u8
+                // and u32, and traps as a u64, which is held in an i64. A
+                // negative constant is rejected for all three.
+                let constant = match &expr.kind {
+                    ExprKind::Literal(Literal::Integer(value)) => Some(*value as i128),
+                    ExprKind::Unary { op: UnaryOp::Neg, arg } => match arg.kind {
+                        ExprKind::Literal(Literal::Integer(value)) => Some(-(value as i128)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let unsigned = builtin_of(&to).is_some_and(|b| b.is_integer() && !b.is_signed());
+                match constant {
+                    Some(value) if unsigned && value < 0 => {
+                        Err(TypeError::LiteralOutOfRange { value, ty: to.to_string() })
+                    }
+                    _ => Ok(to),
+                }
+            }
+            ExprKind::SizeOf(_) => Ok(builtin(BuiltinType::U64)),
+            // Already reported by the parser
+            ExprKind::Error => Ok(Type::new(TypeKind::Error, span)),
+        }
+    }
+
+    /// Type of a literal. Unsuffixed numbers get a variable that can only
+    /// be solved as a number of their kind.
+    fn literal(&mut self, lit: &Literal, span: Span, node: Option<NodeId>) -> Type {
+        match lit {
+            Literal::Integer(value) => {
+                let (var, ty) = self.fresh_number(NumberKind::Integer, span);
+                self.literals.push((var, *value, span, node));
+                ty
+            }
+            Literal::Float(_) => self.fresh_number(NumberKind::Float, span).1,
+            _ => type_from_literal(lit, span),
+        }
+    }
+
+    /// Infer the operands of a binary operator, which have the same type
+    /// once the narrower of two numbers is widened. Returns that type.
+    fn infer_operands(&mut self, left: &Expr, right: &Expr) -> Type {
+        let left_ty = self.infer(left);
+        let right_ty = self.infer(right);
+        if let (Some(l), Some(r)) = (builtin_of(&left_ty), builtin_of(&right_ty)) {
+            if l.widens_to(r) {
+                return right_ty;
+            }
+            if r.widens_to(l) {
+                return left_ty;
+            }
+        }
+        self.unify_at(&left_ty, &right_ty, right);
+        self.apply(&left_ty)
+    }
+
+    /// Check that an operator is defined for its operand type. Types not
+    /// known yet are accepted, number literals as their default type.
+    fn check_operand(&self, op: &str, ty: &Type, valid: fn(BuiltinType) -> bool) -> TResult<()> {
+        let ty = self.apply(ty);
+        let accepted = match &base(&ty).kind {
+            TypeKind::Inference(var) => self.subst.number_kind(*var).is_none_or(|kind| valid(kind.default_type())),
+            TypeKind::Builtin(BuiltinType::Dyn) | TypeKind::Error | TypeKind::Never => true,
+            TypeKind::Builtin(b) => valid(*b),
+            _ => false,
+        };
+        if accepted {
+            Ok(())
+        } else {
+            Err(TypeError::InvalidOperand { op: op.to_string(), ty: self.subst.display(&ty).to_string() })
+        }
+    }
+
+    /// Check that `as` converts `from` to `to`: numbers convert to each
+    /// other, `bool` and `char` to integers, `u8` to `char`, and every
+    /// type to itself
+    fn check_cast(&mut self, from: &Type, to: &Type) -> TResult<()> {
+        let (from, to) = (self.apply(from), self.apply(to));
+        let invalid = || TypeError::InvalidCast { from: from.to_string(), to: to.to_string() };
+        let number = match &base(&from).kind {
+            TypeKind::Inference(var) => self.subst.number_kind(*var),
+            _ => None,
+        };
+        let (from_builtin, to_builtin) = match (number, builtin_of(&to)) {
+            // Only bytes become characters, so that is what a literal is
+            (Some(NumberKind::Integer), Some(BuiltinType::Char)) => {
+                return self.unify(&Type::builtin(BuiltinType::U8, from.span), &from).map_err(|_| invalid());
+            }
+            (Some(kind), to_builtin) => (Some(kind.default_type()), to_builtin),
+            (None, to_builtin) => (builtin_of(&from), to_builtin),
+        };
+        match (from_builtin, to_builtin) {
+            (Some(BuiltinType::Dyn), _) | (_, Some(BuiltinType::Dyn)) => Ok(()),
+            (Some(from_builtin), Some(to_builtin)) => {
+                let castable = from_builtin == to_builtin
+                    || (from_builtin.is_numeric() && to_builtin.is_numeric())
+                    || (matches!(from_builtin, BuiltinType::Bool | BuiltinType::Char) && to_builtin.is_integer())
+                    || (from_builtin == BuiltinType::U8 && to_builtin == BuiltinType::Char);
+                if castable {
+                    Ok(())
+                } else {
+                    Err(invalid())
+                }
+            }
+            // Other types only cast to themselves
+            _ => self.unify(&to, &from).map_err(|_| invalid()),
+        }
+    }
+
+    fn infer_call(&mut self, callee: &Expr, args: &[Expr], span: Span) -> TResult<Type> {
+        let check_args = |infer: &mut Self, error| {
+            for arg in args {
+                infer.infer(arg);
+            }
+            Err(error)
+        };
+        if let ExprKind::Var { name, .. } = &callee.kind {
+            if !self.env.contains(name) {
+                return check_args(self, TypeError::UndefinedFn { name: name.clone() });
+            }
+        }
+
+        let callee_ty = self.infer(callee);
+        let callable = match callee_ty.kind {
+            TypeKind::Inference(var) => self.subst.number_kind(var).is_none(),
+            TypeKind::Fn { .. } | TypeKind::Builtin(BuiltinType::Dyn) | TypeKind::Error => true,
+            _ => false,
+        };
+        if !callable {
+            return check_args(self, TypeError::NotCallable { ty: self.subst.display(&callee_ty).to_string() });
+        }
+
+        // Settle the arity first, so that a wrong argument is reported at
+        // the argument rather than at the call
+        let params: Vec<_> = args.iter().map(|arg| self.fresh(arg.span)).collect();
+        let ret = self.fresh(span);
+        let called = Type::new(TypeKind::Fn { params: params.clone(), ret: Box::new(ret.clone()) }, span);
+        if let Err(error) = self.unify(&callee_ty, &called) {
+            return check_args(self, error);
+        }
+        let signature = format!("called function has type {}", self.subst.display(&callee_ty));
+        for (arg, param) in args.iter().zip(&params) {
+            let because = Label { span: callee.span, message: signature.clone() };
+            self.expect_refined(param, arg, Some(because));
+        }
+        Ok(ret)
+    }
+
+    /// Type of a name, with its generalized variables instantiated afresh
+    fn lookup(&mut self, name: &str, span: Span) -> TResult<Type> {
+        let binding = self.env.get(name).ok_or_else(|| TypeError::UndefinedVar { name: name.to_string() })?;
+        if binding.quantified.is_empty() {
+            return Ok(binding.ty.clone());
+        }
+        let (quantified, ty) = (binding.quantified.clone(), binding.ty.clone());
+        let fresh: Substitution = quantified.into_iter().map(|var| (var, self.fresh(span))).collect();
+        Ok(fresh.apply(&self.subst.apply(&ty)))
+    }
+
+    /// Inference variables of a type that are free in no binding in scope
+    fn generalize(&self, ty: &Type) -> Vec<u32> {
+        let env_vars: FxHashSet<u32> = self
+            .env
+            .bindings()
+            .flat_map(|binding| {
+                let mut vars = free_vars(&self.subst.apply(&binding.ty));
+                for var in &binding.quantified {
+                    vars.remove(var);
+                }
+                vars
+            })
+            .collect();
+        // Number literals are not generic: they default instead
+        let mut vars: Vec<_> = free_vars(&self.subst.apply(ty))
+            .difference(&env_vars)
+            .copied()
+            .filter(|var| self.subst.number_kind(*var).is_none())
+            .collect();
+        vars.sort_unstable();
+        vars
+    }
+
+    /// Convert a written type. Names that are not declared types are
+    /// generic parameters, one inference variable per name in `generics`.
+    /// Declared structs and enums written without type arguments get fresh
+    /// ones; aliases are expanded.
+    fn annotation(&mut self, ty: &Type, generics: &mut FxHashMap<String, Type>) -> Type {
+        let (name, args) = match &ty.kind {
+            TypeKind::Var(name) => match generics.get(name) {
+                Some(param) => return param.clone(),
+                None => (name, None),
+            },
+            TypeKind::Named { name, args } => (name, Some(args)),
+            TypeKind::Refinement(r) => {
+                let base = self.annotation(&r.base, generics);
+                self.check_constraint(r, &base, ty.span);
+                let mut r = r.clone();
+                *r.base = base;
+                return Type::new(TypeKind::Refinement(r), ty.span);
+            }
+            _ => return map_children(ty, &mut |t| self.annotation(t, generics)),
+        };
+        let args: Option<Vec<_>> = args.map(|args| args.iter().map(|arg| self.annotation(arg, generics)).collect());
+
+        let declared = match (self.structs.get(name), self.enums.get(name)) {
+            (Some(info), _) => Some(info.params.len()),
+            (None, Some(info)) => Some(info.params.len()),
+            (None, None) => None,
+        };
+        if let Some(arity) = declared {
+            let args = args.unwrap_or_else(|| (0..arity).map(|_| self.fresh(ty.span)).collect());
+            if !self.check_arity(name, arity, args.len(), ty.span) {
+                return Type::new(TypeKind::Error, ty.span);
+            }
+            return Type::new(TypeKind::Named { name: name.clone(), args }, ty.span);
+        }
+
+        if let Some((params, aliased)) = self.aliases.get(name).cloned() {
+            let args = args.unwrap_or_else(|| params.iter().map(|_| self.fresh(ty.span)).collect());
+            if !self.check_arity(name, params.len(), args.len(), ty.span) {
+                return Type::new(TypeKind::Error, ty.span);
+            }
+            let mut alias_generics: FxHashMap<_, _> = params.into_iter().zip(args).collect();
+            return self.annotation(&aliased, &mut alias_generics);
+        }
+
+        match args {
+            // Only declared types take arguments
+            Some(_) => {
+                let error = TypeError::UndefinedType { name: name.clone() };
+                self.report(TypeDiagnostic::new(error, ty.span, None));
+                Type::new(TypeKind::Error, ty.span)
+            }
+            None => generics.entry(name.clone()).or_insert_with(|| self.fresh(ty.span)).clone(),
+        }
+    }
+
+    /// Report a type applied to the wrong number of arguments
+    fn check_arity(&mut self, name: &str, expected: usize, found: usize, span: Span) -> bool {
+        if expected != found {
+            let error = TypeError::TypeArgCount { name: name.to_string(), expected, found };
+            self.report(TypeDiagnostic::new(error, span, None));
+        }
+        expected == found
+    }
+
+    /// Field types of a struct with the given type arguments
+    fn struct_fields(&mut self, name: &str, args: &[Type]) -> TResult<FxHashMap<String, Type>> {
+        let info = self.structs.get(name).cloned().ok_or_else(|| TypeError::UndefinedType { name: name.to_string() })?;
+        let mut generics: FxHashMap<_, _> = info.params.iter().cloned().zip(args.iter().cloned()).collect();
+        Ok(info.fields.iter().map(|(field, ty)| (field.clone(), self.annotation(ty, &mut generics))).collect())
+    }
+
+    /// A struct type with fresh type arguments, and its field types
+    fn struct_instance(&mut self, name: &str, span: Span) -> TResult<(Type, FxHashMap<String, Type>)> {
+        let params = self.structs.get(name).map(|info| info.params.len()).unwrap_or_default();
+        let args: Vec<_> = (0..params).map(|_| self.fresh(span)).collect();
+        let fields = self.struct_fields(name, &args)?;
+        Ok((Type::new(TypeKind::Named { name: name.to_string(), args }, span), fields))
+    }
+
+    fn infer_field(&mut self, base: &Expr, name: &str, span: Span) -> TResult<Type> {
+        let base = self.infer(base);
+        let named = match &base.kind {
+            TypeKind::Named { name, args } => Some((name, args)),
+            TypeKind::Ref(inner) => match &inner.kind {
+                TypeKind::Named { name, args } => Some((name, args)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match named {
+            Some((ty, args)) if self.structs.contains_key(ty) => {
+                let (ty, args) = (ty.clone(), args.clone());
+                self.struct_fields(&ty, &args)?
+                    .remove(name)
+                    .ok_or_else(|| TypeError::NoField { ty, field: name.to_string() })
+            }
+            // The struct is not known yet
+            _ => Ok(self.fresh(span)),
+        }
+    }
+
+    fn infer_index(&mut self, base: &Expr, index: &Expr, span: Span) -> TResult<Type> {
+        let base = self.infer(base);
+        self.infer(index);
+        let item = self.fresh(span);
+        self.unify(&Type::new(TypeKind::List(Box::new(item.clone())), span), &base)?;
+        Ok(item)
+    }
+
+    fn infer_deref(&mut self, inner: &Expr, span: Span) -> TResult<Type> {
+        let found = self.infer(inner);
+        let target = self.fresh(span);
+        self.unify(&Type::new(TypeKind::Ref(Box::new(target.clone())), span), &found)?;
+        Ok(target)
+    }
+
+    fn infer_break(&mut self, value: Option<&Expr>, span: Span) -> TResult<()> {
+        let expected = self.loops.last().cloned();
+        self.infer_exit(expected, value, span)
+    }
+
+    fn infer_return(&mut self, value: Option<&Expr>, span: Span) -> TResult<()> {
+        let expected = self.returns.last().cloned();
+        self.infer_exit(expected, value, span)
+    }
+
+    /// Check the value of a `break` or `return` against the type it leaves
+    /// with, if it is inside a loop or function
+    fn infer_exit(&mut self, expected: Option<Type>, value: Option<&Expr>, span: Span) -> TResult<()> {
+        match (expected, value) {
+            (Some(expected), Some(value)) => {
+                self.expect_result(&expected, value, None);
+                Ok(())
+            }
+            (Some(expected), None) => self.unify(&expected, &unit_type(span)),
+            (None, Some(value)) => {
+                self.infer(value);
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Type of a function declaration. It may call itself, at its own type.
+    fn infer_fn(&mut self, decl: &FnDecl) -> TResult<Type> {
+        let mut generics = FxHashMap::default();
+        let ret = decl.ret_type.as_ref().map(|ty| self.annotation(ty, &mut generics));
+        // Use the forward declaration if there is one, so that calls checked
+        // before this point constrain the same type
+        let itself = match self.env.get(&decl.name) {
+            Some(binding) if binding.quantified.is_empty() && self.env.in_current_scope(&decl.name) => {
+                binding.ty.clone()
             }
-            _ => Ok(self.fresh_var()),
+            _ => self.fresh(decl.span),
+        };
+        let ty = self.scoped(|infer| {
+            infer.env.decl(decl.name.clone(), itself.clone());
+            infer.infer_function(&decl.params, ret, &mut generics, decl.span, &decl.contracts, decl.body.as_deref())
+        });
+        self.unify(&itself, &ty)?;
+        Ok(self.apply(&ty))
+    }
+
+    /// Type of a function as declared, before its body is checked
+    fn signature(&mut self, decl: &FnDecl) -> Type {
+        let mut generics = FxHashMap::default();
+        let params = decl
+            .params
+            .iter()
+            .map(|param| match &param.ty {
+                Some(ty) => self.annotation(ty, &mut generics),
+                None => self.fresh(param.span),
+            })
+            .collect();
+        let ret = match &decl.ret_type {
+            Some(ty) => self.annotation(ty, &mut generics),
+            None => self.fresh(decl.span),
+        };
+        Type::new(TypeKind::Fn { params, ret: Box::new(ret) }, decl.span)
+    }
+
+    /// Type of a function with the given parameters, contracts and body
+    fn infer_function(
+        &mut self,
+        params: &[Param],
+        ret: Option<Type>,
+        generics: &mut FxHashMap<String, Type>,
+        span: Span,
+        contracts: &[Contract],
+        body: Option<&Expr>,
+    ) -> Type {
+        let param_types: Vec<_> = params
+            .iter()
+            .map(|param| match &param.ty {
+                Some(ty) => self.annotation(ty, generics),
+                None => self.fresh(param.span),
+            })
+            .collect();
+        let because = ret.as_ref().map(|ret| Label { span: ret.span, message: "return type declared here".into() });
+        let ret = ret.unwrap_or_else(|| self.fresh(span));
+
+        let loops = std::mem::take(&mut self.loops);
+        self.returns.push(ret.clone());
+        self.scoped(|infer| {
+            for (param, ty) in params.iter().zip(&param_types) {
+                infer.record(&param.name, DeclKind::Param, None, param.span, ty);
+                infer.env.decl(param.name.clone(), ty.clone());
+                let refinement = infer.refinement(ty);
+                if let Some(refiner) = &mut infer.refiner {
+                    refiner.bind(&param.name, None, refinement);
+                }
+            }
+            for contract in contracts {
+                infer.expect_bool(&contract.expr);
+            }
+            if let Some(body) = body {
+                infer.expect_result(&ret, body, because);
+            }
+        });
+        self.returns.pop();
+        self.loops = loops;
+
+        Type::new(TypeKind::Fn { params: param_types, ret: Box::new(ret) }, span)
+    }
+
+    /// Check a pattern of `expr` against the type of the matched value,
+    /// declaring its bindings in the current scope
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &Type, expr: &Expr) {
+        let span = expr.span;
+        let wrap = |kind: fn(Box<Type>) -> TypeKind, inner: &Type| Type::new(kind(Box::new(inner.clone())), span);
+        // The module may not have been resolved
+        if let Some(name) = fieldless_variant(pattern, |name| self.variants.contains_key(name)) {
+            let variant = Pattern::Variant { name: name.clone(), args: Vec::new() };
+            return self.bind_pattern(&variant, ty, expr);
+        }
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Literal(lit) => {
+                let lit_ty = self.literal(lit, span, expr.id);
+                self.unify_at(ty, &lit_ty, expr);
+            }
+            Pattern::Bind(name) => match self.env.get(name) {
+                // Alternatives of an or-pattern bind the same names
+                Some(bound) if self.env.in_current_scope(name) => {
+                    let bound = bound.ty.clone();
+                    self.unify_at(&bound, ty, expr);
+                }
+                _ => {
+                    self.record(name, DeclKind::Pattern, None, span, ty);
+                    self.env.decl(name.clone(), ty.clone());
+                }
+            },
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    self.bind_pattern(alternative, ty, expr);
+                }
+            }
+            Pattern::Struct { ty: name, fields } => {
+                let declared = match self.struct_instance(name, span) {
+                    Ok((instance, declared)) => {
+                        self.unify_at(ty, &instance, expr);
+                        declared
+                    }
+                    Err(error) => {
+                        self.report(TypeDiagnostic::new(error, span, expr.id));
+                        FxHashMap::default()
+                    }
+                };
+                for (field, pattern) in fields {
+                    let field_ty = match declared.get(field) {
+                        Some(field_ty) => field_ty.clone(),
+                        None => {
+                            if self.structs.contains_key(name) {
+                                let error = TypeError::NoField { ty: name.clone(), field: field.clone() };
+                                self.report(TypeDiagnostic::new(error, span, expr.id));
+                            }
+                            Type::new(TypeKind::Error, span)
+                        }
+                    };
+                    self.bind_pattern(pattern, &field_ty, expr);
+                }
+            }
+            Pattern::Tuple(items) => {
+                let item_types: Vec<_> = items.iter().map(|_| self.fresh(span)).collect();
+                self.unify_at(ty, &Type::new(TypeKind::Tuple(item_types.clone()), span), expr);
+                for (item, item_ty) in items.iter().zip(&item_types) {
+                    self.bind_pattern(item, item_ty, expr);
+                }
+            }
+            Pattern::Slice(items) => {
+                let item_ty = self.fresh(span);
+                self.unify_at(ty, &wrap(TypeKind::List, &item_ty), expr);
+                for item in items {
+                    self.bind_pattern(item, &item_ty, expr);
+                }
+            }
+            Pattern::Variant { name, args } => {
+                // Field types, and the enum type, instantiated afresh
+                let constructor =
+                    if self.variants.contains_key(name) { self.lookup(name, span).ok() } else { None };
+                let (fields, enum_ty) = match constructor {
+                    Some(Type { kind: TypeKind::Fn { params, ret }, .. }) => (params, Some(*ret)),
+                    Some(ty) => (Vec::new(), Some(ty)),
+                    None => (Vec::new(), None),
+                };
+                match enum_ty {
+                    Some(enum_ty) => {
+                        self.unify_at(ty, &enum_ty, expr);
+                        if fields.len() != args.len() {
+                            let error = TypeError::ArgCount { expected: fields.len(), found: args.len() };
+                            self.report(TypeDiagnostic::new(error, span, expr.id));
+                        }
+                    }
+                    None => {
+                        let error = TypeError::UndefinedVariant { name: name.clone() };
+                        self.report(TypeDiagnostic::new(error, span, expr.id));
+                    }
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    let field_ty = fields.get(i).cloned().unwrap_or_else(|| Type::new(TypeKind::Error, span));
+                    self.bind_pattern(arg, &field_ty, expr);
+                }
+            }
+            Pattern::Some(inner) => {
+                let inner_ty = self.fresh(span);
+                self.unify_at(ty, &wrap(TypeKind::Maybe, &inner_ty), expr);
+                self.bind_pattern(inner, &inner_ty, expr);
+            }
+            Pattern::None => {
+                let inner_ty = self.fresh(span);
+                self.unify_at(ty, &wrap(TypeKind::Maybe, &inner_ty), expr);
+            }
+        }
+    }
+
+    /// Report the values the arms of `expr` miss, and the arms that can
+    /// never be reached
+    fn check_arms(&mut self, arms: &[MatchArm], expr: &Expr) {
+        let patterns: Vec<_> = arms.iter().map(|arm| (&arm.pattern, arm.guard.is_some())).collect();
+        let report = exhaust::check(&*self, &patterns);
+        for i in report.unreachable {
+            let arm = &arms[i];
+            self.report(TypeDiagnostic::new(TypeError::UnreachableArm, arm.span, arm.body.id));
+        }
+        if !report.missing.is_empty() {
+            let error = TypeError::NonExhaustive { missing: report.missing };
+            self.report(TypeDiagnostic::new(error, expr.span, expr.id));
+        }
+    }
+
+    /// Infer the type of `expr` and check it against `expected`, reporting
+    /// a mismatch at `expr`. Returns the inferred type.
+    fn expect(&mut self, expected: &Type, expr: &Expr) -> Type {
+        self.expect_because(expected, expr, None)
+    }
+
+    /// Like [`Self::expect`], pointing a mismatch at what made the type
+    /// expected. A number is accepted where a wider one is expected.
+    fn expect_because(&mut self, expected: &Type, expr: &Expr, because: Option<Label>) -> Type {
+        let found = self.infer(expr);
+        if let (Some(from), Some(to)) = (builtin_of(&found), builtin_of(&self.apply(expected))) {
+            if from.widens_to(to) {
+                return found;
+            }
+        }
+        if let Err(error) = self.unify(expected, &found) {
+            let mut diagnostic = TypeDiagnostic::new(error, expr.span, expr.id);
+            diagnostic.labels.extend(because);
+            self.report(diagnostic);
+        }
+        found
+    }
+
+    /// Like [`Self::expect_because`], also checking the constraint of a
+    /// refinement type, for where values meet declared types
+    fn expect_refined(&mut self, expected: &Type, expr: &Expr, because: Option<Label>) -> Type {
+        // Only a refinement the expected type has before `expr` is inferred
+        // was declared; one it gets from `expr` is that of the value
+        let refinement = self.refinement(expected);
+        let found = self.expect_because(expected, expr, because);
+        let Some(refinement) = refinement else {
+            return found;
+        };
+        let Some(refiner) = &mut self.refiner else {
+            return found;
+        };
+        let value = refiner.term(expr);
+        if refiner.violates(&value, &refinement) {
+            let error = TypeError::RefinementViolation { constraint: self.subst.display(expected).to_string() };
+            self.report(TypeDiagnostic::new(error, expr.span, expr.id));
+        }
+        found
+    }
+
+    /// Check a value a function or loop leaves with. Its constraint is only
+    /// checked if the result type is written as a refinement type: an
+    /// inferred one may come from an earlier `return`.
+    fn expect_result(&mut self, expected: &Type, expr: &Expr, because: Option<Label>) -> Type {
+        match expected.kind {
+            TypeKind::Refinement(_) => self.expect_refined(expected, expr, because),
+            _ => self.expect_because(expected, expr, because),
+        }
+    }
+
+    /// Constraint of a refinement type, if it parses
+    fn refinement(&self, ty: &Type) -> Option<Constraint> {
+        match self.apply(ty).kind {
+            TypeKind::Refinement(r) => {
+                let key = (r.var_name, r.constraint);
+                let constraint = self.constraints.get(&key)?.clone()?;
+                Some((key.0, constraint))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse and check the constraint of a refinement type, once per
+    /// constraint
+    fn check_constraint(&mut self, r: &RefinementType, base: &Type, span: Span) {
+        let key = (r.var_name.clone(), r.constraint.clone());
+        if self.constraints.contains_key(&key) {
+            return;
+        }
+        match synton_parser::parse_expr(&r.constraint) {
+            Ok(constraint) => {
+                self.scoped(|infer| {
+                    infer.env.decl(r.var_name.clone(), base.clone());
+                    infer.expect_bool(&constraint);
+                });
+                self.constraints.insert(key, Some(constraint));
+            }
+            Err(error) => {
+                let message = error.to_string();
+                let error = TypeError::InvalidRefinement { constraint: r.constraint.clone(), message };
+                self.report(TypeDiagnostic::new(error, span, None));
+                self.constraints.insert(key, None);
+            }
+        }
+    }
+
+    /// Forget what is known about the variables a loop body assigns
+    fn forget_assigned(&mut self, body: Body<'_>) {
+        if let Some(refiner) = &mut self.refiner {
+            refiner.forget_assigned(body);
+        }
+    }
+
+    fn expect_bool(&mut self, expr: &Expr) {
+        self.expect(&Type::builtin(BuiltinType::Bool, expr.span), expr);
+    }
+
+    /// Unify, reporting a failure at `expr`
+    fn unify_at(&mut self, expected: &Type, found: &Type, expr: &Expr) {
+        if let Err(error) = self.unify(expected, found) {
+            self.report(TypeDiagnostic::new(error, expr.span, expr.id));
+        }
+    }
+
+    /// Remember the type of a binding for [`Self::typed_module`]
+    fn record(&mut self, name: &str, kind: DeclKind, symbol: Option<Symbol>, span: Span, ty: &Type) {
+        self.bindings.push(TypedBinding { name: name.to_string(), kind, symbol, span, ty: ty.clone() });
+    }
+
+    /// Check if a statement or expression is nested past the limit. Every
+    /// one at the limit fails the same way, so only the first is reported.
+    fn too_deep(&mut self, span: Span, node: Option<NodeId>) -> bool {
+        if self.depth < self.max_depth {
+            return false;
+        }
+        if !self.diagnostics.iter().any(|d| matches!(d.error, TypeError::RecursionLimit { .. })) {
+            let error = TypeError::RecursionLimit { max_depth: self.max_depth };
+            self.report(TypeDiagnostic::new(error, span, node));
+        }
+        true
+    }
+
+    fn report(&mut self, diagnostic: TypeDiagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Run `f` in a new scope
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.env.push();
+        if let Some(refiner) = &mut self.refiner {
+            refiner.push();
+        }
+        let result = f(self);
+        if let Some(refiner) = &mut self.refiner {
+            refiner.pop();
+        }
+        self.env.pop();
+        result
+    }
+
+    /// Run `f` as the body of a loop, returning the type of the loop: the
+    /// type of its `break` values
+    fn in_loop(&mut self, span: Span, f: impl FnOnce(&mut Self)) -> Type {
+        let result = self.fresh(span);
+        self.loops.push(result.clone());
+        f(self);
+        self.loops.pop();
+        result
+    }
+}
+
+impl Declarations for TypeInfer {
+    fn siblings(&self, variant: &str) -> Option<Vec<(String, usize)>> {
+        let name = self.variants.get(variant)?;
+        let info = self.enums.get(name)?;
+        Some(info.variants.iter().map(|(variant, types)| (variant.clone(), types.len())).collect())
+    }
+
+    fn fields(&self, name: &str) -> Option<Vec<String>> {
+        self.structs.get(name).map(|info| info.fields.iter().map(|(field, _)| field.clone()).collect())
+    }
+}
+
+impl Types for TypeInfer {
+    fn may_hold_reference(&self, node: Option<NodeId>) -> bool {
+        match node.and_then(|node| self.types.get(&node)) {
+            Some(ty) => self.holds_reference(&self.apply(ty), false, &mut FxHashSet::default()),
+            None => true,
         }
     }
 }
 
+impl TypeInfer {
+    /// Check if a value of a type can hold a reference. Unsolved inference
+    /// variables can. In a type as written in a declaration, names are
+    /// looked up and generic parameters are covered by the arguments the
+    /// declared type is applied to; `seen` holds the names looked into.
+    fn holds_reference(&self, ty: &Type, written: bool, seen: &mut FxHashSet<String>) -> bool {
+        match &ty.kind {
+            TypeKind::Ref(_) | TypeKind::Fn { .. } | TypeKind::Inference(_) => true,
+            TypeKind::Builtin(b) => *b == BuiltinType::Dyn,
+            TypeKind::Never | TypeKind::Unit | TypeKind::Error => false,
+            TypeKind::Refinement(r) => self.holds_reference(&r.base, written, seen),
+            TypeKind::List(inner) | TypeKind::Maybe(inner) => self.holds_reference(inner, written, seen),
+            TypeKind::Tuple(items) => self.any_holds_reference(items, written, seen),
+            TypeKind::Result { ok, err } => self.any_holds_reference([&**ok, &**err], written, seen),
+            TypeKind::Struct(fields) => self.any_holds_reference(fields.iter().map(|field| &field.ty), written, seen),
+            TypeKind::Enum(variants) => {
+                self.any_holds_reference(variants.iter().flat_map(|variant| &variant.types), written, seen)
+            },
+            TypeKind::Var(_) if !written => false,
+            TypeKind::Var(name) | TypeKind::Named { name, .. } => {
+                let args = match &ty.kind {
+                    TypeKind::Named { args, .. } => args.as_slice(),
+                    _ => &[],
+                };
+                if self.any_holds_reference(args, written, seen) {
+                    return true;
+                }
+                if !seen.insert(name.clone()) {
+                    return false;
+                }
+                match (self.structs.get(name), self.enums.get(name), self.aliases.get(name)) {
+                    (Some(info), _, _) => self.any_holds_reference(info.fields.iter().map(|(_, ty)| ty), true, seen),
+                    (None, Some(info), _) => {
+                        self.any_holds_reference(info.variants.iter().flat_map(|(_, types)| types), true, seen)
+                    }
+                    (None, None, Some((_, aliased))) => self.holds_reference(aliased, true, seen),
+                    // A generic parameter
+                    (None, None, None) => false,
+                }
+            }
+        }
+    }
+
+    fn any_holds_reference<'t>(
+        &self,
+        types: impl IntoIterator<Item = &'t Type>,
+        written: bool,
+        seen: &mut FxHashSet<String>,
+    ) -> bool {
+        types.into_iter().any(|ty| self.holds_reference(ty, written, seen))
+    }
+}
+
 impl Default for TypeInfer {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Type a refinement refines, or the type itself
+fn base(ty: &Type) -> &Type {
+    match &ty.kind {
+        TypeKind::Refinement(r) => &r.base,
+        _ => ty,
+    }
+}
+
+/// Builtin type of a type, looking through refinements
+fn builtin_of(ty: &Type) -> Option<BuiltinType> {
+    match base(ty).kind {
+        TypeKind::Builtin(b) => Some(b),
+        _ => None,
+    }
+}
+
+/// Node a statement-level error is attributed to: that of the statement's
+/// main expression, if it has one
+fn stmt_node(stmt: &Stmt) -> Option<NodeId> {
+    match &stmt.kind {
+        StmtKind::Expr(expr) | StmtKind::Const { value: expr, .. } | StmtKind::Assign { value: expr, .. } => expr.id,
+        StmtKind::Let { init: Some(init), .. } => init.id,
+        StmtKind::Return(Some(value)) | StmtKind::Break(Some(value)) => value.id,
+        _ => None,
+    }
+}
@@ -1,19 +1,35 @@
 //! # Synton Type Checker
 //!
-//! Type checking with inference and refinement type support.
+//! Type checking with inference, refinement types, effects and borrows.
 
 #![warn(missing_docs, unused_crate_dependencies)]
 
 use thiserror::Error;
-use synton_ast::{Module, Type, Expr, Stmt, BuiltinType, Span, Position, TypeKind};
+use rustc_hash::FxHashMap;
+use synton_ast::{Module, Type, Expr, Stmt, BuiltinType, Span, Position, TypeKind, Effect, Effects};
+use synton_ast::visit::Visitor;
+use synton_contract::{EvalSolver, Solver};
 
+mod borrow;
+mod depth;
 pub mod error;
+mod effect;
 pub mod env;
+mod exhaust;
 pub mod infer;
+mod refine;
+pub mod resolve;
+pub mod subst;
+pub mod typed;
 
-pub use error::{TypeError, TResult};
+pub use error::{TypeError, TResult, ResolveError, TypeDiagnostic, Label};
 pub use env::{TypeEnv, Binding};
 pub use infer::TypeInfer;
+pub use resolve::{Resolver, Resolution, Symbol, Declaration, DeclKind};
+pub use subst::Substitution;
+pub use typed::{TypedModule, TypedBinding};
+
+use depth::Nesting;
 
 /// Type checker configuration
 #[derive(Debug, Clone)]
@@ -22,6 +38,8 @@ pub struct TypeCheckerConfig {
     pub refinements: bool,
     /// Enable type inference
     pub inference: bool,
+    /// Maximum nesting depth of statements, expressions, patterns and types
+    pub max_depth: usize,
 }
 
 impl Default for TypeCheckerConfig {
@@ -29,6 +47,7 @@ impl Default for TypeCheckerConfig {
         Self {
             refinements: true,
             inference: true,
+            max_depth: depth::MAX_DEPTH,
         }
     }
 }
@@ -36,7 +55,9 @@ impl Default for TypeCheckerConfig {
 /// Main type checker
 pub struct TypeChecker {
     config: TypeCheckerConfig,
-    env: TypeEnv,
+    infer: TypeInfer,
+    /// Effects of the names provided by the host
+    host_effects: FxHashMap<String, Effects>,
 }
 
 impl TypeChecker {
@@ -45,68 +66,137 @@ impl TypeChecker {
     }
 
     pub fn with_config(config: TypeCheckerConfig) -> Self {
-        Self {
-            config,
-            env: TypeEnv::new(),
+        let mut infer = TypeInfer::new().with_max_depth(config.max_depth);
+        if config.refinements {
+            infer = infer.with_solver(Box::new(EvalSolver::new()));
+        }
+        Self { config, infer, host_effects: FxHashMap::default() }
+    }
+
+    /// Decide refinement constraints with `solver` instead of the built-in
+    /// [`EvalSolver`]. Does nothing if refinements are not checked.
+    pub fn with_solver(mut self, solver: Box<dyn Solver>) -> Self {
+        if self.config.refinements {
+            self.infer = std::mem::take(&mut self.infer).with_solver(solver);
+        }
+        self
+    }
+
+    /// Declare names provided by the host, such as the standard library.
+    /// Their types are not known, so they are `dyn`. Unless declared with
+    /// [`Self::with_effects`], calling them has the `host` effect.
+    pub fn with_globals<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let dummy = Span::new(Position::start(), Position::start());
+        for name in names {
+            let name = name.into();
+            self.host_effects.entry(name.clone()).or_insert(Effects::only(Effect::Host));
+            self.infer.declare(name, Type::builtin(BuiltinType::Dyn, dummy));
         }
+        self
+    }
+
+    /// Declare the effects of calling names provided by the host, such as
+    /// [`Effects::PURE`] for a host function that only computes a value
+    pub fn with_effects<I, S>(mut self, effects: I) -> Self
+    where
+        I: IntoIterator<Item = (S, Effects)>,
+        S: Into<String>,
+    {
+        self.host_effects.extend(effects.into_iter().map(|(name, effects)| (name.into(), effects)));
+        self
+    }
+
+    /// Configuration in use
+    pub fn config(&self) -> &TypeCheckerConfig {
+        &self.config
     }
 
-    /// Check a module
-    pub fn check_module(&mut self, module: &Module) -> TResult<()> {
+    /// Check a module, returning the type of every expression and binding
+    /// and the effects of its functions, or every error in it. Warnings
+    /// come with the types, or among the errors if there are any.
+    pub fn check_module(&mut self, module: &Module) -> Result<TypedModule, Vec<TypeDiagnostic>> {
+        let mut nesting = Nesting::new(self.config.max_depth);
+        nesting.visit_module(module);
+        if self.nesting(nesting).is_err() {
+            return Err(self.infer.take_diagnostics());
+        }
+        self.infer.declare_items(&module.stmts);
         for stmt in &module.stmts {
-            self.check_stmt(&stmt)?;
+            self.infer.infer_stmt(stmt);
+        }
+        self.infer.default_literals();
+        let effects = effect::check(&module.stmts, &self.host_effects);
+        self.infer.extend_diagnostics(effects.diagnostics);
+        let borrows = borrow::check(&module.stmts, &self.infer);
+        self.infer.extend_diagnostics(borrows);
+        if self.infer.diagnostics().iter().all(TypeDiagnostic::is_warning) {
+            let mut typed = self.infer.typed_module();
+            typed.effects = effects.effects;
+            self.infer.take_diagnostics();
+            Ok(typed)
+        } else {
+            Err(self.infer.take_diagnostics())
         }
-        Ok(())
     }
 
-    /// Check a statement
+    /// Check a statement, failing with the first error in it. All of its
+    /// errors are kept in [`Self::diagnostics`].
     pub fn check_stmt(&mut self, stmt: &Stmt) -> TResult<Type> {
-        match &stmt.kind {
-            synton_ast::StmtKind::Empty => Ok(unit_type(stmt.span)),
-            synton_ast::StmtKind::Expr(expr) => self.check_expr(expr),
-            synton_ast::StmtKind::Let { name, ty, init, .. } => {
-                let init_ty = if let Some(init) = init {
-                    self.check_expr(init)?
-                } else {
-                    return Err(TypeError::Uninitialized {
-                        name: name.clone(),
-                    });
-                };
-
-                if let Some(expected) = ty {
-                    self.unify(&expected, &init_ty, stmt.span)?;
-                }
-
-                self.env.decl(name.clone(), init_ty);
-                Ok(unit_type(stmt.span))
-            }
-            _ => Ok(unit_type(stmt.span)),
-        }
+        let mut nesting = Nesting::new(self.config.max_depth);
+        nesting.visit_stmt(stmt);
+        self.nesting(nesting)?;
+        let before = self.infer.diagnostics().len();
+        let ty = self.infer.infer_stmt(stmt);
+        self.infer.default_literals();
+        self.first_error_since(before).map_or_else(|| Ok(self.infer.apply(&ty)), Err)
     }
 
-    /// Check an expression
+    /// Check an expression, failing with the first error in it. All of its
+    /// errors are kept in [`Self::diagnostics`].
     pub fn check_expr(&mut self, expr: &Expr) -> TResult<Type> {
-        match &expr.kind {
-            synton_ast::ExprKind::Error => Ok(unknown_type(expr.span)),
-            synton_ast::ExprKind::Literal(lit) => Ok(type_from_literal(lit, expr.span)),
-            _ => Ok(unknown_type(expr.span)),
-        }
+        let mut nesting = Nesting::new(self.config.max_depth);
+        nesting.visit_expr(expr);
+        self.nesting(nesting)?;
+        let before = self.infer.diagnostics().len();
+        let ty = self.infer.infer(expr);
+        self.infer.default_literals();
+        self.first_error_since(before).map_or_else(|| Ok(self.infer.apply(&ty)), Err)
+    }
+
+    /// Errors found by `check_stmt` and `check_expr`
+    pub fn diagnostics(&self) -> &[TypeDiagnostic] {
+        self.infer.diagnostics()
+    }
+
+    /// Report nesting past the limit, which no pass may then recurse into
+    fn nesting(&mut self, nesting: Nesting) -> TResult<()> {
+        let Some((span, node)) = nesting.too_deep() else { return Ok(()) };
+        let error = TypeError::RecursionLimit { max_depth: self.config.max_depth };
+        self.infer.extend_diagnostics(vec![TypeDiagnostic::new(error.clone(), span, node)]);
+        Err(error)
+    }
+
+    fn first_error_since(&self, before: usize) -> Option<TypeError> {
+        self.infer.diagnostics()[before..]
+            .iter()
+            .find(|diagnostic| !diagnostic.is_warning())
+            .map(|diagnostic| diagnostic.error.clone())
     }
 
     /// Unify two types
     pub fn unify(&mut self, expected: &Type, found: &Type, _span: Span) -> TResult<()> {
-        if std::mem::discriminant(&expected.kind) != std::mem::discriminant(&found.kind) {
-            return Err(TypeError::Mismatch {
-                expected: format!("{:?}", expected.kind),
-                found: format!("{:?}", found.kind),
-            });
-        }
-        Ok(())
+        self.infer.unify(expected, found)
     }
 
-    /// Infer the type of an expression
+    /// Infer the type of an expression, with every solved inference
+    /// variable replaced
     pub fn infer_expr(&mut self, expr: &Expr) -> TResult<Type> {
-        self.check_expr(expr)
+        let ty = self.check_expr(expr)?;
+        Ok(self.infer.apply(&ty))
     }
 }
 
@@ -117,7 +207,7 @@ impl Default for TypeChecker {
 }
 
 /// Convenience function to check a module
-pub fn check(module: &Module) -> TResult<()> {
+pub fn check(module: &Module) -> Result<TypedModule, Vec<TypeDiagnostic>> {
     TypeChecker::new().check_module(module)
 }
 
@@ -136,10 +226,13 @@ pub fn type_from_literal(lit: &synton_ast::Literal, span: Span) -> Type {
     let kind = match lit {
         synton_ast::Literal::Integer(_) => TypeKind::Builtin(BuiltinType::I32),
         synton_ast::Literal::Float(_) => TypeKind::Builtin(BuiltinType::F64),
+        synton_ast::Literal::TypedInteger(_, ty) | synton_ast::Literal::TypedFloat(_, ty) => TypeKind::Builtin(*ty),
         synton_ast::Literal::String(_) => TypeKind::Builtin(BuiltinType::String),
         synton_ast::Literal::Bool(_) => TypeKind::Builtin(BuiltinType::Bool),
         synton_ast::Literal::Char(_) => TypeKind::Builtin(BuiltinType::Char),
-        _ => TypeKind::Inference(0),
+        synton_ast::Literal::Byte(_) => TypeKind::Builtin(BuiltinType::U8),
+        synton_ast::Literal::Bytes(_) => TypeKind::List(Box::new(Type::builtin(BuiltinType::U8, span))),
+        synton_ast::Literal::Unit => TypeKind::Unit,
     };
     Type::new(kind, span)
 }
@@ -0,0 +1,218 @@
+//! Refinement types
+//!
+//! A refinement type such as `i32 {> val 0}` is a base type with a
+//! constraint on its value `val`. Where a value meets a declared refinement
+//! type — the initializer of an annotated `let` or `const`, an assigned
+//! value, a call argument, a returned value — the value is translated into
+//! the constraint language and the [`Solver`] is asked for a value that
+//! breaks the constraint, given what is known about the variables the value
+//! mentions. If it finds one, the constraint is violated; if it cannot
+//! decide, the value is accepted.
+//!
+//! What is known about a variable is the refinement it was declared with
+//! and, for an immutable `let`, the value it was bound to. The value is
+//! forgotten when the variable is assigned, and before a loop that assigns
+//! it. Expressions with no form in the constraint language, such as calls,
+//! become unknowns.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use synton_ast::visit::{self, Visitor};
+use synton_ast::{AssignTarget, BinaryOp, Expr, ExprKind, Literal, Stmt, StmtKind, UnaryOp};
+use synton_contract::{Solver, VerificationResult};
+
+/// A refinement constraint, as the variable standing for the value and the
+/// condition over it
+pub(crate) type Constraint = (String, Expr);
+
+/// A value in the constraint language, with what is known about the
+/// unknowns in it
+#[derive(Debug, Clone)]
+pub(crate) struct Term {
+    text: String,
+    facts: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Known {
+    /// Unknown standing for the variable's current value
+    symbol: String,
+    /// Conditions on `symbol` and other unknowns
+    facts: Vec<String>,
+    /// Refinement the variable was declared with, true of every value
+    refinement: Option<Constraint>,
+}
+
+/// What is known about variables, in scopes like the type environment's
+pub(crate) struct Refiner {
+    solver: Box<dyn Solver>,
+    scopes: Vec<FxHashMap<String, Known>>,
+    next_symbol: u32,
+}
+
+impl Refiner {
+    pub fn new(solver: Box<dyn Solver>) -> Self {
+        Self { solver, scopes: vec![FxHashMap::default()], next_symbol: 0 }
+    }
+
+    pub fn push(&mut self) {
+        self.scopes.push(FxHashMap::default());
+    }
+
+    pub fn pop(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Translate an expression into the constraint language
+    pub fn term(&mut self, expr: &Expr) -> Term {
+        let mut facts = Vec::new();
+        let text = self.translate(expr, &FxHashMap::default(), &mut facts);
+        Term { text, facts }
+    }
+
+    /// Declare a variable, with the value it is bound to if that cannot
+    /// change, and the refinement it is declared with
+    pub fn bind(&mut self, name: &str, value: Option<Term>, refinement: Option<Constraint>) {
+        let symbol = self.fresh();
+        let mut facts = Vec::new();
+        if let Some(value) = value {
+            facts.extend(value.facts);
+            facts.push(format!("(== {} {})", symbol, value.text));
+        }
+        if let Some((var, constraint)) = &refinement {
+            let fact = self.instantiate(var, constraint, &symbol, &mut facts);
+            facts.push(fact);
+        }
+        let scope = self.scopes.last_mut().expect("refiner has a global scope");
+        scope.insert(name.to_string(), Known { symbol, facts, refinement });
+    }
+
+    /// Forget the value of a variable, keeping its refinement
+    pub fn forget(&mut self, name: &str) {
+        let Some(depth) = self.scopes.iter().rposition(|scope| scope.contains_key(name)) else {
+            return;
+        };
+        let refinement = self.scopes[depth][name].refinement.clone();
+        let symbol = self.fresh();
+        let mut facts = Vec::new();
+        if let Some((var, constraint)) = &refinement {
+            let fact = self.instantiate(var, constraint, &symbol, &mut facts);
+            facts.push(fact);
+        }
+        self.scopes[depth].insert(name.to_string(), Known { symbol, facts, refinement });
+    }
+
+    /// Forget the values of the variables a loop body assigns
+    pub fn forget_assigned(&mut self, body: Body<'_>) {
+        let mut assigned = Assigned::default();
+        match body {
+            Body::Expr(expr) => assigned.visit_expr(expr),
+            Body::Stmt(stmt) => assigned.visit_stmt(stmt),
+        }
+        for name in assigned.0 {
+            self.forget(&name);
+        }
+    }
+
+    /// Check if some value of `value` breaks `constraint`
+    pub fn violates(&mut self, value: &Term, (var, constraint): &Constraint) -> bool {
+        let mut facts = value.facts.clone();
+        let goal = self.instantiate(var, constraint, &value.text, &mut facts);
+        let mut seen = FxHashSet::default();
+        let query = facts
+            .iter()
+            .rev()
+            .filter(|fact| seen.insert(*fact))
+            .fold(format!("(! {})", goal), |query, fact| format!("(&& {} {})", fact, query));
+
+        self.solver.push();
+        let result = self.solver.assert(&query);
+        self.solver.pop();
+        result == VerificationResult::Sat
+    }
+
+    /// Translate a constraint with `var` standing for `value`
+    fn instantiate(&mut self, var: &str, constraint: &Expr, value: &str, facts: &mut Vec<String>) -> String {
+        let bound = FxHashMap::from_iter([(var, value)]);
+        self.translate(constraint, &bound, facts)
+    }
+
+    fn translate(&mut self, expr: &Expr, bound: &FxHashMap<&str, &str>, facts: &mut Vec<String>) -> String {
+        match &expr.kind {
+            ExprKind::Literal(Literal::Integer(i)) => i.to_string(),
+            ExprKind::Literal(Literal::TypedInteger(i, _)) => i.to_string(),
+            ExprKind::Literal(Literal::Bool(b)) => b.to_string(),
+            ExprKind::Var { name, .. } => match bound.get(name.as_str()) {
+                Some(value) => value.to_string(),
+                None => self.lookup(name, facts),
+            },
+            ExprKind::Unary { op: op @ (UnaryOp::Neg | UnaryOp::Not), arg } => {
+                format!("({} {})", op.token(), self.translate(arg, bound, facts))
+            }
+            ExprKind::Binary {
+                op: op @ (BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::And
+                | BinaryOp::Or),
+                left,
+                right,
+            } => {
+                let left = self.translate(left, bound, facts);
+                format!("({} {} {})", op.token(), left, self.translate(right, bound, facts))
+            }
+            ExprKind::Compare { op, left, right } => {
+                let left = self.translate(left, bound, facts);
+                format!("({} {} {})", op.token(), left, self.translate(right, bound, facts))
+            }
+            ExprKind::If { cond, then_branch, else_branch: Some(else_branch) } => {
+                let cond = self.translate(cond, bound, facts);
+                let then_branch = self.translate(then_branch, bound, facts);
+                format!("(? {} {} {})", cond, then_branch, self.translate(else_branch, bound, facts))
+            }
+            _ => self.fresh(),
+        }
+    }
+
+    /// Unknown standing for a variable, adding what is known about it to
+    /// `facts`
+    fn lookup(&mut self, name: &str, facts: &mut Vec<String>) -> String {
+        if let Some(known) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            facts.extend(known.facts.iter().cloned());
+            return known.symbol.clone();
+        }
+        // Nothing is known: a parameter without a refinement, or a global
+        let symbol = self.fresh();
+        let scope = self.scopes.last_mut().expect("refiner has a global scope");
+        scope.insert(name.to_string(), Known { symbol: symbol.clone(), facts: Vec::new(), refinement: None });
+        symbol
+    }
+
+    fn fresh(&mut self) -> String {
+        self.next_symbol += 1;
+        format!("%{}", self.next_symbol)
+    }
+}
+
+/// Body of a loop
+#[derive(Clone, Copy)]
+pub(crate) enum Body<'a> {
+    Expr(&'a Expr),
+    Stmt(&'a Stmt),
+}
+
+/// Names of the variables assigned in a body
+#[derive(Default)]
+struct Assigned(Vec<String>);
+
+impl Visitor for Assigned {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::Assign { target: AssignTarget::Var(name), .. } = &stmt.kind {
+            self.0.push(name.clone());
+        }
+        visit::walk_stmt(self, stmt);
+    }
+}
@@ -0,0 +1,486 @@
+//! Name resolution
+//!
+//! [`Resolver`] walks a module with lexical scopes and binds every use of a
+//! name to its declaration. Results are written back into the AST, where it
+//! has room for them, and collected in a [`Resolution`] side table:
+//!
+//! - `let` bindings get their [`VarId`] in `StmtKind::Let::id`, variable
+//!   references in `ExprKind::Var::id`, and functions their [`FnId`] in
+//!   `FnDecl::id`
+//! - expressions without a [`NodeId`] are numbered, and
+//!   [`Resolution::symbols`] maps every variable reference to the [`Symbol`]
+//!   it names, including references to functions and globals
+//!
+//! Functions declared in a block are visible in the whole block, so they can
+//! call each other. Other bindings are visible from the end of their
+//! declaration to the end of the enclosing block.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use synton_ast::visit::Visitor;
+use synton_ast::visit_mut::{self, VisitorMut};
+use synton_ast::{
+    AssignTarget, Expr, ExprKind, FnDecl, FnId, MatchArm, Module, NodeId, Param, Pattern, Position, Span, Stmt,
+    StmtKind, VarId,
+};
+
+use crate::depth::{Nesting, MAX_DEPTH};
+use crate::error::ResolveError;
+
+/// What a name refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    /// A variable declared in the module
+    Var(VarId),
+    /// A function declared in the module
+    Fn(FnId),
+    /// An import or a name provided by the host, such as a builtin
+    Global(String),
+}
+
+/// How a name was introduced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclKind {
+    /// `let` binding
+    Let,
+    /// `const` declaration
+    Const,
+    /// Function or lambda parameter
+    Param,
+    /// Binding in a match pattern
+    Pattern,
+    /// Loop variable of a `for`
+    Loop,
+    /// Function declaration
+    Fn,
+    /// Import
+    Import,
+    /// Enum variant
+    Variant,
+}
+
+/// A declared variable or function
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    /// Declared name
+    pub name: String,
+    /// How the name was introduced
+    pub kind: DeclKind,
+    /// Span of the declaration
+    pub span: Span,
+}
+
+/// Result of resolving a module
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    /// Variables, indexed by [`VarId`]
+    pub vars: Vec<Declaration>,
+    /// Functions, indexed by [`FnId`]
+    pub fns: Vec<Declaration>,
+    /// What each resolved variable reference refers to
+    pub symbols: FxHashMap<NodeId, Symbol>,
+    /// Errors and warnings, in source order
+    pub diagnostics: Vec<ResolveError>,
+}
+
+impl Resolution {
+    /// Look up a variable
+    pub fn var(&self, id: VarId) -> &Declaration {
+        &self.vars[id.0 as usize]
+    }
+
+    /// Look up a function
+    pub fn function(&self, id: FnId) -> &Declaration {
+        &self.fns[id.0 as usize]
+    }
+
+    /// Declaration a symbol refers to, `None` for globals
+    pub fn declaration(&self, symbol: &Symbol) -> Option<&Declaration> {
+        match symbol {
+            Symbol::Var(id) => Some(self.var(*id)),
+            Symbol::Fn(id) => Some(self.function(*id)),
+            Symbol::Global(_) => None,
+        }
+    }
+
+    /// Errors, leaving out warnings
+    pub fn errors(&self) -> impl Iterator<Item = &ResolveError> {
+        self.diagnostics.iter().filter(|d| !d.is_warning())
+    }
+
+    /// Check if resolution found any errors
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+}
+
+/// Name resolver
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    globals: FxHashSet<String>,
+    max_depth: usize,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self { globals: FxHashSet::default(), max_depth: MAX_DEPTH }
+    }
+}
+
+impl Resolver {
+    /// Create a resolver that knows no globals
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum nesting depth of statements, expressions, patterns
+    /// and types. A module nested deeper is not resolved.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Add names that resolve without a declaration in the module
+    pub fn with_globals<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.globals.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Resolve a module, writing identifiers back into it
+    pub fn resolve(&self, module: &mut Module) -> Resolution {
+        let mut nesting = Nesting::new(self.max_depth);
+        nesting.visit_module(module);
+        if let Some((span, _)) = nesting.too_deep() {
+            let error = ResolveError::RecursionLimit { max_depth: self.max_depth, span };
+            return Resolution { diagnostics: vec![error], ..Resolution::default() };
+        }
+
+        let mut pass = Pass {
+            globals: &self.globals,
+            scopes: Vec::new(),
+            res: Resolution::default(),
+            next_node: 0,
+            pattern_span: Span::single(Position::start()),
+            or_alternative: false,
+        };
+        pass.next_node = max_node_id(module).map_or(0, |id| id.0 + 1);
+        pass.visit_module_mut(module);
+        pass.res
+    }
+}
+
+/// Convenience function to resolve a module with no globals
+pub fn resolve(module: &mut Module) -> Resolution {
+    Resolver::new().resolve(module)
+}
+
+/// Largest node identifier already present in a module
+fn max_node_id(module: &Module) -> Option<NodeId> {
+    struct MaxId(Option<NodeId>);
+
+    impl synton_ast::visit::Visitor for MaxId {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let Some(id) = expr.id {
+                self.0 = Some(self.0.map_or(id, |max| NodeId(max.0.max(id.0))));
+            }
+            synton_ast::visit::walk_expr(self, expr);
+        }
+    }
+
+    let mut max = MaxId(None);
+    synton_ast::visit::Visitor::visit_module(&mut max, module);
+    max.0
+}
+
+/// Name of the variant a pattern stands for, if it is a variant without
+/// fields. Those look like a binding, or like a struct pattern without
+/// fields, until names are resolved.
+pub(crate) fn fieldless_variant(pattern: &Pattern, is_variant: impl Fn(&str) -> bool) -> Option<&String> {
+    let name = match pattern {
+        Pattern::Bind(name) => name,
+        Pattern::Struct { ty, fields } if fields.is_empty() => ty,
+        _ => return None,
+    };
+    Some(name).filter(|name| is_variant(name))
+}
+
+/// A name bound in a scope
+#[derive(Debug, Clone)]
+struct Entry {
+    symbol: Symbol,
+    kind: DeclKind,
+    span: Span,
+}
+
+/// A lexical scope
+#[derive(Debug, Default)]
+struct Scope {
+    names: FxHashMap<String, Entry>,
+    /// `let` and `const` names the scope declares further down
+    pending: FxHashMap<String, Span>,
+}
+
+struct Pass<'g> {
+    globals: &'g FxHashSet<String>,
+    scopes: Vec<Scope>,
+    res: Resolution,
+    next_node: u32,
+    /// Span reported for bindings in the pattern being visited
+    pattern_span: Span,
+    /// Whether the pattern being visited is a later alternative of an
+    /// or-pattern, which binds the same names as the first one
+    or_alternative: bool,
+}
+
+impl Pass<'_> {
+    /// Run `f` in a new scope
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Scope::default());
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Resolve a statement sequence in a new scope
+    fn block(&mut self, stmts: &mut [Stmt], tail: Option<&mut Expr>) {
+        self.scoped(|pass| {
+            pass.predeclare(stmts);
+            for stmt in stmts {
+                pass.visit_stmt_mut(stmt);
+            }
+            if let Some(tail) = tail {
+                pass.visit_expr_mut(tail);
+            }
+        });
+    }
+
+    /// Resolve the body of an `if` or a loop, which is a scope of its own
+    fn branch(&mut self, stmt: &mut Stmt) {
+        self.block(std::slice::from_mut(stmt), None);
+    }
+
+    /// Declare the functions and enum variants of a block and record its
+    /// later bindings
+    fn predeclare(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            match &mut stmt.kind {
+                StmtKind::FnDecl(decl) => {
+                    let id = FnId(self.res.fns.len() as u32);
+                    self.res.fns.push(Declaration { name: decl.name.clone(), kind: DeclKind::Fn, span: decl.span });
+                    decl.id = Some(id);
+                    self.bind(&decl.name, Symbol::Fn(id), DeclKind::Fn, decl.span);
+                }
+                // Variants are constructor functions, or values when they
+                // have no fields
+                StmtKind::EnumDecl(decl) => {
+                    for variant in &decl.variants {
+                        let symbol = Symbol::Global(variant.name.clone());
+                        self.bind(&variant.name, symbol, DeclKind::Variant, variant.span);
+                    }
+                }
+                StmtKind::Let { name, .. } | StmtKind::Const { name, .. } => {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.pending.entry(name.clone()).or_insert(stmt.span);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Declare a new variable in the current scope
+    fn declare(&mut self, name: &str, kind: DeclKind, span: Span) -> VarId {
+        let id = VarId(self.res.vars.len() as u32);
+        self.res.vars.push(Declaration { name: name.to_string(), kind, span });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.pending.remove(name);
+        }
+        self.bind(name, Symbol::Var(id), kind, span);
+        id
+    }
+
+    /// Bind a name in the current scope, reporting clashes
+    fn bind(&mut self, name: &str, symbol: Symbol, kind: DeclKind, span: Span) {
+        let Some((current, outer)) = self.scopes.split_last_mut() else {
+            return;
+        };
+        if let Some(previous) = current.names.get(name) {
+            if previous.kind == DeclKind::Let && kind == DeclKind::Let {
+                self.res.diagnostics.push(ResolveError::Shadowed {
+                    name: name.to_string(),
+                    span,
+                    previous: previous.span,
+                });
+            } else {
+                self.res.diagnostics.push(ResolveError::Duplicate {
+                    name: name.to_string(),
+                    span,
+                    previous: previous.span,
+                });
+            }
+        } else if let Some(previous) = outer
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(name))
+            .filter(|previous| matches!(previous.symbol, Symbol::Var(_)))
+        {
+            self.res.diagnostics.push(ResolveError::Shadowed {
+                name: name.to_string(),
+                span,
+                previous: previous.span,
+            });
+        }
+        current.names.insert(name.to_string(), Entry { symbol, kind, span });
+    }
+
+    /// Check if a name refers to an enum variant
+    fn is_variant(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(name))
+            .is_some_and(|entry| entry.kind == DeclKind::Variant)
+    }
+
+    /// Find what a name refers to, reporting it if nothing
+    fn lookup(&mut self, name: &str, span: Span) -> Option<Symbol> {
+        if let Some(entry) = self.scopes.iter().rev().find_map(|scope| scope.names.get(name)) {
+            return Some(entry.symbol.clone());
+        }
+        if self.globals.contains(name) {
+            return Some(Symbol::Global(name.to_string()));
+        }
+        let error = match self.scopes.iter().rev().find_map(|scope| scope.pending.get(name)) {
+            Some(&declared) => ResolveError::UseBeforeDeclare { name: name.to_string(), span, declared },
+            None => ResolveError::Undefined { name: name.to_string(), span },
+        };
+        self.res.diagnostics.push(error);
+        None
+    }
+
+    fn fresh_node(&mut self) -> NodeId {
+        let id = NodeId(self.next_node);
+        self.next_node += 1;
+        id
+    }
+}
+
+impl VisitorMut for Pass<'_> {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        self.scoped(|pass| {
+            for import in &module.imports {
+                let name = import.alias.as_ref().unwrap_or(&import.name);
+                pass.bind(name, Symbol::Global(import.name.clone()), DeclKind::Import, import.span);
+            }
+            pass.predeclare(&mut module.stmts);
+            for stmt in &mut module.stmts {
+                pass.visit_stmt_mut(stmt);
+            }
+        });
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            StmtKind::Let { name, id, init, .. } => {
+                if let Some(init) = init {
+                    self.visit_expr_mut(init);
+                }
+                *id = Some(self.declare(name, DeclKind::Let, stmt.span));
+            }
+            StmtKind::Const { name, value, .. } => {
+                self.visit_expr_mut(value);
+                self.declare(name, DeclKind::Const, stmt.span);
+            }
+            StmtKind::Block(stmts) => self.block(stmts, None),
+            StmtKind::Assign { target, value } => {
+                if let AssignTarget::Var(name) = target {
+                    self.lookup(name, stmt.span);
+                }
+                visit_mut::walk_assign_target_mut(self, target);
+                self.visit_expr_mut(value);
+            }
+            StmtKind::If { cond, then_branch, else_branch } => {
+                self.visit_expr_mut(cond);
+                self.branch(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.branch(else_branch);
+                }
+            }
+            StmtKind::Loop { body } => self.branch(body),
+            StmtKind::While { cond, body } => {
+                self.visit_expr_mut(cond);
+                self.branch(body);
+            }
+            StmtKind::For { var, iter, body } => {
+                self.visit_expr_mut(iter);
+                let span = stmt.span;
+                self.scoped(|pass| {
+                    pass.declare(var, DeclKind::Loop, span);
+                    pass.branch(body);
+                });
+            }
+            _ => visit_mut::walk_stmt_mut(self, stmt),
+        }
+    }
+
+    fn visit_fn_decl_mut(&mut self, decl: &mut FnDecl) {
+        self.scoped(|pass| visit_mut::walk_fn_decl_mut(pass, decl));
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let node = match expr.id {
+            Some(node) => node,
+            None => *expr.id.insert(self.fresh_node()),
+        };
+        match &mut expr.kind {
+            ExprKind::Var { id, name } => {
+                let symbol = self.lookup(name, expr.span);
+                if let Some(Symbol::Var(var)) = &symbol {
+                    *id = Some(*var);
+                }
+                if let Some(symbol) = symbol {
+                    self.res.symbols.insert(node, symbol);
+                }
+            }
+            ExprKind::Block(stmts, tail) => self.block(stmts, tail.as_deref_mut()),
+            ExprKind::Lambda { .. } => self.scoped(|pass| visit_mut::walk_expr_mut(pass, expr)),
+            // Method names are looked up on the receiver's type, not in scope
+            _ => visit_mut::walk_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        self.declare(&param.name, DeclKind::Param, param.span);
+        visit_mut::walk_param_mut(self, param);
+    }
+
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        self.pattern_span = arm.span;
+        self.scoped(|pass| visit_mut::walk_match_arm_mut(pass, arm));
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        if let Some(name) = fieldless_variant(pattern, |name| self.is_variant(name)).cloned() {
+            *pattern = Pattern::Variant { name, args: Vec::new() };
+        }
+        match pattern {
+            Pattern::Bind(name) => {
+                let bound = self.scopes.last().is_some_and(|scope| scope.names.contains_key(name.as_str()));
+                if !(self.or_alternative && bound) {
+                    self.declare(name, DeclKind::Pattern, self.pattern_span);
+                }
+            }
+            Pattern::Or(alternatives) => {
+                let outer = self.or_alternative;
+                for (i, alternative) in alternatives.iter_mut().enumerate() {
+                    self.or_alternative = outer || i > 0;
+                    self.visit_pattern_mut(alternative);
+                }
+                self.or_alternative = outer;
+            }
+            _ => visit_mut::walk_pattern_mut(self, pattern),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Substitutions and unification
+//!
+//! Inference variables are [`TypeKind::Inference`] ids. Unifying two types
+//! records what the variables in them stand for in a [`Substitution`]; the
+//! occurs check rejects solutions such as `_0 = list<_0>`, which have no
+//! finite type.
+//!
+//! The variable of an unsuffixed number literal is marked with a
+//! [`NumberKind`]: it can only be solved as a type of that kind, and one
+//! left unsolved defaults to `i32` or `f64`.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use synton_ast::types::{EnumVariant, StructField};
+use synton_ast::{BuiltinType, Position, Span, Type, TypeKind};
+
+use crate::error::{TResult, TypeError};
+
+/// What kind of number an inference variable must be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberKind {
+    /// Any integer type, `i32` by default
+    Integer,
+    /// Any float type, `f64` by default
+    Float,
+}
+
+impl NumberKind {
+    /// Type the variable has if nothing else decides it
+    pub fn default_type(self) -> BuiltinType {
+        match self {
+            NumberKind::Integer => BuiltinType::I32,
+            NumberKind::Float => BuiltinType::F64,
+        }
+    }
+
+    /// Check if a type is of this kind
+    pub fn accepts(self, ty: BuiltinType) -> bool {
+        match self {
+            NumberKind::Integer => ty.is_integer(),
+            NumberKind::Float => ty.is_float(),
+        }
+    }
+}
+
+/// Solved inference variables
+#[derive(Debug, Clone, Default)]
+pub struct Substitution {
+    bindings: FxHashMap<u32, Type>,
+    numbers: FxHashMap<u32, NumberKind>,
+}
+
+impl Substitution {
+    /// Create an empty substitution
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Type a variable is bound to, if any
+    pub fn get(&self, var: u32) -> Option<&Type> {
+        self.bindings.get(&var)
+    }
+
+    /// Number of solved variables
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// Check if no variable is solved
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// Require a variable to be solved as a number of the given kind
+    pub fn mark_number(&mut self, var: u32, kind: NumberKind) {
+        self.numbers.insert(var, kind);
+    }
+
+    /// Kind of number an unsolved variable must be, if any
+    pub fn number_kind(&self, var: u32) -> Option<NumberKind> {
+        self.numbers.get(&var).copied()
+    }
+
+    /// Solve a number variable as its default type. Does nothing to other
+    /// variables.
+    pub fn default_number(&mut self, var: u32) {
+        if let (Some(kind), None) = (self.number_kind(var), self.bindings.get(&var)) {
+            self.bindings.insert(var, Type::builtin(kind.default_type(), Span::single(Position::start())));
+        }
+    }
+
+    /// Solve every unsolved number variable as its default type
+    pub fn default_numbers(&mut self) {
+        let vars: Vec<_> = self.numbers.keys().copied().collect();
+        for var in vars {
+            self.default_number(var);
+        }
+    }
+
+    /// Like [`Self::apply`], with unsolved number variables shown as the
+    /// type they default to. For messages.
+    pub fn display(&self, ty: &Type) -> Type {
+        match &self.apply(ty).kind {
+            TypeKind::Inference(var) => match self.number_kind(*var) {
+                Some(kind) => Type::builtin(kind.default_type(), ty.span),
+                None => self.apply(ty),
+            },
+            _ => map_children(&self.apply(ty), &mut |t| self.display(t)),
+        }
+    }
+
+    /// Replace solved variables in a type, as deep as they go
+    pub fn apply(&self, ty: &Type) -> Type {
+        match &ty.kind {
+            TypeKind::Inference(var) => match self.bindings.get(var) {
+                Some(bound) => self.apply(bound),
+                None => ty.clone(),
+            },
+            _ => map_children(ty, &mut |t| self.apply(t)),
+        }
+    }
+
+    /// Make two types equal by solving inference variables
+    ///
+    /// `dyn`, `!` (the type of `return` and friends) and the error type are
+    /// compatible with every type. A refinement unifies like its base
+    /// type; its constraint is checked elsewhere.
+    pub fn unify(&mut self, expected: &Type, found: &Type) -> TResult<()> {
+        let expected = self.apply(expected);
+        let found = self.apply(found);
+        self.unify_resolved(&expected, &found).map_err(|err| match err {
+            // Report the types the caller asked about, not the innermost
+            // pair that failed
+            TypeError::Mismatch { .. } => TypeError::Mismatch {
+                expected: self.display(&expected).to_string(),
+                found: self.display(&found).to_string(),
+            },
+            err => err,
+        })
+    }
+
+    fn unify_resolved(&mut self, expected: &Type, found: &Type) -> TResult<()> {
+        use TypeKind::*;
+
+        match (&expected.kind, &found.kind) {
+            // Checked before variables, so that a variable is never solved
+            // as `!` just because one branch leaves early, or as the type of
+            // an expression that has already been reported
+            (Never, _) | (_, Never) | (Error, _) | (_, Error) => Ok(()),
+            (Inference(a), Inference(b)) if a == b => Ok(()),
+            (Inference(var), _) => self.bind(*var, found),
+            (_, Inference(var)) => self.bind(*var, expected),
+            (Builtin(BuiltinType::Dyn), _) | (_, Builtin(BuiltinType::Dyn)) => Ok(()),
+            (Refinement(r), _) => self.unify(&r.base, found),
+            (_, Refinement(r)) => self.unify(expected, &r.base),
+            (Builtin(a), Builtin(b)) if a == b => Ok(()),
+            (Var(a), Var(b)) if a == b => Ok(()),
+            (Unit, Unit) => Ok(()),
+            (List(a), List(b)) | (Maybe(a), Maybe(b)) | (Ref(a), Ref(b)) => self.unify(a, b),
+            (Tuple(a), Tuple(b)) if a.len() == b.len() => self.unify_all(a, b),
+            (Named { name: na, args: a }, Named { name: nb, args: b }) if na == nb && a.len() == b.len() => {
+                self.unify_all(a, b)
+            }
+            (Fn { params: pa, ret: ra }, Fn { params: pb, ret: rb }) if pa.len() == pb.len() => {
+                self.unify_all(pa, pb)?;
+                self.unify(ra, rb)
+            }
+            (Fn { params: pa, .. }, Fn { params: pb, .. }) => {
+                Err(TypeError::ArgCount { expected: pa.len(), found: pb.len() })
+            }
+            (Result { ok: oa, err: ea }, Result { ok: ob, err: eb }) => {
+                self.unify(oa, ob)?;
+                self.unify(ea, eb)
+            }
+            (Struct(a), Struct(b))
+                if a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.name == y.name) =>
+            {
+                for (x, y) in a.iter().zip(b) {
+                    self.unify(&x.ty, &y.ty)?;
+                }
+                Ok(())
+            }
+            (Enum(a), Enum(b))
+                if a.len() == b.len()
+                    && a.iter().zip(b).all(|(x, y)| x.name == y.name && x.types.len() == y.types.len()) =>
+            {
+                for (x, y) in a.iter().zip(b) {
+                    self.unify_all(&x.types, &y.types)?;
+                }
+                Ok(())
+            }
+            _ => Err(TypeError::Mismatch { expected: expected.to_string(), found: found.to_string() }),
+        }
+    }
+
+    fn unify_all(&mut self, expected: &[Type], found: &[Type]) -> TResult<()> {
+        for (a, b) in expected.iter().zip(found) {
+            self.unify(a, b)?;
+        }
+        Ok(())
+    }
+
+    /// Solve a variable, unless that would make its type infinite or not
+    /// the kind of number it has to be
+    fn bind(&mut self, var: u32, ty: &Type) -> TResult<()> {
+        if free_vars(ty).contains(&var) {
+            return Err(TypeError::Cycle {
+                var: Type::new(TypeKind::Inference(var), ty.span).to_string(),
+                ty: ty.to_string(),
+            });
+        }
+        if let Some(kind) = self.number_kind(var) {
+            let base = match &ty.kind {
+                TypeKind::Refinement(r) => &r.base.kind,
+                kind => kind,
+            };
+            let accepted = match base {
+                TypeKind::Inference(other) => match self.number_kind(*other) {
+                    Some(other) => other == kind,
+                    None => {
+                        self.numbers.insert(*other, kind);
+                        true
+                    }
+                },
+                TypeKind::Builtin(BuiltinType::Dyn) => true,
+                TypeKind::Builtin(b) => kind.accepts(*b),
+                _ => false,
+            };
+            if !accepted {
+                let number = Type::builtin(kind.default_type(), ty.span);
+                return Err(TypeError::Mismatch { expected: ty.to_string(), found: number.to_string() });
+            }
+        }
+        self.bindings.insert(var, ty.clone());
+        Ok(())
+    }
+}
+
+impl FromIterator<(u32, Type)> for Substitution {
+    fn from_iter<I: IntoIterator<Item = (u32, Type)>>(iter: I) -> Self {
+        Self { bindings: iter.into_iter().collect(), numbers: FxHashMap::default() }
+    }
+}
+
+/// Rebuild a type with `f` applied to each of its direct component types
+pub(crate) fn map_children(ty: &Type, f: &mut impl FnMut(&Type) -> Type) -> Type {
+    let kind = match &ty.kind {
+        TypeKind::Inference(_)
+        | TypeKind::Builtin(_)
+        | TypeKind::Var(_)
+        | TypeKind::Never
+        | TypeKind::Unit
+        | TypeKind::Error => ty.kind.clone(),
+        TypeKind::Refinement(r) => {
+            let mut r = r.clone();
+            *r.base = f(&r.base);
+            TypeKind::Refinement(r)
+        }
+        TypeKind::List(inner) => TypeKind::List(Box::new(f(inner))),
+        TypeKind::Maybe(inner) => TypeKind::Maybe(Box::new(f(inner))),
+        TypeKind::Ref(inner) => TypeKind::Ref(Box::new(f(inner))),
+        TypeKind::Tuple(items) => TypeKind::Tuple(items.iter().map(&mut *f).collect()),
+        TypeKind::Named { name, args } => {
+            TypeKind::Named { name: name.clone(), args: args.iter().map(&mut *f).collect() }
+        }
+        TypeKind::Struct(fields) => TypeKind::Struct(
+            fields.iter().map(|field| StructField { ty: f(&field.ty), ..field.clone() }).collect(),
+        ),
+        TypeKind::Enum(variants) => TypeKind::Enum(
+            variants
+                .iter()
+                .map(|variant| EnumVariant { types: variant.types.iter().map(&mut *f).collect(), ..variant.clone() })
+                .collect(),
+        ),
+        TypeKind::Fn { params, ret } => TypeKind::Fn {
+            params: params.iter().map(&mut *f).collect(),
+            ret: Box::new(f(ret)),
+        },
+        TypeKind::Result { ok, err } => TypeKind::Result { ok: Box::new(f(ok)), err: Box::new(f(err)) },
+    };
+    Type::new(kind, ty.span)
+}
+
+/// Inference variables occurring in a type
+pub fn free_vars(ty: &Type) -> FxHashSet<u32> {
+    let mut vars = FxHashSet::default();
+    collect_vars(ty, &mut vars);
+    vars
+}
+
+fn collect_vars(ty: &Type, vars: &mut FxHashSet<u32>) {
+    match &ty.kind {
+        TypeKind::Inference(var) => {
+            vars.insert(*var);
+        }
+        TypeKind::Builtin(_) | TypeKind::Var(_) | TypeKind::Never | TypeKind::Unit | TypeKind::Error => {}
+        TypeKind::Refinement(r) => collect_vars(&r.base, vars),
+        TypeKind::List(inner) | TypeKind::Maybe(inner) | TypeKind::Ref(inner) => collect_vars(inner, vars),
+        TypeKind::Tuple(items) | TypeKind::Named { args: items, .. } => {
+            items.iter().for_each(|t| collect_vars(t, vars))
+        }
+        TypeKind::Struct(fields) => fields.iter().for_each(|field| collect_vars(&field.ty, vars)),
+        TypeKind::Enum(variants) => {
+            variants.iter().flat_map(|variant| &variant.types).for_each(|t| collect_vars(t, vars))
+        }
+        TypeKind::Fn { params, ret } => {
+            params.iter().for_each(|t| collect_vars(t, vars));
+            collect_vars(ret, vars);
+        }
+        TypeKind::Result { ok, err } => {
+            collect_vars(ok, vars);
+            collect_vars(err, vars);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Types of a checked module
+//!
+//! [`TypedModule`] is a side table: the AST is left as it is, and the type
+//! of an expression is looked up by its [`NodeId`]. Expressions get their
+//! ids from name resolution; ones without an id are not recorded. The
+//! effects of top-level functions are recorded by name.
+
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use synton_ast::{Effects, NodeId, Span, Type};
+
+use crate::error::TypeDiagnostic;
+use crate::resolve::{DeclKind, Symbol};
+
+/// A declared name and its type
+#[derive(Debug, Clone)]
+pub struct TypedBinding {
+    /// Declared name
+    pub name: String,
+    /// How the name was introduced
+    pub kind: DeclKind,
+    /// Variable or function id assigned by name resolution, if any
+    pub symbol: Option<Symbol>,
+    /// Span of the declaration
+    pub span: Span,
+    /// Type, with inference variables left where the type is generic
+    pub ty: Type,
+}
+
+/// Result of checking a module
+#[derive(Debug, Clone, Default)]
+pub struct TypedModule {
+    /// Type of each expression
+    pub types: FxHashMap<NodeId, Type>,
+    /// Every binding, in the order it was checked
+    pub bindings: Vec<TypedBinding>,
+    /// Warnings, such as unreachable match arms
+    pub warnings: Vec<TypeDiagnostic>,
+    /// Effects of each top-level function whose effects are known, by name
+    pub effects: FxHashMap<String, Effects>,
+}
+
+impl TypedModule {
+    /// Type of an expression
+    pub fn type_of(&self, node: NodeId) -> Option<&Type> {
+        self.types.get(&node)
+    }
+
+    /// Last binding of a name
+    pub fn binding(&self, name: &str) -> Option<&TypedBinding> {
+        self.bindings.iter().rev().find(|binding| binding.name == name)
+    }
+
+    /// Effects of calling a top-level function
+    pub fn effects_of(&self, name: &str) -> Option<Effects> {
+        self.effects.get(name).copied()
+    }
+}
+
+impl fmt::Display for TypedModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for binding in &self.bindings {
+            let keyword = match binding.kind {
+                DeclKind::Let => "let",
+                DeclKind::Const => "const",
+                DeclKind::Param => "param",
+                DeclKind::Pattern => "pattern",
+                DeclKind::Loop => "for",
+                DeclKind::Fn => "fn",
+                DeclKind::Import => "import",
+                DeclKind::Variant => "variant",
+            };
+            writeln!(f, "{} {}: {}", keyword, binding.name, binding.ty)?;
+        }
+
+        let mut effects: Vec<_> = self.effects.iter().collect();
+        effects.sort_by_key(|(name, _)| *name);
+        for (name, effects) in effects {
+            writeln!(f, "effects {}: {}", name, effects)?;
+        }
+
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by_key(|(node, _)| node.as_u32());
+        for (node, ty) in types {
+            writeln!(f, "{}: {}", node, ty)?;
+        }
+        Ok(())
+    }
+}
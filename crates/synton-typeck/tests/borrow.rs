@@ -0,0 +1,72 @@
+//! Borrow checking
+
+use synton_ast::{AssignTarget, Span, StmtKind};
+use synton_parser::SyntonParser;
+use synton_typeck::{Resolver, TResult, TypeChecker, TypeError, TypedModule};
+
+fn check_spanned(src: &str) -> Result<TypedModule, (TypeError, Span)> {
+    let mut module = SyntonParser::new().parse_module(src).unwrap();
+    Resolver::new().resolve(&mut module);
+    TypeChecker::new().check_module(&module).map_err(|mut errors| {
+        let error = errors.remove(0);
+        (error.error, error.span)
+    })
+}
+
+fn check(src: &str) -> TResult<TypedModule> {
+    check_spanned(src).map_err(|(error, _)| error)
+}
+
+fn escapes(src: &str) -> bool {
+    matches!(check(src), Err(TypeError::EscapingReference { .. }))
+}
+
+#[test]
+fn test_mutability_syntax() {
+    let parser = SyntonParser::new();
+    let module = parser.parse_module("(let mut x = 1)\n(set (* r) 2)").unwrap();
+    assert!(matches!(module.stmts[0].kind, StmtKind::Let { mutable: true, .. }));
+    assert!(matches!(&module.stmts[1].kind, StmtKind::Assign { target: AssignTarget::Deref(_), .. }));
+    assert!(parser.parse_module("(set (+ x 1) 2)").is_err());
+}
+
+#[test]
+fn test_assignment() {
+    // Only variables declared mutable are assigned, directly or through
+    // a reference
+    check("(let mut x = 1)\n(set x 2)\n(let r = (& x))\n(set (* r) 3)").unwrap();
+    assert!(matches!(check("(let x = 1)\n(set x 2)"), Err(TypeError::AssignImmutable { .. })));
+    assert!(matches!(check("(let a = [1 2])\n(set (.get a 0) 2)"), Err(TypeError::AssignImmutable { .. })));
+    assert!(matches!(check("(let f = (fn [n] { (set n 2) }))"), Err(TypeError::AssignImmutable { .. })));
+    assert!(matches!(check("(let x = 1)\n(let r = (& x))\n(set (* r) 2)"), Err(TypeError::AssignThroughShared)));
+    assert!(matches!(check("(let f = (fn [p v] { (set (* p) v) }))"), Err(TypeError::AssignThroughShared)));
+}
+
+#[test]
+fn test_escaping_references() {
+    // References do not outlive what they borrow
+    assert!(escapes("(let f = (fn [] { (let y = 1) (& y) }))"));
+    assert!(escapes("(let mut r = (& 0))\n{ (let y = 1) (set r (& y)) }"));
+    assert!(escapes("(let v = (loop { (let y = 1) (break (& y)) }))"));
+    assert!(escapes("(let g = { (let y = 1) (fn [z] (& y)) })"));
+    check("(let mut x = 1)\n(let v = { (let r = (& x)) r })\n(set (* v) 2)").unwrap();
+}
+
+#[test]
+fn test_references_through_calls() {
+    // A call may return a reference into its arguments
+    assert!(escapes("(let id = (fn [p] p))\n(let v = { (let y = 1) (id (& y)) })"));
+    check("(let get = (fn [p] (* p)))\n(let v = { (let y = 1) (get (& y)) })").unwrap();
+}
+
+#[test]
+fn test_borrow_error_spans() {
+    // Violations point at the assignment or the escaping reference
+    let at = |src: &str| {
+        let (_, span) = check_spanned(src).unwrap_err();
+        (span.start.line, src[span.range()].to_string())
+    };
+    assert_eq!(at("(let y = 3)\n\n(set y 4)"), (3, "(set y 4)".to_string()));
+    assert_eq!(at("(let mut r = (& 0))\n{ (let y = 1) (set r (& y)) }"), (2, "(set r (& y))".to_string()));
+    assert_eq!(at("(let v = (loop {\n  (let y = 1)\n  (break (& y)) }))"), (3, "(& y)".to_string()));
+}
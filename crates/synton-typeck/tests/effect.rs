@@ -0,0 +1,69 @@
+//! Effect inference and checking
+
+use synton_ast::{Effect, Effects};
+use synton_parser::SyntonParser;
+use synton_typeck::{TypeChecker, TypeDiagnostic, TypeError, TypedModule};
+
+fn check(src: &str) -> Result<TypedModule, Vec<TypeDiagnostic>> {
+    TypeChecker::new()
+        .with_globals(["print", "now"])
+        .with_effects([("print", Effects::only(Effect::Io))])
+        .check_module(&SyntonParser::new().parse_module(src).unwrap())
+}
+
+fn effects_of(src: &str, name: &str) -> String {
+    check(src).unwrap().effects_of(name).unwrap().to_string()
+}
+
+fn first_error(src: &str) -> TypeDiagnostic {
+    check(src).unwrap_err().remove(0)
+}
+
+const EVEN_ODD: &str = "(fn even [n] ! pure (if (== n 0) true (odd (- n 1))))\n\
+                        (fn odd [n] (if (== n 0) false (even (- n 1))))";
+
+#[test]
+fn test_inferred_effects() {
+    // Effects are inferred through calls, and host names without
+    // declared effects call the host
+    assert_eq!(effects_of("(let area = (fn [w h] (* w h)))", "area"), "pure");
+    assert_eq!(effects_of("(let log = (fn [s] (print s)))\n(let hi = (fn [] (log \"hi\")))", "hi"), "io");
+    assert_eq!(effects_of("(let stamp = (fn [] (now 0)))", "stamp"), "host");
+    assert_eq!(effects_of("(let ratio = (fn [a b] (/ a b)))", "ratio"), "panic");
+    assert_eq!(effects_of("(let half = (fn [a] (/ a 2)))", "half"), "pure");
+    assert_eq!(effects_of("(let apply = (fn [f x] (f x)))", "apply"), "io, host, nondet, panic");
+}
+
+#[test]
+fn test_declared_effects() {
+    // A pure function only calls pure ones, through mutual recursion too
+    check(EVEN_ODD).unwrap();
+    check("(let log = (fn [s] (print s)))\n(fn f [n] ! io (log n))").unwrap();
+    let error = first_error("(let log = (fn [s] (print s)))\n(fn f [n] ! pure (log n))");
+    assert_eq!(error.error.to_string(), "io not allowed in `f`, declared pure");
+    assert_eq!(error.span.start.line, 2);
+    assert!(check(&format!("{}\n(fn g [n] ! pure (if (odd n) (print n) n))", EVEN_ODD)).is_err());
+}
+
+#[test]
+fn test_lambda_effects() {
+    // Lambdas declare their effects in source, and a declaration may
+    // allow more than the body has
+    let error = first_error("(let log = (fn [s] (print s)))\n(let f = (fn [n] ! pure (log n)))");
+    assert_eq!(error.error.to_string(), "io not allowed in `f`, declared pure");
+    assert_eq!(error.span.start.line, 2);
+    assert_eq!(effects_of("(let f = (fn [n] ! io, panic (print n)))", "f"), "io, panic");
+    assert_eq!(effects_of("(let f = (fn [n] ! pure n))\n(let g = (fn [n] (f n)))", "g"), "pure");
+    let error = first_error("(let apply = (fn [g] (g 1)))\n(apply (fn [x] ! panic (print x)))");
+    assert_eq!(error.error.to_string(), "io not allowed in a function, declared panic");
+    let error = SyntonParser::new().parse_expr("(fn [x] ! fast x)").unwrap_err();
+    assert!(error.to_string().contains("unknown effect 'fast'"), "{}", error);
+}
+
+#[test]
+fn test_contract_effects() {
+    // Contracts are pure, whatever the function body does
+    let error = first_error("(fn f [n] @pre((> (/ 10 n) 1)) n)").error;
+    assert!(matches!(error, TypeError::DisallowedEffect { context, .. } if context == "a contract"));
+    check("(fn f [n] @pre((> (/ n 2) 1)) (print n))").unwrap();
+}
@@ -0,0 +1,88 @@
+//! Match exhaustiveness and reachability
+
+use synton_ast::{ExprKind, Module, Pattern, StmtKind};
+use synton_parser::SyntonParser;
+use synton_typeck::{Resolver, TypeChecker, TypeError, TypedModule};
+
+fn parse(src: &str) -> Module {
+    let src = format!("(enum Shape (Circle f64) (Rect f64 f64) Empty)\n{}", src);
+    SyntonParser::new().parse_module(&src).unwrap()
+}
+
+fn check(src: &str) -> Result<TypedModule, Vec<TypeError>> {
+    let mut module = parse(src);
+    Resolver::new().resolve(&mut module);
+    TypeChecker::new()
+        .check_module(&module)
+        .map_err(|errors| errors.into_iter().map(|e| e.error).collect())
+}
+
+/// The patterns a match reports as missing
+fn missing(src: &str) -> String {
+    match check(src) {
+        Err(errors) => match errors.as_slice() {
+            [TypeError::NonExhaustive { missing }] => missing.join(", "),
+            other => panic!("expected one non-exhaustive match, got {:?}", other),
+        },
+        Ok(_) => panic!("expected a non-exhaustive match"),
+    }
+}
+
+#[test]
+fn test_constructor_coverage() {
+    // maybe, bool and enums are covered constructor by constructor
+    assert_eq!(missing("(let m = (some 1))\n(match m (some x) => x)"), "(none)");
+    assert_eq!(missing("(match true true => 1)"), "false");
+    assert_eq!(missing("(let s = Empty)\n(match s (Circle r) => r Empty => 0.0)"), "(Rect _ _)");
+    check("(let s = Empty)\n(match s (Circle r) => r (Rect w h) => (* w h) Empty => 0.0)").unwrap();
+}
+
+#[test]
+fn test_nested_coverage() {
+    // Nested patterns, tuples and or-patterns
+    assert_eq!(missing("(match (some true) (some true) => 1 none => 0)"), "(some false)");
+    assert_eq!(missing("(match (true, false) (true, _) => 1 (_, true) => 2)"), "(false, false)");
+    check("(match (some 1) (| (some _) none) => 1)").unwrap();
+}
+
+#[test]
+fn test_catch_alls_and_guards() {
+    // Integers and strings need a catch-all; a guard may fail
+    assert_eq!(missing("(match 3 0 => 1 1 => 2)"), "_");
+    assert_eq!(missing("(match true x if x => 1 false => 0)"), "true");
+}
+
+#[test]
+fn test_bare_variants() {
+    // A bare variant name is not a binding
+    let typed = check("(let s = Empty)\n(match s Empty => 0 _ => 1)").unwrap();
+    assert!(typed.warnings.is_empty());
+    let mut module = parse("(match s Empty => 0)");
+    Resolver::new().resolve(&mut module);
+    let StmtKind::Expr(expr) = &module.stmts[1].kind else { panic!("expected expression") };
+    let ExprKind::Match { arms, .. } = &expr.kind else { panic!("expected match") };
+    assert!(matches!(&arms[0].pattern, Pattern::Variant { name, args } if name == "Empty" && args.is_empty()));
+
+    // Also when the module is checked without resolving it first
+    let unresolved = |src: &str| TypeChecker::new().check_module(&parse(src));
+    let errors = unresolved("(let s = Empty)\n(match s Empty => 0.0 (Circle r) => r)").unwrap_err();
+    let errors: Vec<_> = errors.into_iter().map(|e| e.error).collect();
+    assert!(matches!(errors.as_slice(), [TypeError::NonExhaustive { missing }] if missing == &["(Rect _ _)"]));
+    let typed = unresolved("(let s = Empty)\n(match s Empty => 0.0 (Circle r) => r (Rect w h) => w)").unwrap();
+    assert!(typed.warnings.is_empty());
+}
+
+#[test]
+fn test_unreachable_arms() {
+    // Arms after a catch-all are warned about, not rejected
+    let typed = check("(match (some 1) _ => 0 (some x) => x)").unwrap();
+    assert!(matches!(typed.warnings.as_slice(), [w] if matches!(w.error, TypeError::UnreachableArm)));
+}
+
+#[test]
+fn test_variant_pattern_types() {
+    let errors = check("(let s = Empty)\n(match s (Circle r) => r (Rect w) => w _ => 0.0)").unwrap_err();
+    assert!(matches!(errors.as_slice(), [TypeError::ArgCount { expected: 2, found: 1 }]));
+    let errors = check("(match (some 1) (Circle r) => r _ => 0.0)").unwrap_err();
+    assert!(matches!(errors.as_slice(), [TypeError::Mismatch { .. }]));
+}
@@ -0,0 +1,309 @@
+//! Type inference and checking
+
+use synton_parser::SyntonParser;
+
+#[test]
+fn test_type_inference() {
+    use synton_typeck::{TypeChecker, TypeError};
+
+    let parser = SyntonParser::new();
+    let check = |src: &str| {
+        TypeChecker::new()
+            .with_globals(["print"])
+            .check_module(&parser.parse_module(src).unwrap())
+            .map_err(|mut errors| errors.remove(0).error)
+    };
+
+    // Let-bound functions are generic; parameter types are inferred from use
+    check("(let id = (fn [x] x))\n(let a = (+ (id 1) 2))\n(let b = (if (id true) (id \"s\") \"t\"))").unwrap();
+    check("(let inc = (fn [x] (+ x 1)))\n(print (inc 2))").unwrap();
+    assert!(matches!(
+        check("(let inc = (fn [x] (+ x 1)))\n(inc \"s\")"),
+        Err(TypeError::Mismatch { .. })
+    ));
+
+    // Type arguments are compared, not just the outer constructor
+    let err = check("(let names = (fn [xs: list<string>] xs))\n(names [1 2])").unwrap_err();
+    assert_eq!(err.to_string(), "type mismatch: expected list<string>, found list<i32>");
+
+    // A lambda-bound variable is monomorphic
+    assert!(check("(let f = (fn [g] { (g 1) (g \"s\") }))").is_err());
+    // The occurs check rejects infinite types
+    assert!(matches!(check("(let f = (fn [x] (x x)))"), Err(TypeError::Cycle { .. })));
+
+    let mut checker = TypeChecker::new();
+    let ty = checker.infer_expr(&parser.parse_expr("(fn [x] (if x 1 2))").unwrap()).unwrap();
+    assert_eq!(ty.to_string(), "(bool) -> i32");
+}
+
+#[test]
+fn test_function_types() {
+    use synton_typeck::{TypeChecker, TypeError};
+
+    let parser = SyntonParser::new();
+    let check = |src: &str| {
+        TypeChecker::new()
+            .check_module(&parser.parse_module(src).unwrap())
+            .map_err(|mut errors| errors.remove(0).error)
+    };
+    let even_odd = "(fn even [n: i32] -> bool (if (== n 0) true (odd (- n 1))))\n\
+                    (fn odd [n] (if (== n 0) false (even (- n 1))))";
+
+    // Functions are visible before their declaration, and may call each other
+    check(&format!("(let e = (even 4))\n{}", even_odd)).unwrap();
+    assert!(matches!(check(&format!("(even \"4\")\n{}", even_odd)), Err(TypeError::Mismatch { .. })));
+    let err = check(&format!("(odd 1 2)\n{}", even_odd));
+    assert!(matches!(err, Err(TypeError::ArgCount { expected: 1, found: 2 })));
+    assert!(matches!(check("(missing 1)"), Err(TypeError::UndefinedFn { name }) if name == "missing"));
+    assert!(matches!(check("(let x = 1)\n(x 2)"), Err(TypeError::NotCallable { .. })));
+
+    // Bodies and `return` values are checked against the return type
+    let err = check("(fn name [] -> string 42)").unwrap_err();
+    assert_eq!(err.to_string(), "type mismatch: expected string, found i32");
+    let abs = |early: &str| format!("(fn abs [n: i32] -> i32 (if (< n 0) (return {}) n))", early);
+    check(&abs("(- 0 n)")).unwrap();
+    assert!(check(&abs("\"negative\"")).is_err());
+
+    // A declaration without a body gives only the signature
+    check("(fn len [s: string] -> i32)\n(let n = (+ (len \"a\") 1))").unwrap();
+    assert!(matches!(check("(fn len [s: string] -> i32)\n(len 1)"), Err(TypeError::Mismatch { .. })));
+
+    // Once declared, functions are generic in what their annotations leave
+    // open; uses ahead of the declaration share one type
+    let uses = "(let a = (+ (first 1 \"x\") 1))\n(let b = (first \"x\" 1))";
+    let first = "(fn first [a b] a)";
+    assert!(check(&format!("{}\n{}", uses, first)).is_err());
+    check(&format!("{}\n{}", first, uses)).unwrap();
+}
+
+#[test]
+fn test_type_diagnostics() {
+    use synton_typeck::{Resolver, TypeChecker, TypeError};
+
+    // Every error is reported, each once, at the node it concerns
+    let src = "(let n = (+ 1 \"one\"))\n(let m = (* n 2))\n(if 1 (print n) (print m))\n(let k = [1 true 3])";
+    let mut module = SyntonParser::new().parse_module(src).unwrap();
+    Resolver::new().with_globals(["print"]).resolve(&mut module);
+    let errors = TypeChecker::new().with_globals(["print"]).check_module(&module).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "type mismatch: expected i32, found string",
+            "type mismatch: expected bool, found i32",
+            "type mismatch: expected i32, found bool",
+        ]
+    );
+    assert!(errors.iter().all(|e| e.node.is_some()));
+    assert_eq!(errors[0].code(), "synton::typeck::mismatch");
+    let spans: Vec<_> = errors.iter().map(|e| &src[e.span.range()]).collect();
+    assert_eq!(spans, ["\"one\"", "1", "true"]);
+    assert_eq!((errors[1].span.start.line, errors[1].span.start.column), (3, 5));
+
+    // An undefined name does not also make its uses mismatch
+    let module = SyntonParser::new().parse_module("(let x = (+ y 1))\n(let z = (+ x \"s\"))").unwrap();
+    let errors = TypeChecker::new().check_module(&module).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0].error, TypeError::UndefinedVar { name } if name == "y"));
+
+    // Mismatched branches point at the other branch
+    let module = SyntonParser::new().parse_module("(let v = (if true 1 \"s\"))").unwrap();
+    let error = &TypeChecker::new().check_module(&module).unwrap_err()[0];
+    assert_eq!(error.labels.len(), 1);
+    assert_eq!(error.labels[0].message, "then branch has type i32");
+    assert_eq!(&"(let v = (if true 1 \"s\"))"[error.labels[0].span.range()], "1");
+}
+
+#[test]
+fn test_recursion_limit() {
+    use synton_typeck::{TypeChecker, TypeCheckerConfig, TypeError};
+
+    // A statement holding (+ 1 (+ 1 ... 0)) with ten additions, twelve
+    // levels deep
+    let src = format!("(let n = {}0{})", "(+ 1 ".repeat(10), ")".repeat(10));
+    let module = SyntonParser::new().parse_module(&src).unwrap();
+    let check = |max_depth| {
+        let config = TypeCheckerConfig { max_depth, ..TypeCheckerConfig::default() };
+        TypeChecker::with_config(config).check_module(&module)
+    };
+
+    assert_eq!(check(12).unwrap().binding("n").unwrap().ty.to_string(), "i32");
+
+    // Past the limit the checker stops with a single error at the first
+    // expression too deep to visit: the left operand of the innermost sum
+    let errors = check(11).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].error, TypeError::RecursionLimit { max_depth: 11 }));
+    assert_eq!(errors[0].code(), "synton::typeck::recursion_limit");
+    assert_eq!(errors[0].span.range().start, src.rfind("(+ 1").unwrap() + 3);
+    assert_eq!(check(4).unwrap_err().len(), 1);
+}
+
+#[test]
+fn test_statement_recursion_limit() {
+    use synton_ast::{Expr, ExprKind, Literal, Module, ModuleId, Position, Span, Stmt, StmtKind};
+    use synton_typeck::{ResolveError, Resolver, TypeChecker, TypeCheckerConfig, TypeError, TypeInfer};
+
+    // (while true (print 1)) is four levels deep: the loop, the statement
+    // in its body, the call and its operands
+    let nested = |n: usize| format!("{}(print 1){}", "(while true ".repeat(n), ")".repeat(n));
+    let check = |n| {
+        let config = TypeCheckerConfig { max_depth: 4, ..TypeCheckerConfig::default() };
+        let module = SyntonParser::new().parse_module(&nested(n)).unwrap();
+        TypeChecker::with_config(config).with_globals(["print"]).check_module(&module)
+    };
+    check(1).unwrap();
+    let errors = check(20).unwrap_err();
+    assert!(matches!(errors[..], [ref error] if matches!(error.error, TypeError::RecursionLimit { max_depth: 4 })));
+
+    // Inference counts statements as well as expressions
+    let stmt = SyntonParser::new().parse_stmt(&nested(20)).unwrap();
+    let mut infer = TypeInfer::new().with_max_depth(4);
+    infer.infer_stmt(&stmt);
+    assert!(infer.diagnostics().iter().any(|d| matches!(d.error, TypeError::RecursionLimit { max_depth: 4 })));
+
+    // No pass recurses into a module nested far deeper than the parser
+    // allows, with the default limit
+    let span = Span::new(Position::start(), Position::start());
+    let cond = || Box::new(Expr::new(ExprKind::Literal(Literal::Bool(true)), span));
+    let mut body = Stmt::new(StmtKind::Expr(cond()), span);
+    for _ in 0..5_000 {
+        body = Stmt::new(StmtKind::While { cond: cond(), body: Box::new(body) }, span);
+    }
+    let mut module = Module::new(ModuleId::new(String::new()));
+    module.stmts.push(body);
+    let resolution = Resolver::new().resolve(&mut module);
+    assert!(matches!(resolution.diagnostics[..], [ResolveError::RecursionLimit { max_depth: 256, .. }]));
+    let errors = TypeChecker::new().check_module(&module).unwrap_err();
+    assert!(matches!(errors[..], [ref error] if matches!(error.error, TypeError::RecursionLimit { max_depth: 256 })));
+}
+
+#[test]
+fn test_typed_module() {
+    use synton_ast::{ExprKind, StmtKind, VarId};
+    use synton_typeck::{DeclKind, Resolver, Symbol, TypeChecker};
+
+    let src = "(let n = (+ 1 2))\n(let id = (fn [x] x))\n(let s = (id \"s\"))";
+    let mut module = SyntonParser::new().parse_module(src).unwrap();
+    Resolver::new().resolve(&mut module);
+    let typed = TypeChecker::new().check_module(&module).unwrap();
+
+    // Every binding, in order, with its final type
+    let bindings: Vec<_> = typed.bindings.iter().map(|b| (b.kind, b.name.as_str(), b.ty.to_string())).collect();
+    assert_eq!(
+        bindings,
+        [
+            (DeclKind::Let, "n", "i32".to_string()),
+            (DeclKind::Param, "x", "_2".to_string()),
+            (DeclKind::Let, "id", "(_2) -> _2".to_string()),
+            (DeclKind::Let, "s", "string".to_string()),
+        ]
+    );
+    assert_eq!(typed.binding("n").unwrap().symbol, Some(Symbol::Var(VarId::new(0))));
+
+    // Every expression with a node id, looked up by id
+    let StmtKind::Let { init: Some(init), .. } = &module.stmts[2].kind else { panic!("expected let") };
+    let ExprKind::Call { callee, .. } = &init.kind else { panic!("expected Call") };
+    assert_eq!(typed.type_of(init.id.unwrap()).unwrap().to_string(), "string");
+    assert_eq!(typed.type_of(callee.id.unwrap()).unwrap().to_string(), "(string) -> string");
+    assert!(typed.to_string().starts_with("let n: i32\nparam x: _2\n"));
+}
+
+#[test]
+fn test_generics() {
+    use synton_typeck::{TypeChecker, TypeError};
+
+    let parser = SyntonParser::new();
+    let ty = |src: &str| parser.parse_type(src).unwrap();
+    let decls = "(struct Pair<A, B> first: A second: B)\n(enum Opt<T> (Just T) Nothing)\n";
+    let check = |src: &str| {
+        let module = parser.parse_module(&format!("{}{}", decls, src)).unwrap();
+        TypeChecker::new().check_module(&module).map_err(|mut errors| errors.remove(0).error)
+    };
+    let type_of = |src: &str, name: &str| check(src).unwrap().binding(name).unwrap().ty.to_string();
+
+    // Applied types parse with their arguments
+    assert_eq!(ty("Pair<i32, list<T>>").to_string(), "Pair<i32, list<T>>");
+    assert_eq!(ty("list<Opt<i32>>").to_string(), "list<Opt<i32>>");
+
+    // A generic struct is instantiated afresh at each literal, and field
+    // types follow the arguments
+    let pairs = "(let p = (Pair first: 1 second: \"a\"))\n(let q = (Pair first: true second: 2))";
+    assert_eq!(type_of(pairs, "p"), "Pair<i32, string>");
+    assert_eq!(type_of(&format!("{}\n(let b = (.field q second))", pairs), "b"), "i32");
+    assert!(check(&format!("{}\n(let b = (+ (.field p second) 1))", pairs)).is_err());
+
+    // Generic functions over generic structs, written once
+    let swap = "(let swap = (fn [p: Pair<A, B>] (Pair first: (.field p second) second: (.field p first))))";
+    let swapped = format!("{}\n(let s = (swap (Pair first: 1 second: true)))", swap);
+    assert_eq!(type_of(&swapped, "s"), "Pair<bool, i32>");
+
+    // Enum variants are generic constructors
+    assert_eq!(type_of("(let j = (Just 1))", "j"), "Opt<i32>");
+    assert_eq!(type_of("(let n = Nothing)\n(let m = [n (Just \"x\")])", "m"), "list<Opt<string>>");
+    assert!(matches!(check("(let m = [(Just 1) (Just \"x\")])"), Err(TypeError::Mismatch { .. })));
+    assert!(check("(let f = (fn [o: Opt<i32>] o))\n(f (Just 1))").is_ok());
+
+    // Arity is checked against the declaration
+    assert!(matches!(
+        check("(let f = (fn [o: Opt<i32, bool>] o))"),
+        Err(TypeError::TypeArgCount { expected: 1, found: 2, .. })
+    ));
+    assert!(matches!(check("(let f = (fn [o: Nope<i32>] o))"), Err(TypeError::UndefinedType { .. })));
+}
+
+#[test]
+fn test_numeric_types() {
+    use synton_typeck::{TypeChecker, TypeError};
+
+    let parser = SyntonParser::new();
+    let check = |src: &str| {
+        let module = parser.parse_module(src).unwrap();
+        TypeChecker::new().check_module(&module).map_err(|mut errors| errors.remove(0).error)
+    };
+    let type_of = |src: &str, name: &str| check(src).unwrap().binding(name).unwrap().ty.to_string();
+
+    // Unsuffixed literals take the type their use gives them, or default
+    assert_eq!(type_of("(let n = 1)", "n"), "i32");
+    assert_eq!(type_of("(let x = 1.5)", "x"), "f64");
+    assert_eq!(type_of("(let n = 1)\n(let m = (+ n 2u8))", "n"), "u8");
+    assert_eq!(type_of("(let f = (fn [x: f32] (* x 2.0)))", "f"), "(f32) -> f32");
+    assert!(matches!(check("(let x = (+ 1 2.0))"), Err(TypeError::Mismatch { .. })));
+    assert!(matches!(
+        check("(let f = (fn [x: u8] x))\n(let y = (f 256))"),
+        Err(TypeError::LiteralOutOfRange { value: 256, .. })
+    ));
+
+    // Narrower numbers widen implicitly, but signed and unsigned never mix
+    assert_eq!(type_of("(let f = (fn [a: i64 b: i32] (+ a b)))", "f"), "(i64, i32) -> i64");
+    assert_eq!(type_of("(let f = (fn [a: u8 b: u64] (- b a)))", "f"), "(u8, u64) -> u64");
+    check("(let f = (fn [a: i64] a))\n(let g = (fn [b: i32] (f b)))").unwrap();
+    assert!(matches!(check("(let f = (fn [a: i64 b: u32] (+ a b)))"), Err(TypeError::Mismatch { .. })));
+    assert!(check("(let f = (fn [a: i32] a))\n(let g = (fn [b: i64] (f b)))").is_err());
+
+    // Operators are only defined for the types they make sense for
+    let invalid = |src: &str| matches!(check(src), Err(TypeError::InvalidOperand { .. }));
+    assert!(invalid("(let f = (fn [a: u32] (- a)))"));
+    assert!(invalid("(let x = (& 1.5 2.5))"));
+    assert!(invalid("(let x = (* \"a\" \"b\"))"));
+    check("(let x = (+ \"a\" \"b\"))\n(let y = (<< 1u64 3u64))").unwrap();
+
+    // `as` converts between numbers, and from bools and chars
+    assert_eq!(type_of("(let f = (fn [a: i64] (as a u8)))", "f"), "(i64) -> u8");
+    assert_eq!(type_of("(let b = (as true i32))\n(let c = (as 65 char))", "c"), "char");
+    check("(let f = (fn [c: char x: f64] [(as c u32) (as x u32)]))").unwrap();
+    let invalid = |src: &str| matches!(check(src), Err(TypeError::InvalidCast { .. }));
+    assert!(invalid("(let x = (as \"1\" i32))"));
+    assert!(invalid("(let f = (fn [a: i32] (as a bool)))"));
+    assert!(invalid("(let f = (fn [a: u32] (as a char)))"));
+
+    // Negative constants are not cast to unsigned types. Other negative
+    // values wrap to u8 and u32 at run time, and trap as u64.
+    for ty in ["u8", "u32", "u64"] {
+        let negative = |src: &str| matches!(check(src), Err(TypeError::LiteralOutOfRange { value: -1, .. }));
+        assert!(negative(&format!("(let x = (as -1 {}))", ty)), "{}", ty);
+        assert!(negative(&format!("(let x = (as (- 1) {}))", ty)), "{}", ty);
+        check(&format!("(let x = (as 1 {0}))\n(let f = (fn [a: i32] (as a {0})))", ty)).unwrap();
+    }
+    check("(let x = -1)\n(let y = (as x u64))").unwrap();
+}
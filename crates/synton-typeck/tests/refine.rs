@@ -0,0 +1,76 @@
+//! Refinement types
+
+use synton_parser::SyntonParser;
+use synton_typeck::{TResult, TypeChecker, TypeCheckerConfig, TypeError, TypedModule};
+
+fn check_with(src: &str, config: TypeCheckerConfig) -> TResult<TypedModule> {
+    let module = SyntonParser::new().parse_module(src).unwrap();
+    TypeChecker::with_config(config).check_module(&module).map_err(|mut errors| errors.remove(0).error)
+}
+
+fn check(src: &str) -> TResult<TypedModule> {
+    check_with(src, TypeCheckerConfig::default())
+}
+
+fn violated(src: &str) -> bool {
+    matches!(check(src), Err(TypeError::RefinementViolation { .. }))
+}
+
+/// Check `(fn f [n: param] -> ret body)`
+fn check_fn(param: &str, ret: &str, body: &str) -> TResult<TypedModule> {
+    check(&format!("(fn f [n: {}] -> {} {})", param, ret, body))
+}
+
+#[test]
+fn test_refinement_syntax() {
+    let ty = synton_parser::parse_type("i32 {> val 0}").unwrap();
+    assert_eq!(ty.to_string(), "{val: i32 | (> val 0)}");
+
+    // Constraints are boolean expressions
+    assert!(matches!(check("(let x: i32 {+ val 1} = 1)"), Err(TypeError::Mismatch { .. })));
+    assert!(matches!(check("(let x: i32 {> val} = 1)"), Err(TypeError::InvalidRefinement { .. })));
+}
+
+#[test]
+fn test_refinement_bindings() {
+    // Values are checked where they meet a declared refinement type
+    check("(let x: i32 {> val 0} = 5)").unwrap();
+    assert!(violated("(let x: i32 {> val 0} = (- 0 5))"));
+    assert!(violated("(let f = (fn [n: i32 {> val 0}] n))\n(let y = (f 0))"));
+}
+
+#[test]
+fn test_refinement_calls() {
+    // Arguments to declared functions are checked, wherever they are declared
+    let positive = "(fn f [n: i32 {> val 0}] -> i32 n)";
+    assert!(violated(&format!("{}\n(let y = (f 0))", positive)));
+    check(&format!("{}\n(let y = (f 3))", positive)).unwrap();
+    assert!(violated(&format!("(let y = (f (- 0 1)))\n{}", positive)));
+}
+
+#[test]
+fn test_refinement_returns() {
+    // Results of declared functions are checked, including early returns
+    assert!(matches!(
+        check_fn("i32 {>= val 0}", "i32 {>= val 0}", "(- n 1)"),
+        Err(TypeError::RefinementViolation { .. })
+    ));
+    assert!(violated("(fn f [n: i32] -> i32 {> val 0} (if (< n 0) (return (- 0 1)) 1))"));
+}
+
+#[test]
+fn test_refinement_facts() {
+    // What is known about variables carries over
+    assert!(violated("(let a = (- 0 5))\n(let x: i32 {> val 0} = a)"));
+    check("(let a: i32 {> val 1} = 2)\n(let b: i32 {> val 0} = (- a 1))").unwrap();
+    check("(let f = (fn [n: i32 {> val 0}] n))\n(let g = (fn [m: i32 {> val 5}] (f m)))").unwrap();
+    check_fn("i32 {>= val 0}", "i32 {> val 0}", "(+ n 1)").unwrap();
+    // A value that cannot be shown to break the constraint is accepted
+    check_fn("i32", "i32 {>= val 0}", "(if (< n 0) (- 0 n) n)").unwrap();
+}
+
+#[test]
+fn test_refinements_disabled() {
+    let unchecked = TypeCheckerConfig { refinements: false, ..TypeCheckerConfig::default() };
+    check_with("(let x: i32 {> val 0} = (- 0 5))", unchecked).unwrap();
+}
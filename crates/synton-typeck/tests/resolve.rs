@@ -0,0 +1,58 @@
+//! Name resolution
+
+use synton_ast::{ExprKind, Module, StmtKind, VarId};
+use synton_parser::SyntonParser;
+use synton_typeck::{ResolveError, Resolution, Resolver, Symbol};
+
+fn resolve(src: &str) -> (Module, Resolution) {
+    let mut module = SyntonParser::new().parse_module(src).unwrap();
+    let resolution = Resolver::new().with_globals(["print"]).resolve(&mut module);
+    (module, resolution)
+}
+
+const SRC: &str = "(let n = 1)\n(let f = (fn [a] (+ a n)))\n(print (f n))\n(let n = (+ n 1))";
+
+#[test]
+fn test_resolve_names() {
+    let (_, resolution) = resolve(SRC);
+    let names: Vec<_> = resolution.vars.iter().map(|var| var.name.as_str()).collect();
+    assert_eq!(names, ["n", "a", "f", "n"]);
+    assert!(!resolution.has_errors());
+    // Rebinding `n` in the same scope is allowed, but reported
+    assert!(matches!(&resolution.diagnostics[..], [ResolveError::Shadowed { name, .. }] if name == "n"));
+}
+
+#[test]
+fn test_resolved_ids() {
+    // Ids are written back into the AST
+    let (module, resolution) = resolve(SRC);
+    let StmtKind::Let { id, init: Some(init), .. } = &module.stmts[3].kind else { panic!("expected let") };
+    assert_eq!(*id, Some(VarId::new(3)));
+    let ExprKind::Binary { left, .. } = &init.kind else { panic!("expected Binary") };
+    assert!(matches!(left.kind, ExprKind::Var { id: Some(id), .. } if id == VarId::new(0)));
+
+    // Every reference gets a node id and a symbol
+    let StmtKind::Expr(print) = &module.stmts[2].kind else { panic!("expected call") };
+    let ExprKind::Call { callee, args } = &print.kind else { panic!("expected Call") };
+    assert_eq!(resolution.symbols[&callee.id.unwrap()], Symbol::Global("print".into()));
+    let ExprKind::Call { callee, .. } = &args[0].kind else { panic!("expected Call") };
+    assert_eq!(resolution.symbols[&callee.id.unwrap()], Symbol::Var(VarId::new(2)));
+}
+
+#[test]
+fn test_resolve_functions() {
+    // Functions see each other regardless of order; parameters must be distinct
+    let src = "(let k = (+ later 1))\n(let later = 2)\n(missing)\n(fn even [x] (odd x))\n(fn odd [x x] (even x))";
+    let (module, resolution) = resolve(src);
+    let StmtKind::FnDecl(odd) = &module.stmts[4].kind else { panic!("expected fn") };
+    assert_eq!(odd.id.map(|id| resolution.function(id).name.as_str()), Some("odd"));
+    let errors: Vec<_> = resolution.errors().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        [
+            "'later' is used before it is declared",
+            "undefined name: 'missing'",
+            "duplicate definition of 'x'",
+        ]
+    );
+}